@@ -0,0 +1,23 @@
+/// P_TR Project
+/// `File` plugins/vignette.wgsl
+/// `Description` Example post-process plugin, vignetting of presented image.
+/// `Author` TioT2
+
+struct Parameters {
+    strength: f32, // darkening at image corners, in [0, 1] range
+    radius: f32,   // distance from center darkening starts at, relative to half diagonal
+}
+
+@group(1) @binding(0) var<uniform> parameters: Parameters;
+
+@fragment
+fn fs_main(@builtin(position) position: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(position.xy);
+    let extent = vec2f(textureDimensions(collector));
+    let distance = length(position.xy / extent * 2.0 - 1.0) / sqrt(2.0);
+    let factor = 1.0 - parameters.strength * smoothstep(parameters.radius, 1.0, distance);
+
+    return vec4f(load_mean(pixel) * factor, 1.0);
+}
+
+// file vignette.wgsl
@@ -0,0 +1,309 @@
+use crate::math::{Mat4f, Vec3f};
+
+/// Axis-aligned bounding box
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Vec3f,
+    pub max: Vec3f,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl Aabb {
+    pub fn empty() -> Self {
+        Self {
+            min: Vec3f::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Vec3f::new(-f32::MAX, -f32::MAX, -f32::MAX),
+        }
+    }
+
+    pub fn new(min: Vec3f, max: Vec3f) -> Self {
+        Self { min, max }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend_point(&mut self, point: Vec3f) {
+        self.min = self.min.min(point);
+        self.max = self.max.max(point);
+    }
+
+    pub fn extend(&mut self, other: &Aabb) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn center(&self) -> Vec3f {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3f {
+        self.max - self.min
+    }
+
+    /// Bounds of transformed box
+    pub fn transformed(&self, transform: &Mat4f) -> Aabb {
+        let mut result = Aabb::empty();
+        if self.is_empty() {
+            return result;
+        }
+
+        for corner in 0..8 {
+            result.extend_point(transform.transform_point(Vec3f::new(
+                if corner & 1 == 0 { self.min.x } else { self.max.x },
+                if corner & 2 == 0 { self.min.y } else { self.max.y },
+                if corner & 4 == 0 { self.min.z } else { self.max.z },
+            )));
+        }
+
+        result
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let e = self.extent();
+        2.0 * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+}
+
+/// Anything BVH could be built over
+pub trait Bounded {
+    fn bounds(&self) -> Aabb;
+
+    fn centroid(&self) -> Vec3f {
+        self.bounds().center()
+    }
+}
+
+impl Bounded for Aabb {
+    fn bounds(&self) -> Aabb {
+        *self
+    }
+}
+
+/// BVH node. Leaf nodes reference `count` primitive indices starting from `offset`,
+/// inner nodes have zero `count`, left child placed right after them and right child at `offset`.
+#[derive(Copy, Clone, Debug)]
+pub struct BvhNode {
+    pub bounds: Aabb,
+    pub offset: u32,
+    pub count: u32,
+}
+
+impl BvhNode {
+    pub fn is_leaf(&self) -> bool {
+        self.count != 0
+    }
+}
+
+/// BVH node in GPU-side layout
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+pub struct FlatBvhNode {
+    pub min: Vec3f,
+    pub offset: u32,
+    pub max: Vec3f,
+    pub count: u32,
+}
+
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    /// Primitive indices in leaf order
+    pub indices: Vec<u32>,
+    pub build_time: std::time::Duration,
+}
+
+const SAH_BIN_COUNT: usize = 16;
+const SAH_TRAVERSAL_COST: f32 = 1.0;
+const SAH_INTERSECTION_COST: f32 = 1.0;
+const MAX_LEAF_SIZE: usize = 8;
+
+/// Deepest node level, nodes on it are leaves regardless of size. Traversal stack holds
+/// at most `MAX_DEPTH + 1` nodes, so it must match `BVH_STACK_SIZE` - 1 in intersect.wgsl.
+pub const MAX_DEPTH: usize = 31;
+
+#[derive(Copy, Clone, Default)]
+struct SahBin {
+    bounds: Aabb,
+    count: usize,
+}
+
+struct BvhBuilder<'a> {
+    bounds: &'a [Aabb],
+    centroids: &'a [Vec3f],
+    indices: Vec<u32>,
+    nodes: Vec<BvhNode>,
+}
+
+impl<'a> BvhBuilder<'a> {
+    fn range_bounds(&self, first: usize, count: usize) -> (Aabb, Aabb) {
+        let mut bounds = Aabb::empty();
+        let mut centroid_bounds = Aabb::empty();
+
+        for index in &self.indices[first..first + count] {
+            bounds.extend(&self.bounds[*index as usize]);
+            centroid_bounds.extend_point(self.centroids[*index as usize]);
+        }
+
+        (bounds, centroid_bounds)
+    }
+
+    /// Find best split by binned SAH. Returns (axis, split bin, cost)
+    fn find_split(&self, first: usize, count: usize, centroid_bounds: &Aabb) -> Option<(usize, usize, f32)> {
+        let mut best: Option<(usize, usize, f32)> = None;
+        let extent = centroid_bounds.extent();
+
+        for axis in 0..3 {
+            if extent[axis] <= f32::EPSILON {
+                continue;
+            }
+
+            let mut bins = [SahBin::default(); SAH_BIN_COUNT];
+            let scale = SAH_BIN_COUNT as f32 / extent[axis];
+
+            for index in &self.indices[first..first + count] {
+                let bin_index = (((self.centroids[*index as usize][axis] - centroid_bounds.min[axis]) * scale) as usize).min(SAH_BIN_COUNT - 1);
+                bins[bin_index].count += 1;
+                bins[bin_index].bounds.extend(&self.bounds[*index as usize]);
+            }
+
+            // Sweep from the right to collect right-side areas
+            let mut right_areas = [0.0f32; SAH_BIN_COUNT];
+            let mut right_counts = [0usize; SAH_BIN_COUNT];
+            let mut accumulated = SahBin::default();
+            for i in (1..SAH_BIN_COUNT).rev() {
+                accumulated.count += bins[i].count;
+                accumulated.bounds.extend(&bins[i].bounds);
+                right_areas[i] = accumulated.bounds.surface_area();
+                right_counts[i] = accumulated.count;
+            }
+
+            let mut accumulated = SahBin::default();
+            for split in 1..SAH_BIN_COUNT {
+                accumulated.count += bins[split - 1].count;
+                accumulated.bounds.extend(&bins[split - 1].bounds);
+
+                if accumulated.count == 0 || right_counts[split] == 0 {
+                    continue;
+                }
+
+                let cost = accumulated.bounds.surface_area() * accumulated.count as f32 + right_areas[split] * right_counts[split] as f32;
+
+                if best.map(|(_, _, c)| cost < c).unwrap_or(true) {
+                    best = Some((axis, split, cost));
+                }
+            }
+        }
+
+        best
+    }
+
+    fn build_node(&mut self, first: usize, count: usize, depth: usize) -> u32 {
+        let node_index = self.nodes.len();
+        let (bounds, centroid_bounds) = self.range_bounds(first, count);
+
+        self.nodes.push(BvhNode { bounds, offset: first as u32, count: count as u32 });
+
+        if count <= 2 || depth >= MAX_DEPTH {
+            return node_index as u32;
+        }
+
+        let leaf_cost = SAH_INTERSECTION_COST * count as f32;
+        let split = self.find_split(first, count, &centroid_bounds);
+
+        let middle = match split {
+            Some((axis, split_bin, cost)) => {
+                let split_cost = SAH_TRAVERSAL_COST + SAH_INTERSECTION_COST * cost / bounds.surface_area().max(f32::MIN_POSITIVE);
+
+                if split_cost >= leaf_cost && count <= MAX_LEAF_SIZE {
+                    return node_index as u32;
+                }
+
+                let scale = SAH_BIN_COUNT as f32 / centroid_bounds.extent()[axis];
+                let min = centroid_bounds.min[axis];
+                let centroids = self.centroids;
+                let range = &mut self.indices[first..first + count];
+
+                let mut left = 0;
+                for i in 0..range.len() {
+                    let bin_index = (((centroids[range[i] as usize][axis] - min) * scale) as usize).min(SAH_BIN_COUNT - 1);
+                    if bin_index < split_bin {
+                        range.swap(i, left);
+                        left += 1;
+                    }
+                }
+
+                first + left
+            }
+            // All centroids match, so the only option is to split in the middle
+            None => {
+                if count <= MAX_LEAF_SIZE {
+                    return node_index as u32;
+                }
+                first + count / 2
+            }
+        };
+
+        self.build_node(first, middle - first, depth + 1);
+        let right = self.build_node(middle, first + count - middle, depth + 1);
+
+        self.nodes[node_index].offset = right;
+        self.nodes[node_index].count = 0;
+
+        node_index as u32
+    } // fn build_node
+}
+
+impl Bvh {
+    /// Build BVH using binned surface area heuristic
+    pub fn build<T: Bounded>(primitives: &[T]) -> Self {
+        let start_time = std::time::Instant::now();
+
+        let bounds = primitives.iter().map(Bounded::bounds).collect::<Vec<_>>();
+        let centroids = primitives.iter().map(Bounded::centroid).collect::<Vec<_>>();
+
+        let mut builder = BvhBuilder {
+            bounds: &bounds,
+            centroids: &centroids,
+            indices: (0..primitives.len() as u32).collect(),
+            nodes: Vec::with_capacity(primitives.len() * 2),
+        };
+
+        if primitives.is_empty() {
+            builder.nodes.push(BvhNode { bounds: Aabb::empty(), offset: 0, count: 0 });
+        } else {
+            builder.build_node(0, primitives.len(), 0);
+        }
+
+        Self {
+            nodes: builder.nodes,
+            indices: builder.indices,
+            build_time: start_time.elapsed(),
+        }
+    } // fn build
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes[0].bounds
+    }
+
+    /// Build GPU-side node array
+    pub fn flatten(&self) -> Vec<FlatBvhNode> {
+        self.nodes
+            .iter()
+            .map(|node| FlatBvhNode {
+                min: node.bounds.min,
+                offset: node.offset,
+                max: node.bounds.max,
+                count: node.count,
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+
+use crate::{bookmark, input::{self, KeyCode}, locale::{tr, tr_args}};
+
+/// User command. Key bindings and command palette execute the same actions.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum Action {
+    ToggleFullscreen,
+    /// Show or hide runtime controls overlay
+    ToggleUi,
+    /// Show or hide raw values of pixel under cursor
+    TogglePixelInspector,
+    /// Set focus distance to depth of surface at image center, Ctrl+click focuses at clicked pixel
+    FocusAtCenter,
+    /// Trace paths through pixel under cursor with their vertices recorded, save them as JSON and OBJ
+    /// and draw them over image. Alt+click records paths through clicked pixel.
+    RecordPixelPaths,
+    /// Hide recorded paths drawn over image
+    ClearPathOverlay,
+    CycleSelection,
+    ClearSelection,
+    ReloadScene,
+    LoadScene(PathBuf),
+    OpenCommandPalette,
+    NextCamera,
+    ToggleDenoiser,
+    ToggleVertexColors,
+    ToggleSampleCountView,
+    /// Show or hide heatmap of estimated pixel error
+    ToggleVarianceView,
+    ToggleFaceOrientationView,
+    /// Switch between fly and orbit camera controls
+    ToggleOrbitCamera,
+    /// Fly through scene cameras in loop
+    ToggleCameraPath,
+    /// Save last frame to file, presented image to PNG or linear radiance to EXR by path extension
+    SaveFrame(PathBuf),
+    /// Denoise current frame with Intel Open Image Denoise and save it
+    #[cfg(feature = "oidn")]
+    DenoiseFrame,
+    /// Scene camera index and name
+    SelectCamera(usize, String),
+    /// Save camera viewpoint to bookmark slot
+    SaveBookmark(u32),
+    /// Move camera to viewpoint saved in bookmark slot
+    RecallBookmark(u32),
+}
+
+/// Files command palette saves frames to, one of each format
+const SAVED_FRAME_PATHS: [&str; 2] = ["frame.png", "frame.exr"];
+
+impl Action {
+    /// Human-readable name, used by command palette
+    pub fn name(&self) -> String {
+        match self {
+            Action::ToggleFullscreen => tr("action.toggle_fullscreen").to_string(),
+            Action::ToggleUi => tr("action.toggle_ui").to_string(),
+            Action::TogglePixelInspector => tr("action.toggle_pixel_inspector").to_string(),
+            Action::FocusAtCenter => tr("action.focus_at_center").to_string(),
+            Action::RecordPixelPaths => tr("action.record_pixel_paths").to_string(),
+            Action::ClearPathOverlay => tr("action.clear_path_overlay").to_string(),
+            Action::CycleSelection => tr("action.cycle_selection").to_string(),
+            Action::ClearSelection => tr("action.clear_selection").to_string(),
+            Action::ReloadScene => tr("action.reload_scene").to_string(),
+            Action::LoadScene(path) => tr_args("action.load_scene", &[&path.display()]),
+            Action::OpenCommandPalette => tr("action.open_command_palette").to_string(),
+            Action::NextCamera => tr("action.next_camera").to_string(),
+            Action::ToggleDenoiser => tr("action.toggle_denoiser").to_string(),
+            Action::ToggleVertexColors => tr("action.toggle_vertex_colors").to_string(),
+            Action::ToggleSampleCountView => tr("action.toggle_sample_count_view").to_string(),
+            Action::ToggleVarianceView => tr("action.toggle_variance_view").to_string(),
+            Action::ToggleFaceOrientationView => tr("action.toggle_face_orientation_view").to_string(),
+            Action::ToggleOrbitCamera => tr("action.toggle_orbit_camera").to_string(),
+            Action::ToggleCameraPath => tr("action.toggle_camera_path").to_string(),
+            Action::SaveFrame(path) => tr_args("action.save_frame", &[&path.display()]),
+            #[cfg(feature = "oidn")]
+            Action::DenoiseFrame => tr("action.denoise_frame").to_string(),
+            Action::SelectCamera(_, name) => tr_args("action.select_camera", &[name]),
+            Action::SaveBookmark(slot) => tr_args("action.save_bookmark", &[slot]),
+            Action::RecallBookmark(slot) => tr_args("action.recall_bookmark", &[slot]),
+        }
+    }
+
+    /// All actions available in current state. Scene loading actions are listed for scenes near `scene_path`,
+    /// bookmark recall ones for `bookmark_slots` with saved viewpoints.
+    pub fn list(scene_path: &Path, camera_names: &[String], bookmark_slots: &[u32]) -> Vec<Action> {
+        let mut actions = vec![
+            Action::ToggleFullscreen,
+            Action::ToggleUi,
+            Action::TogglePixelInspector,
+            Action::FocusAtCenter,
+            Action::RecordPixelPaths,
+            Action::ClearPathOverlay,
+            Action::CycleSelection,
+            Action::ClearSelection,
+            Action::ReloadScene,
+            Action::ToggleDenoiser,
+            Action::ToggleVertexColors,
+            Action::ToggleSampleCountView,
+            Action::ToggleVarianceView,
+            Action::ToggleFaceOrientationView,
+            Action::ToggleOrbitCamera,
+            #[cfg(feature = "oidn")]
+            Action::DenoiseFrame,
+        ];
+        actions.extend(SAVED_FRAME_PATHS.map(|path| Action::SaveFrame(PathBuf::from(path))));
+
+        if !camera_names.is_empty() {
+            actions.push(Action::NextCamera);
+            if camera_names.len() >= 2 {
+                actions.push(Action::ToggleCameraPath);
+            }
+            actions.extend(camera_names.iter().cloned().enumerate().map(|(index, name)| Action::SelectCamera(index, name)));
+        }
+        actions.extend(bookmark::SLOT_RANGE.map(Action::SaveBookmark));
+        actions.extend(bookmark_slots.iter().copied().map(Action::RecallBookmark));
+
+        let directory = scene_path.parent().filter(|path| !path.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let mut scene_paths = std::fs::read_dir(directory)
+            .map(|entries| entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|extension| extension == "ron"))
+                .collect::<Vec<_>>()
+            )
+            .unwrap_or_default();
+        scene_paths.sort();
+
+        actions.extend(scene_paths.into_iter().map(Action::LoadScene));
+        actions
+    }
+}
+
+/// Key (with optional Ctrl modifier) that triggers action
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct KeyBinding {
+    pub key: KeyCode,
+    #[serde(default)]
+    pub ctrl: bool,
+    pub action: Action,
+}
+
+impl KeyBinding {
+    pub fn new(key: KeyCode, ctrl: bool, action: Action) -> Self {
+        Self { key, ctrl, action }
+    }
+
+    pub fn is_triggered(&self, input_state: &input::State) -> bool {
+        let ctrl = input_state.is_key_pressed(KeyCode::ControlLeft) || input_state.is_key_pressed(KeyCode::ControlRight);
+        input_state.is_key_clicked(self.key) && ctrl == self.ctrl
+    }
+}
+
+/// Digit keys of bookmark slots
+const BOOKMARK_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4, KeyCode::Digit5,
+    KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+];
+
+pub fn default_key_bindings() -> Vec<KeyBinding> {
+    let mut bindings = vec![
+        KeyBinding::new(KeyCode::F11, false, Action::ToggleFullscreen),
+        KeyBinding::new(KeyCode::F1, false, Action::ToggleUi),
+        KeyBinding::new(KeyCode::F2, false, Action::TogglePixelInspector),
+        KeyBinding::new(KeyCode::F3, false, Action::RecordPixelPaths),
+        KeyBinding::new(KeyCode::Tab, false, Action::CycleSelection),
+        KeyBinding::new(KeyCode::Escape, false, Action::ClearSelection),
+        KeyBinding::new(KeyCode::F5, false, Action::ReloadScene),
+        KeyBinding::new(KeyCode::KeyP, true, Action::OpenCommandPalette),
+        KeyBinding::new(KeyCode::KeyC, false, Action::NextCamera),
+        KeyBinding::new(KeyCode::KeyN, false, Action::ToggleDenoiser),
+        KeyBinding::new(KeyCode::KeyV, false, Action::ToggleVertexColors),
+        KeyBinding::new(KeyCode::KeyH, false, Action::ToggleSampleCountView),
+        KeyBinding::new(KeyCode::KeyG, false, Action::ToggleVarianceView),
+        KeyBinding::new(KeyCode::KeyB, false, Action::ToggleFaceOrientationView),
+        KeyBinding::new(KeyCode::KeyM, false, Action::ToggleOrbitCamera),
+        KeyBinding::new(KeyCode::KeyT, false, Action::ToggleCameraPath),
+        KeyBinding::new(KeyCode::KeyZ, false, Action::FocusAtCenter),
+        KeyBinding::new(KeyCode::KeyX, false, Action::ClearPathOverlay),
+    ];
+
+    // Digit recalls bookmark of its slot, Ctrl+digit saves it
+    for (slot, key) in bookmark::SLOT_RANGE.zip(BOOKMARK_KEYS) {
+        bindings.push(KeyBinding::new(key, false, Action::RecallBookmark(slot)));
+        bindings.push(KeyBinding::new(key, true, Action::SaveBookmark(slot)));
+    }
+    bindings
+}
+
+/// Default bindings with bindings of actions present in `overrides` replaced by them
+pub fn key_bindings(overrides: &[KeyBinding]) -> Vec<KeyBinding> {
+    let mut bindings = default_key_bindings();
+    bindings.retain(|binding| overrides.iter().all(|binding_override| binding_override.action != binding.action));
+    bindings.extend(overrides.iter().cloned());
+    bindings
+}
+
+/// Action bindings sharing key with held controls, both act on its press. Ctrl modifier doesn't stop controls,
+/// so bindings requiring it conflict too.
+pub fn control_conflicts<'a>(bindings: &'a [KeyBinding], controls: &input::ControlMap) -> Vec<(input::Control, &'a KeyBinding)> {
+    controls
+        .iter()
+        .flat_map(|(control, key)| bindings.iter().filter(move |binding| binding.key == key).map(move |binding| (control, binding)))
+        .collect()
+}
@@ -0,0 +1,203 @@
+use std::path::Path;
+
+use crate::{
+    camera::Camera,
+    config::AppConfig,
+    crash, golden,
+    locale::{tr, tr_args},
+    math::Ext2u,
+    render::{AdapterSelector, Aov, GpuContext, Render, RenderBuilder},
+    scene,
+};
+
+// Scene, resolution and pass counts are fixed, so reports of different runs and machines are comparable
+pub const SCENE_PATH: &str = "scenes/default.ron";
+pub const WIDTH: u32 = 1280;
+pub const HEIGHT: u32 = 720;
+/// Passes traced before measurement, they hide pipeline and cache warmup
+pub const WARMUP_PASS_COUNT: u32 = 8;
+pub const PASS_COUNT: u32 = 64;
+
+/// Summary of durations in milliseconds
+struct Statistics {
+    mean: f64,
+    min: f64,
+    median: f64,
+    p95: f64,
+    max: f64,
+}
+
+impl Statistics {
+    fn new(durations: &[f64]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+
+        let mut sorted = durations.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+
+        Some(Self {
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            min: sorted[0],
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"mean\":{:.4},\"min\":{:.4},\"median\":{:.4},\"p95\":{:.4},\"max\":{:.4}}}",
+            self.mean, self.min, self.median, self.p95, self.max
+        )
+    }
+}
+
+pub fn json_string(value: &str) -> String {
+    let mut result = String::from("\"");
+    for character in value.chars() {
+        match character {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            character if character.is_control() => result.push_str(&format!("\\u{:04x}", character as u32)),
+            character => result.push(character),
+        }
+    }
+    result.push('"');
+    result
+}
+
+/// Measurements of benchmark passes
+pub struct Report {
+    pub adapter_name: String,
+    pub extent: Ext2u,
+    /// Samples per pixel accumulated by measured passes
+    pub sample_count: u32,
+    /// Wall time of every measured pass in milliseconds, including wait for GPU
+    pub pass_times: Vec<f64>,
+    /// GPU time of every measured pass in milliseconds, empty if device has no timestamp queries
+    pub gpu_pass_times: Vec<f64>,
+    /// Comparison of final image with golden one, set by `--golden`
+    pub golden: Option<golden::Comparison>,
+}
+
+impl Report {
+    /// Single-line JSON object. Rays are counted as camera paths, as bounces are not tracked.
+    pub fn to_json(&self) -> String {
+        let total_seconds = self.pass_times.iter().sum::<f64>() / 1000.0;
+        let ray_count = self.sample_count as f64 * self.extent.w as f64 * self.extent.h as f64;
+        let null = || "null".to_string();
+
+        format!(
+            "{{\"scene\":{},\"adapter\":{},\"width\":{},\"height\":{},\"passes\":{},\"samples_per_pixel\":{},\"primary_rays_per_second\":{:.0},\"frame_time_ms\":{},\"gpu_time_ms\":{},\"golden\":{}}}",
+            json_string(SCENE_PATH),
+            json_string(&self.adapter_name),
+            self.extent.w,
+            self.extent.h,
+            self.pass_times.len(),
+            self.sample_count,
+            if total_seconds > 0.0 { ray_count / total_seconds } else { 0.0 },
+            Statistics::new(&self.pass_times).map_or_else(null, |statistics| statistics.to_json()),
+            Statistics::new(&self.gpu_pass_times).map_or_else(null, |statistics| statistics.to_json()),
+            self.golden.as_ref().map_or_else(null, golden::Comparison::to_json),
+        )
+    }
+}
+
+/// Trace warmup and measured accumulation passes of loaded scene without presenting
+fn measure(render: &mut Render) -> Report {
+    for _ in 0..WARMUP_PASS_COUNT {
+        render.trace_pass();
+    }
+
+    let start_sample_count = render.progress().sample_count;
+    let mut pass_times = Vec::new();
+    let mut gpu_pass_times = Vec::new();
+
+    for _ in 0..PASS_COUNT {
+        let start = std::time::Instant::now();
+        let gpu_time = render.trace_pass();
+        pass_times.push(start.elapsed().as_secs_f64() * 1000.0);
+        gpu_pass_times.extend(gpu_time);
+    }
+
+    Report {
+        adapter_name: render.adapter_name().to_string(),
+        extent: render.extent(),
+        sample_count: render.progress().sample_count - start_sample_count,
+        pass_times,
+        gpu_pass_times,
+        golden: None,
+    }
+}
+
+/// Measure accumulation passes of benchmark scene without window and print report as JSON, `p_tr --benchmark`.
+/// Final image is compared with `golden` within `thresholds` or written to it if `update_golden` is set.
+/// Fails if scene can't be rendered or golden comparison fails.
+pub fn run(selector: &AdapterSelector, golden: Option<&Path>, update_golden: bool, thresholds: &golden::Thresholds) -> std::process::ExitCode {
+    // Benchmark ignores user settings, so its results depend only on machine
+    let config = AppConfig::default();
+
+    let Some(context) = GpuContext::new(selector) else {
+        crash::error!("{}", tr("error.no_adapter"));
+        return std::process::ExitCode::FAILURE;
+    };
+    let mut render = match RenderBuilder::new(context, Ext2u::new(WIDTH, HEIGHT)).collector_precision(config.collector_precision).build() {
+        Ok(render) => render,
+        Err(error) => {
+            crash::error!("{}", tr_args("error.render", &[&error]));
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    crash::set_adapter(render.adapter_info());
+    if config.reduce_software_settings && render.is_software() {
+        crash::info!("{}", tr_args("render.software", &[&render.adapter_name()]));
+        render.set_settings(config.render.reduced());
+    } else {
+        render.set_settings(config.render);
+    }
+
+    let scene = match scene::load(Path::new(SCENE_PATH)) {
+        Ok(scene) => scene,
+        Err(error) => {
+            crash::error!("{}", tr_args("error.load_scene", &[&error]));
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    render.set_scene(&scene);
+    let camera = scene.cameras.first().map_or_else(Camera::initial, Camera::from_scene);
+    render.set_camera(&camera.descriptor(WIDTH as f32 / HEIGHT as f32));
+
+    let mut report = measure(&mut render);
+    let mut exit_code = std::process::ExitCode::SUCCESS;
+
+    if let Some(path) = golden {
+        let image = render.read_aov(Aov::Beauty);
+        if update_golden {
+            match image.write_pfm(path) {
+                Ok(()) => crash::info!("{}", tr_args("golden.saved", &[&path.display()])),
+                Err(error) => {
+                    crash::error!("{}", tr_args("error.golden", &[&format!("{}: {}", path.display(), error)]));
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        } else {
+            match golden::Comparison::new(path, &image, thresholds) {
+                Ok(comparison) => {
+                    if !comparison.is_passed() {
+                        exit_code = std::process::ExitCode::FAILURE;
+                    }
+                    report.golden = Some(comparison);
+                }
+                Err(error) => {
+                    crash::error!("{}", tr_args("error.golden", &[&error]));
+                    return std::process::ExitCode::FAILURE;
+                }
+            }
+        }
+    }
+
+    println!("{}", report.to_json());
+    exit_code
+} // fn run
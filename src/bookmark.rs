@@ -0,0 +1,80 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{camera::Camera, math::{Vec3d, Vec3f}};
+
+pub const BOOKMARKS_PATH: &str = "bookmarks.toml";
+
+/// Bookmark slots, bound to digit keys
+pub const SLOT_RANGE: std::ops::RangeInclusive<u32> = 1..=9;
+
+/// Saved camera viewpoint
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Bookmark {
+    pub location: Vec3d,
+    pub at: Vec3d,
+    pub up: Vec3f,
+    /// Vertical field of view, in degrees
+    pub fov: f32,
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+}
+
+impl Bookmark {
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            location: camera.location,
+            at: camera.at,
+            up: camera.up,
+            fov: camera.fov,
+            aperture_radius: camera.aperture_radius,
+            focus_distance: camera.focus_distance,
+        }
+    }
+
+    /// Move `camera` to bookmark, other camera settings are kept
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.set(self.location, self.at, self.up);
+        camera.fov = self.fov;
+        camera.aperture_radius = self.aperture_radius;
+        camera.focus_distance = self.focus_distance;
+    }
+}
+
+/// Bookmarks by slot, shared by all scenes so scene variants can be compared from the same viewpoint
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct Bookmarks {
+    /// TOML keys are strings, so slots are stored as ones
+    #[serde(default)]
+    slots: BTreeMap<String, Bookmark>,
+}
+
+impl Bookmarks {
+    /// Load bookmarks from `path`, missing file gives no bookmarks
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => toml::from_str(&source).map_err(|error| format!("{}: {}", path.display(), error)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("{}: {}", path.display(), error)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let source = toml::to_string(self).map_err(|error| error.to_string())?;
+        std::fs::write(path, source).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+
+    pub fn get(&self, slot: u32) -> Option<&Bookmark> {
+        self.slots.get(&slot.to_string())
+    }
+
+    pub fn set(&mut self, slot: u32, bookmark: Bookmark) {
+        self.slots.insert(slot.to_string(), bookmark);
+    }
+
+    /// Slots with bookmarks, in ascending order
+    pub fn saved_slots(&self) -> Vec<u32> {
+        let mut slots = self.slots.keys().filter_map(|slot| slot.parse().ok()).collect::<Vec<u32>>();
+        slots.sort();
+        slots
+    }
+}
@@ -0,0 +1,457 @@
+use crate::{input, math::{Ext2f, Vec2f, Vec3d, Vec3f}, render, scene, timer};
+
+/// Vertical field of view of cameras without one set, in degrees
+pub const DEFAULT_FOV: f32 = 90.0;
+
+/// Range of vertical field of view, in degrees
+pub const FOV_RANGE: std::ops::RangeInclusive<f32> = 1.0..=170.0;
+
+/// Range of fisheye angle of view across image height, in degrees
+pub const FISHEYE_FOV_RANGE: std::ops::RangeInclusive<f32> = 1.0..=350.0;
+
+/// Range of field of view of `projection`, in degrees
+pub fn fov_range(projection: scene::Projection) -> std::ops::RangeInclusive<f32> {
+    match projection {
+        scene::Projection::Fisheye(_) => FISHEYE_FOV_RANGE,
+        _ => FOV_RANGE,
+    }
+}
+
+/// Range of focus distance, in scene units
+pub const FOCUS_DISTANCE_RANGE: std::ops::RangeInclusive<f32> = 0.01..=1000.0;
+
+/// Range of orthographic view height, in scene units
+pub const ORTHOGRAPHIC_HEIGHT_RANGE: std::ops::RangeInclusive<f32> = 1e-3..=1e5;
+
+#[derive(Clone)]
+pub struct Camera {
+    /// Double precision keeps movement smooth far from world origin
+    pub location: Vec3d,
+    pub at: Vec3d,
+
+    pub direction: Vec3f,
+    pub right: Vec3f,
+    pub up: Vec3f,
+
+    pub projection: scene::Projection,
+    /// Vertical field of view, in degrees
+    pub fov: f32,
+    /// Thin lens radius, zero for pinhole camera
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+    pub aperture_blades: u32,
+    /// In degrees
+    pub aperture_rotation: f32,
+    pub bokeh_texture: Option<u32>,
+    /// Exposure compensation in stops
+    pub exposure: f32,
+    pub lens_shift: Vec2f,
+    /// In degrees
+    pub lens_tilt: Vec2f,
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            location: Vec3d::new(0.0, 0.0, 1.0),
+            at: Vec3d::new(0.0, 0.0, 0.0),
+            direction: Vec3f::new(0.0, 0.0, -1.0),
+            right: Vec3f::new(1.0, 0.0, 0.0),
+            up: Vec3f::new(0.0, 1.0, 0.0),
+            projection: scene::Projection::Perspective,
+            fov: DEFAULT_FOV,
+            aperture_radius: 0.0,
+            focus_distance: 4.0,
+            aperture_blades: 0,
+            aperture_rotation: 0.0,
+            bokeh_texture: None,
+            exposure: 0.0,
+            lens_shift: Vec2f::new(0.0, 0.0),
+            lens_tilt: Vec2f::new(0.0, 0.0),
+        }
+    }
+
+    /// Camera of scenes without cameras
+    pub fn initial() -> Self {
+        let mut camera = Self::new();
+        camera.set(Vec3d::new(-3.2, 2.8, 0.3), Vec3d::new(-2.4, 2.4, -0.1), Vec3f::new(0.0, 1.0, 0.0));
+        camera
+    }
+
+    pub fn from_scene(scene_camera: &scene::Camera) -> Self {
+        let mut camera = Self {
+            projection: scene_camera.projection,
+            fov: scene_camera.fov,
+            aperture_radius: scene_camera.aperture_radius,
+            focus_distance: scene_camera.focus_distance,
+            aperture_blades: scene_camera.aperture_blades,
+            aperture_rotation: scene_camera.aperture_rotation,
+            bokeh_texture: scene_camera.bokeh_texture,
+            exposure: scene_camera.exposure,
+            lens_shift: scene_camera.lens_shift,
+            lens_tilt: scene_camera.lens_tilt,
+            ..Self::new()
+        };
+        camera.set(scene_camera.location.to_f64(), scene_camera.at.to_f64(), scene_camera.up);
+        camera
+    }
+
+    pub fn set(&mut self, location: Vec3d, at: Vec3d, approx_up: Vec3f) {
+        self.direction = (at - location).to_f32().normalized();
+        self.right = (self.direction % approx_up).normalized();
+        self.up = (self.right % self.direction).normalized();
+        self.location = location;
+        self.at = at;
+    }
+
+    /// Half extent of image plane at unit distance for image of `aspect_ratio` (width / height),
+    /// half extent of view in scene units for orthographic camera and half extent of image in fisheye mapping
+    /// distances. Panorama has unit extent, so resize resampling maps whole image to whole image.
+    pub fn projection_extent(&self, aspect_ratio: f32) -> Ext2f {
+        let height = match self.projection {
+            scene::Projection::Perspective => (self.fov.clamp(*FOV_RANGE.start(), *FOV_RANGE.end()).to_radians() * 0.5).tan(),
+            scene::Projection::Orthographic { height } => height.clamp(*ORTHOGRAPHIC_HEIGHT_RANGE.start(), *ORTHOGRAPHIC_HEIGHT_RANGE.end()) * 0.5,
+            // Panorama covers all directions at any aspect ratio
+            scene::Projection::Panorama => return Ext2f::new(1.0, 1.0),
+            scene::Projection::Fisheye(mapping) => {
+                let angle = self.fov.clamp(*FISHEYE_FOV_RANGE.start(), *FISHEYE_FOV_RANGE.end()).to_radians() * 0.5;
+                match mapping {
+                    scene::FisheyeMapping::Equidistant => angle,
+                    scene::FisheyeMapping::Stereographic => 2.0 * (angle * 0.5).tan(),
+                }
+            }
+        };
+        Ext2f::new(height * aspect_ratio, height)
+    }
+
+    /// Render camera for image of `aspect_ratio` (width / height)
+    pub fn descriptor(&self, aspect_ratio: f32) -> render::CameraDescriptor {
+        render::CameraDescriptor {
+            at: self.at,
+            dir: self.direction,
+            location: self.location,
+            near: 1.0,
+            projection: self.projection,
+            projection_extent: self.projection_extent(aspect_ratio),
+            right: self.right,
+            up: self.up,
+            aperture_radius: self.aperture_radius,
+            focus_distance: self.focus_distance,
+            aperture_blades: self.aperture_blades,
+            aperture_rotation: self.aperture_rotation,
+            bokeh_texture: self.bokeh_texture,
+            exposure: self.exposure,
+            lens_shift: self.lens_shift,
+            lens_tilt: self.lens_tilt,
+        }
+    }
+
+    /// Scale field of view, or view height of orthographic camera, by `factor`. Panorama has fixed view.
+    pub fn widen(&mut self, factor: f32) {
+        match &mut self.projection {
+            scene::Projection::Perspective | scene::Projection::Fisheye(_) => {
+                let range = fov_range(self.projection);
+                self.fov = (self.fov * factor).clamp(*range.start(), *range.end());
+            }
+            scene::Projection::Orthographic { height } => {
+                *height = (*height * factor).clamp(*ORTHOGRAPHIC_HEIGHT_RANGE.start(), *ORTHOGRAPHIC_HEIGHT_RANGE.end());
+            }
+            scene::Projection::Panorama => {}
+        }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Camera control parameters shared by all controllers
+#[derive(Copy, Clone, Debug)]
+pub struct ControlSettings {
+    /// Camera speed in scene units per second
+    pub move_speed: f32,
+    /// Camera rotation in radians per mouse device unit
+    pub mouse_sensitivity: f32,
+    /// Mouse movement rotates camera
+    pub is_mouse_look: bool,
+    /// Roll controls turn camera around view direction. Selection rotation shares their default keys,
+    /// so they are disabled while anything is selected.
+    pub is_roll: bool,
+}
+
+/// Camera control scheme
+pub trait CameraController {
+    /// Move camera by frame input, returns true if camera is changed
+    fn update(&mut self, camera: &mut Camera, settings: &ControlSettings, input: &input::State, timer: &timer::State) -> bool;
+}
+
+/// Camera control scheme selected by user
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Free movement along view axes, rotation around camera location
+    Fly,
+    /// Rotation around `Camera::at`, dolly towards it and pan of both camera and its target
+    Orbit,
+    /// Looped flight through scene cameras
+    Path,
+}
+
+impl CameraMode {
+    /// Locale key of mode description
+    pub fn description_key(self) -> &'static str {
+        match self {
+            CameraMode::Fly => "camera.fly",
+            CameraMode::Orbit => "camera.orbit",
+            CameraMode::Path => "camera.path",
+        }
+    }
+}
+
+/// Orbit camera doesn't dolly closer than this to its target, in scene units
+const MIN_ORBIT_DISTANCE: f64 = 1e-3;
+
+/// Relative change of orbit distance per mouse wheel line
+const ORBIT_DOLLY_PER_WHEEL_LINE: f64 = 0.1;
+
+/// Relative change of fly speed per mouse wheel line
+const FLY_SPEED_PER_WHEEL_LINE: f32 = 1.25;
+
+/// Range of fly speed scaled by mouse wheel, in scene units per second
+pub const MOVE_SPEED_RANGE: std::ops::RangeInclusive<f32> = 1e-3..=1e5;
+
+/// Fly speed scaled by mouse wheel rotation, so tiny and huge scenes are both walkable
+pub fn scale_move_speed(move_speed: f32, wheel_delta: f32) -> f32 {
+    (move_speed * FLY_SPEED_PER_WHEEL_LINE.powf(wheel_delta)).clamp(*MOVE_SPEED_RANGE.start(), *MOVE_SPEED_RANGE.end())
+}
+
+/// Movement along camera right, up and forward axes
+fn move_axis(input: &input::State) -> Vec3f {
+    Vec3f::new(
+        input.control_axis(input::Control::MoveRight, input::Control::MoveLeft),
+        input.control_axis(input::Control::MoveUp, input::Control::MoveDown),
+        input.control_axis(input::Control::MoveForward, input::Control::MoveBackward),
+    )
+}
+
+/// Right and down rotation angles by turn controls and mouse-look
+fn rotation_delta(input: &input::State, settings: &ControlSettings, delta_time: f32) -> Vec2f {
+    let rotate_axis = Vec2f::new(
+        input.control_axis(input::Control::TurnRight, input::Control::TurnLeft),
+        input.control_axis(input::Control::TurnDown, input::Control::TurnUp),
+    );
+
+    // Mouse-look angles, right and down movement turn camera right and down as arrows do
+    let look_delta = if settings.is_mouse_look {
+        input.mouse_delta() * settings.mouse_sensitivity
+    } else {
+        Vec2f::default()
+    };
+
+    rotate_axis * (delta_time * 2.0) + look_delta
+}
+
+/// Clockwise roll angle by roll controls
+fn roll_delta(input: &input::State, settings: &ControlSettings, delta_time: f32) -> f32 {
+    if !settings.is_roll {
+        return 0.0;
+    }
+    input.control_axis(input::Control::RollRight, input::Control::RollLeft) * delta_time
+}
+
+/// World up direction, level camera has right axis perpendicular to it
+const WORLD_UP: Vec3f = Vec3f { x: 0.0, y: 1.0, z: 0.0 };
+
+/// Camera direction is kept at least this angle away from world up and down, in radians
+const MIN_POLE_ANGLE: f32 = 0.01;
+
+/// `v` rotated by `angle` radians around unit `axis`, counterclockwise looking against the axis
+fn rotate_around(v: Vec3f, axis: Vec3f, angle: f32) -> Vec3f {
+    let (sin, cos) = angle.sin_cos();
+    v * cos + (axis % v) * sin + axis * ((axis ^ v) * (1.0 - cos))
+}
+
+/// Camera direction and up turned by screen-space `delta` angles (right and down) and by `roll` angle clockwise.
+/// Turns are applied to level camera frame, so horizon doesn't drift, and camera roll is kept on top of it.
+fn rotate_basis(camera: &Camera, delta: Vec2f, roll: f32) -> (Vec3f, Vec3f) {
+    // Level frame is undefined at poles, camera right is level enough there
+    let level_right = {
+        let right = camera.direction % WORLD_UP;
+        if right.length2() > 1e-8 { right.normalized() } else { camera.right }
+    };
+    let level_up = level_right % camera.direction;
+    let current_roll = (camera.up ^ level_right).atan2(camera.up ^ level_up);
+
+    // Screen axes of rolled camera are level ones turned by roll angle
+    let (roll_sin, roll_cos) = current_roll.sin_cos();
+    let yaw = delta.x * roll_cos - delta.y * roll_sin;
+    let pitch = delta.x * roll_sin + delta.y * roll_cos;
+
+    let direction = rotate_around(camera.direction, WORLD_UP, -yaw);
+    let right = rotate_around(level_right, WORLD_UP, -yaw);
+
+    let pole_angle = (direction ^ WORLD_UP).clamp(-1.0, 1.0).acos();
+    let new_pole_angle = (pole_angle + pitch).clamp(MIN_POLE_ANGLE, std::f32::consts::PI - MIN_POLE_ANGLE);
+    let direction = rotate_around(direction, right, pole_angle - new_pole_angle).normalized();
+
+    let (roll_sin, roll_cos) = (current_roll + roll).sin_cos();
+    let up = (right % direction) * roll_cos + right * roll_sin;
+    (direction, up)
+} // fn rotate_basis
+
+/// Distance to camera target, orbit camera continues around point at the same distance ahead of fly camera
+fn target_distance(camera: &Camera) -> f64 {
+    (camera.at - camera.location).length().max(MIN_ORBIT_DISTANCE)
+}
+
+/// Free movement along view axes, rotation around camera location
+pub struct FlyController;
+
+impl CameraController for FlyController {
+    fn update(&mut self, camera: &mut Camera, settings: &ControlSettings, input: &input::State, timer: &timer::State) -> bool {
+        let delta_time = timer.get_delta_time() as f32;
+        let move_axis = move_axis(input);
+        let rotation = rotation_delta(input, settings, delta_time);
+        let roll = roll_delta(input, settings, delta_time);
+
+        if move_axis.length() <= 0.01 && rotation.x == 0.0 && rotation.y == 0.0 && roll == 0.0 {
+            return false;
+        }
+
+        let movement_delta = (
+            camera.right     * move_axis.x +
+            camera.up        * move_axis.y +
+            camera.direction * move_axis.z
+        ) * delta_time * settings.move_speed;
+
+        let distance = target_distance(camera);
+        let location = camera.location + movement_delta.to_f64();
+        let (direction, up) = rotate_basis(camera, rotation, roll);
+        camera.set(location, location + direction.to_f64() * distance, up);
+        true
+    }
+}
+
+/// Rotation around camera target, dolly by forward/backward movement and mouse wheel, pan by other movement
+/// and mouse with middle button held
+pub struct OrbitController;
+
+impl CameraController for OrbitController {
+    fn update(&mut self, camera: &mut Camera, settings: &ControlSettings, input: &input::State, timer: &timer::State) -> bool {
+        let delta_time = timer.get_delta_time() as f32;
+        let move_axis = move_axis(input);
+        let rotation = rotation_delta(input, settings, delta_time);
+        let roll = roll_delta(input, settings, delta_time);
+        let wheel_delta = input.wheel_delta();
+        let pan_delta = if input.is_button_pressed(input::MouseButton::Middle) {
+            input.mouse_delta() * settings.mouse_sensitivity
+        } else {
+            Vec2f::default()
+        };
+
+        if move_axis.length() <= 0.01 && rotation.x == 0.0 && rotation.y == 0.0 && roll == 0.0 && wheel_delta == 0.0 && pan_delta.length() == 0.0 {
+            return false;
+        }
+
+        // Pan is proportional to distance, so target moves by the same view fraction at any distance
+        let distance = target_distance(camera);
+        let pan = (
+            camera.right * (move_axis.x * delta_time - pan_delta.x) +
+            camera.up    * (move_axis.y * delta_time + pan_delta.y)
+        ).to_f64() * distance;
+        let dolly = -wheel_delta as f64 * ORBIT_DOLLY_PER_WHEEL_LINE - (move_axis.z * delta_time) as f64;
+
+        let at = camera.at + pan;
+        let distance = (distance * dolly.exp()).max(MIN_ORBIT_DISTANCE);
+        let (direction, up) = rotate_basis(camera, rotation, roll);
+        camera.set(at - direction.to_f64() * distance, at, up);
+        true
+    }
+}
+
+/// Time of flight between neighbour path cameras, in seconds
+const PATH_SEGMENT_DURATION: f64 = 4.0;
+
+/// Camera pose on path
+#[derive(Copy, Clone, Debug)]
+pub struct PathPoint {
+    pub location: Vec3d,
+    pub at: Vec3d,
+    pub up: Vec3f,
+}
+
+/// Looped flight through path points along Catmull-Rom spline. Lens settings are left to user.
+pub struct PathController {
+    points: Vec<PathPoint>,
+    /// Time since path start, in seconds
+    time: f64,
+}
+
+impl PathController {
+    /// Path through `points`, None if there are less than two of them
+    pub fn new(points: Vec<PathPoint>) -> Option<Self> {
+        if points.len() < 2 {
+            return None;
+        }
+
+        Some(Self { points, time: 0.0 })
+    }
+
+    /// Path through scene cameras in scene order
+    pub fn from_scene_cameras(cameras: &[scene::Camera]) -> Option<Self> {
+        Self::new(cameras
+            .iter()
+            .map(|camera| PathPoint {
+                location: camera.location.to_f64(),
+                at: camera.at.to_f64(),
+                up: camera.up,
+            })
+            .collect()
+        )
+    }
+
+    /// Pose at `time` seconds since path start
+    pub fn point(&self, time: f64) -> PathPoint {
+        let count = self.points.len();
+        let position = (time / PATH_SEGMENT_DURATION).rem_euclid(count as f64);
+        let index = position as usize % count;
+        let t = position.fract();
+
+        let point = |offset: usize| &self.points[(index + count - 1 + offset) % count];
+        let (p0, p1, p2, p3) = (point(0), point(1), point(2), point(3));
+
+        PathPoint {
+            location: catmull_rom(p0.location, p1.location, p2.location, p3.location, t),
+            at: catmull_rom(p0.at, p1.at, p2.at, p3.at, t),
+            up: catmull_rom(p0.up.to_f64(), p1.up.to_f64(), p2.up.to_f64(), p3.up.to_f64(), t).to_f32(),
+        }
+    }
+}
+
+/// Uniform Catmull-Rom spline segment between `p1` and `p2`
+fn catmull_rom(p0: Vec3d, p1: Vec3d, p2: Vec3d, p3: Vec3d, t: f64) -> Vec3d {
+    let t2 = t * t;
+    let t3 = t2 * t;
+
+    (
+        p1 * 2.0 +
+        (p2 - p0) * t +
+        (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2 +
+        (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3
+    ) * 0.5
+}
+
+impl CameraController for PathController {
+    fn update(&mut self, camera: &mut Camera, _settings: &ControlSettings, _input: &input::State, timer: &timer::State) -> bool {
+        let delta_time = timer.get_delta_time();
+        if delta_time == 0.0 {
+            return false;
+        }
+
+        self.time += delta_time;
+        let point = self.point(self.time);
+        camera.set(point.location, point.at, point.up);
+        true
+    }
+}
@@ -0,0 +1,122 @@
+use std::path::PathBuf;
+
+use crate::{golden, render::AdapterSelector};
+
+/// Graphics API used for rendering
+#[derive(Copy, Clone, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum Backend {
+    Vulkan,
+    Metal,
+    Dx12,
+    Gl,
+}
+
+impl Backend {
+    fn backends(self) -> wgpu::Backends {
+        match self {
+            Backend::Vulkan => wgpu::Backends::VULKAN,
+            Backend::Metal => wgpu::Backends::METAL,
+            Backend::Dx12 => wgpu::Backends::DX12,
+            Backend::Gl => wgpu::Backends::GL,
+        }
+    }
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Load and lint scenes without opening window, fails if any scene has problems
+    Validate {
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+    },
+    /// Print graphics adapters, their features and limits, and device and render settings renderer would use.
+    /// Respects --backend and --adapter.
+    Info,
+    /// Render analytic scenes (furnace, Lambertian plane lit by sphere light) headless and compare measured radiance
+    /// with analytic one, fails if integrator is biased. Respects --backend, --adapter, --software and --spp.
+    Selftest,
+}
+
+/// Interactive path tracer
+#[derive(Debug, clap::Parser)]
+#[command(version, about)]
+#[command(group = clap::ArgGroup::new("target").args(["spp", "target_error"]).multiple(true))]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Scene file to load
+    #[arg(long, default_value = "scenes/default.ron")]
+    pub scene: PathBuf,
+
+    /// Window width in pixels, overrides config
+    #[arg(long)]
+    pub width: Option<u32>,
+
+    /// Window height in pixels, overrides config
+    #[arg(long)]
+    pub height: Option<u32>,
+
+    /// Samples per pixel to accumulate, overrides target sample count of config
+    #[arg(long)]
+    pub spp: Option<u32>,
+
+    /// Mean relative change of pixels by accumulation pass to stop at, overrides target error of config
+    #[arg(long)]
+    pub target_error: Option<f32>,
+
+    /// Save frame to PNG or EXR file (chosen by extension) once it reaches --spp samples or --target-error and exit
+    /// PNG holds presented image, EXR holds linear radiance before post-processing
+    #[arg(long, requires = "target")]
+    pub output: Option<PathBuf>,
+
+    /// Graphics API, any available one if not set
+    #[arg(long, value_enum)]
+    pub backend: Option<Backend>,
+
+    /// Use graphics adapter whose name contains this string (case-insensitive)
+    #[arg(long)]
+    pub adapter: Option<String>,
+
+    /// Accept newline-delimited RON live-link commands (scene, camera and material sync) on this TCP address, e.g. 127.0.0.1:7878
+    #[arg(long)]
+    pub listen: Option<String>,
+
+    /// Use software adapter (llvmpipe, WARP), for machines without GPU. It's used automatically if no GPU is found.
+    #[arg(long)]
+    pub software: bool,
+
+    /// Serve render job HTTP API on this address, e.g. 127.0.0.1:8080. Jobs render at window size.
+    #[arg(long)]
+    pub serve: Option<String>,
+
+    /// Render fixed scene in hidden window and print timings as JSON, ignores scene, size and config
+    #[arg(long)]
+    pub benchmark: bool,
+
+    /// Write pass and resource dependency graph of frame with config settings as Graphviz DOT file and exit, no window is opened
+    #[arg(long)]
+    pub dump_framegraph: Option<PathBuf>,
+
+    /// Compare final benchmark image with golden PFM image by RMSE, SSIM and FLIP, fails if any metric exceeds its threshold
+    #[arg(long, requires = "benchmark")]
+    pub golden: Option<PathBuf>,
+
+    /// Write final benchmark image to --golden file instead of comparing with it
+    #[arg(long, requires = "golden")]
+    pub update_golden: bool,
+
+    /// Override threshold of golden comparison metric, e.g. flip=0.08. Defaults are rmse=0.02, ssim=0.95 and flip=0.05.
+    #[arg(long, requires = "golden", value_parser = golden::parse_threshold)]
+    pub golden_threshold: Vec<(golden::Threshold, f64)>,
+}
+
+impl Cli {
+    pub fn adapter_selector(&self) -> AdapterSelector {
+        AdapterSelector {
+            backends: self.backend.map_or(wgpu::Backends::all(), Backend::backends),
+            name: self.adapter.clone(),
+            software: self.software,
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{action::KeyBinding, export::ExportConfig, input::{Control, KeyCode}, math::Vec3f, render::{CollectorPrecision, RenderSettings, SurfaceFormat}, scene::ParseError, theme::{FalseColorPalette, Theme, ThemePreset}};
+
+fn default_language() -> String {
+    "en".to_string()
+}
+
+/// Overlay theme preset with optional per-field overrides
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct ThemeConfig {
+    #[serde(default)]
+    pub preset: ThemePreset,
+    pub outline_color: Option<Vec3f>,
+    pub box_color: Option<Vec3f>,
+    pub font_scale: Option<f32>,
+    pub false_color: Option<FalseColorPalette>,
+}
+
+impl ThemeConfig {
+    pub fn theme(&self) -> Theme {
+        let preset = Theme::preset(self.preset);
+
+        Theme {
+            outline_color: self.outline_color.unwrap_or(preset.outline_color),
+            box_color: self.box_color.unwrap_or(preset.box_color),
+            font_scale: self.font_scale.unwrap_or(preset.font_scale),
+            false_color: self.false_color.unwrap_or(preset.false_color),
+        }
+    }
+}
+
+/// Monitor to go fullscreen on
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub enum MonitorSelector {
+    /// Index in system monitor list
+    Index(usize),
+    /// Monitor whose name contains this string
+    Name(String),
+}
+
+/// Exclusive fullscreen video mode, missing fields match any value
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct VideoModeConfig {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub refresh_rate_hz: Option<u32>,
+}
+
+/// Fullscreen toggle behavior
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct FullscreenConfig {
+    /// Start in fullscreen, saved on exit
+    #[serde(default)]
+    pub enabled: bool,
+    /// Monitor to use, current window monitor if not set
+    #[serde(default)]
+    pub monitor: Option<MonitorSelector>,
+    /// Change monitor video mode instead of covering it with borderless window
+    #[serde(default)]
+    pub exclusive: bool,
+    /// Exclusive fullscreen video mode, highest resolution and refresh rate if not set
+    #[serde(default)]
+    pub video_mode: VideoModeConfig,
+}
+
+fn default_window_width() -> u32 {
+    800
+}
+
+fn default_window_height() -> u32 {
+    600
+}
+
+fn default_decorations() -> bool {
+    true
+}
+
+/// Window-level options, applied at window creation
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct WindowConfig {
+    /// Size of window in pixels, saved on exit
+    #[serde(default = "default_window_width")]
+    pub width: u32,
+    #[serde(default = "default_window_height")]
+    pub height: u32,
+    /// Wait for display refresh on presentation, caps frame rate but stops tearing
+    #[serde(default)]
+    pub vsync: bool,
+    #[serde(default)]
+    pub always_on_top: bool,
+    /// Window border and title bar
+    #[serde(default = "default_decorations")]
+    pub decorations: bool,
+    /// Window background transparency, if supported by platform
+    #[serde(default)]
+    pub transparent: bool,
+    /// Prevent window contents from being captured by other applications (Windows and macOS only)
+    #[serde(default)]
+    pub content_protected: bool,
+    /// Swapchain format, first 8-bit sRGB one if not set or unsupported
+    #[serde(default)]
+    pub surface_format: SurfaceFormat,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: default_window_width(),
+            height: default_window_height(),
+            vsync: false,
+            always_on_top: false,
+            decorations: default_decorations(),
+            transparent: false,
+            content_protected: false,
+            surface_format: SurfaceFormat::default(),
+        }
+    }
+}
+
+/// Tracing while window is out of focus
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum UnfocusedMode {
+    /// Keep tracing at full rate
+    #[default]
+    Run,
+    /// Stop tracing until window gets focus back
+    Pause,
+    /// Trace at most given number of frames per second
+    Throttle(f32),
+}
+
+fn default_show_ui() -> bool {
+    true
+}
+
+fn default_reduce_software_settings() -> bool {
+    true
+}
+
+fn default_watchdog_timeout() -> f32 {
+    2.0
+}
+
+fn default_reduce_slow_frames() -> bool {
+    true
+}
+
+fn default_shader_directory() -> String {
+    "src/render/shaders".to_string()
+}
+
+fn default_plugins_directory() -> String {
+    "plugins".to_string()
+}
+
+fn default_move_speed() -> f32 {
+    8.0
+}
+
+fn default_mouse_sensitivity() -> f32 {
+    0.002
+}
+
+/// Application settings, loaded from `p_tr.toml` in working directory
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct AppConfig {
+    /// UI language, name of string table in `locales` directory
+    #[serde(default = "default_language")]
+    pub language: String,
+    /// Integrator parameters
+    #[serde(default)]
+    pub render: RenderSettings,
+    /// UI overlay colors and scale
+    #[serde(default)]
+    pub theme: ThemeConfig,
+    #[serde(default)]
+    pub fullscreen: FullscreenConfig,
+    #[serde(default)]
+    pub window: WindowConfig,
+    /// Frame sequence and video output
+    #[serde(default)]
+    pub export: ExportConfig,
+    /// Show runtime controls overlay, toggled by F1 and saved on exit
+    #[serde(default = "default_show_ui")]
+    pub show_ui: bool,
+    /// Camera speed in scene units per second, scaled by mouse wheel in fly mode and saved on exit
+    #[serde(default = "default_move_speed")]
+    pub move_speed: f32,
+    /// Mouse-look camera rotation in radians per mouse device unit
+    #[serde(default = "default_mouse_sensitivity")]
+    pub mouse_sensitivity: f32,
+    /// Power saving while window is out of focus, ignored while serving jobs or live-link and by --output renders
+    #[serde(default)]
+    pub unfocused: UnfocusedMode,
+    /// Bindings that replace default bindings of their actions
+    #[serde(default)]
+    pub key_bindings: Vec<KeyBinding>,
+    /// Keys that replace default keys of held controls
+    #[serde(default)]
+    pub controls: BTreeMap<Control, KeyCode>,
+    /// Cap render settings on software adapters, see `RenderSettings::reduced`
+    #[serde(default = "default_reduce_software_settings")]
+    pub reduce_software_settings: bool,
+    /// Format of accumulated image, half precision saves memory, but limits sample count
+    #[serde(default)]
+    pub collector_precision: CollectorPrecision,
+    /// GPU duration of frame in seconds at which it's reported as risking driver timeout reset, zero disables it
+    #[serde(default = "default_watchdog_timeout")]
+    pub watchdog_timeout: f32,
+    /// Lower samples per frame and tile size for the session on each watchdog report, see `RenderSettings::lightened`
+    #[serde(default = "default_reduce_slow_frames")]
+    pub reduce_slow_frames: bool,
+    /// Write panic message, backtrace, adapter, settings and last log lines to local crash report file
+    #[serde(default)]
+    pub crash_reports: bool,
+    /// Rebuild pipelines on save of shader files in `shader_directory`, embedded shaders are used until then
+    #[serde(default)]
+    pub hot_reload_shaders: bool,
+    /// Directory of shader files watched by hot reload, relative to working directory
+    #[serde(default = "default_shader_directory")]
+    pub shader_directory: String,
+    /// Directory of post-process plugin files loaded at startup
+    #[serde(default = "default_plugins_directory")]
+    pub plugins_directory: String,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            language: default_language(),
+            render: RenderSettings::default(),
+            theme: ThemeConfig::default(),
+            fullscreen: FullscreenConfig::default(),
+            window: WindowConfig::default(),
+            export: ExportConfig::default(),
+            show_ui: default_show_ui(),
+            move_speed: default_move_speed(),
+            mouse_sensitivity: default_mouse_sensitivity(),
+            unfocused: UnfocusedMode::default(),
+            key_bindings: Vec::new(),
+            controls: BTreeMap::new(),
+            reduce_software_settings: default_reduce_software_settings(),
+            collector_precision: CollectorPrecision::default(),
+            watchdog_timeout: default_watchdog_timeout(),
+            reduce_slow_frames: default_reduce_slow_frames(),
+            crash_reports: false,
+            hot_reload_shaders: false,
+            shader_directory: default_shader_directory(),
+            plugins_directory: default_plugins_directory(),
+        }
+    }
+}
+
+pub const CONFIG_PATH: &str = "p_tr.toml";
+
+/// Config of earlier versions, it isn't read anymore
+pub const LEGACY_CONFIG_PATH: &str = "config.ron";
+
+/// Write values of `current` that differ from `loaded` into `document`. Nested tables
+/// are updated key by key, so comments and untouched values of document are kept.
+fn merge_changes(document: &mut toml_edit::Table, loaded: &toml::Table, current: &toml::Table) -> Result<(), String> {
+    for (key, value) in current {
+        let loaded_value = loaded.get(key);
+        if loaded_value == Some(value) {
+            continue;
+        }
+
+        if let (toml::Value::Table(table), Some(toml::Value::Table(loaded_table))) = (value, loaded_value) {
+            // Missing table is added as implicit, so it gets header only if it has values
+            let document_table = document.entry(key).or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            });
+            if let Some(document_table) = document_table.as_table_mut() {
+                merge_changes(document_table, loaded_table, table)?;
+                continue;
+            }
+        }
+
+        // Value is formatted as a single-key document, so tables get their own section
+        let mut formatted = toml::to_string(&toml::Table::from_iter([(key.clone(), value.clone())]))
+            .map_err(|error| error.to_string())?
+            .parse::<toml_edit::DocumentMut>()
+            .map_err(|error| error.to_string())?;
+        let Some(mut item) = formatted.remove(key) else {
+            continue;
+        };
+        match (document.get_mut(key), item.as_value_mut()) {
+            // Value is replaced in place, so comments around it are kept
+            (Some(toml_edit::Item::Value(old_value)), Some(value)) => {
+                *value.decor_mut() = old_value.decor().clone();
+                *old_value = value.clone();
+            }
+            _ => {
+                document.insert(key, item);
+            }
+        }
+    }
+
+    Ok(())
+} // fn merge_changes
+
+/// `loaded` render settings with fields changed from `initial` to `current` during run. Fields that differ
+/// from loaded ones at start (command line targets, software adapter reductions) aren't user's preferred values.
+pub fn edited_render_settings(loaded: &RenderSettings, initial: &RenderSettings, current: &RenderSettings) -> RenderSettings {
+    let to_table = |settings: &RenderSettings| toml::Table::try_from(settings).unwrap_or_default();
+    let (initial, current) = (to_table(initial), to_table(current));
+    let mut edited = to_table(loaded);
+    for (key, value) in current {
+        if initial.get(&key) != Some(&value) {
+            edited.insert(key, value);
+        }
+    }
+    toml::Value::Table(edited).try_into().unwrap_or(*loaded)
+}
+
+impl AppConfig {
+    /// Load config from `path`, missing file gives default config
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read_to_string(path) {
+            Ok(source) => toml::from_str(&source).map_err(|error| {
+                let offset = error.span().map_or(0, |span| span.start);
+                format!("{}:{}", path.display(), ParseError::at_offset(&source, offset, error.message().to_string()))
+            }),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(format!("{}: {}", path.display(), error)),
+        }
+    }
+
+    /// Write settings changed since `loaded` to config file at `path`, other contents of file are kept
+    pub fn save_changes(&self, loaded: &AppConfig, path: &Path) -> Result<(), String> {
+        let to_table = |config: &AppConfig| toml::Table::try_from(config).map_err(|error| error.to_string());
+        let (loaded, current) = (to_table(loaded)?, to_table(self)?);
+        if loaded == current {
+            return Ok(());
+        }
+
+        let source = match std::fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(format!("{}: {}", path.display(), error)),
+        };
+        let mut document = source.parse::<toml_edit::DocumentMut>().map_err(|error| format!("{}: {}", path.display(), error))?;
+
+        merge_changes(document.as_table_mut(), &loaded, &current)?;
+        std::fs::write(path, document.to_string()).map_err(|error| format!("{}: {}", path.display(), error))
+    }
+}
@@ -0,0 +1,154 @@
+use std::{collections::VecDeque, io::Write, path::PathBuf, sync::{Mutex, PoisonError, TryLockError}};
+
+use crate::locale::{tr, tr_args};
+
+/// Directory crash reports are written to, relative to working directory
+pub const REPORT_DIRECTORY: &str = "crash_reports";
+
+/// Count of last log lines kept for crash report
+const LOG_LINE_COUNT: usize = 100;
+
+static LOG: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+static ADAPTER: Mutex<Option<String>> = Mutex::new(None);
+static SETTINGS: Mutex<Option<String>> = Mutex::new(None);
+static RENDER_SETTINGS: Mutex<Option<String>> = Mutex::new(None);
+
+/// Print line to stdout (or stderr if `is_error` is set) and keep it for crash report
+pub fn log(is_error: bool, line: String) {
+    if is_error {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+
+    let mut lines = LOG.lock().unwrap_or_else(PoisonError::into_inner);
+    if lines.len() == LOG_LINE_COUNT {
+        lines.pop_front();
+    }
+    lines.push_back(line);
+}
+
+/// `println!` whose line is kept for crash report, used as `crash::info!`
+#[macro_export]
+macro_rules! crash_info {
+    ($($arg:tt)*) => {
+        $crate::crash::log(false, format!($($arg)*))
+    };
+}
+
+/// `eprintln!` whose line is kept for crash report, used as `crash::error!`
+#[macro_export]
+macro_rules! crash_error {
+    ($($arg:tt)*) => {
+        $crate::crash::log(true, format!($($arg)*))
+    };
+}
+
+pub use crate::{crash_error as error, crash_info as info};
+
+pub fn set_adapter(info: &wgpu::AdapterInfo) {
+    *ADAPTER.lock().unwrap_or_else(PoisonError::into_inner) = Some(format!("{:#?}", info));
+}
+
+/// Set settings written to crash report, e.g. config serialized to TOML
+pub fn set_settings(settings: String) {
+    *SETTINGS.lock().unwrap_or_else(PoisonError::into_inner) = Some(settings);
+}
+
+/// Set render settings written to crash report, called on every change as they may differ from config ones
+pub fn set_render_settings(settings: &crate::render::RenderSettings) {
+    *RENDER_SETTINGS.lock().unwrap_or_else(PoisonError::into_inner) = toml::to_string(settings).ok();
+}
+
+/// Lock state without blocking, as panicking thread may hold the lock already
+fn try_read<T, R>(mutex: &Mutex<T>, read: impl FnOnce(&T) -> R) -> Option<R> {
+    match mutex.try_lock() {
+        Ok(value) => Some(read(&value)),
+        Err(TryLockError::Poisoned(error)) => Some(read(&error.into_inner())),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+fn write_report(info: &std::panic::PanicHookInfo) -> std::io::Result<PathBuf> {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let message = info.payload().downcast_ref::<&str>().copied()
+        .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("<non-string payload>");
+    let location = info.location().map_or("<unknown>".to_string(), ToString::to_string);
+    let thread = std::thread::current();
+    let unavailable = || "<unavailable>".to_string();
+
+    let adapter = try_read(&ADAPTER, |adapter| adapter.clone().unwrap_or_else(unavailable)).unwrap_or_else(unavailable);
+    let settings = try_read(&SETTINGS, |settings| settings.clone().unwrap_or_else(unavailable)).unwrap_or_else(unavailable);
+    let render_settings = try_read(&RENDER_SETTINGS, |settings| settings.clone().unwrap_or_else(unavailable)).unwrap_or_else(unavailable);
+    let log = try_read(&LOG, |lines| lines.iter().map(String::as_str).collect::<Vec<_>>().join("\n")).unwrap_or_else(unavailable);
+
+    std::fs::create_dir_all(REPORT_DIRECTORY)?;
+    // Milliseconds keep reports of panics in the same second apart
+    let path = PathBuf::from(REPORT_DIRECTORY).join(format!("crash-{}-{:03}.txt", time.as_secs(), time.subsec_millis()));
+    let mut file = std::fs::File::create(&path)?;
+
+    writeln!(file, "P_TR {} crash report", env!("CARGO_PKG_VERSION"))?;
+    writeln!(file, "Time: {} (Unix seconds)", time.as_secs())?;
+    writeln!(file, "OS: {} {}", std::env::consts::OS, std::env::consts::ARCH)?;
+    writeln!(file, "Thread: {}", thread.name().unwrap_or("<unnamed>"))?;
+    writeln!(file, "Panic: {}", message)?;
+    writeln!(file, "Location: {}", location)?;
+    writeln!(file, "\n[Backtrace]\n{}", std::backtrace::Backtrace::force_capture())?;
+    writeln!(file, "\n[Adapter]\n{}", adapter)?;
+    writeln!(file, "\n[Settings]\n{}", settings)?;
+    writeln!(file, "\n[Render settings]\n{}", render_settings)?;
+    writeln!(file, "\n[Log, last {} lines]\n{}", LOG_LINE_COUNT, log)?;
+
+    Ok(path)
+} // fn write_report
+
+/// Show message box by platform tools, returns false if none of them worked
+fn show_dialog(title: &str, message: &str) -> bool {
+    use std::process::Command;
+
+    let succeeded = |command: &mut Command| command.status().is_ok_and(|status| status.success());
+
+    if cfg!(target_os = "windows") {
+        let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+        succeeded(Command::new("powershell").args([
+            "-NoProfile",
+            "-Command",
+            &format!(
+                "Add-Type -AssemblyName PresentationFramework; [System.Windows.MessageBox]::Show({}, {}, 'OK', 'Error')",
+                quote(message),
+                quote(title),
+            ),
+        ]))
+    } else if cfg!(target_os = "macos") {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        succeeded(Command::new("osascript").args([
+            "-e",
+            &format!("display alert {} message {} as critical", quote(title), quote(message)),
+        ]))
+    } else {
+        succeeded(Command::new("zenity").args(["--error", "--no-markup", "--title", title, "--text", message]))
+            || succeeded(Command::new("kdialog").args(["--title", title, "--error", message]))
+    }
+} // fn show_dialog
+
+/// Install panic hook writing crash report to `REPORT_DIRECTORY` and pointing user to it.
+/// Report is local only, nothing is sent anywhere.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        match write_report(info) {
+            Ok(path) => {
+                let message = tr_args("crash.saved", &[&path.display()]);
+                eprintln!("{}", message);
+                show_dialog(tr("crash.title"), &message);
+            }
+            Err(error) => eprintln!("{}", tr_args("error.crash_report", &[&error])),
+        }
+    }));
+}
@@ -0,0 +1,265 @@
+use std::{io::Write, path::{Path, PathBuf}, process::{Child, ChildStdin, Command, Stdio}};
+
+use crate::{math::Ext2u, render::{Aov, AovImage, Render}};
+
+/// Image file format of exported frames
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FrameFormat {
+    /// 8-bit sRGB of presented image, with post-processing and tone mapping
+    #[default]
+    Png,
+    /// Linear 32-bit float RGB of accumulated radiance before post-processing, uncompressed
+    Exr,
+}
+
+impl FrameFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Exr => "exr",
+        }
+    }
+
+    /// Format of file with given extension, PNG for unknown ones
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("exr") => FrameFormat::Exr,
+            _ => FrameFormat::Png,
+        }
+    }
+}
+
+/// Presented frames that are exported
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FrameTrigger {
+    /// Frame reaching target sample count of render settings
+    #[default]
+    Converged,
+    /// Every Nth presented frame
+    Every(u32),
+}
+
+fn default_frame_rate() -> u32 {
+    30
+}
+
+fn default_ffmpeg_executable() -> PathBuf {
+    PathBuf::from("ffmpeg")
+}
+
+/// Video encoding of exported frames by ffmpeg child process
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct FfmpegConfig {
+    /// Video file, container and codec are chosen by ffmpeg from extension
+    pub output: PathBuf,
+    #[serde(default = "default_frame_rate")]
+    pub frame_rate: u32,
+    #[serde(default = "default_ffmpeg_executable")]
+    pub executable: PathBuf,
+}
+
+/// Frame export options, export is disabled unless directory or ffmpeg output is set
+#[derive(Clone, Debug, Default, serde::Deserialize, serde::Serialize)]
+pub struct ExportConfig {
+    /// Directory of numbered frame files
+    #[serde(default)]
+    pub directory: Option<PathBuf>,
+    #[serde(default)]
+    pub format: FrameFormat,
+    #[serde(default)]
+    pub trigger: FrameTrigger,
+    #[serde(default)]
+    pub ffmpeg: Option<FfmpegConfig>,
+}
+
+/// Running ffmpeg process that reads raw RGB frames from standard input
+struct FfmpegPipe {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    extent: Ext2u,
+}
+
+impl FfmpegPipe {
+    fn spawn(config: &FfmpegConfig, extent: Ext2u) -> Result<Self, String> {
+        let mut child = Command::new(&config.executable)
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", extent.w, extent.h)])
+            .args(["-r", &config.frame_rate.to_string()])
+            .args(["-i", "-", "-pix_fmt", "yuv420p"])
+            .arg(&config.output)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|error| format!("{}: {}", config.executable.display(), error))?;
+
+        Ok(Self { stdin: child.stdin.take(), child, extent })
+    }
+}
+
+impl Drop for FfmpegPipe {
+    fn drop(&mut self) {
+        // Closed input finishes the video
+        self.stdin = None;
+        _ = self.child.wait();
+    }
+}
+
+/// Linear value to 8-bit sRGB
+fn encode_srgb(value: f32) -> u8 {
+    let value = value.clamp(0.0, 1.0);
+    let encoded = if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 };
+    (encoded * 255.0 + 0.5) as u8
+}
+
+/// Interleaved 8-bit sRGB RGB pixels of three-channel image
+fn srgb_bytes(image: &AovImage) -> Vec<u8> {
+    image.data.chunks(image.channel_count).flat_map(|pixel| [0, 1, 2].map(|channel| encode_srgb(pixel[channel]))).collect()
+}
+
+/// Write three-channel image as uncompressed single-part scanline OpenEXR file
+fn write_exr(path: &Path, image: &AovImage) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let (width, height) = (image.extent.w as i32, image.extent.h as i32);
+
+    let mut header = Vec::<u8>::new();
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        header.extend_from_slice(name.as_bytes());
+        header.push(0);
+        header.extend_from_slice(kind.as_bytes());
+        header.push(0);
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+
+    // Channels are stored in alphabetical order, each is 32-bit float without subsampling
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend_from_slice(name.as_bytes());
+        channels.extend_from_slice(&[0, 2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0]);
+    }
+    channels.push(0);
+
+    let window = [0, 0, width - 1, height - 1].iter().flat_map(|value: &i32| value.to_le_bytes()).collect::<Vec<_>>();
+
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+
+    file.write_all(&[0x76, 0x2F, 0x31, 0x01])?;
+    file.write_all(&2u32.to_le_bytes())?;
+    file.write_all(&header)?;
+
+    // Offset table is followed by scanlines, every scanline is block with its Y coordinate and data size
+    let line_size = 3 * 4 * width as u64;
+    let first_line_offset = 8 + header.len() as u64 + 8 * height as u64;
+    for y in 0..height as u64 {
+        file.write_all(&(first_line_offset + y * (8 + line_size)).to_le_bytes())?;
+    }
+
+    for (y, row) in image.data.chunks(image.extent.w as usize * image.channel_count).enumerate() {
+        file.write_all(&(y as i32).to_le_bytes())?;
+        file.write_all(&(line_size as i32).to_le_bytes())?;
+        for channel in [2, 1, 0] {
+            for pixel in row.chunks(image.channel_count) {
+                file.write_all(&pixel[channel].to_le_bytes())?;
+            }
+        }
+    }
+
+    file.flush()
+} // fn write_exr
+
+/// Last rendered frame in form stored by `format`: presented image for display-referred PNG,
+/// linear radiance before post-processing for EXR
+pub fn read_frame(render: &mut Render, format: FrameFormat) -> AovImage {
+    match format {
+        FrameFormat::Png => render.read_presented(),
+        FrameFormat::Exr => render.read_aov(Aov::Beauty),
+    }
+}
+
+/// Write three-channel linear image to file
+pub fn save_image(path: &Path, format: FrameFormat, image: &AovImage) -> Result<(), String> {
+    match format {
+        FrameFormat::Png => image::RgbImage::from_raw(image.extent.w, image.extent.h, srgb_bytes(image))
+            .ok_or_else(|| "invalid image size".to_string())?
+            .save(path)
+            .map_err(|error| format!("{}: {}", path.display(), error)),
+        FrameFormat::Exr => write_exr(path, image).map_err(|error| format!("{}: {}", path.display(), error)),
+    }
+}
+
+/// Writes presented frames to numbered image files and ffmpeg
+pub struct FrameExporter {
+    config: ExportConfig,
+    /// Number of written frames, used in file names
+    frame_count: u32,
+    presented_frame_count: u32,
+    ffmpeg: Option<FfmpegPipe>,
+}
+
+impl FrameExporter {
+    pub fn new(config: ExportConfig) -> Self {
+        Self { config, frame_count: 0, presented_frame_count: 0, ffmpeg: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.directory.is_some() || self.config.ffmpeg.is_some()
+    }
+
+    /// Count presented frame and check if it must be exported, `is_converged` is set for frame that reached target sample count
+    pub fn is_frame_due(&mut self, is_converged: bool) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        let index = self.presented_frame_count;
+        self.presented_frame_count += 1;
+
+        match self.config.trigger {
+            FrameTrigger::Converged => is_converged,
+            FrameTrigger::Every(step) => index.is_multiple_of(step.max(1)),
+        }
+    }
+
+    /// Write last rendered frame to next numbered file and ffmpeg, video gets presented image.
+    /// Failed ffmpeg process is not restarted, as video would miss frames anyway.
+    pub fn write(&mut self, render: &mut Render) -> Result<(), String> {
+        let index = self.frame_count;
+        self.frame_count += 1;
+
+        if let Some(directory) = &self.config.directory {
+            std::fs::create_dir_all(directory).map_err(|error| format!("{}: {}", directory.display(), error))?;
+            let path = directory.join(format!("frame_{:05}.{}", index, self.config.format.extension()));
+            save_image(&path, self.config.format, &read_frame(render, self.config.format))?;
+        }
+
+        let Some(config) = &self.config.ffmpeg else {
+            return Ok(());
+        };
+        let image = &render.read_presented();
+
+        if index == 0 {
+            self.ffmpeg = Some(FfmpegPipe::spawn(config, image.extent)?);
+        }
+        let Some(pipe) = &mut self.ffmpeg else {
+            return Ok(());
+        };
+
+        let result = if pipe.extent.w != image.extent.w || pipe.extent.h != image.extent.h {
+            Err(format!("frame size changed to {}x{}, video is finished", image.extent.w, image.extent.h))
+        } else {
+            pipe.stdin.as_mut().map_or(Ok(()), |stdin| stdin.write_all(&srgb_bytes(image))).map_err(|error| error.to_string())
+        };
+
+        if result.is_err() {
+            self.ffmpeg = None;
+        }
+        result
+    } // fn write
+}
@@ -0,0 +1,353 @@
+use std::path::Path;
+
+use crate::{benchmark::json_string, render::AovImage};
+
+/// Default thresholds of metrics images are considered matching within. Golden and compared images are traced
+/// with the same sample sequence, so only adapter differences and regressions should show up.
+pub const MAX_RMSE: f64 = 0.02;
+pub const MIN_SSIM: f64 = 0.95;
+pub const MAX_FLIP: f64 = 0.05;
+
+/// Metric thresholds of comparison, defaults are overridden by `--golden-threshold`
+#[derive(Copy, Clone, Debug)]
+pub struct Thresholds {
+    pub max_rmse: f64,
+    pub min_ssim: f64,
+    pub max_flip: f64,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { max_rmse: MAX_RMSE, min_ssim: MIN_SSIM, max_flip: MAX_FLIP }
+    }
+}
+
+impl Thresholds {
+    /// Defaults with `(metric, threshold)` overrides applied in order
+    pub fn new(overrides: &[(Threshold, f64)]) -> Self {
+        let mut thresholds = Self::default();
+        for &(metric, value) in overrides {
+            match metric {
+                Threshold::Rmse => thresholds.max_rmse = value,
+                Threshold::Ssim => thresholds.min_ssim = value,
+                Threshold::Flip => thresholds.max_flip = value,
+            }
+        }
+        thresholds
+    }
+}
+
+/// Metric whose threshold is overridden
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Threshold {
+    Rmse,
+    Ssim,
+    Flip,
+}
+
+/// Parse `metric=value` threshold override, e.g. `flip=0.08`
+pub fn parse_threshold(argument: &str) -> Result<(Threshold, f64), String> {
+    let (name, value) = argument.split_once('=').ok_or_else(|| format!("expected metric=value, got '{}'", argument))?;
+    let metric = match name.trim() {
+        "rmse" => Threshold::Rmse,
+        "ssim" => Threshold::Ssim,
+        "flip" => Threshold::Flip,
+        name => return Err(format!("unknown metric '{}', expected rmse, ssim or flip", name)),
+    };
+    let value = value.trim().parse::<f64>().ok().filter(|value| value.is_finite()).ok_or_else(|| format!("invalid threshold '{}'", value))?;
+    Ok((metric, value))
+}
+
+/// Viewing condition of FLIP, pixels per degree of visual angle. Default of FLIP paper:
+/// 0.7 m wide 4K monitor viewed from 0.7 m.
+const FLIP_PIXELS_PER_DEGREE: f32 = 67.0;
+
+/// Gaussian window of SSIM
+const SSIM_SIGMA: f32 = 1.5;
+const SSIM_C1: f32 = 0.01 * 0.01;
+const SSIM_C2: f32 = 0.03 * 0.03;
+
+/// Single-channel image, rows are top to bottom
+#[derive(Clone)]
+struct Plane {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Plane {
+    fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        Self { width, height, data }
+    }
+
+    fn map2(&self, other: &Plane, function: impl Fn(f32, f32) -> f32) -> Plane {
+        Plane::new(self.width, self.height, self.data.iter().zip(&other.data).map(|(&a, &b)| function(a, b)).collect())
+    }
+
+    /// Convolution by separable kernel of `horizontal` and `vertical` odd-sized factors, edge pixels are repeated
+    fn convolve(&self, horizontal: &[f32], vertical: &[f32]) -> Plane {
+        let sample = |data: &[f32], x: isize, y: isize| {
+            data[y.clamp(0, self.height as isize - 1) as usize * self.width + x.clamp(0, self.width as isize - 1) as usize]
+        };
+
+        let radius = horizontal.len() as isize / 2;
+        let rows = (0..self.height * self.width).map(|index| {
+            let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+            horizontal.iter().enumerate().map(|(offset, weight)| weight * sample(&self.data, x + offset as isize - radius, y)).sum()
+        }).collect::<Vec<f32>>();
+
+        let radius = vertical.len() as isize / 2;
+        let data = (0..self.height * self.width).map(|index| {
+            let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+            vertical.iter().enumerate().map(|(offset, weight)| weight * sample(&rows, x, y + offset as isize - radius)).sum()
+        }).collect();
+
+        Plane::new(self.width, self.height, data)
+    }
+
+    fn mean(&self) -> f64 {
+        self.data.iter().map(|&value| value as f64).sum::<f64>() / self.data.len().max(1) as f64
+    }
+}
+
+/// Unnormalized gaussian `exp(-x^2 / (2 sigma^2))` sampled at integer offsets within `radius`
+fn gaussian(radius: usize, sigma: f32) -> Vec<f32> {
+    (-(radius as isize)..=radius as isize).map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp()).collect()
+}
+
+fn normalized(kernel: Vec<f32>) -> Vec<f32> {
+    let sum = kernel.iter().sum::<f32>();
+    kernel.into_iter().map(|weight| weight / sum).collect()
+}
+
+fn srgb_encode(value: f32) -> f32 {
+    let value = value.clamp(0.0, 1.0);
+    if value <= 0.0031308 { value * 12.92 } else { 1.055 * value.powf(1.0 / 2.4) - 0.055 }
+}
+
+fn luminance(rgb: [f32; 3]) -> f32 {
+    0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+}
+
+/// Root mean square difference of linear radiance over all channels
+fn rmse(reference: &[f32], test: &[f32]) -> f64 {
+    let sum = reference.iter().zip(test).map(|(&a, &b)| (a as f64 - b as f64).powi(2)).sum::<f64>();
+    (sum / reference.len().max(1) as f64).sqrt()
+}
+
+/// Mean structural similarity of sRGB-encoded luminance, compared within gaussian window
+fn ssim(reference: &Plane, test: &Plane) -> f64 {
+    let window = normalized(gaussian((SSIM_SIGMA * 3.0).ceil() as usize, SSIM_SIGMA));
+    let blur = |plane: &Plane| plane.convolve(&window, &window);
+
+    let (mean_a, mean_b) = (blur(reference), blur(test));
+    let square_a = blur(&reference.map2(reference, |a, b| a * b));
+    let square_b = blur(&test.map2(test, |a, b| a * b));
+    let product = blur(&reference.map2(test, |a, b| a * b));
+
+    let data = (0..reference.data.len()).map(|index| {
+        let (ma, mb) = (mean_a.data[index], mean_b.data[index]);
+        let variance_a = square_a.data[index] - ma * ma;
+        let variance_b = square_b.data[index] - mb * mb;
+        let covariance = product.data[index] - ma * mb;
+        ((2.0 * ma * mb + SSIM_C1) * (2.0 * covariance + SSIM_C2)) / ((ma * ma + mb * mb + SSIM_C1) * (variance_a + variance_b + SSIM_C2))
+    }).collect();
+
+    Plane::new(reference.width, reference.height, data).mean()
+}
+
+const RGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.119192, 0.9503041],
+];
+const XYZ_TO_RGB: [[f32; 3]; 3] = [
+    [ 3.2404542, -1.5371385, -0.4985314],
+    [-0.969266,  1.8760108,  0.0415560],
+    [ 0.0556434, -0.2040259,  1.0572252],
+];
+/// XYZ of linear RGB white
+const WHITE: [f32; 3] = [0.950489, 1.0, 1.08884];
+
+fn transform(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+fn rgb_to_ycxcz(rgb: [f32; 3]) -> [f32; 3] {
+    let [x, y, z] = transform(&RGB_TO_XYZ, rgb);
+    let (x, y, z) = (x / WHITE[0], y / WHITE[1], z / WHITE[2]);
+    [116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z)]
+}
+
+fn ycxcz_to_rgb(ycxcz: [f32; 3]) -> [f32; 3] {
+    let y = (ycxcz[0] + 16.0) / 116.0;
+    let xyz = [(ycxcz[1] / 500.0 + y) * WHITE[0], y * WHITE[1], (y - ycxcz[2] / 200.0) * WHITE[2]];
+    transform(&XYZ_TO_RGB, xyz)
+}
+
+/// CIELAB with Hunt effect applied, chroma is scaled by lightness
+fn rgb_to_hunt_lab(rgb: [f32; 3]) -> [f32; 3] {
+    let f = |t: f32| if t > (6.0f32 / 29.0).powi(3) { t.cbrt() } else { t / (3.0 * (6.0f32 / 29.0).powi(2)) + 4.0 / 29.0 };
+    let [x, y, z] = transform(&RGB_TO_XYZ, rgb);
+    let (fx, fy, fz) = (f(x / WHITE[0]), f(y / WHITE[1]), f(z / WHITE[2]));
+    let lightness = 116.0 * fy - 16.0;
+    [lightness, 0.01 * lightness * 500.0 * (fx - fy), 0.01 * lightness * 200.0 * (fy - fz)]
+}
+
+/// HyAB color distance, L1 in lightness and L2 in chroma
+fn hyab(a: [f32; 3], b: [f32; 3]) -> f32 {
+    (a[0] - b[0]).abs() + ((a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Contrast sensitivity filters of YCxCz channels, each is sum of separable gaussians given by
+/// horizontal and vertical factors. Parameters are from FLIP paper.
+fn csf_filters() -> [Vec<Vec<f32>>; 3] {
+    const PARAMETERS: [[(f32, f32); 2]; 3] = [
+        [(1.0, 0.0047), (0.0, 1e-5)],
+        [(1.0, 0.0053), (0.0, 1e-5)],
+        [(34.1, 0.04), (13.5, 0.025)],
+    ];
+    let max_scale = PARAMETERS.iter().flatten().map(|&(_, b)| b).fold(0.0, f32::max);
+    let radius = (3.0 * (max_scale / (2.0 * std::f32::consts::PI.powi(2))).sqrt() * FLIP_PIXELS_PER_DEGREE).ceil() as isize;
+
+    PARAMETERS.map(|terms| {
+        let terms = terms.map(|(a, b)| {
+            let factor = (-radius..=radius).map(|x| {
+                let degrees = x as f32 / FLIP_PIXELS_PER_DEGREE;
+                (-std::f32::consts::PI.powi(2) * degrees * degrees / b).exp()
+            }).collect::<Vec<f32>>();
+            (a * (std::f32::consts::PI / b).sqrt(), factor)
+        });
+        // Kernel is normalized to unit sum, scale of term is split between its factors
+        let sum = terms.iter().map(|(scale, factor)| scale * factor.iter().sum::<f32>().powi(2)).sum::<f32>();
+        terms.into_iter()
+            .filter(|(scale, _)| *scale > 0.0)
+            .map(|(scale, factor)| factor.into_iter().map(|weight| weight * (scale / sum).sqrt()).collect())
+            .collect()
+    })
+}
+
+/// Edge or point detector of FLIP, derivative of gaussian with positive and negative weights normalized
+/// to unit sums, and gaussian factor across it
+fn feature_kernel(is_point: bool) -> (Vec<f32>, Vec<f32>) {
+    let sigma = 0.5 * 0.082 * FLIP_PIXELS_PER_DEGREE;
+    let radius = (3.0 * sigma).ceil() as usize;
+    let gauss = gaussian(radius, sigma);
+    let derivative = gauss.iter().enumerate().map(|(index, weight)| {
+        let x = index as f32 - radius as f32;
+        weight * if is_point { x * x / (sigma * sigma) - 1.0 } else { -x }
+    }).collect::<Vec<f32>>();
+
+    let positive = derivative.iter().filter(|&&weight| weight > 0.0).sum::<f32>();
+    let negative = -derivative.iter().filter(|&&weight| weight < 0.0).sum::<f32>();
+    let derivative = derivative.into_iter().map(|weight| if weight > 0.0 { weight / positive } else { weight / negative }).collect();
+    (derivative, normalized(gauss))
+}
+
+/// Mean LDR-FLIP error of linear RGB images clamped to unit range
+fn flip(reference: &AovImage, test: &AovImage) -> f64 {
+    let (width, height) = (reference.extent.w as usize, reference.extent.h as usize);
+    let to_ycxcz = |image: &AovImage| {
+        let ycxcz = image.data.chunks(3).map(|rgb| rgb_to_ycxcz([rgb[0], rgb[1], rgb[2]].map(|value| value.clamp(0.0, 1.0)))).collect::<Vec<_>>();
+        [0, 1, 2].map(|channel| Plane::new(width, height, ycxcz.iter().map(|color| color[channel]).collect()))
+    };
+    let (reference, test) = (to_ycxcz(reference), to_ycxcz(test));
+
+    // Color pipeline, images are filtered as perceived and compared in perceptual color space
+    let filters = csf_filters();
+    let perceived = |image: &[Plane; 3]| {
+        let filtered = [0, 1, 2].map(|channel| {
+            filters[channel].iter().map(|factor| image[channel].convolve(factor, factor)).reduce(|a, b| a.map2(&b, |a, b| a + b)).unwrap()
+        });
+        (0..width * height).map(|index| {
+            let rgb = ycxcz_to_rgb([0, 1, 2].map(|channel| filtered[channel].data[index]));
+            rgb_to_hunt_lab(rgb.map(|value| value.clamp(0.0, 1.0)))
+        }).collect::<Vec<_>>()
+    };
+    let (reference_lab, test_lab) = (perceived(&reference), perceived(&test));
+
+    const COLOR_EXPONENT: f32 = 0.7;
+    const COLOR_CUTOFF: f32 = 0.4;
+    const COLOR_CUTOFF_ERROR: f32 = 0.95;
+    let max_color_error = hyab(rgb_to_hunt_lab([0.0, 1.0, 0.0]), rgb_to_hunt_lab([0.0, 0.0, 1.0])).powf(COLOR_EXPONENT);
+    let color_error = reference_lab.iter().zip(&test_lab).map(|(&a, &b)| {
+        let error = hyab(a, b).powf(COLOR_EXPONENT);
+        if error < COLOR_CUTOFF * max_color_error {
+            COLOR_CUTOFF_ERROR / COLOR_CUTOFF * error / max_color_error
+        } else {
+            COLOR_CUTOFF_ERROR + (error - COLOR_CUTOFF * max_color_error) / (max_color_error - COLOR_CUTOFF * max_color_error) * (1.0 - COLOR_CUTOFF_ERROR)
+        }
+    });
+
+    // Feature pipeline, differences of edges and points of achromatic channel
+    let features = |image: &[Plane; 3], is_point: bool| {
+        let lightness = Plane::new(width, height, image[0].data.iter().map(|y| (y + 16.0) / 116.0).collect());
+        let (derivative, gauss) = feature_kernel(is_point);
+        let along_x = lightness.convolve(&derivative, &gauss);
+        let along_y = lightness.convolve(&gauss, &derivative);
+        along_x.map2(&along_y, |x, y| (x * x + y * y).sqrt())
+    };
+    let edge_difference = features(&reference, false).map2(&features(&test, false), |a, b| (a - b).abs());
+    let point_difference = features(&reference, true).map2(&features(&test, true), |a, b| (a - b).abs());
+    let feature_error = edge_difference.map2(&point_difference, |edge, point| (edge.max(point) / std::f32::consts::SQRT_2).sqrt());
+
+    let error = color_error.zip(&feature_error.data).map(|(color, &feature)| color.powf(1.0 - feature)).collect();
+    Plane::new(width, height, error).mean()
+} // fn flip
+
+/// Metric value and threshold it passes within
+pub struct Metric {
+    pub name: &'static str,
+    pub value: f64,
+    pub threshold: f64,
+    /// Metric grows with similarity, so threshold is its minimum
+    pub is_similarity: bool,
+}
+
+impl Metric {
+    pub fn is_passed(&self) -> bool {
+        if self.is_similarity { self.value >= self.threshold } else { self.value <= self.threshold }
+    }
+
+    fn to_json(&self) -> String {
+        format!("{{\"value\":{:.6},\"threshold\":{},\"passed\":{}}}", self.value, self.threshold, self.is_passed())
+    }
+}
+
+/// Comparison of rendered image with golden one
+pub struct Comparison {
+    pub golden_path: String,
+    pub metrics: Vec<Metric>,
+}
+
+impl Comparison {
+    /// Compare `image` with golden image read from PFM file at `path`
+    pub fn new(path: &Path, image: &AovImage, thresholds: &Thresholds) -> Result<Self, String> {
+        let golden = AovImage::read_pfm(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        if golden.extent.w != image.extent.w || golden.extent.h != image.extent.h || golden.channel_count != 3 || image.channel_count != 3 {
+            return Err(format!("{}: golden image is {}x{} with {} channels, rendered one is {}x{} with {}",
+                path.display(), golden.extent.w, golden.extent.h, golden.channel_count, image.extent.w, image.extent.h, image.channel_count));
+        }
+
+        let (width, height) = (image.extent.w as usize, image.extent.h as usize);
+        let srgb_luminance = |image: &AovImage| Plane::new(width, height, image.data.chunks(3).map(|rgb| srgb_encode(luminance([rgb[0], rgb[1], rgb[2]]))).collect());
+
+        Ok(Self {
+            golden_path: path.display().to_string(),
+            metrics: vec![
+                Metric { name: "rmse", value: rmse(&golden.data, &image.data), threshold: thresholds.max_rmse, is_similarity: false },
+                Metric { name: "ssim", value: ssim(&srgb_luminance(&golden), &srgb_luminance(image)), threshold: thresholds.min_ssim, is_similarity: true },
+                Metric { name: "flip", value: flip(&golden, image), threshold: thresholds.max_flip, is_similarity: false },
+            ],
+        })
+    }
+
+    pub fn is_passed(&self) -> bool {
+        self.metrics.iter().all(Metric::is_passed)
+    }
+
+    pub fn to_json(&self) -> String {
+        let metrics = self.metrics.iter().map(|metric| format!("\"{}\":{}", metric.name, metric.to_json())).collect::<Vec<_>>();
+        format!("{{\"path\":{},\"metrics\":{{{}}},\"passed\":{}}}", json_string(&self.golden_path), metrics.join(","), self.is_passed())
+    }
+}
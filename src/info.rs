@@ -0,0 +1,82 @@
+use crate::{config::AppConfig, locale::{tr, tr_args}, render::{self, AdapterSelector}};
+
+/// Features relevant to path tracer, printed as supported or not
+const FEATURES: &[(&str, wgpu::Features)] = &[
+    ("ray query", wgpu::Features::RAY_QUERY),
+    ("timestamp queries", wgpu::Features::TIMESTAMP_QUERY),
+    ("float32 filterable", wgpu::Features::FLOAT32_FILTERABLE),
+    ("shader f16", wgpu::Features::SHADER_F16),
+    ("texture binding arrays", wgpu::Features::TEXTURE_BINDING_ARRAY),
+];
+
+fn print_limits(limits: &wgpu::Limits, indent: &str) {
+    let values: &[(&str, u64)] = &[
+        ("max_texture_dimension_2d", limits.max_texture_dimension_2d.into()),
+        ("max_buffer_size", limits.max_buffer_size),
+        ("max_storage_buffer_binding_size", limits.max_storage_buffer_binding_size.into()),
+        ("max_uniform_buffer_binding_size", limits.max_uniform_buffer_binding_size.into()),
+        ("max_storage_buffers_per_shader_stage", limits.max_storage_buffers_per_shader_stage.into()),
+        ("max_sampled_textures_per_shader_stage", limits.max_sampled_textures_per_shader_stage.into()),
+        ("max_bind_groups", limits.max_bind_groups.into()),
+        ("max_color_attachments", limits.max_color_attachments.into()),
+    ];
+    for (name, value) in values {
+        println!("{}{}: {}", indent, name, value);
+    }
+}
+
+fn print_adapter(adapter: &wgpu::Adapter) {
+    let info = adapter.get_info();
+    println!("    {} ({:?}, {:?})", info.name, info.backend, info.device_type);
+    println!("        driver: {} {}", info.driver, info.driver_info);
+    println!("        vendor: {:#06x}, device: {:#06x}", info.vendor, info.device);
+
+    let features = adapter.features();
+    for (name, feature) in FEATURES {
+        println!("        {}: {}", name, tr(if features.contains(*feature) { "info.yes" } else { "info.no" }));
+    }
+    print_limits(&adapter.limits(), "        ");
+}
+
+/// Print adapters of all backends, their capabilities, and device and render settings renderer would use,
+/// `p_tr info`. Adapter is chosen without window, so surface support isn't checked.
+pub fn print(selector: &AdapterSelector, config: &AppConfig) -> std::process::ExitCode {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: selector.backends,
+        ..Default::default()
+    });
+
+    println!("{}", tr_args("info.version", &[&env!("CARGO_PKG_VERSION")]));
+    println!("{}", tr_args("info.backends", &[&format!("{:?}", selector.backends)]));
+    println!();
+
+    let adapters = instance.enumerate_adapters(selector.backends);
+    println!("{}", tr_args("info.adapters", &[&adapters.len()]));
+    for adapter in &adapters {
+        print_adapter(adapter);
+    }
+    println!();
+
+    let Some(adapter) = selector.select(&instance, None) else {
+        eprintln!("{}", tr("error.no_adapter"));
+        return std::process::ExitCode::FAILURE;
+    };
+    let info = adapter.get_info();
+    println!("{}", tr_args("info.selected", &[&info.name, &format!("{:?}", info.backend)]));
+    let is_software = info.device_type == wgpu::DeviceType::Cpu;
+    if is_software {
+        println!("{}", tr("info.software"));
+    }
+    println!("{}", tr_args("info.device_features", &[&format!("{:?}", render::device_features(adapter.features()))]));
+    println!("{}", tr("info.device_limits"));
+    print_limits(&adapter.limits(), "    ");
+    let settings = if is_software && config.reduce_software_settings { config.render.reduced() } else { config.render };
+    if let Ok(settings) = toml::to_string(&settings) {
+        println!("{}", tr("info.render_settings"));
+        for line in settings.lines() {
+            println!("    {}", line);
+        }
+    }
+
+    std::process::ExitCode::SUCCESS
+} // fn print
@@ -1,7 +1,112 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::math::Vec2f;
 
 pub type KeyCode = winit::keyboard::KeyCode;
+pub type MouseButton = winit::event::MouseButton;
+
+/// Control held by key. Controls act every frame while their keys are held, unlike `action::Action`s
+/// that are triggered once by key press.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Deserialize, serde::Serialize)]
+pub enum Control {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    TurnLeft,
+    TurnRight,
+    TurnUp,
+    TurnDown,
+    /// Camera is rolled counterclockwise around view direction
+    RollLeft,
+    RollRight,
+    /// Selected instances are moved along camera heading
+    SelectionForward,
+    SelectionBackward,
+    SelectionLeft,
+    SelectionRight,
+    SelectionUp,
+    SelectionDown,
+    /// Selected instances are rotated around Y axis
+    SelectionRotateLeft,
+    SelectionRotateRight,
+    SelectionGrow,
+    SelectionShrink,
+    ApertureOpen,
+    ApertureClose,
+    FocusFarther,
+    FocusCloser,
+    ExposureUp,
+    ExposureDown,
+    /// Vertical field of view, or view height of orthographic camera, is widened
+    FovWider,
+    FovNarrower,
+}
+
+/// Keys of controls
+#[derive(Clone, Debug)]
+pub struct ControlMap {
+    keys: HashMap<Control, KeyCode>,
+}
+
+impl ControlMap {
+    /// Default keys with keys of controls present in `overrides` replaced by them
+    pub fn new(overrides: &BTreeMap<Control, KeyCode>) -> Self {
+        let mut keys = HashMap::from([
+            (Control::MoveForward, KeyCode::KeyW),
+            (Control::MoveBackward, KeyCode::KeyS),
+            (Control::MoveLeft, KeyCode::KeyA),
+            (Control::MoveRight, KeyCode::KeyD),
+            (Control::MoveUp, KeyCode::KeyR),
+            (Control::MoveDown, KeyCode::KeyF),
+            (Control::TurnLeft, KeyCode::ArrowLeft),
+            (Control::TurnRight, KeyCode::ArrowRight),
+            (Control::TurnUp, KeyCode::ArrowUp),
+            (Control::TurnDown, KeyCode::ArrowDown),
+            (Control::RollLeft, KeyCode::KeyQ),
+            (Control::RollRight, KeyCode::KeyE),
+            (Control::SelectionForward, KeyCode::KeyI),
+            (Control::SelectionBackward, KeyCode::KeyK),
+            (Control::SelectionLeft, KeyCode::KeyJ),
+            (Control::SelectionRight, KeyCode::KeyL),
+            (Control::SelectionUp, KeyCode::KeyO),
+            (Control::SelectionDown, KeyCode::KeyU),
+            (Control::SelectionRotateLeft, KeyCode::KeyQ),
+            (Control::SelectionRotateRight, KeyCode::KeyE),
+            (Control::SelectionGrow, KeyCode::Equal),
+            (Control::SelectionShrink, KeyCode::Minus),
+            (Control::ApertureOpen, KeyCode::BracketRight),
+            (Control::ApertureClose, KeyCode::BracketLeft),
+            (Control::FocusFarther, KeyCode::Period),
+            (Control::FocusCloser, KeyCode::Comma),
+            (Control::ExposureUp, KeyCode::PageUp),
+            (Control::ExposureDown, KeyCode::PageDown),
+            (Control::FovWider, KeyCode::NumpadAdd),
+            (Control::FovNarrower, KeyCode::NumpadSubtract),
+        ]);
+        keys.extend(overrides);
+        Self { keys }
+    }
 
+    pub fn key(&self, control: Control) -> Option<KeyCode> {
+        self.keys.get(&control).copied()
+    }
+
+    /// Controls with their keys in control declaration order
+    pub fn iter(&self) -> impl Iterator<Item = (Control, KeyCode)> {
+        let mut keys = self.keys.iter().map(|(control, key)| (*control, *key)).collect::<Vec<_>>();
+        keys.sort();
+        keys.into_iter()
+    }
+}
+
+impl Default for ControlMap {
+    fn default() -> Self {
+        Self::new(&BTreeMap::new())
+    }
+}
 
 #[derive(Copy, Clone, PartialEq, Eq, Default, Hash)]
 pub struct KeyState {
@@ -11,6 +116,11 @@ pub struct KeyState {
 
 pub struct State<'a> {
     states: &'a HashMap<KeyCode, KeyState>,
+    controls: &'a ControlMap,
+    button_states: &'a HashMap<MouseButton, KeyState>,
+    cursor_position: Vec2f,
+    mouse_delta: Vec2f,
+    wheel_delta: f32,
 }
 
 impl<'a> State<'a> {
@@ -36,38 +146,137 @@ impl<'a> State<'a> {
 
         !state.pressed && state.changed
     }
+
+    pub fn is_control_pressed(&self, control: Control) -> bool {
+        self.controls.key(control).is_some_and(|key| self.is_key_pressed(key))
+    }
+
+    /// One if only `positive` control is held, minus one if only `negative` one is, zero otherwise
+    pub fn control_axis(&self, positive: Control, negative: Control) -> f32 {
+        (self.is_control_pressed(positive) as i32 - self.is_control_pressed(negative) as i32) as f32
+    }
+
+    pub fn get_button_state(&self, button: MouseButton) -> KeyState {
+        self.button_states
+            .get(&button)
+            .copied()
+            .unwrap_or(KeyState::default())
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.get_button_state(button).pressed
+    }
+
+    pub fn is_button_clicked(&self, button: MouseButton) -> bool {
+        let state = self.get_button_state(button);
+
+        state.pressed && state.changed
+    }
+
+    pub fn is_button_released(&self, button: MouseButton) -> bool {
+        let state = self.get_button_state(button);
+
+        !state.pressed && state.changed
+    }
+
+    /// Cursor position in window, in physical pixels from top left corner
+    pub fn cursor_position(&self) -> Vec2f {
+        self.cursor_position
+    }
+
+    /// Raw mouse movement since last `clear_changed`, in device units. It's not bound by window
+    /// borders or cursor position, so it keeps coming while cursor is grabbed.
+    pub fn mouse_delta(&self) -> Vec2f {
+        self.mouse_delta
+    }
+
+    /// Mouse wheel rotation since last `clear_changed`, in lines, positive away from user
+    pub fn wheel_delta(&self) -> f32 {
+        self.wheel_delta
+    }
 }
 
 pub struct Input {
     key_states: HashMap<KeyCode, KeyState>,
+    controls: ControlMap,
+    button_states: HashMap<MouseButton, KeyState>,
+    cursor_position: Vec2f,
+    mouse_delta: Vec2f,
+    wheel_delta: f32,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Input {
     pub fn new() -> Input {
         Input {
             key_states: HashMap::new(),
+            controls: ControlMap::default(),
+            button_states: HashMap::new(),
+            cursor_position: Vec2f::default(),
+            mouse_delta: Vec2f::default(),
+            wheel_delta: 0.0,
         }
     }
 
+    pub fn set_controls(&mut self, controls: ControlMap) {
+        self.controls = controls;
+    }
+
+    fn update_state(state: &mut KeyState, new_pressed: bool) {
+        state.changed = state.pressed != new_pressed;
+        state.pressed = new_pressed;
+    }
+
     pub fn on_key_change(&mut self, key: KeyCode, new_pressed: bool) {
-        let value = self.key_states.entry(key).or_insert(KeyState {
+        Self::update_state(self.key_states.entry(key).or_insert(KeyState {
             pressed: !new_pressed,
             changed: false,
-        });
+        }), new_pressed);
+    }
+
+    pub fn on_button_change(&mut self, button: MouseButton, new_pressed: bool) {
+        Self::update_state(self.button_states.entry(button).or_insert(KeyState {
+            pressed: !new_pressed,
+            changed: false,
+        }), new_pressed);
+    }
+
+    /// Cursor moved in window, e.g. by `WindowEvent::CursorMoved`, in physical pixels
+    pub fn on_cursor_move(&mut self, position: Vec2f) {
+        self.cursor_position = position;
+    }
+
+    /// Accumulate raw mouse motion, e.g. of `DeviceEvent::MouseMotion`
+    pub fn on_mouse_motion(&mut self, delta: Vec2f) {
+        self.mouse_delta += delta;
+    }
 
-        value.changed = value.pressed != new_pressed;
-        value.pressed = new_pressed;
+    /// Accumulate mouse wheel rotation, in lines
+    pub fn on_mouse_wheel(&mut self, delta: f32) {
+        self.wheel_delta += delta;
     }
 
     pub fn clear_changed(&mut self) {
-        for state in self.key_states.values_mut() {
+        for state in self.key_states.values_mut().chain(self.button_states.values_mut()) {
             state.changed = false;
         }
+        self.mouse_delta = Vec2f::default();
+        self.wheel_delta = 0.0;
     }
 
     pub fn get_state<'a>(&'a self) -> State<'a> {
         State {
             states: &self.key_states,
+            controls: &self.controls,
+            button_states: &self.button_states,
+            cursor_position: self.cursor_position,
+            mouse_delta: self.mouse_delta,
+            wheel_delta: self.wheel_delta,
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::{io::{BufRead, BufReader, Write}, net::{TcpListener, TcpStream}, path::PathBuf, sync::mpsc};
+
+use crate::{math::Vec3f, scene::{Material, ParameterValue, Transform}};
+
+/// Version of command set, checked by `Hello` so outdated clients fail early
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Live-link command, e.g. from Blender add-on using path tracer as external viewport. Instances
+/// and materials are referenced by their index in loaded scene. Coordinates are in scene space
+/// with Y axis up, so Blender clients map their (x, y, z) to (x, z, -y).
+#[derive(Clone, Debug, serde::Deserialize)]
+pub enum Command {
+    /// Handshake, fails if client uses other protocol version
+    Hello { version: u32 },
+    /// Replace scene by scene file, e.g. one exported by client on structural changes
+    LoadScene { path: PathBuf },
+    /// Replace instance world transform
+    MoveInstance { instance: u32, transform: Transform },
+    /// Set material parameter by its scene file field name, e.g. `roughness`
+    SetMaterialParameter { material: u32, parameter: String, value: ParameterValue },
+    /// Replace material, its texture maps are kept
+    SetMaterial { material: u32, value: Material },
+    /// Move active camera, lens parameters that aren't set are kept
+    SetCamera {
+        location: Vec3f,
+        at: Vec3f,
+        up: Vec3f,
+        #[serde(default)]
+        aperture_radius: Option<f32>,
+        #[serde(default)]
+        focus_distance: Option<f32>,
+    },
+    /// Resize window to match client viewport, ignored in fullscreen
+    Resize { width: u32, height: u32 },
+    /// Save current frame to PNG or EXR file (chosen by extension), e.g. for client to display it
+    SaveFrame { path: PathBuf },
+}
+
+/// Command received from client, replied to once applied so replies keep request order
+pub struct Request {
+    /// Parsed command, or parse error message
+    pub command: Result<Command, String>,
+    client: TcpStream,
+}
+
+impl Request {
+    /// Send `ok` or `error: <message>` line to client
+    pub fn reply(mut self, result: Result<(), String>) {
+        let line = match result {
+            Ok(()) => "ok".to_string(),
+            Err(message) => format!("error: {}", message.replace('\n', " ")),
+        };
+        // Disconnected client doesn't need reply
+        _ = writeln!(self.client, "{}", line);
+    }
+}
+
+/// TCP server of newline-delimited RON commands, each client connection is served by own thread
+pub struct IpcServer {
+    receiver: mpsc::Receiver<Request>,
+}
+
+impl IpcServer {
+    pub fn start(address: &str) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let (sender, receiver) = mpsc::channel();
+
+        std::thread::spawn(move || {
+            for client in listener.incoming().flatten() {
+                let sender = sender.clone();
+                std::thread::spawn(move || serve_client(client, sender));
+            }
+        });
+
+        Ok(Self { receiver })
+    }
+
+    /// Requests received since last call, in arrival order
+    pub fn requests(&self) -> impl Iterator<Item = Request> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+fn serve_client(client: TcpStream, sender: mpsc::Sender<Request>) {
+    let Ok(reader) = client.try_clone() else {
+        return;
+    };
+
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Ok(client) = client.try_clone() else {
+            return;
+        };
+
+        let command = ron::from_str::<Command>(&line).map_err(|error| error.to_string());
+        if sender.send(Request { command, client }).is_err() {
+            return;
+        }
+    }
+} // fn serve_client
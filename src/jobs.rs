@@ -0,0 +1,148 @@
+use std::{path::PathBuf, sync::{Arc, Mutex}};
+
+use crate::{export::FrameFormat, render::RenderSettings};
+
+/// Directory result images of render jobs are saved to, relative to working directory
+pub const RESULT_DIRECTORY: &str = "jobs";
+
+/// Body of `POST /jobs`
+#[derive(Clone, Debug, serde::Deserialize)]
+pub struct JobRequest {
+    /// Scene file, relative to working directory of path tracer
+    pub scene: PathBuf,
+    /// Samples per pixel to accumulate, must be positive
+    pub samples: u32,
+    /// Settings replacing ones of config, missing fields take default values
+    #[serde(default)]
+    pub settings: Option<RenderSettings>,
+    #[serde(default)]
+    pub format: FrameFormat,
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Rendering { sample_count: u32 },
+    Done { result: PathBuf },
+    Failed { error: String },
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+struct Job {
+    id: u32,
+    #[serde(skip)]
+    request: JobRequest,
+    #[serde(flatten)]
+    status: JobStatus,
+}
+
+type Response = tiny_http::Response<std::io::Cursor<Vec<u8>>>;
+
+fn json_response(status_code: u16, body: &impl serde::Serialize) -> Response {
+    let header = tiny_http::Header::from_bytes("Content-Type", "application/json").expect("Invalid header");
+    tiny_http::Response::from_string(serde_json::to_string(body).unwrap_or_default())
+        .with_status_code(status_code)
+        .with_header(header)
+}
+
+fn error_response(status_code: u16, error: &str) -> Response {
+    json_response(status_code, &serde_json::json!({ "error": error }))
+}
+
+/// HTTP API for build pipelines: `POST /jobs` queues render job, `GET /jobs/<id>` reports its status
+/// and `GET /jobs/<id>/result` returns result image. Jobs are rendered one by one at window size.
+pub struct JobServer {
+    jobs: Arc<Mutex<Vec<Job>>>,
+}
+
+impl JobServer {
+    pub fn start(address: &str) -> Result<Self, String> {
+        let server = tiny_http::Server::http(address).map_err(|error| error.to_string())?;
+        let jobs = Arc::new(Mutex::new(Vec::new()));
+
+        let server_jobs = jobs.clone();
+        std::thread::spawn(move || {
+            for mut request in server.incoming_requests() {
+                let response = handle_request(&mut request, &server_jobs);
+                // Disconnected client doesn't need response
+                _ = request.respond(response);
+            }
+        });
+
+        Ok(Self { jobs })
+    }
+
+    /// Oldest queued job, it's marked as rendering
+    pub fn start_next(&self) -> Option<(u32, JobRequest)> {
+        let mut jobs = self.jobs.lock().expect("Job list lock poisoned");
+        let job = jobs.iter_mut().find(|job| matches!(job.status, JobStatus::Queued))?;
+        job.status = JobStatus::Rendering { sample_count: 0 };
+        Some((job.id, job.request.clone()))
+    }
+
+    /// Result image format of job
+    pub fn format(&self, id: u32) -> FrameFormat {
+        let jobs = self.jobs.lock().expect("Job list lock poisoned");
+        jobs.iter().find(|job| job.id == id).map(|job| job.request.format).unwrap_or_default()
+    }
+
+    pub fn set_status(&self, id: u32, status: JobStatus) {
+        let mut jobs = self.jobs.lock().expect("Job list lock poisoned");
+        if let Some(job) = jobs.iter_mut().find(|job| job.id == id) {
+            job.status = status;
+        }
+    }
+}
+
+fn handle_request(request: &mut tiny_http::Request, jobs: &Mutex<Vec<Job>>) -> Response {
+    let url = request.url().split('?').next().unwrap_or_default().trim_matches('/').to_string();
+    let segments = url.split('/').collect::<Vec<_>>();
+    let job_index = |jobs: &[Job], id: &str| id.parse::<u32>().ok().and_then(|id| jobs.iter().position(|job| job.id == id));
+
+    match (request.method(), segments.as_slice()) {
+        (tiny_http::Method::Post, ["jobs"]) => {
+            let mut body = String::new();
+            if let Err(error) = request.as_reader().read_to_string(&mut body) {
+                return error_response(400, &error.to_string());
+            }
+            let job_request = match serde_json::from_str::<JobRequest>(&body) {
+                Ok(job_request) if job_request.samples > 0 => job_request,
+                Ok(_) => return error_response(400, "samples must be positive"),
+                Err(error) => return error_response(400, &error.to_string()),
+            };
+
+            let mut jobs = jobs.lock().expect("Job list lock poisoned");
+            let id = jobs.len() as u32 + 1;
+            jobs.push(Job { id, request: job_request, status: JobStatus::Queued });
+            json_response(201, &jobs[jobs.len() - 1])
+        }
+        (tiny_http::Method::Get, ["jobs", id]) => {
+            let jobs = jobs.lock().expect("Job list lock poisoned");
+            match job_index(&jobs, id) {
+                Some(index) => json_response(200, &jobs[index]),
+                None => error_response(404, "no such job"),
+            }
+        }
+        (tiny_http::Method::Get, ["jobs", id, "result"]) => {
+            let jobs = jobs.lock().expect("Job list lock poisoned");
+            let Some(index) = job_index(&jobs, id) else {
+                return error_response(404, "no such job");
+            };
+            let JobStatus::Done { result } = &jobs[index].status else {
+                return error_response(409, "job isn't done");
+            };
+
+            let content_type = match jobs[index].request.format {
+                FrameFormat::Png => "image/png",
+                FrameFormat::Exr => "image/x-exr",
+            };
+            match std::fs::read(result) {
+                Ok(data) => tiny_http::Response::from_data(data)
+                    .with_header(tiny_http::Header::from_bytes("Content-Type", content_type).expect("Invalid header")),
+                Err(error) => error_response(500, &error.to_string()),
+            }
+        }
+        _ => error_response(404, "unknown endpoint"),
+    }
+} // fn handle_request
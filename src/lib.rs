@@ -0,0 +1,27 @@
+//! P_TR path tracer. `render` module draws scenes with built-in or custom integrators, see `render::Integrator`.
+
+pub mod timer;
+pub mod camera;
+pub mod input;
+pub mod math;
+pub mod accel;
+pub mod scene;
+pub mod render;
+pub mod action;
+pub mod palette;
+pub mod locale;
+pub mod config;
+pub mod theme;
+pub mod export;
+pub mod benchmark;
+pub mod golden;
+pub mod cli;
+pub mod ipc;
+pub mod jobs;
+pub mod ui;
+pub mod crash;
+pub mod info;
+pub mod bookmark;
+pub mod selftest;
+#[cfg(feature = "oidn")]
+pub mod oidn;
@@ -0,0 +1,76 @@
+use std::{collections::HashMap, sync::OnceLock};
+
+/// Built-in English table, used for missing locale files and keys
+const DEFAULT_LOCALE: &str = include_str!("../locales/en.ron");
+
+/// Directory with `<language>.ron` string tables
+const LOCALE_DIRECTORY: &str = "locales";
+
+/// User-facing string table. Values may contain `{}` placeholders, substituted in order by `tr_args`.
+pub struct Locale {
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Locale {
+    fn parse(source: &str) -> Result<HashMap<String, String>, ron::error::SpannedError> {
+        ron::from_str(source)
+    }
+
+    /// Load table for `language`, keys missing from it are taken from built-in English table
+    pub fn load(language: &str) -> Self {
+        let fallback = Self::parse(DEFAULT_LOCALE).expect("Built-in locale must be valid");
+        let path = std::path::Path::new(LOCALE_DIRECTORY).join(format!("{}.ron", language));
+
+        let strings = match std::fs::read_to_string(&path) {
+            Ok(source) => Self::parse(&source).unwrap_or_else(|error| {
+                crate::crash::error!("{}:{}", path.display(), error);
+                HashMap::new()
+            }),
+            Err(error) => {
+                if language != "en" {
+                    crate::crash::error!("{}: {}", path.display(), error);
+                }
+                HashMap::new()
+            }
+        };
+
+        Self { strings, fallback }
+    }
+
+    /// String by key, key itself if it's missing from all tables
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.strings.get(key).or_else(|| self.fallback.get(key)).map(String::as_str).unwrap_or(key)
+    }
+}
+
+static LOCALE: OnceLock<Locale> = OnceLock::new();
+
+/// Select UI language, has no effect after first string lookup
+pub fn init(language: &str) {
+    _ = LOCALE.set(Locale::load(language));
+}
+
+fn locale() -> &'static Locale {
+    LOCALE.get_or_init(|| Locale::load("en"))
+}
+
+/// Translated string by key
+pub fn tr(key: &'static str) -> &'static str {
+    locale().get(key)
+}
+
+/// Translated string by key with `{}` placeholders replaced by `args` in order
+pub fn tr_args(key: &'static str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut parts = tr(key).split("{}");
+    let mut result = parts.next().unwrap_or_default().to_string();
+
+    for (index, part) in parts.enumerate() {
+        if let Some(arg) = args.get(index) {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(part);
+    }
+
+    result
+}
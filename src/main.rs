@@ -1,40 +1,15 @@
-use std::sync::Arc;
+use std::{collections::BTreeSet, path::{Path, PathBuf}, sync::Arc};
 
-use math::{Ext2f, Ext2u, Vec2f, Vec3f};
+use p_tr::{
+    action, benchmark, bookmark, camera, cli, config, crash, export, golden, info, input, ipc, jobs, locale, math, palette, render, scene, selftest,
+    timer, ui,
+};
+#[cfg(feature = "oidn")]
+use p_tr::oidn;
 
-pub mod timer;
-pub mod input;
-pub mod math;
-pub mod render;
+use camera::{Camera, CameraMode};
+use math::{Ext2u, Mat4f, Vec2f, Vec2u, Vec3f};
 
-struct Camera {
-    pub location: Vec3f,
-    pub at: Vec3f,
-
-    pub direction: Vec3f,
-    pub right: Vec3f,
-    pub up: Vec3f,
-}
-
-impl Camera {
-    pub fn new() -> Self {
-        Self {
-            location: Vec3f::new(0.0, 0.0, 1.0),
-            at: Vec3f::new(0.0, 0.0, 0.0),
-            direction: Vec3f::new(0.0, 0.0, -1.0),
-            right: Vec3f::new(1.0, 0.0, 0.0),
-            up: Vec3f::new(0.0, 1.0, 0.0),
-        }
-    }
-
-    pub fn set(&mut self, location: Vec3f, at: Vec3f, approx_up: Vec3f) {
-        self.direction = (at - location).normalized();
-        self.right = (self.direction % approx_up).normalized();
-        self.up = (self.right % self.direction).normalized();
-        self.location = location;
-        self.at = at;
-    }
-}
 
 struct System<'t> {
     window: Arc<winit::window::Window>,
@@ -42,48 +17,717 @@ struct System<'t> {
     timer: timer::Timer,
     input: input::Input,
     camera: Camera,
+    /// Scene cameras with their names. Active camera state is stored back on switch, so camera settings are preserved.
+    cameras: Vec<(String, Camera)>,
+    camera_index: usize,
+    /// Camera viewpoints saved to slots, persisted at each save
+    bookmarks: bookmark::Bookmarks,
+
+    scene: scene::Scene,
+    /// Indices of selected scene instances
+    selection: BTreeSet<u32>,
+    /// Cursor position at left mouse button press
+    drag_start: Option<Vec2f>,
+
+    scene_path: PathBuf,
+    fullscreen: config::FullscreenConfig,
+    /// Camera speed in scene units per second
+    move_speed: f32,
+    /// Camera rotation in radians per mouse device unit
+    mouse_sensitivity: f32,
+    /// Cursor is hidden and grabbed, mouse movement rotates camera
+    is_cursor_grabbed: bool,
+    camera_mode: CameraMode,
+    camera_controller: Box<dyn camera::CameraController>,
+    key_bindings: Vec<action::KeyBinding>,
+    /// Open command palette, it captures keyboard input
+    palette: Option<palette::CommandPalette>,
+    /// Runtime controls overlay, it captures input over its windows
+    ui: ui::Ui,
+    /// Target sample count is reached, completion is reported once per accumulation
+    is_render_complete: bool,
+    exporter: export::FrameExporter,
+    /// File completed frame is saved to before exit, set by `--output`
+    output: Option<PathBuf>,
+    exit_code: std::process::ExitCode,
+    /// Live-link command server, set by `--listen`
+    ipc: Option<ipc::IpcServer>,
+    /// Render job server, set by `--serve`
+    jobs: Option<jobs::JobServer>,
+    /// ID of job being rendered and its own headless renderer, so jobs don't change interactive scene, camera and settings
+    active_job: Option<(u32, render::Render<'t>)>,
+    /// Render settings set at start, they aren't saved to config unless changed
+    initial_settings: render::RenderSettings,
+    /// Lighten render settings on watchdog report of slow frame
+    reduce_slow_frames: bool,
+    unfocused: config::UnfocusedMode,
+    is_focused: bool,
+    /// Time of next frame while throttled
+    next_redraw: Option<std::time::Instant>,
 }
 
+/// Lowest frame rate of throttled unfocused window, keeps invalid config from stalling it for long
+const MIN_THROTTLE_FRAME_RATE: f32 = 0.1;
+
+/// Cursor must move further than this (in pixels) while button is held to start box selection
+const BOX_SELECTION_THRESHOLD: f32 = 4.0;
+
+/// Pixels of touchpad scroll counted as one mouse wheel line
+const PIXELS_PER_WHEEL_LINE: f32 = 40.0;
+
+/// Number of paths recorded through pixel by `RecordPixelPaths` action
+const RECORDED_PATH_COUNT: u32 = 16;
+
+/// Output files of recorded paths
+const PATH_RECORD_JSON_PATH: &str = "path_record.json";
+const PATH_RECORD_OBJ_PATH: &str = "path_record.obj";
+
+/// Output file of OIDN-denoised frame
+#[cfg(feature = "oidn")]
+const DENOISED_FRAME_PATH: &str = "denoised.pfm";
+
 impl<'t> System<'t> {
-    pub fn new(window: winit::window::Window) -> Self {
+    /// Create system with empty scene, `scene_path` is loaded by `load_scene`.
+    /// Returns error message if no adapter is found or it doesn't support renderer options.
+    pub fn new(window: winit::window::Window, config: &config::AppConfig, scene_path: &Path, adapter_selector: &render::AdapterSelector) -> Result<Self, String> {
         let window_size = window.inner_size();
         let window = Arc::new(window);
 
+        let (context, surface) = render::GpuContext::with_surface(adapter_selector, window.clone())
+            .ok_or_else(|| locale::tr("error.no_adapter").to_string())?;
+        let render = render::RenderBuilder::new(context, Ext2u::new(window_size.width, window_size.height))
+            .surface(surface)
+            .surface_format(config.window.surface_format)
+            .present_mode(if config.window.vsync { wgpu::PresentMode::AutoVsync } else { wgpu::PresentMode::AutoNoVsync })
+            .collector_precision(config.collector_precision)
+            .build()
+            .map_err(|error| locale::tr_args("error.render", &[&error]))?;
+
         let mut s = Self {
-            render: render::Render::new(window.clone(), Ext2u::new(window_size.width, window_size.height)).unwrap(),
+            render,
             window,
             timer: timer::Timer::new(),
-            input: input::Input::new(),
-            camera: Camera::new(),
+            input: {
+                let mut input = input::Input::new();
+                input.set_controls(input::ControlMap::new(&config.controls));
+                input
+            },
+            camera: Camera::initial(),
+            cameras: Vec::new(),
+            camera_index: 0,
+            bookmarks: bookmark::Bookmarks::load(Path::new(bookmark::BOOKMARKS_PATH)).unwrap_or_else(|error| {
+                crash::error!("{}", locale::tr_args("error.bookmarks", &[&error]));
+                bookmark::Bookmarks::default()
+            }),
+            scene: scene::Scene::default(),
+            selection: BTreeSet::new(),
+            drag_start: None,
+            scene_path: scene_path.to_path_buf(),
+            fullscreen: config.fullscreen.clone(),
+            move_speed: config.move_speed,
+            mouse_sensitivity: config.mouse_sensitivity,
+            is_cursor_grabbed: false,
+            camera_mode: CameraMode::Fly,
+            camera_controller: Box::new(camera::FlyController),
+            key_bindings: action::key_bindings(&config.key_bindings),
+            palette: None,
+            ui: ui::Ui::new(config.show_ui),
+            is_render_complete: false,
+            exporter: export::FrameExporter::new(config.export.clone()),
+            output: None,
+            exit_code: std::process::ExitCode::SUCCESS,
+            ipc: None,
+            jobs: None,
+            active_job: None,
+            initial_settings: config.render,
+            reduce_slow_frames: config.reduce_slow_frames,
+            unfocused: config.unfocused,
+            is_focused: true,
+            next_redraw: None,
         };
-        s.camera.set(
-            Vec3f::new(-3.2, 2.8, 0.3),
-            Vec3f::new(-2.4, 2.4, -0.1),
-            Vec3f::new(0.0, 1.0, 0.0)
-        );
-
+        crash::set_adapter(s.render.adapter_info());
+        if !config.window.surface_format.matches(s.render.surface_format()) {
+            crash::error!("{}", locale::tr_args("render.surface_format", &[
+                &format!("{:?}", config.window.surface_format),
+                &format!("{:?}", s.render.surface_format()),
+            ]));
+        }
+        if config.reduce_software_settings && s.render.is_software() {
+            crash::info!("{}", locale::tr_args("render.software", &[&s.render.adapter_name()]));
+            s.initial_settings = config.render.reduced();
+        }
+        s.render.set_settings(s.initial_settings);
+        if config.watchdog_timeout > 0.0 {
+            s.render.set_watchdog_timeout(Some(std::time::Duration::from_secs_f32(config.watchdog_timeout)));
+        }
+        s.render.set_theme(&config.theme.theme());
+        if config.hot_reload_shaders {
+            s.render.enable_shader_hot_reload(Path::new(&config.shader_directory));
+        }
+        load_plugins(&mut s.render, Path::new(&config.plugins_directory));
+        if config.fullscreen.enabled {
+            s.window.set_fullscreen(Some(s.fullscreen_mode()));
+        }
         s.update_render_camera();
-        s
+        Ok(s)
     }
 
     fn update_render_camera(&mut self) {
-        self.render.set_camera(&render::CameraDescriptor {
-            at: self.camera.at,
-            dir: self.camera.direction,
-            location: self.camera.location,
-            near: 1.0,
-            projection_extent: {
-                let size = self.window.inner_size();
-                let min = u32::min(size.width, size.height) as f32;
+        let size = self.window.inner_size();
+        self.render.set_camera(&self.camera.descriptor(size.width as f32 / size.height.max(1) as f32));
+    }
 
-                Ext2f::new(
-                    size.width as f32 / min,
-                    size.height as f32 / min,
-                )
-            },
-            right: self.camera.right,
-            up: self.camera.up,
+    /// Returns false if scene failed to load, current scene is kept then
+    fn load_scene(&mut self, path: &Path) -> bool {
+        match scene::load(path) {
+            Ok(scene) => {
+                self.render.set_scene(&scene);
+                self.scene = scene;
+                self.scene_path = path.to_path_buf();
+                self.selection.clear();
+                self.render.set_selection(&self.selection);
+
+                self.cameras = self.scene.cameras.iter().map(|camera| (camera.name.clone(), Camera::from_scene(camera))).collect();
+                self.camera_index = 0;
+                if let Some((_, camera)) = self.cameras.first() {
+                    self.camera = camera.clone();
+                    self.update_render_camera();
+                }
+
+                // Path follows cameras of new scene
+                if self.camera_mode == CameraMode::Path {
+                    self.set_camera_mode(CameraMode::Path);
+                }
+                true
+            }
+            Err(error) => {
+                crash::error!("{}", locale::tr_args("error.load_scene", &[&error]));
+                false
+            }
+        }
+    } // fn load_scene
+
+    fn apply_command(&mut self, command: &ipc::Command) -> Result<(), String> {
+        match command {
+            ipc::Command::Hello { version } => {
+                if *version != ipc::PROTOCOL_VERSION {
+                    return Err(format!("protocol version {} is not supported, expected {}", version, ipc::PROTOCOL_VERSION));
+                }
+            }
+            ipc::Command::LoadScene { path } => {
+                if !self.load_scene(path) {
+                    return Err(format!("failed to load scene {}", path.display()));
+                }
+            }
+            ipc::Command::MoveInstance { instance, transform } => {
+                let instance = self.scene.instances
+                    .get_mut(*instance as usize)
+                    .ok_or_else(|| format!("no instance {}", instance))?;
+                instance.transform = transform.matrix();
+                self.render.set_instances(&self.scene.instances);
+            }
+            ipc::Command::SetMaterialParameter { material, parameter, value } => {
+                self.scene.materials
+                    .get_mut(*material as usize)
+                    .ok_or_else(|| format!("no material {}", material))?
+                    .material
+                    .set_parameter(parameter, *value)?;
+                self.render.set_materials(&self.scene.materials);
+            }
+            ipc::Command::SetMaterial { material, value } => {
+                self.scene.materials
+                    .get_mut(*material as usize)
+                    .ok_or_else(|| format!("no material {}", material))?
+                    .material = *value;
+                self.render.set_materials(&self.scene.materials);
+            }
+            ipc::Command::SetCamera { location, at, up, aperture_radius, focus_distance } => {
+                // Degenerate basis is NaN, which would poison accumulated image and reprojection history
+                let right = (*at - *location).normalized() % up.normalized();
+                if right.length().is_nan() || right.length() < 1e-4 {
+                    return Err("camera target must differ from location and up must not be parallel to view direction".to_string());
+                }
+                if aperture_radius.is_some_and(|radius| radius.is_nan() || radius < 0.0) || focus_distance.is_some_and(|distance| distance.is_nan() || distance <= 0.0) {
+                    return Err("aperture radius must be non-negative and focus distance positive".to_string());
+                }
+                self.camera.set(location.to_f64(), at.to_f64(), *up);
+                self.camera.aperture_radius = aperture_radius.unwrap_or(self.camera.aperture_radius);
+                self.camera.focus_distance = focus_distance.unwrap_or(self.camera.focus_distance);
+                self.update_render_camera();
+            }
+            ipc::Command::Resize { width, height } => {
+                if self.window.fullscreen().is_none() {
+                    _ = self.window.request_inner_size(winit::dpi::PhysicalSize::new(*width, *height));
+                }
+            }
+            ipc::Command::SaveFrame { path } => self.save_frame(path)?,
+        }
+        Ok(())
+    } // fn apply_command
+
+    /// Save last frame to `path`, format is chosen by its extension
+    fn save_frame(&mut self, path: &Path) -> Result<(), String> {
+        let format = export::FrameFormat::from_path(path);
+        export::save_image(path, format, &export::read_frame(&mut self.render, format))
+    }
+
+    /// Apply live-link commands received since last frame
+    fn apply_ipc_requests(&mut self) {
+        let Some(server) = self.ipc.take() else {
+            return;
+        };
+
+        for request in server.requests() {
+            let result = request.command.as_ref().map_err(Clone::clone).and_then(|command| self.apply_command(command));
+            request.reply(result);
+        }
+        self.ipc = Some(server);
+    }
+
+    fn update_jobs(&mut self) {
+        let Some(server) = self.jobs.take() else {
+            return;
+        };
+        self.update_job(&server);
+        self.jobs = Some(server);
+    }
+
+    /// Warn about frame close to GPU timeout, lightening render settings if enabled
+    fn on_slow_frame(&mut self, duration: std::time::Duration) {
+        let seconds = format!("{:.1}", duration.as_secs_f32());
+        let lightened = self.render.settings().lightened().filter(|_| self.reduce_slow_frames);
+        match lightened {
+            Some(settings) => {
+                crash::error!("{}", locale::tr_args("render.slow_frame_reduced", &[&seconds, &settings.samples_per_frame, &settings.tile_size]));
+                self.render.set_settings(settings);
+                // Lightening is session-only, so it isn't saved as edit of settings
+                self.initial_settings.samples_per_frame = settings.samples_per_frame;
+                self.initial_settings.tile_size = settings.tile_size;
+            }
+            None => crash::error!("{}", locale::tr_args("render.slow_frame", &[&seconds])),
+        }
+    }
+
+    /// Headless renderer of job scene at window size. Job is viewed from first scene camera,
+    /// settings missing in request are interactive ones.
+    fn create_job_render(&self, request: &jobs::JobRequest) -> Result<render::Render<'t>, String> {
+        let scene = scene::load(&request.scene).map_err(|error| error.to_string())?;
+        let mut render = render::RenderBuilder::new(self.render.context().clone(), self.render.extent())
+            .build()
+            .map_err(|error| error.to_string())?;
+        render.set_scene(&scene);
+        render.set_settings(render::RenderSettings {
+            target_sample_count: request.samples,
+            ..request.settings.unwrap_or(*self.render.settings())
         });
+        let camera = scene.cameras.first().map_or_else(Camera::initial, Camera::from_scene);
+        let extent = render.extent();
+        render.set_camera(&camera.descriptor(extent.w as f32 / extent.h.max(1) as f32));
+        Ok(render)
+    }
+
+    /// Start next queued render job, or trace frame of active one and save its result once it converges
+    fn update_job(&mut self, server: &jobs::JobServer) {
+        let Some((id, render)) = &mut self.active_job else {
+            let Some((id, request)) = server.start_next() else {
+                return;
+            };
+            match self.create_job_render(&request) {
+                Ok(render) => self.active_job = Some((id, render)),
+                Err(error) => server.set_status(id, jobs::JobStatus::Failed { error }),
+            }
+            return;
+        };
+
+        render.render();
+        let progress = render.progress();
+        if !progress.is_complete() {
+            server.set_status(*id, jobs::JobStatus::Rendering { sample_count: progress.sample_count });
+            return;
+        }
+
+        let id = *id;
+        let format = server.format(id);
+        let path = Path::new(jobs::RESULT_DIRECTORY).join(format!("{}.{}", id, format.extension()));
+        let result = std::fs::create_dir_all(jobs::RESULT_DIRECTORY)
+            .map_err(|error| format!("{}: {}", jobs::RESULT_DIRECTORY, error))
+            .and_then(|()| export::save_image(&path, format, &export::read_frame(render, format)));
+        server.set_status(id, match result {
+            Ok(()) => jobs::JobStatus::Done { result: path },
+            Err(error) => jobs::JobStatus::Failed { error },
+        });
+        self.active_job = None;
+    } // fn update_job
+
+    /// Replace camera controller, path mode falls back to fly one if scene has less than two cameras
+    fn set_camera_mode(&mut self, mode: CameraMode) {
+        let (mode, controller): (CameraMode, Box<dyn camera::CameraController>) = match mode {
+            CameraMode::Fly => (mode, Box::new(camera::FlyController)),
+            CameraMode::Orbit => (mode, Box::new(camera::OrbitController)),
+            CameraMode::Path => match camera::PathController::from_scene_cameras(&self.scene.cameras) {
+                Some(controller) => (mode, Box::new(controller)),
+                None => {
+                    crash::error!("{}", locale::tr("error.camera_path"));
+                    (CameraMode::Fly, Box::new(camera::FlyController))
+                }
+            },
+        };
+
+        self.camera_mode = mode;
+        self.camera_controller = controller;
+        crash::info!("{}", locale::tr(mode.description_key()));
+    }
+
+    /// Switch to scene camera, current camera state is kept for later switches back
+    fn select_camera(&mut self, index: usize) {
+        if self.cameras.is_empty() {
+            return;
+        }
+
+        self.cameras[self.camera_index].1 = self.camera.clone();
+        self.camera_index = index % self.cameras.len();
+        self.camera = self.cameras[self.camera_index].1.clone();
+        self.update_render_camera();
+    } // fn select_camera
+
+    /// Save camera viewpoint to bookmark slot and write bookmarks to disk
+    fn save_bookmark(&mut self, slot: u32) {
+        self.bookmarks.set(slot, bookmark::Bookmark::from_camera(&self.camera));
+        match self.bookmarks.save(Path::new(bookmark::BOOKMARKS_PATH)) {
+            Ok(()) => crash::info!("{}", locale::tr_args("bookmark.saved", &[&slot, &bookmark::BOOKMARKS_PATH])),
+            Err(error) => crash::error!("{}", locale::tr_args("error.bookmarks", &[&error])),
+        }
+    }
+
+    /// Move camera to bookmark of slot, camera path flight is stopped so it doesn't move camera away
+    fn recall_bookmark(&mut self, slot: u32) {
+        let Some(bookmark) = self.bookmarks.get(slot) else {
+            crash::info!("{}", locale::tr_args("bookmark.empty", &[&slot]));
+            return;
+        };
+        bookmark.apply(&mut self.camera);
+        self.update_render_camera();
+        if self.camera_mode == CameraMode::Path {
+            self.set_camera_mode(CameraMode::Fly);
+        }
+    }
+
+    /// Fullscreen mode selected by config
+    fn fullscreen_mode(&self) -> winit::window::Fullscreen {
+        let monitor = match &self.fullscreen.monitor {
+            Some(selector) => {
+                let monitor = self.window.available_monitors().enumerate().find(|(index, monitor)| match selector {
+                    config::MonitorSelector::Index(selected_index) => index == selected_index,
+                    config::MonitorSelector::Name(name) => monitor.name().is_some_and(|monitor_name| monitor_name.contains(name.as_str())),
+                }).map(|(_, monitor)| monitor);
+
+                if monitor.is_none() {
+                    crash::error!("{}", locale::tr("error.no_monitor"));
+                }
+                monitor.or_else(|| self.window.current_monitor())
+            }
+            None => self.window.current_monitor(),
+        };
+
+        if self.fullscreen.exclusive {
+            let mode = &self.fullscreen.video_mode;
+            let video_mode = monitor.as_ref().and_then(|monitor| monitor
+                .video_modes()
+                .filter(|video_mode| {
+                    let size = video_mode.size();
+                    mode.width.is_none_or(|width| width == size.width)
+                        && mode.height.is_none_or(|height| height == size.height)
+                        && mode.refresh_rate_hz.is_none_or(|rate| rate == (video_mode.refresh_rate_millihertz() + 500) / 1000)
+                })
+                .max_by_key(|video_mode| (video_mode.size().width * video_mode.size().height, video_mode.refresh_rate_millihertz(), video_mode.bit_depth()))
+            );
+
+            match video_mode {
+                Some(video_mode) => return winit::window::Fullscreen::Exclusive(video_mode),
+                None => crash::error!("{}", locale::tr("error.no_video_mode")),
+            }
+        }
+
+        winit::window::Fullscreen::Borderless(monitor)
+    } // fn fullscreen_mode
+
+    fn execute(&mut self, action: &action::Action) {
+        match action {
+            action::Action::ToggleFullscreen => {
+                self.window.set_fullscreen(if self.window.fullscreen().is_some() {
+                    None
+                } else {
+                    Some(self.fullscreen_mode())
+                });
+            }
+            action::Action::ToggleUi => self.ui.is_visible = !self.ui.is_visible,
+            action::Action::TogglePixelInspector => self.ui.is_inspector_visible = !self.ui.is_inspector_visible,
+            action::Action::FocusAtCenter => {
+                let size = self.window.inner_size();
+                self.focus_at(Vec2u::new(size.width / 2, size.height / 2));
+            }
+            action::Action::RecordPixelPaths => {
+                let cursor = self.input.get_state().cursor_position();
+                let (pixel, _) = Self::selection_rect(cursor, cursor);
+                self.record_paths_at(pixel);
+            }
+            action::Action::ClearPathOverlay => self.render.clear_path_overlay(),
+            action::Action::CycleSelection => if !self.scene.instances.is_empty() {
+                let index = self.selection.last().map(|index| (index + 1) % self.scene.instances.len() as u32).unwrap_or(0);
+                self.selection = BTreeSet::from([index]);
+                self.render.set_selection(&self.selection);
+            }
+            action::Action::ClearSelection => {
+                self.selection.clear();
+                self.render.set_selection(&self.selection);
+            }
+            action::Action::ReloadScene => _ = self.load_scene(&self.scene_path.clone()),
+            action::Action::LoadScene(path) => _ = self.load_scene(path),
+            action::Action::NextCamera => self.select_camera(self.camera_index + 1),
+            action::Action::SelectCamera(index, _) => self.select_camera(*index),
+            action::Action::SaveBookmark(slot) => self.save_bookmark(*slot),
+            action::Action::SaveFrame(path) => match self.save_frame(path) {
+                Ok(()) => crash::info!("{}", locale::tr_args("frame.saved", &[&path.display()])),
+                Err(error) => crash::error!("{}", locale::tr_args("error.export", &[&error])),
+            },
+            action::Action::RecallBookmark(slot) => self.recall_bookmark(*slot),
+            #[cfg(feature = "oidn")]
+            action::Action::DenoiseFrame => {
+                let beauty = self.render.read_aov(render::Aov::Beauty);
+                let albedo = self.render.read_aov(render::Aov::Albedo);
+                let normal = self.render.read_aov(render::Aov::Normal);
+                let path = Path::new(DENOISED_FRAME_PATH);
+
+                match oidn::denoise(&beauty, &albedo, &normal).and_then(|image| image.write_pfm(path).map_err(|error| error.to_string())) {
+                    Ok(()) => crash::info!("{}", locale::tr_args("denoise.saved", &[&path.display()])),
+                    Err(error) => crash::error!("{}", locale::tr_args("error.denoise", &[&error])),
+                }
+            }
+            action::Action::ToggleDenoiser => {
+                let mut settings = *self.render.settings();
+                settings.denoise = !settings.denoise;
+                self.render.set_settings(settings);
+            }
+            action::Action::ToggleVertexColors => {
+                let mut settings = *self.render.settings();
+                settings.show_vertex_colors = !settings.show_vertex_colors;
+                self.render.set_settings(settings);
+            }
+            action::Action::ToggleSampleCountView => {
+                let mut settings = *self.render.settings();
+                settings.show_sample_count = !settings.show_sample_count;
+                settings.show_variance = false;
+                self.render.set_settings(settings);
+            }
+            action::Action::ToggleVarianceView => {
+                let mut settings = *self.render.settings();
+                settings.show_variance = !settings.show_variance;
+                settings.show_sample_count = false;
+                self.render.set_settings(settings);
+            }
+            action::Action::ToggleFaceOrientationView => {
+                let mut settings = *self.render.settings();
+                settings.show_face_orientation = !settings.show_face_orientation;
+                self.render.set_settings(settings);
+            }
+            action::Action::ToggleOrbitCamera => self.set_camera_mode(match self.camera_mode {
+                CameraMode::Orbit => CameraMode::Fly,
+                _ => CameraMode::Orbit,
+            }),
+            action::Action::ToggleCameraPath => self.set_camera_mode(match self.camera_mode {
+                CameraMode::Path => CameraMode::Fly,
+                _ => CameraMode::Path,
+            }),
+            action::Action::OpenCommandPalette => {
+                let camera_names = self.cameras.iter().map(|(name, _)| name.clone()).collect::<Vec<_>>();
+                let palette = palette::CommandPalette::new(action::Action::list(&self.scene_path, &camera_names, &self.bookmarks.saved_slots()));
+                self.window.set_title(&format!("{} | {}", locale::tr("window.title"), palette.title()));
+                self.palette = Some(palette);
+            }
+        }
+    } // fn execute
+
+    /// Handle key press while command palette is open
+    fn on_palette_key(&mut self, event: &winit::event::KeyEvent) {
+        let Some(palette) = self.palette.as_mut() else {
+            return;
+        };
+
+        let mut action = None;
+        match event.logical_key {
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::Escape) => self.palette = None,
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::Enter) => {
+                action = palette.selected_action().cloned();
+                self.palette = None;
+            }
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::Backspace) => palette.pop_char(),
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowUp) => palette.move_selection(-1),
+            winit::keyboard::Key::Named(winit::keyboard::NamedKey::ArrowDown) => palette.move_selection(1),
+            _ => if let Some(text) = &event.text {
+                palette.push_text(text);
+            }
+        }
+
+        match &self.palette {
+            Some(palette) => self.window.set_title(&format!("{} | {}", locale::tr("window.title"), palette.title())),
+            None => self.window.set_title(locale::tr("window.title")),
+        }
+
+        if let Some(action) = action {
+            self.execute(&action);
+        }
+    } // fn on_palette_key
+
+    /// Selection rectangle corners in pixels
+    fn selection_rect(start: Vec2f, end: Vec2f) -> (Vec2u, Vec2u) {
+        let to_pixel = |x: f32, y: f32| Vec2u::new(x.max(0.0) as u32, y.max(0.0) as u32);
+
+        (to_pixel(start.x.min(end.x), start.y.min(end.y)), to_pixel(start.x.max(end.x), start.y.max(end.y)))
+    }
+
+    /// Set focus distance to depth of surface at pixel, accumulation restarts
+    fn focus_at(&mut self, pixel: Vec2u) {
+        match self.render.depth_at(pixel.x, pixel.y) {
+            Some(depth) => {
+                self.camera.focus_distance = depth.clamp(*camera::FOCUS_DISTANCE_RANGE.start(), *camera::FOCUS_DISTANCE_RANGE.end());
+                self.update_render_camera();
+                crash::info!("{}", locale::tr_args("focus.set", &[&format!("{:.3}", self.camera.focus_distance)]));
+            }
+            None => crash::info!("{}", locale::tr("focus.no_surface")),
+        }
+    }
+
+    /// Record paths through pixel, save them and draw them over image
+    fn record_paths_at(&mut self, pixel: Vec2u) {
+        let Some(paths) = self.render.record_paths(pixel.x, pixel.y, RECORDED_PATH_COUNT) else {
+            return;
+        };
+        self.render.set_path_overlay(&paths);
+
+        let result = std::fs::File::create(PATH_RECORD_JSON_PATH)
+            .and_then(|file| render::write_paths_json(&paths, std::io::BufWriter::new(file)))
+            .and_then(|()| std::fs::File::create(PATH_RECORD_OBJ_PATH))
+            .and_then(|file| render::write_paths_obj(&paths, std::io::BufWriter::new(file)));
+
+        match result {
+            Ok(()) => crash::info!("{}", locale::tr_args("paths.saved", &[&paths.len(), &pixel.x, &pixel.y, &PATH_RECORD_JSON_PATH, &PATH_RECORD_OBJ_PATH])),
+            Err(error) => crash::error!("{}", locale::tr_args("error.record_paths", &[&error])),
+        }
+    }
+
+    /// Select instances under cursor (click) or inside selection box (drag).
+    /// With Shift held, clicked instances are toggled and boxed ones are added to selection.
+    fn select(&mut self, start: Vec2f, end: Vec2f, is_additive: bool) {
+        let is_box = (end - start).length() > BOX_SELECTION_THRESHOLD;
+        let picked = if is_box {
+            let (min, max) = Self::selection_rect(start, end);
+            self.render.pick_instances(min, max)
+        } else {
+            let (pixel, _) = Self::selection_rect(end, end);
+            match self.render.pick(pixel.x, pixel.y) {
+                Some(render::PickHit::Instance { instance, triangle }) => {
+                    crash::info!("{}", locale::tr_args("pick.instance", &[&instance, &triangle]));
+                    BTreeSet::from([instance])
+                }
+                Some(render::PickHit::Primitive(primitive)) => {
+                    crash::info!("{}", locale::tr_args("pick.primitive", &[&primitive]));
+                    BTreeSet::new()
+                }
+                None => BTreeSet::new(),
+            }
+        };
+
+        if !is_additive {
+            self.selection = picked;
+        } else if is_box {
+            self.selection.extend(picked);
+        } else {
+            for index in picked {
+                if !self.selection.remove(&index) {
+                    self.selection.insert(index);
+                }
+            }
+        }
+
+        self.render.set_selection_box(None);
+        self.render.set_selection(&self.selection);
+    } // fn select
+
+    /// Apply transform to all selected instances around their common center
+    fn transform_selection(&mut self, transform: Mat4f) {
+        let selected = self.selection
+            .iter()
+            .filter_map(|index| self.scene.instances.get(*index as usize))
+            .map(|instance| Vec3f::new(instance.transform.m[3][0], instance.transform.m[3][1], instance.transform.m[3][2]))
+            .collect::<Vec<_>>();
+
+        if selected.is_empty() {
+            return;
+        }
+
+        let pivot = selected.iter().fold(Vec3f::default(), |sum, location| sum + *location) / selected.len() as f32;
+        let transform = Mat4f::translate(pivot) * transform * Mat4f::translate(-pivot);
+
+        for index in &self.selection {
+            if let Some(instance) = self.scene.instances.get_mut(*index as usize) {
+                instance.transform = transform * instance.transform;
+            }
+        }
+
+        self.render.set_instances(&self.scene.instances);
+    } // fn transform_selection
+
+    /// Request next frame, it's delayed or skipped while window is out of focus
+    fn schedule_redraw(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        // Batch renders and remote clients expect frames regardless of focus
+        let is_remote = self.output.is_some() || self.ipc.is_some() || self.jobs.is_some();
+
+        match if self.is_focused || is_remote { config::UnfocusedMode::Run } else { self.unfocused } {
+            config::UnfocusedMode::Run => self.window.request_redraw(),
+            config::UnfocusedMode::Pause => {}
+            config::UnfocusedMode::Throttle(frame_rate) => {
+                let time = std::time::Instant::now() + std::time::Duration::from_secs_f32(1.0 / frame_rate.max(MIN_THROTTLE_FRAME_RATE));
+                self.next_redraw = Some(time);
+                event_loop.set_control_flow(winit::event_loop::ControlFlow::WaitUntil(time));
+            }
+        }
+    }
+
+    /// Hide and grab cursor for mouse-look, it's confined to window if platform can't lock it in place
+    fn set_cursor_grabbed(&mut self, is_grabbed: bool) {
+        use winit::window::CursorGrabMode;
+
+        let result = if is_grabbed {
+            self.window.set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            self.window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(error) = result {
+            crash::error!("{}", locale::tr_args("error.cursor_grab", &[&error]));
+            return;
+        }
+
+        self.window.set_cursor_visible(!is_grabbed);
+        self.is_cursor_grabbed = is_grabbed;
+        if is_grabbed {
+            crash::info!("{}", locale::tr("input.cursor_grabbed"));
+        }
+    }
+
+    fn on_device_event(&mut self, event: winit::event::DeviceEvent) {
+        if let winit::event::DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            self.input.on_mouse_motion(Vec2f::new(x as f32, y as f32));
+        }
+    }
+
+    /// Draw throttled frame once its time comes
+    fn on_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if self.next_redraw.is_some_and(|time| time <= std::time::Instant::now()) {
+            self.next_redraw = None;
+            event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
+            self.window.request_redraw();
+        }
     }
 
     fn on_window_event(
@@ -95,14 +739,91 @@ impl<'t> System<'t> {
         if self.window.id() != window_id {
             return;
         }
+        if self.ui.on_window_event(&event, self.window.scale_factor() as f32) {
+            return;
+        }
 
         match event {
             winit::event::WindowEvent::CloseRequested => {
                 event_loop.exit();
             }
             winit::event::WindowEvent::KeyboardInput { device_id: _, event, is_synthetic: _ } => {
-                if let winit::keyboard::PhysicalKey::Code(code) = event.physical_key {
-                    self.input.on_key_change(code, event.state == winit::event::ElementState::Pressed);
+                let is_pressed = event.state == winit::event::ElementState::Pressed;
+
+                // Escape releases grabbed cursor before it gets to bound actions
+                let is_escape = event.physical_key == winit::keyboard::PhysicalKey::Code(input::KeyCode::Escape);
+                if self.is_cursor_grabbed && is_escape && is_pressed {
+                    self.set_cursor_grabbed(false);
+                    return;
+                }
+
+                // Palette captures key presses, releases still go to input so no key gets stuck
+                if self.palette.is_some() && is_pressed {
+                    self.on_palette_key(&event);
+                } else if let winit::keyboard::PhysicalKey::Code(code) = event.physical_key {
+                    self.input.on_key_change(code, is_pressed);
+                }
+            }
+            winit::event::WindowEvent::CursorMoved { device_id: _, position } => {
+                let position = Vec2f::new(position.x as f32, position.y as f32);
+                self.input.on_cursor_move(position);
+
+                if let Some(start) = self.drag_start {
+                    if (position - start).length() > BOX_SELECTION_THRESHOLD {
+                        self.render.set_selection_box(Some(Self::selection_rect(start, position)));
+                    }
+                }
+            }
+            winit::event::WindowEvent::MouseInput { device_id: _, state, button } => {
+                let is_pressed = state == winit::event::ElementState::Pressed;
+                self.input.on_button_change(button, is_pressed);
+
+                // Right button grabs cursor for mouse-look, left one selects instances (focuses with Ctrl held,
+                // records paths with Alt held) while cursor is free
+                if button == winit::event::MouseButton::Right && is_pressed && !self.is_cursor_grabbed {
+                    self.set_cursor_grabbed(true);
+                }
+                if button != winit::event::MouseButton::Left || self.is_cursor_grabbed {
+                    return;
+                }
+
+                let input_state = self.input.get_state();
+                let cursor_position = input_state.cursor_position();
+                match state {
+                    winit::event::ElementState::Pressed => self.drag_start = Some(cursor_position),
+                    winit::event::ElementState::Released => if let Some(start) = self.drag_start.take() {
+                        if input_state.is_key_pressed(input::KeyCode::ControlLeft) || input_state.is_key_pressed(input::KeyCode::ControlRight) {
+                            let (pixel, _) = Self::selection_rect(cursor_position, cursor_position);
+                            self.focus_at(pixel);
+                            return;
+                        }
+                        if input_state.is_key_pressed(input::KeyCode::AltLeft) || input_state.is_key_pressed(input::KeyCode::AltRight) {
+                            let (pixel, _) = Self::selection_rect(cursor_position, cursor_position);
+                            self.record_paths_at(pixel);
+                            return;
+                        }
+                        let is_additive = input_state.is_key_pressed(input::KeyCode::ShiftLeft) || input_state.is_key_pressed(input::KeyCode::ShiftRight);
+
+                        self.select(start, cursor_position, is_additive);
+                    }
+                }
+            }
+            winit::event::WindowEvent::MouseWheel { device_id: _, delta, phase: _ } => {
+                self.input.on_mouse_wheel(match delta {
+                    winit::event::MouseScrollDelta::LineDelta(_, y) => y,
+                    winit::event::MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_WHEEL_LINE,
+                });
+            }
+            winit::event::WindowEvent::Focused(is_focused) => {
+                self.is_focused = is_focused;
+                if !is_focused && self.is_cursor_grabbed {
+                    self.set_cursor_grabbed(false);
+                }
+                // Paused or throttled frame loop resumes at once, redraw requests of running one are merged
+                if is_focused {
+                    self.next_redraw = None;
+                    event_loop.set_control_flow(winit::event_loop::ControlFlow::Wait);
+                    self.window.request_redraw();
                 }
             }
             winit::event::WindowEvent::Resized(new_extent) => {
@@ -110,60 +831,87 @@ impl<'t> System<'t> {
                 self.update_render_camera();
             }
             winit::event::WindowEvent::RedrawRequested => {
+                self.apply_ipc_requests();
+                for reload in self.render.reload_shaders() {
+                    match reload {
+                        render::ShaderReload::Reloaded(path) => crash::info!("{}", locale::tr_args("shader.reloaded", &[&path.display()])),
+                        render::ShaderReload::Failed(path, error) => crash::error!("{}", locale::tr_args("error.shader", &[&path.display(), &error])),
+                    }
+                }
                 self.timer.response();
                 let timer_state = self.timer.get_state();
                 let input_state = self.input.get_state();
 
-                if input_state.is_key_clicked(input::KeyCode::F11) {
-                    self.window.set_fullscreen(if self.window.fullscreen().is_some() {
-                        None
-                    } else {
-                        Some(winit::window::Fullscreen::Borderless(None))
-                    });
-                }
+                let actions = self.key_bindings
+                    .iter()
+                    .filter(|binding| binding.is_triggered(&input_state))
+                    .map(|binding| binding.action.clone())
+                    .collect::<Vec<_>>();
 
-                // Update camera and so on
-                let camera_update_required = 'camera_control: {
+                // Selected instances are moved (IJKL/UO), rotated around Y (Q/E) and scaled (+/-) as a group
+                let selection_transform = 'selection_control: {
                     let move_axis = Vec3f::new(
-                        (input_state.is_key_pressed(input::KeyCode::KeyD) as i32 - input_state.is_key_pressed(input::KeyCode::KeyA) as i32) as f32,
-                        (input_state.is_key_pressed(input::KeyCode::KeyR) as i32 - input_state.is_key_pressed(input::KeyCode::KeyF) as i32) as f32,
-                        (input_state.is_key_pressed(input::KeyCode::KeyW) as i32 - input_state.is_key_pressed(input::KeyCode::KeyS) as i32) as f32,
-                    );
-                    let rotate_axis = Vec2f::new(
-                      (input_state.is_key_pressed(input::KeyCode::ArrowRight) as i32 - input_state.is_key_pressed(input::KeyCode::ArrowLeft) as i32) as f32,
-                      (input_state.is_key_pressed(input::KeyCode::ArrowDown) as i32 - input_state.is_key_pressed(input::KeyCode::ArrowUp) as i32) as f32,
+                        input_state.control_axis(input::Control::SelectionRight, input::Control::SelectionLeft),
+                        input_state.control_axis(input::Control::SelectionUp, input::Control::SelectionDown),
+                        input_state.control_axis(input::Control::SelectionForward, input::Control::SelectionBackward),
                     );
+                    let rotation = input_state.control_axis(input::Control::SelectionRotateLeft, input::Control::SelectionRotateRight);
+                    let scale = input_state.control_axis(input::Control::SelectionGrow, input::Control::SelectionShrink);
 
-                    if move_axis.length() <= 0.01 && rotate_axis.length() <= 0.01 {
-                        break 'camera_control false;
+                    if self.selection.is_empty() || (move_axis.length() <= 0.01 && rotation == 0.0 && scale == 0.0) {
+                        break 'selection_control None;
                     }
 
-                    let movement_delta = (
-                        self.camera.right     * move_axis.x +
-                        self.camera.up        * move_axis.y +
-                        self.camera.direction * move_axis.z
-                    ) * timer_state.get_delta_time() as f32 * 8.0;
+                    let delta_time = timer_state.get_delta_time() as f32;
 
-                    let mut azimuth = self.camera.direction.y.acos();
-                    let mut elevator = self.camera.direction.z.signum() * (
-                        self.camera.direction.x / (
-                            self.camera.direction.x * self.camera.direction.x +
-                            self.camera.direction.z * self.camera.direction.z
-                        ).sqrt()
-                    ).acos();
+                    // Horizontal movement follows camera heading
+                    let forward = Vec3f::new(self.camera.direction.x, 0.0, self.camera.direction.z).normalized();
+                    let right = Vec3f::new(self.camera.right.x, 0.0, self.camera.right.z).normalized();
+                    let translation = (right * move_axis.x + Vec3f::new(0.0, move_axis.y, 0.0) + forward * move_axis.z) * delta_time * 2.0;
 
-                    elevator += rotate_axis.x * timer_state.get_delta_time() as f32 * 2.0;
-                    azimuth += rotate_axis.y * timer_state.get_delta_time() as f32 * 2.0;
+                    Some(
+                        Mat4f::translate(translation)
+                            * Mat4f::rotate_y(rotation * delta_time)
+                            * Mat4f::scale(Vec3f::new(1.0, 1.0, 1.0) * (scale * delta_time).exp())
+                    )
+                };
 
-                    azimuth = azimuth.clamp(0.01, std::f32::consts::PI - 0.01);
+                // Mouse wheel scales fly speed, orbit camera dollies by it instead
+                if self.camera_mode == CameraMode::Fly {
+                    self.move_speed = camera::scale_move_speed(self.move_speed, input_state.wheel_delta());
+                }
+
+                // Update camera and so on
+                let camera_update_required = self.camera_controller.update(
+                    &mut self.camera,
+                    &camera::ControlSettings {
+                        move_speed: self.move_speed,
+                        mouse_sensitivity: self.mouse_sensitivity,
+                        is_mouse_look: self.is_cursor_grabbed,
+                        is_roll: self.selection.is_empty(),
+                    },
+                    &input_state,
+                    &timer_state
+                );
 
-                    let new_direction = Vec3f{
-                        x: azimuth.sin() * elevator.cos(),
-                        y: azimuth.cos(),
-                        z: azimuth.sin() * elevator.sin()
-                    };
+                // Aperture ([/]) and focus distance (,/.) of thin lens, exposure (PageUp/PageDown),
+                // field of view or orthographic view height (numpad +/-)
+                let lens_update_required = 'lens_control: {
+                    let aperture_axis = input_state.control_axis(input::Control::ApertureOpen, input::Control::ApertureClose);
+                    let focus_axis = input_state.control_axis(input::Control::FocusFarther, input::Control::FocusCloser);
+                    let exposure_axis = input_state.control_axis(input::Control::ExposureUp, input::Control::ExposureDown);
+                    let fov_axis = input_state.control_axis(input::Control::FovWider, input::Control::FovNarrower);
 
-                    self.camera.set(self.camera.location + movement_delta, self.camera.location + movement_delta + new_direction, Vec3f {x: 0.0, y: 1.0, z: 0.0});
+                    if aperture_axis == 0.0 && focus_axis == 0.0 && exposure_axis == 0.0 && fov_axis == 0.0 {
+                        break 'lens_control false;
+                    }
+
+                    let delta_time = timer_state.get_delta_time() as f32;
+
+                    self.camera.aperture_radius = (self.camera.aperture_radius + aperture_axis * delta_time * 0.1).clamp(0.0, 1.0);
+                    self.camera.focus_distance = (self.camera.focus_distance * (focus_axis * delta_time).exp()).clamp(*camera::FOCUS_DISTANCE_RANGE.start(), *camera::FOCUS_DISTANCE_RANGE.end());
+                    self.camera.exposure += exposure_axis * delta_time * 2.0;
+                    self.camera.widen((fov_axis * delta_time).exp());
                     true
                 };
 
@@ -176,7 +924,12 @@ impl<'t> System<'t> {
 
                         if delta.as_secs_f32() > 1.0 {
                             T = Some(now);
-                            println!("{}", timer_state.get_fps());
+                            crash::info!("{}", locale::tr_args("hud.fps", &[&timer_state.get_fps()]));
+
+                            let progress = self.render.progress();
+                            if progress.target_sample_count != 0 && !progress.is_complete() {
+                                crash::info!("{}", locale::tr_args("hud.progress", &[&progress.sample_count, &progress.target_sample_count]));
+                            }
                         }
                     } else {
                         T = Some(std::time::Instant::now());
@@ -185,11 +938,94 @@ impl<'t> System<'t> {
 
                 self.input.clear_changed();
 
-                if camera_update_required {
+                for action in &actions {
+                    self.execute(action);
+                }
+
+                if let Some(transform) = selection_transform {
+                    self.transform_selection(transform);
+                }
+
+                let stats = ui::Stats {
+                    fps: self.timer.get_state().get_fps(),
+                    sample_count: self.render.progress().sample_count,
+                    convergence: self.render.progress().convergence,
+                    camera_location: self.camera.location,
+                    pixel: self.ui.is_inspector_visible
+                        .then(|| self.input.get_state().cursor_position())
+                        .map(|position| Vec2u::new(position.x as u32, position.y as u32))
+                        .and_then(|pixel| Some((pixel, self.render.inspect_pixel(pixel.x, pixel.y)?))),
+                };
+                let controls = ui::Controls {
+                    settings: *self.render.settings(),
+                    exposure: self.camera.exposure,
+                    aperture_radius: self.camera.aperture_radius,
+                    focus_distance: self.camera.focus_distance,
+                    fov: self.camera.fov,
+                    projection: self.camera.projection,
+                    move_speed: self.move_speed,
+                    buffer_view: self.render.buffer_view(),
+                };
+                let mut edited_controls = controls;
+                let mut edited_materials = self.scene.materials.clone();
+                let overlay = self.ui.run(
+                    self.window.inner_size(),
+                    self.window.scale_factor() as f32,
+                    &stats,
+                    &mut edited_controls,
+                    &mut edited_materials,
+                    &self.scene.material_names,
+                );
+                self.render.set_overlay(overlay);
+
+                // Only material buffer is uploaded, accumulation restarts
+                if edited_materials != self.scene.materials {
+                    self.scene.materials = edited_materials;
+                    self.render.set_materials(&self.scene.materials);
+                }
+
+                let controls_update_required = edited_controls != controls;
+                if controls_update_required {
+                    self.render.set_settings(edited_controls.settings);
+                    self.camera.exposure = edited_controls.exposure;
+                    self.camera.aperture_radius = edited_controls.aperture_radius;
+                    self.camera.focus_distance = edited_controls.focus_distance;
+                    self.camera.fov = edited_controls.fov;
+                    self.move_speed = edited_controls.move_speed;
+                    self.render.set_buffer_view(edited_controls.buffer_view);
+                }
+
+                if camera_update_required || lens_update_required || controls_update_required {
                     self.update_render_camera();
                 }
                 self.render.render();
-                self.window.request_redraw();
+                if let Some(duration) = self.render.take_slow_frame() {
+                    self.on_slow_frame(duration);
+                }
+                self.update_jobs();
+                self.schedule_redraw(event_loop);
+
+                let progress = self.render.progress();
+                let is_converged = progress.is_complete() && !self.is_render_complete;
+                if is_converged {
+                    crash::info!("{}", locale::tr_args("hud.render_complete", &[&progress.sample_count]));
+
+                    if let Some(path) = &self.output {
+                        let format = export::FrameFormat::from_path(path);
+                        if let Err(error) = export::save_image(path, format, &export::read_frame(&mut self.render, format)) {
+                            crash::error!("{}", locale::tr_args("error.export", &[&error]));
+                            self.exit_code = std::process::ExitCode::FAILURE;
+                        }
+                        event_loop.exit();
+                    }
+                }
+                self.is_render_complete = progress.is_complete();
+
+                if self.exporter.is_frame_due(is_converged) {
+                    if let Err(error) = self.exporter.write(&mut self.render) {
+                        crash::error!("{}", locale::tr_args("error.export", &[&error]));
+                    }
+                }
             }
             _ => {}
         }
@@ -197,22 +1033,107 @@ impl<'t> System<'t> {
 }
 
 struct Application<'t> {
+    /// Config as loaded from file, changes made during run are saved against it on exit
+    loaded_config: config::AppConfig,
+    /// Loaded config with command line overrides
+    config: config::AppConfig,
+    cli: cli::Cli,
+    exit_code: std::process::ExitCode,
     system: Option<System<'t>>,
 }
 
 impl<'t> Application<'t> {
-    pub fn new() -> Self {
-        Self { system: None }
+    pub fn new(loaded_config: config::AppConfig, cli: cli::Cli) -> Self {
+        let mut config = loaded_config.clone();
+        if let Some(spp) = cli.spp {
+            config.render.target_sample_count = spp;
+        }
+        if let Some(target_error) = cli.target_error {
+            config.render.target_error = target_error;
+        }
+        if let Ok(settings) = toml::to_string(&config) {
+            crash::set_settings(settings);
+        }
+
+        Self { loaded_config, config, cli, exit_code: std::process::ExitCode::SUCCESS, system: None }
+    }
+
+    /// Loaded config with settings changed during run: render settings, camera speed, fullscreen state and window size
+    fn current_config(&self, system: &System) -> config::AppConfig {
+        let mut config = self.loaded_config.clone();
+        config.render = config::edited_render_settings(&config.render, &system.initial_settings, system.render.settings());
+        config.fullscreen.enabled = system.window.fullscreen().is_some();
+        config.show_ui = system.ui.is_visible;
+        config.move_speed = system.move_speed;
+
+        // Fullscreen window size and size set by command line aren't user's preferred window size
+        if !config.fullscreen.enabled && self.cli.width.is_none() && self.cli.height.is_none() {
+            let size = system.window.inner_size();
+            config.window.width = size.width;
+            config.window.height = size.height;
+        }
+        config
+    }
+
+    fn fail(&mut self, event_loop: &winit::event_loop::ActiveEventLoop, message: &str) {
+        crash::error!("{}", message);
+        self.exit_code = std::process::ExitCode::FAILURE;
+        event_loop.exit();
     }
 }
 
 impl<'t> winit::application::ApplicationHandler for Application<'t> {
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let window_config = &self.config.window;
+
         if let Ok(window) = event_loop.create_window(winit::window::WindowAttributes::default()
-            .with_title("PathTRacing")
-            .with_inner_size(winit::dpi::PhysicalSize::new(800, 600))
+            .with_title(locale::tr("window.title"))
+            .with_inner_size(winit::dpi::PhysicalSize::new(
+                self.cli.width.unwrap_or(window_config.width),
+                self.cli.height.unwrap_or(window_config.height),
+            ))
+            .with_window_level(if window_config.always_on_top { winit::window::WindowLevel::AlwaysOnTop } else { winit::window::WindowLevel::Normal })
+            .with_decorations(window_config.decorations)
+            .with_transparent(window_config.transparent)
+            .with_content_protected(window_config.content_protected)
         ) {
-            self.system = Some(System::new(window));
+            let mut system = match System::new(window, &self.config, &self.cli.scene, &self.cli.adapter_selector()) {
+                Ok(system) => system,
+                Err(error) => return self.fail(event_loop, &error),
+            };
+            system.output = self.cli.output.clone();
+            system.load_scene(&self.cli.scene);
+
+            if let Some(address) = &self.cli.listen {
+                match ipc::IpcServer::start(address) {
+                    Ok(server) => {
+                        crash::info!("{}", locale::tr_args("ipc.listening", &[address]));
+                        system.ipc = Some(server);
+                    }
+                    Err(error) => crash::error!("{}", locale::tr_args("error.ipc", &[address, &error])),
+                }
+            }
+            if let Some(address) = &self.cli.serve {
+                match jobs::JobServer::start(address) {
+                    Ok(server) => {
+                        crash::info!("{}", locale::tr_args("jobs.serving", &[address]));
+                        system.jobs = Some(server);
+                    }
+                    Err(error) => crash::error!("{}", locale::tr_args("error.jobs", &[address, &error])),
+                }
+            }
+            self.system = Some(system);
+        }
+    }
+
+    fn exiting(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Some(system) = &self.system else {
+            return;
+        };
+        self.exit_code = system.exit_code;
+
+        if let Err(error) = self.current_config(system).save_changes(&self.loaded_config, Path::new(config::CONFIG_PATH)) {
+            crash::error!("{}", locale::tr_args("error.save_config", &[&error]));
         }
     }
 
@@ -229,10 +1150,144 @@ impl<'t> winit::application::ApplicationHandler for Application<'t> {
 
         system.on_window_event(event_loop, window_id, event);
     }
+
+    fn device_event(
+            &mut self,
+            _event_loop: &winit::event_loop::ActiveEventLoop,
+            _device_id: winit::event::DeviceId,
+            event: winit::event::DeviceEvent,
+        ) {
+        if let Some(system) = self.system.as_mut() {
+            system.on_device_event(event);
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        if let Some(system) = self.system.as_mut() {
+            system.on_wait(event_loop);
+        }
+    }
+}
+
+/// Load and lint scenes without opening window, `p_tr validate scene.ron...`. Fails if any scene
+/// fails to load or has content problems.
+fn validate(paths: &[PathBuf]) -> std::process::ExitCode {
+    let mut is_valid = true;
+    for path in paths {
+        let lint_scene = match scene::load_for_lint(path) {
+            Ok(lint_scene) => lint_scene,
+            Err(error) => {
+                eprintln!("{}", locale::tr_args("error.load_scene", &[&error]));
+                is_valid = false;
+                continue;
+            }
+        };
+
+        let issues = lint_scene.issues();
+        let scene = &lint_scene.scene;
+        if issues.is_empty() {
+            println!("{}", locale::tr_args("validate.ok", &[
+                &path.display(),
+                &scene.materials.len(),
+                &scene.meshes.len(),
+                &scene.instances.len(),
+                &scene.primitives.len(),
+            ]));
+        } else {
+            eprintln!("{}", locale::tr_args("validate.issues", &[&path.display(), &issues.len()]));
+            for issue in &issues {
+                eprintln!("    {}", issue);
+            }
+            is_valid = false;
+        }
+    }
+
+    if is_valid { std::process::ExitCode::SUCCESS } else { std::process::ExitCode::FAILURE }
+} // fn validate
+
+/// Add post-processing passes of plugins in `directory`, failed plugins are reported and skipped
+fn load_plugins(render: &mut render::Render, directory: &Path) {
+    for plugin in render::PostProcessPlugin::load_directory(directory) {
+        match plugin.map_err(|error| error.to_string()).and_then(|plugin| {
+            render.add_post_process(&plugin).map_err(|error| locale::tr_args("error.plugin_compile", &[&plugin.name, &error]))?;
+            Ok(plugin)
+        }) {
+            Ok(plugin) => crash::info!("{}", locale::tr_args("plugin.loaded", &[&plugin.name, &plugin.path.display()])),
+            Err(error) => crash::error!("{}", locale::tr_args("error.plugin", &[&error])),
+        }
+    }
 }
 
-fn main() {
+/// Write frame graph of renderer with config settings and plugins to `path`, `p_tr --dump-framegraph`.
+/// Renderer is headless and has no scene, so scene-dependent passes are shown disabled.
+fn dump_framegraph(selector: &render::AdapterSelector, config: &config::AppConfig, path: &Path) -> std::process::ExitCode {
+    let Some(context) = render::GpuContext::new(selector) else {
+        crash::error!("{}", locale::tr("error.no_adapter"));
+        return std::process::ExitCode::FAILURE;
+    };
+    let extent = Ext2u::new(config.window.width, config.window.height);
+    let mut render = match render::RenderBuilder::new(context, extent).collector_precision(config.collector_precision).build() {
+        Ok(render) => render,
+        Err(error) => {
+            crash::error!("{}", locale::tr_args("error.render", &[&error]));
+            return std::process::ExitCode::FAILURE;
+        }
+    };
+    let is_reduced = config.reduce_software_settings && render.is_software();
+    render.set_settings(if is_reduced { config.render.reduced() } else { config.render });
+    load_plugins(&mut render, Path::new(&config.plugins_directory));
+
+    let result = std::fs::File::create(path).and_then(|file| render.frame_graph().write_dot(std::io::BufWriter::new(file)));
+    match result {
+        Ok(()) => {
+            crash::info!("{}", locale::tr_args("framegraph.saved", &[&path.display()]));
+            std::process::ExitCode::SUCCESS
+        }
+        Err(error) => {
+            crash::error!("{}", locale::tr_args("error.framegraph", &[&format!("{}: {}", path.display(), error)]));
+            std::process::ExitCode::FAILURE
+        }
+    }
+} // fn dump_framegraph
+
+fn main() -> std::process::ExitCode {
+    let config = config::AppConfig::load(Path::new(config::CONFIG_PATH));
+
+    // Locale is selected first, so config error message is translated too
+    locale::init(config.as_ref().map(|config| config.language.as_str()).unwrap_or("en"));
+    let config = config.unwrap_or_else(|error| {
+        crash::error!("{}", locale::tr_args("error.config", &[&error]));
+        config::AppConfig::default()
+    });
+    if Path::new(config::LEGACY_CONFIG_PATH).exists() {
+        crash::error!("{}", locale::tr_args("error.legacy_config", &[&config::LEGACY_CONFIG_PATH, &config::CONFIG_PATH]));
+    }
+    for (control, binding) in action::control_conflicts(&action::key_bindings(&config.key_bindings), &input::ControlMap::new(&config.controls)) {
+        let key = if binding.ctrl { format!("Ctrl+{:?}", binding.key) } else { format!("{:?}", binding.key) };
+        crash::error!("{}", locale::tr_args("error.key_conflict", &[&key, &format!("{:?}", control), &format!("{:?}", binding.action)]));
+    }
+
+    if config.crash_reports {
+        crash::install();
+    }
+
+    let cli = <cli::Cli as clap::Parser>::parse();
+    match &cli.command {
+        Some(cli::Command::Validate { paths }) => return validate(paths),
+        Some(cli::Command::Info) => return info::print(&cli.adapter_selector(), &config),
+        Some(cli::Command::Selftest) => return selftest::run(&cli.adapter_selector(), cli.spp.unwrap_or(selftest::DEFAULT_SAMPLE_COUNT)),
+        None => {}
+    }
+    if cli.benchmark {
+        return benchmark::run(&cli.adapter_selector(), cli.golden.as_deref(), cli.update_golden, &golden::Thresholds::new(&cli.golden_threshold));
+    }
+    if let Some(path) = &cli.dump_framegraph {
+        return dump_framegraph(&cli.adapter_selector(), &config, path);
+    }
+
+    let mut application = Application::new(config, cli);
+
     let event_loop = winit::event_loop::EventLoop::new().expect("Error creating WINIT event loop");
-    let mut application = Application::new();
     event_loop.run_app(&mut application).expect("Error starting WINIT Application");
+    application.exit_code
 }
@@ -1,4 +1,4 @@
-use std::ops::{Add, AddAssign, BitXor, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign};
+use std::ops::{Add, AddAssign, BitXor, Div, DivAssign, Index, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign};
 
 pub mod numeric_traits {
     pub trait Sqrt {
@@ -74,7 +74,8 @@ macro_rules! impl_vecn_unary_operator {
 
 macro_rules! impl_vecn {
     ($struct_name: ident, $($x: ident),*) => {
-        #[derive(Copy, Clone, Debug, Default)]
+        #[derive(Copy, Clone, Debug, Default, PartialEq)]
+        #[repr(C)]
         pub struct $struct_name<T> {
             $( pub $x : T, )*
         }
@@ -103,7 +104,8 @@ impl_vecn!(Vec2, x, y);
 impl_vecn!(Vec3, x, y, z);
 impl_vecn!(Vec4, x, y, z, w);
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
 pub struct Ext2<T> {
     pub w: T,
     pub h: T,
@@ -118,7 +120,9 @@ impl<T> Ext2<T> {
 pub type Ext2u = Ext2<u32>;
 pub type Ext2f = Ext2<f32>;
 pub type Vec2f = Vec2<f32>;
+pub type Vec2u = Vec2<u32>;
 pub type Vec3f = Vec3<f32>;
+pub type Vec3d = Vec3<f64>;
 
 impl Rem for Vec3f {
     type Output = Self;
@@ -151,10 +155,76 @@ impl BitXor for Vec2f {
     }
 }
 
+impl<T> From<[T; 2]> for Vec2<T> {
+    fn from([x, y]: [T; 2]) -> Self {
+        Self { x, y }
+    }
+}
+
+impl<T> From<[T; 3]> for Vec3<T> {
+    fn from([x, y, z]: [T; 3]) -> Self {
+        Self { x, y, z }
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Vec2<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <[T; 2]>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for Vec2<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.x, &self.y).serialize(serializer)
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Vec3<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <[T; 3]>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for Vec3<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.x, &self.y, &self.z).serialize(serializer)
+    }
+}
+
+impl<T> From<[T; 4]> for Vec4<T> {
+    fn from([x, y, z, w]: [T; 4]) -> Self {
+        Self { x, y, z, w }
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Vec4<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <[T; 4]>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for Vec4<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        (&self.x, &self.y, &self.z, &self.w).serialize(serializer)
+    }
+}
+
+impl<T> Index<usize> for Vec3<T> {
+    type Output = T;
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vec3 index {} out of range", index),
+        }
+    }
+}
+
 impl Vec3f {
     #[inline]
     pub fn length2(&self) -> f32 {
-        *self ^ *self
+        self.x * self.x + self.y * self.y + self.z * self.z
     }
 
     #[inline]
@@ -171,12 +241,47 @@ impl Vec3f {
     pub fn normalize(&mut self) {
         *self /= self.length();
     }
+
+    #[inline]
+    pub fn min(&self, rhs: Self) -> Self {
+        Self::new(self.x.min(rhs.x), self.y.min(rhs.y), self.z.min(rhs.z))
+    }
+
+    #[inline]
+    pub fn max(&self, rhs: Self) -> Self {
+        Self::new(self.x.max(rhs.x), self.y.max(rhs.y), self.z.max(rhs.z))
+    }
+}
+
+impl Vec3d {
+    #[inline]
+    pub fn length(&self) -> f64 {
+        (self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    #[inline]
+    pub fn to_f32(&self) -> Vec3f {
+        Vec3f::new(self.x as f32, self.y as f32, self.z as f32)
+    }
+}
+
+impl Vec3f {
+    #[inline]
+    pub fn to_f64(&self) -> Vec3d {
+        Vec3d::new(self.x as f64, self.y as f64, self.z as f64)
+    }
+
+    /// Point relative to `origin`, difference is taken in double precision
+    #[inline]
+    pub fn relative_to(&self, origin: Vec3d) -> Self {
+        (self.to_f64() - origin).to_f32()
+    }
 }
 
 impl Vec2f {
     #[inline]
     pub fn length2(&self) -> f32 {
-        *self ^ *self
+        self.x * self.x + self.y * self.y
     }
 
     #[inline]
@@ -193,4 +298,179 @@ impl Vec2f {
     pub fn normalize(&mut self) {
         *self /= self.length();
     }
-}
\ No newline at end of file
+}
+pub type Vec4f = Vec4<f32>;
+
+/// 4x4 matrix, stored column-major (`m[column][row]`) to match GPU-side layout
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
+pub struct Mat4<T> {
+    pub m: [[T; 4]; 4],
+}
+
+pub type Mat4f = Mat4<f32>;
+
+impl Default for Mat4f {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Mat4f {
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut m = [[0.0f32; 4]; 4];
+
+        for (column, rhs_column) in m.iter_mut().zip(rhs.m.iter()) {
+            for (row, value) in column.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[k][row] * rhs_column[k]).sum();
+            }
+        }
+
+        Self { m }
+    }
+}
+
+impl Mat4f {
+    pub fn identity() -> Self {
+        Self {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    pub fn translate(v: Vec3f) -> Self {
+        let mut result = Self::identity();
+        result.m[3] = [v.x, v.y, v.z, 1.0];
+        result
+    }
+
+    pub fn scale(v: Vec3f) -> Self {
+        let mut result = Self::identity();
+        result.m[0][0] = v.x;
+        result.m[1][1] = v.y;
+        result.m[2][2] = v.z;
+        result
+    }
+
+    /// Rotation around X axis, angle in radians
+    pub fn rotate_x(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let mut result = Self::identity();
+        result.m[1] = [0.0, c, s, 0.0];
+        result.m[2] = [0.0, -s, c, 0.0];
+        result
+    }
+
+    /// Rotation around Y axis, angle in radians
+    pub fn rotate_y(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let mut result = Self::identity();
+        result.m[0] = [c, 0.0, -s, 0.0];
+        result.m[2] = [s, 0.0, c, 0.0];
+        result
+    }
+
+    /// Rotation around Z axis, angle in radians
+    pub fn rotate_z(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let mut result = Self::identity();
+        result.m[0] = [c, s, 0.0, 0.0];
+        result.m[1] = [-s, c, 0.0, 0.0];
+        result
+    }
+
+    pub fn transposed(&self) -> Self {
+        let mut m = [[0.0f32; 4]; 4];
+        for (column, values) in m.iter_mut().enumerate() {
+            for (row, value) in values.iter_mut().enumerate() {
+                *value = self.m[row][column];
+            }
+        }
+        Self { m }
+    }
+
+    /// Matrix inverse, None for singular matrices
+    pub fn inversed(&self) -> Option<Self> {
+        let m = &self.m;
+        let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+        let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+        let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+        let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+        let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+        let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+        let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
+
+        let determinant = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if determinant.abs() <= f32::MIN_POSITIVE {
+            return None;
+        }
+        let inv = 1.0 / determinant;
+
+        Some(Self {
+            m: [
+                [
+                    (m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inv,
+                    (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inv,
+                    (m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inv,
+                    (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inv,
+                ],
+                [
+                    (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inv,
+                    (m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inv,
+                    (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inv,
+                    (m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inv,
+                ],
+                [
+                    (m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inv,
+                    (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inv,
+                    (m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inv,
+                    (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inv,
+                ],
+                [
+                    (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inv,
+                    (m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inv,
+                    (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inv,
+                    (m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inv,
+                ],
+            ],
+        })
+    } // fn inversed
+
+    /// Affine transform with translation relative to `origin`, difference is taken in double precision
+    pub fn relative_to(&self, origin: Vec3d) -> Self {
+        let translation = Vec3f::new(self.m[3][0], self.m[3][1], self.m[3][2]).relative_to(origin);
+        let mut result = *self;
+        result.m[3][0] = translation.x;
+        result.m[3][1] = translation.y;
+        result.m[3][2] = translation.z;
+        result
+    }
+
+    pub fn transform_point(&self, p: Vec3f) -> Vec3f {
+        let m = &self.m;
+        Vec3f::new(
+            m[0][0] * p.x + m[1][0] * p.y + m[2][0] * p.z + m[3][0],
+            m[0][1] * p.x + m[1][1] * p.y + m[2][1] * p.z + m[3][1],
+            m[0][2] * p.x + m[1][2] * p.y + m[2][2] * p.z + m[3][2],
+        )
+    }
+
+    pub fn transform_vector(&self, v: Vec3f) -> Vec3f {
+        let m = &self.m;
+        Vec3f::new(
+            m[0][0] * v.x + m[1][0] * v.y + m[2][0] * v.z,
+            m[0][1] * v.x + m[1][1] * v.y + m[2][1] * v.z,
+            m[0][2] * v.x + m[1][2] * v.y + m[2][2] * v.z,
+        )
+    }
+}
@@ -0,0 +1,130 @@
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+use crate::render::AovImage;
+
+#[allow(non_camel_case_types)]
+type OIDNDevice = *mut c_void;
+#[allow(non_camel_case_types)]
+type OIDNFilter = *mut c_void;
+
+const OIDN_DEVICE_TYPE_CPU: c_int = 1;
+const OIDN_FORMAT_FLOAT3: c_int = 3;
+const OIDN_ERROR_NONE: c_int = 0;
+
+// Intel Open Image Denoise library must be installed in the system
+#[link(name = "OpenImageDenoise")]
+extern "C" {
+    fn oidnNewDevice(device_type: c_int) -> OIDNDevice;
+    fn oidnCommitDevice(device: OIDNDevice);
+    fn oidnGetDeviceError(device: OIDNDevice, out_message: *mut *const c_char) -> c_int;
+    fn oidnReleaseDevice(device: OIDNDevice);
+
+    fn oidnNewFilter(device: OIDNDevice, filter_type: *const c_char) -> OIDNFilter;
+    fn oidnSetSharedFilterImage(
+        filter: OIDNFilter,
+        name: *const c_char,
+        ptr: *mut c_void,
+        format: c_int,
+        width: usize,
+        height: usize,
+        byte_offset: usize,
+        pixel_byte_stride: usize,
+        row_byte_stride: usize
+    );
+    fn oidnSetFilterBool(filter: OIDNFilter, name: *const c_char, value: bool);
+    fn oidnCommitFilter(filter: OIDNFilter);
+    fn oidnExecuteFilter(filter: OIDNFilter);
+    fn oidnReleaseFilter(filter: OIDNFilter);
+}
+
+/// Device and filter handles, released on drop
+struct Denoiser {
+    device: OIDNDevice,
+    filter: OIDNFilter,
+}
+
+impl Denoiser {
+    fn check_error(&self) -> Result<(), String> {
+        let mut message = std::ptr::null();
+        let error = unsafe { oidnGetDeviceError(self.device, &mut message) };
+
+        if error == OIDN_ERROR_NONE {
+            return Ok(());
+        }
+        Err(if message.is_null() {
+            format!("OIDN error {}", error)
+        } else {
+            unsafe { CStr::from_ptr(message) }.to_string_lossy().into_owned()
+        })
+    }
+}
+
+impl Drop for Denoiser {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.filter.is_null() {
+                oidnReleaseFilter(self.filter);
+            }
+            oidnReleaseDevice(self.device);
+        }
+    }
+}
+
+/// Denoise HDR beauty image on CPU, albedo and normal AOVs guide the filter
+pub fn denoise(beauty: &AovImage, albedo: &AovImage, normal: &AovImage) -> Result<AovImage, String> {
+    let extent = beauty.extent;
+    for image in [beauty, albedo, normal] {
+        if image.channel_count != 3 || image.extent.w != extent.w || image.extent.h != extent.h {
+            return Err("denoiser inputs must be three-channel images of the same size".to_string());
+        }
+    }
+
+    // OIDN takes mutable pointers to all images, so inputs are copied
+    let mut color = beauty.data.clone();
+    let mut albedo = albedo.data.clone();
+    let mut normal = normal.data.clone();
+    let mut output = vec![0.0f32; color.len()];
+
+    let device = unsafe { oidnNewDevice(OIDN_DEVICE_TYPE_CPU) };
+    if device.is_null() {
+        return Err("no OIDN device available".to_string());
+    }
+
+    let mut denoiser = Denoiser { device, filter: std::ptr::null_mut() };
+    unsafe { oidnCommitDevice(denoiser.device) };
+    denoiser.check_error()?;
+
+    denoiser.filter = unsafe { oidnNewFilter(denoiser.device, c"RT".as_ptr()) };
+    denoiser.check_error()?;
+
+    let images: [(&CStr, &mut Vec<f32>); 4] = [
+        (c"color", &mut color),
+        (c"albedo", &mut albedo),
+        (c"normal", &mut normal),
+        (c"output", &mut output),
+    ];
+
+    unsafe {
+        for (name, data) in images {
+            oidnSetSharedFilterImage(
+                denoiser.filter,
+                name.as_ptr(),
+                data.as_mut_ptr().cast(),
+                OIDN_FORMAT_FLOAT3,
+                extent.w as usize,
+                extent.h as usize,
+                0,
+                0,
+                0
+            );
+        }
+        oidnSetFilterBool(denoiser.filter, c"hdr".as_ptr(), true);
+        oidnCommitFilter(denoiser.filter);
+    }
+    denoiser.check_error()?;
+
+    unsafe { oidnExecuteFilter(denoiser.filter) };
+    denoiser.check_error()?;
+
+    Ok(AovImage { extent, channel_count: 3, data: output })
+} // fn denoise
@@ -0,0 +1,92 @@
+use crate::{action::Action, locale::tr_args};
+
+/// Fuzzy match score of `query` against `name`, None if query isn't a (case-insensitive) subsequence of name.
+/// Consecutive characters and word starts are scored higher.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let mut score = 0;
+    let mut name_chars = name.chars().flat_map(char::to_lowercase).enumerate();
+    let mut previous_match = None::<usize>;
+    let mut previous_char = None::<char>;
+
+    for query_char in query.chars().flat_map(char::to_lowercase).filter(|c| !c.is_whitespace()) {
+        loop {
+            let (index, name_char) = name_chars.next()?;
+            let is_word_start = previous_char.map(|c| !c.is_alphanumeric()).unwrap_or(true);
+            previous_char = Some(name_char);
+
+            if name_char != query_char {
+                continue;
+            }
+
+            score += 1;
+            if previous_match.is_some_and(|previous| previous + 1 == index) {
+                score += 3;
+            }
+            if is_word_start {
+                score += 2;
+            }
+            previous_match = Some(index);
+            break;
+        }
+    }
+
+    Some(score)
+}
+
+/// Command palette state, all text is shown in window title
+pub struct CommandPalette {
+    query: String,
+    actions: Vec<Action>,
+    matches: Vec<usize>,
+    selected: usize,
+}
+
+impl CommandPalette {
+    pub fn new(actions: Vec<Action>) -> Self {
+        let mut palette = Self { query: String::new(), actions, matches: Vec::new(), selected: 0 };
+        palette.update_matches();
+        palette
+    }
+
+    fn update_matches(&mut self) {
+        let mut matches = self.actions
+            .iter()
+            .enumerate()
+            .filter_map(|(index, action)| fuzzy_score(&self.query, &action.name()).map(|score| (index, score)))
+            .collect::<Vec<_>>();
+
+        // Stable sort keeps registration order for equal scores
+        matches.sort_by_key(|(_, score)| -score);
+
+        self.matches = matches.into_iter().map(|(index, _)| index).collect();
+        self.selected = 0;
+    }
+
+    pub fn push_text(&mut self, text: &str) {
+        self.query.extend(text.chars().filter(|c| !c.is_control()));
+        self.update_matches();
+    }
+
+    pub fn pop_char(&mut self) {
+        self.query.pop();
+        self.update_matches();
+    }
+
+    /// Move selection by `delta` matches, wrapping around
+    pub fn move_selection(&mut self, delta: i32) {
+        if !self.matches.is_empty() {
+            self.selected = (self.selected as i32 + delta).rem_euclid(self.matches.len() as i32) as usize;
+        }
+    }
+
+    pub fn selected_action(&self) -> Option<&Action> {
+        self.matches.get(self.selected).map(|index| &self.actions[*index])
+    }
+
+    pub fn title(&self) -> String {
+        match self.selected_action() {
+            Some(action) => tr_args("palette.match", &[&self.query, &action.name(), &(self.selected + 1), &self.matches.len()]),
+            None => tr_args("palette.no_match", &[&self.query]),
+        }
+    }
+}
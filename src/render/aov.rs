@@ -0,0 +1,294 @@
+use crate::math::{Ext2u, Vec2f, Vec2u, Vec3f};
+
+use super::{read_rgba_pixel, read_rgba_texture};
+
+/// Both AOV buffers are four-channel float images, so they fit color attachment limits together
+const AOV_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+/// Auxiliary image of primary hits at pixel centers
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// Accumulated radiance with exposure applied (RGB), linear and before denoiser and post-processing chain,
+    /// see `Render::read_presented` for presented image
+    Beauty,
+    /// First hit surface color with textures and decals applied (RGB), zero for sky
+    Albedo,
+    /// World space shading normal (XYZ), zero for sky
+    Normal,
+    /// Hit distance along camera view direction, zero for sky
+    Depth,
+    /// Offset from pixel center to its position at previous frame (XY), in pixels
+    MotionVector,
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [Aov::Beauty, Aov::Albedo, Aov::Normal, Aov::Depth, Aov::MotionVector];
+
+    pub fn channel_count(self) -> usize {
+        match self {
+            Aov::Beauty | Aov::Albedo | Aov::Normal => 3,
+            Aov::Depth => 1,
+            Aov::MotionVector => 2,
+        }
+    }
+
+    /// Lowercase name, used in file names of exported images
+    pub fn name(self) -> &'static str {
+        match self {
+            Aov::Beauty => "beauty",
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::MotionVector => "motion",
+        }
+    }
+
+    /// Index of AOV buffer and first channel in it
+    fn location(self) -> (usize, usize) {
+        match self {
+            Aov::Beauty | Aov::Albedo => (0, 0),
+            Aov::Depth => (0, 3),
+            Aov::Normal => (1, 0),
+            Aov::MotionVector => (1, 2),
+        }
+    }
+}
+
+/// AOV values of single pixel, see `Aov` for their meaning
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AovPixel {
+    pub albedo: Vec3f,
+    pub normal: Vec3f,
+    pub depth: f32,
+    pub motion_vector: Vec2f,
+}
+
+/// AOV image read back from GPU, rows are top to bottom
+pub struct AovImage {
+    pub extent: Ext2u,
+    pub channel_count: usize,
+    pub data: Vec<f32>,
+}
+
+impl AovImage {
+    /// Write image in Portable Float Map format, two-channel images are padded with zero blue channel
+    pub fn write_pfm(&self, path: &std::path::Path) -> std::io::Result<()> {
+        use std::io::Write;
+
+        let output_channel_count = if self.channel_count == 1 { 1 } else { 3 };
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        // Negative scale stands for little endian
+        write!(file, "{}\n{} {}\n-1.0\n", if output_channel_count == 1 { "Pf" } else { "PF" }, self.extent.w, self.extent.h)?;
+
+        // PFM rows are bottom to top
+        for row in self.data.chunks(self.extent.w as usize * self.channel_count).rev() {
+            for pixel in row.chunks(self.channel_count) {
+                for channel in 0..output_channel_count {
+                    file.write_all(&pixel.get(channel).copied().unwrap_or(0.0).to_le_bytes())?;
+                }
+            }
+        }
+
+        file.flush()
+    } // fn write_pfm
+
+    /// Read image in Portable Float Map format, inverse of `write_pfm`
+    pub fn read_pfm(path: &std::path::Path) -> std::io::Result<Self> {
+        let invalid = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string());
+        let bytes = std::fs::read(path)?;
+
+        // Header is three whitespace-separated lines: format, size and scale whose sign gives endianness
+        let mut tokens = Vec::new();
+        let mut offset = 0;
+        while tokens.len() < 4 {
+            let start = offset + bytes[offset..].iter().position(|byte| !byte.is_ascii_whitespace()).ok_or_else(|| invalid("truncated header"))?;
+            let end = start + bytes[start..].iter().position(|byte| byte.is_ascii_whitespace()).ok_or_else(|| invalid("truncated header"))?;
+            tokens.push(std::str::from_utf8(&bytes[start..end]).map_err(|_| invalid("invalid header"))?);
+            offset = end + 1;
+        }
+
+        let channel_count = match tokens[0] {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(invalid("not a PFM file")),
+        };
+        let parse = |token: &str| token.parse::<u32>().map_err(|_| invalid("invalid size"));
+        let extent = Ext2u::new(parse(tokens[1])?, parse(tokens[2])?);
+        let is_little_endian = tokens[3].parse::<f32>().map_err(|_| invalid("invalid scale"))? < 0.0;
+        if extent.w == 0 || extent.h == 0 {
+            return Err(invalid("empty image"));
+        }
+
+        // Size is checked against file length, so corrupted header doesn't overflow or allocate
+        let data_size = (extent.w as usize)
+            .checked_mul(extent.h as usize)
+            .and_then(|pixel_count| pixel_count.checked_mul(channel_count * 4))
+            .filter(|&size| size <= bytes.len().saturating_sub(offset))
+            .ok_or_else(|| invalid("image size exceeds file size"))?;
+        let values = &bytes[offset..offset + data_size];
+        let values = values.chunks(4).map(|value| {
+            let value = [value[0], value[1], value[2], value[3]];
+            if is_little_endian { f32::from_le_bytes(value) } else { f32::from_be_bytes(value) }
+        }).collect::<Vec<_>>();
+
+        // PFM rows are bottom to top
+        let data = values.chunks(extent.w as usize * channel_count).rev().flatten().copied().collect();
+        Ok(Self { extent, channel_count, data })
+    } // fn read_pfm
+}
+
+/// Octahedral-encoded unit vector decode, inverse of `octahedral_encode` from path tracing shader
+fn octahedral_decode(x: f32, y: f32) -> [f32; 3] {
+    let z = 1.0 - x.abs() - y.abs();
+    let (x, y) = if z < 0.0 {
+        ((1.0 - y.abs()) * x.signum(), (1.0 - x.abs()) * y.signum())
+    } else {
+        (x, y)
+    };
+
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+/// Arbitrary output variables. Primary hits at pixel centers are traced by separate pass into two buffers:
+/// albedo with linear depth and octahedral normal with motion vector.
+pub struct Aovs {
+    pipeline: wgpu::RenderPipeline,
+    textures: [wgpu::Texture; 2],
+    views: [wgpu::TextureView; 2],
+}
+
+impl Aovs {
+    fn create_textures(device: &wgpu::Device, extent: Ext2u) -> [wgpu::Texture; 2] {
+        std::array::from_fn(|_| device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: AOV_FORMAT,
+            label: Some("AOV buffer"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }))
+    }
+
+    /// AOV pass uses `fs_aov` entry point of path tracing shader with the same pipeline layout
+    pub fn create_pipeline(device: &wgpu::Device, render_pipeline_layout: &wgpu::PipelineLayout, render_shader_module: &wgpu::ShaderModule) -> wgpu::RenderPipeline {
+        let target = Some(wgpu::ColorTargetState {
+            blend: None,
+            format: AOV_FORMAT,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+
+        let constants = super::render_module_constants();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("AOV pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "fs_aov",
+                module: render_shader_module,
+                targets: &[target.clone(), target]
+            }),
+            layout: Some(render_pipeline_layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "vs_main",
+                module: render_shader_module,
+            }
+        })
+    }
+
+    /// Replace AOV pipeline, e.g. by one of reloaded shader
+    pub fn set_pipeline(&mut self, pipeline: wgpu::RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        render_pipeline_layout: &wgpu::PipelineLayout,
+        render_shader_module: &wgpu::ShaderModule,
+        extent: Ext2u
+    ) -> Self {
+        let pipeline = Self::create_pipeline(device, render_pipeline_layout, render_shader_module);
+        let textures = Self::create_textures(device, extent);
+        let views = textures.each_ref().map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        Self { pipeline, textures, views }
+    } // fn new
+
+    pub fn resize(&mut self, device: &wgpu::Device, extent: Ext2u) {
+        self.textures = Self::create_textures(device, extent);
+        self.views = self.textures.each_ref().map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+    }
+
+    /// Albedo with depth and normal with motion vector buffers
+    pub fn views(&self) -> &[wgpu::TextureView; 2] {
+        &self.views
+    }
+
+    /// Render AOVs, path tracing bind groups are expected in pipeline layout order
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, bind_groups: &[&wgpu::BindGroup]) {
+        let color_attachments = self.views.each_ref().map(|view| Some(wgpu::RenderPassColorAttachment {
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+            resolve_target: None,
+            view,
+        }));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &color_attachments,
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            render_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Read whole AOV image, beauty is not stored in AOV buffers
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue, aov: Aov) -> AovImage {
+        let (texture_index, first_channel) = aov.location();
+        let (extent, pixels) = read_rgba_texture(device, queue, &self.textures[texture_index], 0);
+
+        let data = match aov {
+            Aov::Normal => pixels.into_iter()
+                .flat_map(|pixel| if pixel[0] > 1.0 {
+                    // Sky normal lies outside of encoded normal square
+                    [0.0; 3]
+                } else {
+                    octahedral_decode(pixel[0], pixel[1])
+                })
+                .collect(),
+            _ => pixels.into_iter()
+                .flat_map(|pixel| pixel.into_iter().skip(first_channel).take(aov.channel_count()))
+                .collect(),
+        };
+
+        AovImage { extent, channel_count: aov.channel_count(), data }
+    } // fn read
+
+    /// Read AOVs of single pixel, pixel must lie inside of AOV buffers
+    pub fn read_pixel(&self, device: &wgpu::Device, queue: &wgpu::Queue, pixel: Vec2u) -> AovPixel {
+        let [albedo_depth, normal_motion] = self.textures.each_ref().map(|texture| read_rgba_pixel(device, queue, texture, 0, pixel));
+        let [nx, ny, nz] = if normal_motion[0] > 1.0 { [0.0; 3] } else { octahedral_decode(normal_motion[0], normal_motion[1]) };
+
+        AovPixel {
+            albedo: Vec3f::new(albedo_depth[0], albedo_depth[1], albedo_depth[2]),
+            normal: Vec3f::new(nx, ny, nz),
+            depth: albedo_depth[3],
+            motion_vector: Vec2f::new(normal_motion[2], normal_motion[3]),
+        }
+    }
+}
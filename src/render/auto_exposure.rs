@@ -0,0 +1,240 @@
+use crate::math::Ext2u;
+
+use super::RenderSettings;
+
+/// Histogram bins and log2 luminance range, match auto_exposure.wgsl. Bin 0 is darker than range.
+const BIN_COUNT: usize = 256;
+const MIN_LOG_LUMINANCE: f32 = -16.0;
+const MAX_LOG_LUMINANCE: f32 = 16.0;
+
+/// Pixels per workgroup side of histogram pass, matches auto_exposure.wgsl
+const WORKGROUP_SIZE: u32 = 16;
+
+const HISTOGRAM_SIZE: u64 = (BIN_COUNT * std::mem::size_of::<u32>()) as u64;
+
+/// Fractions of darkest and brightest metered pixels ignored by metering, so small light sources
+/// and deep shadows don't swing exposure
+const LOW_PERCENTILE: f32 = 0.1;
+const HIGH_PERCENTILE: f32 = 0.9;
+
+/// Mean luminance presented with unit exposure scale, middle grey
+const KEY_LUMINANCE: f32 = 0.18;
+
+/// Log2 luminance of middle of histogram bin
+fn bin_log_luminance(bin: usize) -> f32 {
+    MIN_LOG_LUMINANCE + (bin as f32 - 0.5) / (BIN_COUNT - 1) as f32 * (MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE)
+}
+
+/// Metered scene brightness in EV, log2 of mean luminance between percentiles relative to middle grey.
+/// None if no pixel is bright enough to be metered.
+fn metered_ev(histogram: &[u32; BIN_COUNT]) -> Option<f32> {
+    let total = histogram.iter().map(|&count| count as f64).sum::<f64>();
+    let low = total * LOW_PERCENTILE as f64;
+    let high = total * HIGH_PERCENTILE as f64;
+
+    // Pixels of each bin are clipped to part between percentiles
+    let mut passed = 0.0;
+    let mut weight_sum = 0.0;
+    let mut log_sum = 0.0;
+    for (bin, &count) in histogram.iter().enumerate() {
+        let count = count as f64;
+        let weight = ((passed + count).min(high) - passed.max(low)).max(0.0);
+        passed += count;
+        if bin != 0 {
+            weight_sum += weight;
+            log_sum += weight * bin_log_luminance(bin) as f64;
+        }
+    }
+
+    (weight_sum > 0.0).then(|| (log_sum / weight_sum) as f32 - KEY_LUMINANCE.log2())
+}
+
+/// Automatic exposure by log2 luminance histogram of presented image. Histogram is built on GPU and read back
+/// without waiting, metered brightness is then approached smoothly over time within settings EV range.
+pub struct AutoExposure {
+    pipeline: wgpu::ComputePipeline,
+    histogram_buffer: wgpu::Buffer,
+    histogram_bind_group: wgpu::BindGroup,
+    readback_buffer: wgpu::Buffer,
+
+    /// Map result of histogram in flight
+    readback: Option<futures::channel::oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    /// Histogram is encoded, but not submitted yet
+    is_measured: bool,
+    /// Last metered brightness, in EV
+    metered_ev: Option<f32>,
+    /// Brightness exposure is adapted to, in EV
+    adapted_ev: Option<f32>,
+    last_adaptation: Option<std::time::Instant>,
+}
+
+impl AutoExposure {
+    /// Collector bind group layout is used to read presented image
+    pub fn new(device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        let histogram_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Auto exposure histogram bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    },
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                },
+            ],
+        });
+
+        let histogram_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Auto exposure histogram buffer"),
+            mapped_at_creation: false,
+            size: HISTOGRAM_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Auto exposure readback buffer"),
+            mapped_at_creation: false,
+            size: HISTOGRAM_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let histogram_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: histogram_buffer.as_entire_binding() },
+            ],
+            label: Some("Auto exposure histogram bind group"),
+            layout: &histogram_bind_group_layout,
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Auto Exposure Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/auto_exposure.wgsl")))
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[collector_bind_group_layout, &histogram_bind_group_layout],
+            ..Default::default()
+        });
+
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Auto exposure histogram pipeline"),
+            layout: Some(&layout),
+            module: &shader_module,
+            entry_point: "cs_histogram",
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        Self {
+            pipeline,
+            histogram_buffer,
+            histogram_bind_group,
+            readback_buffer,
+            readback: None,
+            is_measured: false,
+            metered_ev: None,
+            adapted_ev: None,
+            last_adaptation: None,
+        }
+    } // fn new
+
+    /// Presented image scale relative to camera exposure
+    pub fn scale(&self) -> f32 {
+        self.adapted_ev.map_or(1.0, |ev| (-ev).exp2())
+    }
+
+    /// Forget adapted exposure, so next metering is applied at once.
+    /// Histogram in flight is dropped, as it's measured from image before reset.
+    pub fn reset(&mut self) {
+        self.metered_ev = None;
+        self.adapted_ev = None;
+        self.last_adaptation = None;
+        self.is_measured = false;
+        if self.readback.take().is_some() {
+            // Cancels pending mapping, so buffer can be copied to by next measurement
+            self.readback_buffer.unmap();
+        }
+    }
+
+    /// Build histogram of presented collector of `extent`, skipped while previous one is in flight.
+    /// `submit` must be called after submission of `encoder`.
+    pub fn measure(&mut self, encoder: &mut wgpu::CommandEncoder, collector_bind_group: &wgpu::BindGroup, extent: Ext2u) {
+        if self.readback.is_some() {
+            return;
+        }
+
+        encoder.clear_buffer(&self.histogram_buffer, 0, None);
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Auto exposure histogram pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, collector_bind_group, &[]);
+            compute_pass.set_bind_group(1, &self.histogram_bind_group, &[]);
+            compute_pass.dispatch_workgroups(extent.w.div_ceil(WORKGROUP_SIZE), extent.h.div_ceil(WORKGROUP_SIZE), 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.histogram_buffer, 0, &self.readback_buffer, 0, HISTOGRAM_SIZE);
+        self.is_measured = true;
+    }
+
+    /// Start readback of submitted histogram
+    pub fn submit(&mut self) {
+        if !std::mem::take(&mut self.is_measured) {
+            return;
+        }
+
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| _ = sender.send(result));
+        self.readback = Some(receiver);
+    }
+
+    /// Take metering of finished histogram, doesn't wait for one in flight
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(receiver) = &mut self.readback else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let result = match receiver.try_recv() {
+            Ok(None) => return,
+            Ok(Some(result)) => result,
+            Err(_) => Err(wgpu::BufferAsyncError),
+        };
+        self.readback = None;
+        if result.is_err() {
+            return;
+        }
+
+        let histogram = {
+            let mapped = self.readback_buffer.slice(..).get_mapped_range();
+            std::array::from_fn(|bin| {
+                let offset = bin * std::mem::size_of::<u32>();
+                u32::from_ne_bytes(std::array::from_fn(|byte| mapped[offset + byte]))
+            })
+        };
+        self.readback_buffer.unmap();
+
+        // Black images keep previous metering
+        if let Some(ev) = metered_ev(&histogram) {
+            self.metered_ev = Some(ev);
+        }
+    } // fn poll
+
+    /// Move adapted exposure towards last metering clamped to settings EV range, by time passed since previous adaptation.
+    /// The first metering is applied at once.
+    pub fn adapt(&mut self, settings: &RenderSettings) {
+        let now = std::time::Instant::now();
+        let elapsed = self.last_adaptation.replace(now).map_or(0.0, |last| (now - last).as_secs_f32());
+        let Some(metered_ev) = self.metered_ev else {
+            return;
+        };
+
+        let target_ev = metered_ev.clamp(settings.auto_exposure_min_ev, settings.auto_exposure_max_ev.max(settings.auto_exposure_min_ev));
+        self.adapted_ev = Some(match self.adapted_ev {
+            Some(ev) => ev + (target_ev - ev) * (1.0 - (-elapsed * settings.auto_exposure_speed.max(0.0)).exp()),
+            None => target_ev,
+        });
+    }
+}
@@ -0,0 +1,118 @@
+/// Side of tiled blue noise texture, in pixels
+pub const SIZE: u32 = 64;
+
+/// Energy filter deviation, in pixels
+const SIGMA: f32 = 1.5;
+
+/// Fraction of pixels set in initial binary pattern
+const INITIAL_DENSITY: f32 = 0.1;
+
+/// Toroidal point set with gaussian energy of its points kept for every pixel
+#[derive(Clone)]
+struct Pattern {
+    is_set: Vec<bool>,
+    energy: Vec<f32>,
+    /// Energy contribution by toroidal offset
+    filter: Vec<f32>,
+}
+
+impl Pattern {
+    fn new() -> Self {
+        let size = SIZE as usize;
+        let filter = (0..size * size).map(|index| {
+            let toroidal = |d: usize| d.min(size - d) as f32;
+            let (dx, dy) = (toroidal(index % size), toroidal(index / size));
+            (-(dx * dx + dy * dy) / (2.0 * SIGMA * SIGMA)).exp()
+        }).collect();
+
+        Self {
+            is_set: vec![false; size * size],
+            energy: vec![0.0; size * size],
+            filter,
+        }
+    }
+
+    fn toggle(&mut self, index: usize) {
+        let size = SIZE as usize;
+        let sign = if self.is_set[index] { -1.0 } else { 1.0 };
+        self.is_set[index] = !self.is_set[index];
+
+        let (x, y) = (index % size, index / size);
+        for (other, energy) in self.energy.iter_mut().enumerate() {
+            let dx = (other % size + size - x) % size;
+            let dy = (other / size + size - y) % size;
+            *energy += sign * self.filter[dy * size + dx];
+        }
+    }
+
+    /// Set pixel with maximal energy
+    fn tightest_cluster(&self) -> usize {
+        (0..self.is_set.len())
+            .filter(|index| self.is_set[*index])
+            .max_by(|a, b| self.energy[*a].total_cmp(&self.energy[*b]))
+            .unwrap()
+    }
+
+    /// Unset pixel with minimal energy
+    fn largest_void(&self) -> usize {
+        (0..self.is_set.len())
+            .filter(|index| !self.is_set[*index])
+            .min_by(|a, b| self.energy[*a].total_cmp(&self.energy[*b]))
+            .unwrap()
+    }
+}
+
+/// Blue noise threshold map of `SIZE`×`SIZE` pixels, built by void-and-cluster method.
+/// Values are uniformly distributed in (0, 1), rows are top to bottom.
+pub fn generate() -> Vec<f32> {
+    let pixel_count = (SIZE * SIZE) as usize;
+    let mut pattern = Pattern::new();
+
+    // Fixed seed keeps texture (and so rendered noise) the same between runs
+    let mut seed = 0x9E3779B9u32;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed
+    };
+
+    let initial_count = (pixel_count as f32 * INITIAL_DENSITY) as usize;
+    while pattern.is_set.iter().filter(|is_set| **is_set).count() < initial_count {
+        let index = random() as usize % pixel_count;
+        if !pattern.is_set[index] {
+            pattern.toggle(index);
+        }
+    }
+
+    // Points are moved from clusters to voids until pattern becomes uniform
+    loop {
+        let cluster = pattern.tightest_cluster();
+        pattern.toggle(cluster);
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0usize; pixel_count];
+
+    // Initial points are ranked by removal from clusters
+    let mut removal = pattern.clone();
+    for rank in (0..initial_count).rev() {
+        let cluster = removal.tightest_cluster();
+        removal.toggle(cluster);
+        ranks[cluster] = rank;
+    }
+
+    // Other points are ranked by filling voids
+    for rank in initial_count..pixel_count {
+        let void = pattern.largest_void();
+        pattern.toggle(void);
+        ranks[void] = rank;
+    }
+
+    ranks.into_iter().map(|rank| (rank as f32 + 0.5) / pixel_count as f32).collect()
+} // fn generate
@@ -0,0 +1,230 @@
+use super::{as_bytes, output_constants, shader_include};
+
+/// Internal texture presented by buffer view
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Buffer {
+    /// Radiance sum (RGB) and sample count (A) of ping-pong collectors
+    #[default]
+    CollectorA,
+    CollectorB,
+    /// Octahedral normal (XY), primary hit distance (Z) and luminance second moment sum (W) of collectors
+    FeaturesA,
+    FeaturesB,
+    /// Albedo (RGB) and linear depth (A)
+    AovAlbedoDepth,
+    /// Octahedral normal (XY) and motion vector (ZW)
+    AovNormalMotion,
+    /// Ping-pong denoiser images, filtered color (RGB) and its variance (A). B holds the output,
+    /// whose alpha is unit sample count instead.
+    DenoiserA,
+    DenoiserB,
+}
+
+impl Buffer {
+    pub const ALL: [Buffer; 8] = [
+        Buffer::CollectorA,
+        Buffer::CollectorB,
+        Buffer::FeaturesA,
+        Buffer::FeaturesB,
+        Buffer::AovAlbedoDepth,
+        Buffer::AovNormalMotion,
+        Buffer::DenoiserA,
+        Buffer::DenoiserB,
+    ];
+
+    /// Denoiser images are only filled by denoiser pass
+    pub fn is_denoiser(self) -> bool {
+        matches!(self, Buffer::DenoiserA | Buffer::DenoiserB)
+    }
+}
+
+/// Channels of buffer presented, single channel is shown as grayscale
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Channels {
+    #[default]
+    Rgb,
+    R,
+    G,
+    B,
+    A,
+}
+
+impl Channels {
+    pub const ALL: [Channels; 5] = [Channels::Rgb, Channels::R, Channels::G, Channels::B, Channels::A];
+}
+
+/// Mapping of buffer values to displayed range, applied after `BufferView::scale`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Normalization {
+    /// Values are shown as is, clamped to [0, 1] by presentation
+    #[default]
+    Raw,
+    /// Values divided by alpha, e.g. sample count of collectors
+    PerSample,
+    /// [-1, 1] mapped to [0, 1], e.g. for normals and motion vectors
+    Signed,
+    /// log2(1 + x), for values of high dynamic range
+    Log,
+}
+
+impl Normalization {
+    pub const ALL: [Normalization; 4] = [Normalization::Raw, Normalization::PerSample, Normalization::Signed, Normalization::Log];
+}
+
+/// Debug presentation of internal texture instead of place pass output
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BufferView {
+    pub buffer: Buffer,
+    pub channels: Channels,
+    pub normalization: Normalization,
+    pub scale: f32,
+}
+
+impl Default for BufferView {
+    fn default() -> Self {
+        Self {
+            buffer: Buffer::default(),
+            channels: Channels::default(),
+            normalization: Normalization::PerSample,
+            scale: 1.0,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct BufferViewData {
+    channels: u32,
+    normalization: u32,
+    scale: f32,
+    _pad0: f32,
+}
+
+/// Presents texture chosen by `BufferView` over whole surface, non-finite values are highlighted
+pub struct BufferPresenter {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    buffer: wgpu::Buffer,
+}
+
+impl BufferPresenter {
+    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Buffer view bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Buffer view UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<BufferViewData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Buffer View Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shader_include::compose_embedded("buffer_view.wgsl")))
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+            ..Default::default()
+        });
+
+        let constants = output_constants(surface_format);
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Buffer view pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "fs_main",
+                module: &shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: surface_format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            layout: Some(&layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: &shader_module,
+            }
+        });
+
+        Self { pipeline, bind_group_layout, buffer }
+    } // fn new
+
+    /// Present `source` texture of surface size to `target`
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        source: &wgpu::TextureView,
+        target: &wgpu::TextureView,
+        view: &BufferView
+    ) {
+        queue.write_buffer(&self.buffer, 0, as_bytes(&BufferViewData {
+            channels: view.channels as u32,
+            normalization: view.normalization as u32,
+            scale: view.scale,
+            ..Default::default()
+        }));
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(source) },
+                wgpu::BindGroupEntry { binding: 1, resource: self.buffer.as_entire_binding() },
+            ],
+            label: Some("Buffer view bind group"),
+            layout: &self.bind_group_layout,
+        });
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Buffer view pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: target,
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    } // fn render
+}
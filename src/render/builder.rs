@@ -0,0 +1,201 @@
+use std::sync::Arc;
+
+use crate::{locale::{tr, tr_args}, math::Ext2u};
+
+use super::{integrator::{Configured, ConfiguredIntegrator}, GpuContext, Integrator, Render, SurfaceFormat, Target};
+
+/// Format of accumulated radiance sums
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum CollectorPrecision {
+    /// 32-bit float channels
+    #[default]
+    Full,
+    /// 16-bit float channels, halves accumulator memory. Sums lose precision as they grow, so accumulation
+    /// is stopped at `HALF_MAX_SAMPLE_COUNT` and it suits previews on memory-limited adapters.
+    Half,
+}
+
+/// Samples per pixel accumulated by half precision collector. It's 256 passes, so pass count is exact
+/// and mean radiance up to 255 doesn't overflow sum limit of 65504.
+pub const HALF_MAX_SAMPLE_COUNT: u32 = 1024;
+
+impl CollectorPrecision {
+    /// Samples per pixel accumulation is stopped at, None if it's unlimited
+    pub fn max_sample_count(self) -> Option<u32> {
+        match self {
+            CollectorPrecision::Full => None,
+            CollectorPrecision::Half => Some(HALF_MAX_SAMPLE_COUNT),
+        }
+    }
+
+    pub fn format(self) -> wgpu::TextureFormat {
+        match self {
+            CollectorPrecision::Full => wgpu::TextureFormat::Rgba32Float,
+            CollectorPrecision::Half => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+}
+
+/// Combination of builder options the adapter doesn't support
+#[derive(Debug)]
+pub enum BuildError {
+    /// Image has zero width or height
+    EmptyExtent(Ext2u),
+    /// Requested extent and maximal texture side of adapter
+    ExtentTooLarge(Ext2u, u32),
+    /// Adapter can't present to surface
+    SurfaceUnsupported,
+    /// Requested present mode and present modes supported by surface
+    PresentModeUnsupported(wgpu::PresentMode, Vec<wgpu::PresentMode>),
+    /// Requested features missing on device
+    FeaturesUnsupported(wgpu::Features),
+    /// Collector format can't be rendered to or read by shaders
+    CollectorUnsupported(CollectorPrecision),
+    /// Integrator name and shader compilation error
+    IntegratorFailed(String, String),
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            BuildError::EmptyExtent(extent) => tr_args("error.render_extent_empty", &[&extent.w, &extent.h]),
+            BuildError::ExtentTooLarge(extent, max) => tr_args("error.render_extent_large", &[&extent.w, &extent.h, max]),
+            BuildError::SurfaceUnsupported => tr("error.render_surface").to_string(),
+            BuildError::PresentModeUnsupported(mode, modes) => tr_args("error.render_present_mode", &[&format!("{:?}", mode), &format!("{:?}", modes)]),
+            BuildError::FeaturesUnsupported(features) => tr_args("error.render_features", &[&format!("{:?}", features)]),
+            BuildError::CollectorUnsupported(precision) => tr_args("error.render_collector", &[&format!("{:?}", precision)]),
+            BuildError::IntegratorFailed(name, error) => tr_args("error.render_integrator", &[name, error]),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Renderer options, checked against adapter capabilities by `build`
+pub struct RenderBuilder<'t> {
+    context: Arc<GpuContext>,
+    surface: Option<wgpu::Surface<'t>>,
+    extent: Ext2u,
+    surface_format: SurfaceFormat,
+    present_mode: wgpu::PresentMode,
+    features: wgpu::Features,
+    collector_precision: CollectorPrecision,
+    integrator: Option<Box<dyn ConfiguredIntegrator>>,
+}
+
+impl<'t> RenderBuilder<'t> {
+    /// Headless renderer of `extent` image, presented frames are written to internal texture
+    pub fn new(context: Arc<GpuContext>, extent: Ext2u) -> Self {
+        Self {
+            context,
+            surface: None,
+            extent,
+            surface_format: SurfaceFormat::Auto,
+            present_mode: wgpu::PresentMode::AutoNoVsync,
+            features: wgpu::Features::empty(),
+            collector_precision: CollectorPrecision::Full,
+            integrator: None,
+        }
+    }
+
+    /// Present frames to `surface` of `context` instance
+    pub fn surface(mut self, surface: wgpu::Surface<'t>) -> Self {
+        self.surface = Some(surface);
+        self
+    }
+
+    /// Preferred format of presented image, first sRGB format supported by surface is used if it isn't supported
+    pub fn surface_format(mut self, format: SurfaceFormat) -> Self {
+        self.surface_format = format;
+        self
+    }
+
+    /// Surface present mode, ignored by headless renderer
+    pub fn present_mode(mut self, mode: wgpu::PresentMode) -> Self {
+        self.present_mode = mode;
+        self
+    }
+
+    /// Device features renderer is required to have, optional ones are used if device has them
+    pub fn features(mut self, features: wgpu::Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    pub fn collector_precision(mut self, precision: CollectorPrecision) -> Self {
+        self.collector_precision = precision;
+        self
+    }
+
+    /// Integrator with its settings used instead of built-in path tracer
+    pub fn integrator<I: Integrator>(mut self, integrator: I, settings: I::Settings) -> Self {
+        self.integrator = Some(Box::new(Configured { integrator, settings }));
+        self
+    }
+
+    pub fn build(self) -> Result<Render<'t>, BuildError> {
+        let context = &self.context;
+
+        if self.extent.w == 0 || self.extent.h == 0 {
+            return Err(BuildError::EmptyExtent(self.extent));
+        }
+        let max_extent = context.device.limits().max_texture_dimension_2d;
+        if self.extent.w > max_extent || self.extent.h > max_extent {
+            return Err(BuildError::ExtentTooLarge(self.extent, max_extent));
+        }
+
+        let missing_features = self.features - context.device.features();
+        if !missing_features.is_empty() {
+            return Err(BuildError::FeaturesUnsupported(missing_features));
+        }
+
+        // Accumulation renders to collectors, all other passes read them
+        let collector_format = self.collector_precision.format();
+        let required_usages = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        if !context.adapter.get_texture_format_features(collector_format).allowed_usages.contains(required_usages) {
+            return Err(BuildError::CollectorUnsupported(self.collector_precision));
+        }
+
+        let format = match &self.surface {
+            Some(surface) => {
+                let capabilities = surface.get_capabilities(&context.adapter);
+                let format = self.surface_format.select(&capabilities.formats)
+                    .or_else(|| SurfaceFormat::Auto.select(&capabilities.formats))
+                    .ok_or(BuildError::SurfaceUnsupported)?;
+
+                // Automatic modes fall back to FIFO, which is always supported
+                let is_automatic = matches!(self.present_mode, wgpu::PresentMode::AutoVsync | wgpu::PresentMode::AutoNoVsync);
+                if !is_automatic && !capabilities.present_modes.contains(&self.present_mode) {
+                    return Err(BuildError::PresentModeUnsupported(self.present_mode, capabilities.present_modes));
+                }
+                format
+            }
+            None => self.surface_format.headless_format(),
+        };
+
+        let surface_configuration = wgpu::SurfaceConfiguration {
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+            desired_maximum_frame_latency: 3,
+            present_mode: self.present_mode,
+            format,
+            width: self.extent.w,
+            height: self.extent.h,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: vec![format],
+        };
+
+        let target = match self.surface {
+            Some(surface) => Target::Surface(surface),
+            None => Target::Texture(Target::create_texture(&context.device, &surface_configuration)),
+        };
+
+        let mut render = Render::new(self.context, target, surface_configuration, collector_format);
+        render.max_sample_count = self.collector_precision.max_sample_count();
+        if let Some(integrator) = self.integrator {
+            let name = integrator.name().to_string();
+            render.set_configured_integrator(integrator).map_err(|error| BuildError::IntegratorFailed(name, error))?;
+        }
+        Ok(render)
+    } // fn build
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+/// Graphics adapter choice, high-performance adapter of any backend by default
+#[derive(Clone, Debug)]
+pub struct AdapterSelector {
+    pub backends: wgpu::Backends,
+    /// Case-insensitive substring of adapter name
+    pub name: Option<String>,
+    /// Use software (fallback) adapter, e.g. llvmpipe or WARP
+    pub software: bool,
+}
+
+impl AdapterSelector {
+    /// Pick adapter, high performance one if name isn't set. Adapter must support `surface` if it's set.
+    /// Software adapter is picked if there are no hardware ones, e.g. on CI machines.
+    pub fn select(&self, instance: &wgpu::Instance, surface: Option<&wgpu::Surface>) -> Option<wgpu::Adapter> {
+        if let Some(name) = &self.name {
+            let name = name.to_lowercase();
+            return instance.enumerate_adapters(self.backends).into_iter().find(|adapter| {
+                let info = adapter.get_info();
+                info.name.to_lowercase().contains(&name)
+                    && (!self.software || info.device_type == wgpu::DeviceType::Cpu)
+                    && surface.is_none_or(|surface| adapter.is_surface_supported(surface))
+            });
+        }
+
+        let request = |force_fallback_adapter| futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            compatible_surface: surface,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback_adapter,
+        }));
+        if self.software {
+            request(true)
+        } else {
+            request(false).or_else(|| request(true))
+        }
+    }
+}
+
+impl Default for AdapterSelector {
+    fn default() -> Self {
+        Self { backends: wgpu::Backends::all(), name: None, software: false }
+    }
+}
+
+/// Features requested from device of adapter with `adapter_features`
+pub fn device_features(adapter_features: wgpu::Features) -> wgpu::Features {
+    // Timestamps are optional, they are only used to measure passes traced by `trace_pass`
+    adapter_features & wgpu::Features::TIMESTAMP_QUERY
+}
+
+/// GPU instance, adapter, device and queue. It's created once and shared by renderer and other GPU users
+/// (UI pass, baking tools, tests) through `Arc`, so they can exchange resources.
+pub struct GpuContext {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl GpuContext {
+    fn create(instance: wgpu::Instance, adapter: wgpu::Adapter) -> Option<Self> {
+        let (device, queue) = futures::executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("Device"),
+            required_features: device_features(adapter.features()),
+            required_limits: adapter.limits(),
+        }, None)).ok()?;
+
+        Some(Self { instance, adapter, device, queue })
+    }
+
+    fn create_instance(selector: &AdapterSelector) -> wgpu::Instance {
+        wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: selector.backends,
+            ..Default::default()
+        })
+    }
+
+    /// Context for offscreen work. Returns None if no adapter matches `selector` or device creation fails.
+    pub fn new(selector: &AdapterSelector) -> Option<Arc<Self>> {
+        let instance = Self::create_instance(selector);
+        let adapter = selector.select(&instance, None)?;
+        Self::create(instance, adapter).map(Arc::new)
+    }
+
+    /// Context with surface of `window`, its adapter is picked among ones able to present to it.
+    /// Returns None if there's no such adapter or surface or device creation fails.
+    pub fn with_surface<'t>(selector: &AdapterSelector, window: impl wgpu::WindowHandle + 't) -> Option<(Arc<Self>, wgpu::Surface<'t>)> {
+        let instance = Self::create_instance(selector);
+        let surface = instance.create_surface(window).ok()?;
+        let adapter = selector.select(&instance, Some(&surface))?;
+        Some((Arc::new(Self::create(instance, adapter)?), surface))
+    }
+}
@@ -0,0 +1,202 @@
+use crate::math::Ext2u;
+
+/// Pixels per workgroup side of partial sum pass, matches convergence.wgsl
+const PARTIAL_WORKGROUP_SIZE: u32 = 8;
+
+/// Relative change sum and sampled pixel count
+const SUM_SIZE: u64 = 2 * std::mem::size_of::<f32>() as u64;
+
+/// Convergence metric of accumulation: mean relative luminance change of pixels sampled by last pass.
+/// It's measured on GPU and read back without waiting, so value lags behind rendered frames.
+pub struct ConvergenceMeter {
+    partial_pipeline: wgpu::ComputePipeline,
+    total_pipeline: wgpu::ComputePipeline,
+    sum_bind_group_layout: wgpu::BindGroupLayout,
+    total_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+
+    workgroup_count: Ext2u,
+    sum_bind_group: wgpu::BindGroup,
+
+    /// Map result of measurement in flight
+    readback: Option<futures::channel::oneshot::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+    /// Measurement in flight was encoded before accumulation restart
+    is_readback_stale: bool,
+    /// Measurement is encoded, but not submitted yet
+    is_measured: bool,
+    value: Option<f32>,
+}
+
+impl ConvergenceMeter {
+    fn create_sum_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, total_buffer: &wgpu::Buffer, workgroup_count: Ext2u) -> wgpu::BindGroup {
+        let partial_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Convergence partial sum buffer"),
+            mapped_at_creation: false,
+            size: (workgroup_count.w * workgroup_count.h) as u64 * SUM_SIZE,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: partial_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: total_buffer.as_entire_binding() },
+            ],
+            label: Some("Convergence sum bind group"),
+            layout,
+        })
+    }
+
+    fn workgroup_count(extent: Ext2u) -> Ext2u {
+        Ext2u::new(extent.w.div_ceil(PARTIAL_WORKGROUP_SIZE), extent.h.div_ceil(PARTIAL_WORKGROUP_SIZE))
+    }
+
+    /// Collector bind group layout is used to read collector states before and after pass
+    pub fn new(device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, extent: Ext2u) -> Self {
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            count: None,
+            ty: wgpu::BindingType::Buffer {
+                has_dynamic_offset: false,
+                min_binding_size: None,
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+            },
+            visibility: wgpu::ShaderStages::COMPUTE,
+        };
+        let sum_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Convergence sum bind group layout"),
+            entries: &[storage_entry(0), storage_entry(1)],
+        });
+
+        let total_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Convergence total sum buffer"),
+            mapped_at_creation: false,
+            size: SUM_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Convergence readback buffer"),
+            mapped_at_creation: false,
+            size: SUM_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Convergence Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/convergence.wgsl")))
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[collector_bind_group_layout, collector_bind_group_layout, &sum_bind_group_layout],
+            ..Default::default()
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&layout),
+            module: &shader_module,
+            entry_point,
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        });
+
+        let workgroup_count = Self::workgroup_count(extent);
+        let sum_bind_group = Self::create_sum_bind_group(device, &sum_bind_group_layout, &total_buffer, workgroup_count);
+
+        Self {
+            partial_pipeline: create_pipeline("Convergence partial sum pipeline", "cs_partial"),
+            total_pipeline: create_pipeline("Convergence total sum pipeline", "cs_total"),
+            sum_bind_group_layout,
+            total_buffer,
+            readback_buffer,
+            workgroup_count,
+            sum_bind_group,
+            readback: None,
+            is_readback_stale: false,
+            is_measured: false,
+            value: None,
+        }
+    } // fn new
+
+    pub fn resize(&mut self, device: &wgpu::Device, extent: Ext2u) {
+        self.workgroup_count = Self::workgroup_count(extent);
+        self.sum_bind_group = Self::create_sum_bind_group(device, &self.sum_bind_group_layout, &self.total_buffer, self.workgroup_count);
+    }
+
+    /// Last measured metric, None until accumulation after restart is measured
+    pub fn value(&self) -> Option<f32> {
+        self.value
+    }
+
+    /// Forget metric of restarted accumulation, including one in flight
+    pub fn reset(&mut self) {
+        self.value = None;
+        self.is_readback_stale = self.readback.is_some();
+    }
+
+    /// Measure change between collector states before and after pass, skipped while previous measurement is in flight.
+    /// `submit` must be called after submission of `encoder`.
+    pub fn measure(&mut self, encoder: &mut wgpu::CommandEncoder, previous: &wgpu::BindGroup, current: &wgpu::BindGroup) {
+        if self.readback.is_some() {
+            return;
+        }
+
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Convergence pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_bind_group(0, previous, &[]);
+            compute_pass.set_bind_group(1, current, &[]);
+            compute_pass.set_bind_group(2, &self.sum_bind_group, &[]);
+            compute_pass.set_pipeline(&self.partial_pipeline);
+            compute_pass.dispatch_workgroups(self.workgroup_count.w, self.workgroup_count.h, 1);
+            compute_pass.set_pipeline(&self.total_pipeline);
+            compute_pass.dispatch_workgroups(1, 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&self.total_buffer, 0, &self.readback_buffer, 0, SUM_SIZE);
+        self.is_measured = true;
+    }
+
+    /// Start readback of submitted measurement
+    pub fn submit(&mut self) {
+        if !std::mem::take(&mut self.is_measured) {
+            return;
+        }
+
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        self.readback_buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| _ = sender.send(result));
+        self.readback = Some(receiver);
+    }
+
+    /// Take value of finished measurement, doesn't wait for one in flight
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let Some(receiver) = &mut self.readback else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let result = match receiver.try_recv() {
+            Ok(None) => return,
+            Ok(Some(result)) => result,
+            Err(_) => Err(wgpu::BufferAsyncError),
+        };
+        self.readback = None;
+        let is_stale = std::mem::take(&mut self.is_readback_stale);
+        if result.is_err() {
+            return;
+        }
+
+        let [change_sum, pixel_count] = {
+            let mapped = self.readback_buffer.slice(..).get_mapped_range();
+            [0, 1].map(|index| {
+                let offset = index * std::mem::size_of::<f32>();
+                f32::from_ne_bytes(std::array::from_fn(|byte| mapped[offset + byte]))
+            })
+        };
+        self.readback_buffer.unmap();
+
+        // Passes sampling no measurable pixels don't change metric
+        if !is_stale && pixel_count > 0.0 {
+            self.value = Some(change_sum / pixel_count);
+        }
+    } // fn poll
+}
@@ -0,0 +1,303 @@
+use crate::math::Ext2u;
+
+use super::{as_bytes, Collector, Render};
+
+/// Number of à-trous filter iterations, filter footprint doubles with each of them
+const ITERATION_COUNT: usize = 5;
+
+/// Filtered color (RGB) and its variance (A)
+const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct IterationData {
+    /// Distance between filter taps, in pixels
+    step: u32,
+    /// Last iteration writes unit sample count instead of variance, so place pass can present result directly
+    is_last: u32,
+    _pad0: [u32; 2],
+}
+
+/// Spatial denoiser in SVGF style. Per-pixel variance is estimated from accumulated luminance moments,
+/// then image is smoothed by edge-aware à-trous wavelet filter guided by primary hit normals and distances.
+/// Filter strength follows variance, so denoising fades out as accumulation converges.
+///
+/// In component mode diffuse radiance is divided by primary hit albedo, so textures aren't blurred, and it's
+/// filtered jointly with remaining specular radiance, so highlights aren't smeared by diffuse noise.
+/// Filtered components are recombined by albedo.
+pub struct Denoiser {
+    prepare_pipeline: wgpu::RenderPipeline,
+    iteration_pipeline: wgpu::RenderPipeline,
+    image_bind_group_layout: wgpu::BindGroupLayout,
+    iteration_buffers: Vec<wgpu::Buffer>,
+
+    prepare_components_pipeline: wgpu::RenderPipeline,
+    iteration_components_pipeline: wgpu::RenderPipeline,
+    combine_pipeline: wgpu::RenderPipeline,
+    components_bind_group_layout: wgpu::BindGroupLayout,
+
+    extent: Ext2u,
+    /// Ping-pong filter images, irradiance ones in component mode
+    views: [wgpu::TextureView; 2],
+    /// Ping-pong specular images, allocated by first component mode render
+    specular_views: Option<[wgpu::TextureView; 2]>,
+    /// Iteration input image and parameters
+    iteration_bind_groups: Vec<wgpu::BindGroup>,
+}
+
+impl Denoiser {
+    fn create_views(device: &wgpu::Device, extent: Ext2u) -> [wgpu::TextureView; 2] {
+        std::array::from_fn(|_| device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: IMAGE_FORMAT,
+            label: Some("Denoiser image"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default()))
+    }
+
+    fn create_iteration_bind_groups(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        views: &[wgpu::TextureView; 2],
+        buffers: &[wgpu::Buffer]
+    ) -> Vec<wgpu::BindGroup> {
+        buffers.iter().enumerate().map(|(index, buffer)| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&views[index % 2]) },
+                wgpu::BindGroupEntry { binding: 1, resource: buffer.as_entire_binding() },
+            ],
+            label: Some("Denoiser iteration bind group"),
+            layout,
+        })).collect()
+    }
+
+    /// Collector bind group layout is used to read accumulated image and its features
+    pub fn new(device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, extent: Ext2u) -> Self {
+        let image_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Denoiser image bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            count: None,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            visibility: wgpu::ShaderStages::FRAGMENT,
+        };
+
+        // Image layout extended by specular image and albedo guide
+        let components_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Denoiser components bind group layout"),
+            entries: &[
+                texture_entry(0),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                texture_entry(2),
+                texture_entry(3),
+            ],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Denoiser Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/denoise.wgsl")))
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str, bind_group_layouts: &[&wgpu::BindGroupLayout], target_count: usize| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts,
+                ..Default::default()
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                depth_stencil: None,
+                fragment: Some(wgpu::FragmentState {
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    entry_point,
+                    module: &shader_module,
+                    targets: &vec![Some(wgpu::ColorTargetState {
+                        blend: None,
+                        format: IMAGE_FORMAT,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }); target_count],
+                }),
+                layout: Some(&layout),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    entry_point: "vs_main",
+                    module: &shader_module,
+                }
+            })
+        };
+
+        let prepare_pipeline = create_pipeline("Denoiser prepare pipeline", "fs_prepare", &[collector_bind_group_layout], 1);
+        let iteration_pipeline = create_pipeline("Denoiser iteration pipeline", "fs_iteration", &[collector_bind_group_layout, &image_bind_group_layout], 1);
+
+        let components_layouts = [collector_bind_group_layout, &components_bind_group_layout];
+        let prepare_components_pipeline = create_pipeline("Denoiser component prepare pipeline", "fs_prepare_components", &components_layouts, 2);
+        let iteration_components_pipeline = create_pipeline("Denoiser component iteration pipeline", "fs_iteration_components", &components_layouts, 2);
+        let combine_pipeline = create_pipeline("Denoiser combine pipeline", "fs_combine", &components_layouts, 1);
+
+        let iteration_buffers = (0..ITERATION_COUNT).map(|index| {
+            let data = IterationData {
+                step: 1 << index,
+                is_last: (index + 1 == ITERATION_COUNT) as u32,
+                ..Default::default()
+            };
+
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Denoiser iteration UBO"),
+                mapped_at_creation: true,
+                size: std::mem::size_of::<IterationData>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            buffer.slice(..).get_mapped_range_mut().copy_from_slice(as_bytes(&data));
+            buffer.unmap();
+            buffer
+        }).collect::<Vec<_>>();
+
+        let views = Self::create_views(device, extent);
+        let iteration_bind_groups = Self::create_iteration_bind_groups(device, &image_bind_group_layout, &views, &iteration_buffers);
+
+        Self {
+            prepare_pipeline,
+            iteration_pipeline,
+            image_bind_group_layout,
+            iteration_buffers,
+            prepare_components_pipeline,
+            iteration_components_pipeline,
+            combine_pipeline,
+            components_bind_group_layout,
+            extent,
+            views,
+            specular_views: None,
+            iteration_bind_groups,
+        }
+    } // fn new
+
+    pub fn resize(&mut self, device: &wgpu::Device, extent: Ext2u) {
+        self.extent = extent;
+        self.views = Self::create_views(device, extent);
+        self.specular_views = None;
+        self.iteration_bind_groups = Self::create_iteration_bind_groups(device, &self.image_bind_group_layout, &self.views, &self.iteration_buffers);
+    }
+
+    /// Ping-pong filter images, contents of last `render` call
+    pub fn views(&self) -> &[wgpu::TextureView; 2] {
+        &self.views
+    }
+
+    /// Denoise collector contents, by components if primary hit `albedo` is given.
+    /// Returned bind group has collector layout, so place pass can present it as is.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        collector_bind_group_layout: &wgpu::BindGroupLayout,
+        collector: &Collector,
+        albedo: Option<&wgpu::TextureView>
+    ) -> wgpu::BindGroup {
+        let mut run_pass = |targets: [Option<&wgpu::TextureView>; 2], pipeline: &wgpu::RenderPipeline, image_bind_group: Option<&wgpu::BindGroup>| {
+            let color_attachments = targets.map(|target| target.map(|view| wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view,
+            }));
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: if targets[1].is_some() { &color_attachments } else { &color_attachments[..1] },
+                ..Default::default()
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &collector.bind_group, &[]);
+            if let Some(image_bind_group) = image_bind_group {
+                render_pass.set_bind_group(1, image_bind_group, &[]);
+            }
+            render_pass.draw(0..4, 0..1);
+        };
+
+        let Some(albedo) = albedo else {
+            run_pass([Some(&self.views[0]), None], &self.prepare_pipeline, None);
+            for (index, bind_group) in self.iteration_bind_groups.iter().enumerate() {
+                run_pass([Some(&self.views[(index + 1) % 2]), None], &self.iteration_pipeline, Some(bind_group));
+            }
+
+            return Render::create_collector_bind_group(device, collector_bind_group_layout, &self.views[ITERATION_COUNT % 2], &collector.feature_view, &collector.diffuse_view);
+        };
+
+        let extent = self.extent;
+        let views = &self.views;
+        let specular_views = self.specular_views.get_or_insert_with(|| Self::create_views(device, extent));
+
+        // Iteration inputs with albedo guide. Prepare pass reads albedo only, so it takes bind group of images
+        // it doesn't write to.
+        let bind_groups = self.iteration_buffers.iter().enumerate().map(|(index, buffer)| device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&views[index % 2]) },
+                wgpu::BindGroupEntry { binding: 1, resource: buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(&specular_views[index % 2]) },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(albedo) },
+            ],
+            label: Some("Denoiser components bind group"),
+            layout: &self.components_bind_group_layout,
+        })).collect::<Vec<_>>();
+
+        run_pass([Some(&views[0]), Some(&specular_views[0])], &self.prepare_components_pipeline, Some(&bind_groups[1]));
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            run_pass([Some(&views[(index + 1) % 2]), Some(&specular_views[(index + 1) % 2])], &self.iteration_components_pipeline, Some(bind_group));
+        }
+
+        // Filtered components are in images of last iteration output, they're combined into the other ones
+        let output_view = &views[(ITERATION_COUNT + 1) % 2];
+        run_pass([Some(output_view), None], &self.combine_pipeline, Some(&bind_groups[ITERATION_COUNT % 2]));
+
+        Render::create_collector_bind_group(device, collector_bind_group_layout, output_view, &collector.feature_view, &collector.diffuse_view)
+    } // fn render
+}
@@ -0,0 +1,87 @@
+/// GPU pass of frame with resources it reads and writes
+pub struct FramePass {
+    pub name: String,
+    pub reads: Vec<String>,
+    pub writes: Vec<String>,
+    /// Frames the pass runs in, every frame if not set
+    pub condition: Option<&'static str>,
+    /// Pass is enabled by render settings
+    pub is_enabled: bool,
+}
+
+impl FramePass {
+    pub fn new(name: impl Into<String>, reads: &[&str], writes: &[&str]) -> Self {
+        let to_strings = |resources: &[&str]| resources.iter().map(|resource| resource.to_string()).collect();
+        Self { name: name.into(), reads: to_strings(reads), writes: to_strings(writes), condition: None, is_enabled: true }
+    }
+
+    pub fn condition(mut self, condition: &'static str) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn enabled(mut self, is_enabled: bool) -> Self {
+        self.is_enabled = is_enabled;
+        self
+    }
+}
+
+/// Passes of presented frame in encoding order, see `Render::frame_graph`
+pub struct FrameGraph {
+    pub passes: Vec<FramePass>,
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl FrameGraph {
+    /// Write graph in Graphviz DOT format. Passes are boxes numbered in encoding order, resources are ellipses.
+    /// Passes that don't run every frame are dashed, ones disabled by render settings are gray.
+    pub fn write_dot(&self, mut writer: impl std::io::Write) -> std::io::Result<()> {
+        writeln!(writer, "digraph frame {{")?;
+        writeln!(writer, "    rankdir=LR;")?;
+        writeln!(writer, "    node [fontname=\"sans-serif\"];")?;
+
+        let mut resources = Vec::<&str>::new();
+        for pass in &self.passes {
+            for resource in pass.reads.iter().chain(&pass.writes) {
+                if !resources.contains(&resource.as_str()) {
+                    resources.push(resource);
+                }
+            }
+        }
+        for resource in &resources {
+            writeln!(writer, "    \"{}\" [shape=ellipse];", escape(resource))?;
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            let id = format!("\"pass {}\"", index);
+            // Condition is on separate label line
+            let label = match pass.condition {
+                Some(condition) => format!("{}. {}\\n({})", index + 1, escape(&pass.name), escape(condition)),
+                None => format!("{}. {}", index + 1, escape(&pass.name)),
+            };
+            let mut style = vec!["filled"];
+            if pass.condition.is_some() {
+                style.push("dashed");
+            }
+            writeln!(writer, "    {} [shape=box, label=\"{}\", style=\"{}\", fillcolor={}, fontcolor={}];",
+                id,
+                label,
+                style.join(","),
+                if pass.is_enabled { "\"#dde8f6\"" } else { "\"#f0f0f0\"" },
+                if pass.is_enabled { "black" } else { "gray" },
+            )?;
+
+            for resource in &pass.reads {
+                writeln!(writer, "    \"{}\" -> {};", escape(resource), id)?;
+            }
+            for resource in &pass.writes {
+                writeln!(writer, "    {} -> \"{}\";", id, escape(resource))?;
+            }
+        }
+
+        writeln!(writer, "}}")
+    } // fn write_dot
+}
@@ -0,0 +1,596 @@
+use std::f32::consts::PI;
+
+use crate::math::Ext2u;
+
+use super::as_bytes;
+
+/// Surface pixels per glare image pixel along each axis, matches glare.wgsl
+const GLARE_DOWNSAMPLE: u32 = 2;
+
+/// Complex images, real and imaginary parts of R and G in one texture and of B in other one
+const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+/// Glare radiance read by place pass, filterable for upsampling
+const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Side of aperture image, diffraction pattern has the same size and spreads light at most by half of it
+const PATTERN_SIZE: usize = 256;
+
+/// Aperture diameter in aperture image pixels. Diffraction pattern scale is inversely proportional to it,
+/// first dark ring of round aperture is at `1.22 * PATTERN_SIZE / APERTURE_DIAMETER` glare image pixels.
+const APERTURE_DIAMETER: f32 = 64.0;
+
+/// Pattern is computed at this wavelength and scaled for others, in nm
+const REFERENCE_WAVELENGTH: f32 = 550.0;
+
+/// Wavelengths averaged by each color channel, in nm, so diffraction rings and spikes are smeared into spectrum
+const CHANNEL_WAVELENGTHS: [[f32; 4]; 3] = [
+    [590.0, 620.0, 650.0, 680.0],
+    [500.0, 530.0, 560.0, 590.0],
+    [420.0, 445.0, 470.0, 495.0],
+];
+
+/// In-place radix-2 FFT of power of two sized sequence of complex numbers
+fn fft(data: &mut [[f32; 2]]) {
+    let size = data.len();
+    let bit_count = size.trailing_zeros();
+    for index in 1..size {
+        let reversed = index.reverse_bits() >> (usize::BITS - bit_count);
+        if index < reversed {
+            data.swap(index, reversed);
+        }
+    }
+
+    let mut span = 2;
+    while span <= size {
+        for start in (0..size).step_by(span) {
+            for offset in 0..span / 2 {
+                let (sin, cos) = (-2.0 * PI * offset as f32 / span as f32).sin_cos();
+                let [even_re, even_im] = data[start + offset];
+                let [odd_re, odd_im] = data[start + offset + span / 2];
+                let product = [odd_re * cos - odd_im * sin, odd_re * sin + odd_im * cos];
+
+                data[start + offset] = [even_re + product[0], even_im + product[1]];
+                data[start + offset + span / 2] = [even_re - product[0], even_im - product[1]];
+            }
+        }
+        span *= 2;
+    }
+} // fn fft
+
+/// Aperture of given number of blades (round if less than 3) and rotation in radians, for aperture image pixel at `offset` from center
+fn aperture_coverage(offset: (f32, f32), blades: u32, rotation: f32) -> f32 {
+    let radius = (offset.0 * offset.0 + offset.1 * offset.1).sqrt();
+    let mut edge_radius = APERTURE_DIAMETER * 0.5;
+
+    if blades >= 3 {
+        // Distance to polygon edge along pixel direction, polygon vertices are at blade angles
+        let blade_angle = 2.0 * PI / blades as f32;
+        let angle = (offset.1.atan2(offset.0) - rotation).rem_euclid(blade_angle);
+        edge_radius *= (blade_angle * 0.5).cos() / (angle - blade_angle * 0.5).cos();
+    }
+
+    // Edge is antialiased over one pixel
+    (edge_radius - radius + 0.5).clamp(0.0, 1.0)
+}
+
+/// Fraunhofer diffraction pattern of aperture at reference wavelength, PATTERN_SIZE^2 image centered at
+/// `PATTERN_SIZE / 2` with unit sum
+fn diffraction_pattern(blades: u32, rotation: f32) -> Vec<f32> {
+    let center = PATTERN_SIZE as f32 * 0.5;
+    let mut spectrum = (0..PATTERN_SIZE * PATTERN_SIZE)
+        .map(|index| {
+            let offset = ((index % PATTERN_SIZE) as f32 + 0.5 - center, (index / PATTERN_SIZE) as f32 + 0.5 - center);
+            [aperture_coverage(offset, blades, rotation), 0.0]
+        })
+        .collect::<Vec<_>>();
+
+    for row in spectrum.chunks_exact_mut(PATTERN_SIZE) {
+        fft(row);
+    }
+    let mut column = vec![[0.0; 2]; PATTERN_SIZE];
+    for x in 0..PATTERN_SIZE {
+        for y in 0..PATTERN_SIZE {
+            column[y] = spectrum[y * PATTERN_SIZE + x];
+        }
+        fft(&mut column);
+        for y in 0..PATTERN_SIZE {
+            spectrum[y * PATTERN_SIZE + x] = column[y];
+        }
+    }
+
+    // Zero frequency is moved to center
+    let half = PATTERN_SIZE / 2;
+    let mut pattern = (0..PATTERN_SIZE * PATTERN_SIZE)
+        .map(|index| {
+            let [re, im] = spectrum[(index / PATTERN_SIZE + half) % PATTERN_SIZE * PATTERN_SIZE + (index % PATTERN_SIZE + half) % PATTERN_SIZE];
+            re * re + im * im
+        })
+        .collect::<Vec<_>>();
+
+    let sum = pattern.iter().sum::<f32>();
+    pattern.iter_mut().for_each(|value| *value /= sum);
+    pattern
+} // fn diffraction_pattern
+
+/// Bilinear sample of diffraction pattern at `offset` from its center, zero outside of it
+fn sample_pattern(pattern: &[f32], offset: (f32, f32)) -> f32 {
+    let x = offset.0 + PATTERN_SIZE as f32 * 0.5;
+    let y = offset.1 + PATTERN_SIZE as f32 * 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+
+    let texel = |x: f32, y: f32| if x >= 0.0 && y >= 0.0 && x < PATTERN_SIZE as f32 && y < PATTERN_SIZE as f32 {
+        pattern[y as usize * PATTERN_SIZE + x as usize]
+    } else {
+        0.0
+    };
+
+    (texel(x0, y0) * (1.0 - tx) + texel(x0 + 1.0, y0) * tx) * (1.0 - ty)
+        + (texel(x0, y0 + 1.0) * (1.0 - tx) + texel(x0 + 1.0, y0 + 1.0) * tx) * ty
+}
+
+/// Glare kernel on `grid` of glare image size, as complex images in shader layout. Kernel origin is at (0, 0),
+/// negative offsets wrap around. Pattern core is removed, as image already contains it, and kernel is
+/// prescaled for inverse FFT normalization.
+fn kernel_images(pattern: &[f32], grid: Ext2u) -> [Vec<[f32; 4]>; 2] {
+    let mut images = [vec![[0.0; 4]; (grid.w * grid.h) as usize], vec![[0.0; 4]; (grid.w * grid.h) as usize]];
+    let radius = (PATTERN_SIZE / 2) as i32;
+    let core_radius = 1.22 * PATTERN_SIZE as f32 / APERTURE_DIAMETER;
+    let normalization = 1.0 / (grid.w * grid.h) as f32;
+
+    for dy in -radius..radius {
+        for dx in -radius..radius {
+            let distance = ((dx * dx + dy * dy) as f32).sqrt();
+            // Smooth window avoids ringing of truncated pattern
+            let window = (1.0 - (distance / radius as f32).powi(2)).max(0.0).powi(2);
+            let core = ((distance - 0.5 * core_radius) / (0.5 * core_radius)).clamp(0.0, 1.0);
+            if window * core == 0.0 {
+                continue;
+            }
+
+            // Pattern scales with wavelength, intensity is rescaled to keep its energy
+            let channels = CHANNEL_WAVELENGTHS.map(|wavelengths| wavelengths
+                .iter()
+                .map(|wavelength| {
+                    let scale = wavelength / REFERENCE_WAVELENGTH;
+                    sample_pattern(pattern, (dx as f32 / scale, dy as f32 / scale)) / (scale * scale)
+                })
+                .sum::<f32>() / wavelengths.len() as f32 * window * core * normalization
+            );
+
+            let index = (dy.rem_euclid(grid.h as i32) as u32 * grid.w + dx.rem_euclid(grid.w as i32) as u32) as usize;
+            images[0][index][0] += channels[0];
+            images[0][index][2] += channels[1];
+            images[1][index][0] += channels[2];
+        }
+    }
+
+    images
+} // fn kernel_images
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct StageData {
+    axis: u32,
+    span: u32,
+    bit_count: u32,
+    is_inverse: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct ResolveData {
+    strength: f32,
+    _pad0: [f32; 3],
+}
+
+/// Complex image in two textures, see `IMAGE_FORMAT`
+struct ComplexImage {
+    textures: [wgpu::Texture; 2],
+    views: [wgpu::TextureView; 2],
+    bind_group: wgpu::BindGroup,
+}
+
+impl ComplexImage {
+    fn new(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, grid: Ext2u) -> Self {
+        let textures: [wgpu::Texture; 2] = std::array::from_fn(|_| device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: IMAGE_FORMAT,
+            label: Some("Glare complex image"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: grid.w, height: grid.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        }));
+        let views = textures.each_ref().map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&views[0]) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&views[1]) },
+            ],
+            label: Some("Glare complex image bind group"),
+            layout,
+        });
+
+        Self { textures, views, bind_group }
+    }
+}
+
+/// Resources depending on glare image size
+struct GlareImages {
+    /// Power of two sized glare image, surface image is downsampled into its corner and padded with zeros
+    grid: Ext2u,
+    /// Ping-pong FFT images
+    images: [ComplexImage; 2],
+    /// Spectrum of glare kernel
+    kernel: ComplexImage,
+    /// Row stages followed by column ones
+    forward_stages: Vec<wgpu::BindGroup>,
+    inverse_stages: Vec<wgpu::BindGroup>,
+    output_bind_group: wgpu::BindGroup,
+    output_view: wgpu::TextureView,
+    /// Aperture of kernel in kernel image, None if it isn't computed yet
+    kernel_aperture: Option<(u32, f32)>,
+}
+
+/// Glare by aperture diffraction. Presented image is convolved by diffraction pattern of camera aperture
+/// in frequency domain, pattern is smeared over wavelengths of each channel. Convolution is performed
+/// at reduced resolution and wraps around padded image borders, glare is added to image by place pass.
+pub struct Glare {
+    downsample_pipeline: wgpu::RenderPipeline,
+    fft_pipeline: wgpu::RenderPipeline,
+    multiply_pipeline: wgpu::RenderPipeline,
+    resolve_pipeline: wgpu::RenderPipeline,
+    image_bind_group_layout: wgpu::BindGroupLayout,
+    stage_bind_group_layout: wgpu::BindGroupLayout,
+    output_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    resolve_buffer: wgpu::Buffer,
+    resolve_bind_group: wgpu::BindGroup,
+    /// Output of disabled glare, single black pixel
+    disabled_bind_group: wgpu::BindGroup,
+
+    /// Aperture blades and rotation with their diffraction pattern
+    pattern: Option<((u32, f32), Vec<f32>)>,
+    extent: Ext2u,
+    /// Created on first use, so disabled glare takes no memory
+    images: Option<GlareImages>,
+}
+
+impl Glare {
+    fn create_stage_buffer(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, data: StageData) -> wgpu::BindGroup {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glare FFT stage UBO"),
+            mapped_at_creation: true,
+            size: std::mem::size_of::<StageData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        buffer.slice(..).get_mapped_range_mut().copy_from_slice(as_bytes(&data));
+        buffer.unmap();
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+            label: Some("Glare FFT stage bind group"),
+            layout,
+        })
+    }
+
+    /// FFT stages over rows, then over columns of grid
+    fn create_stages(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, grid: Ext2u, is_inverse: bool) -> Vec<wgpu::BindGroup> {
+        [grid.w, grid.h].into_iter().enumerate().flat_map(|(axis, size)| {
+            let bit_count = size.trailing_zeros();
+            (1..=bit_count).map(move |stage| StageData {
+                axis: axis as u32,
+                span: 1 << stage,
+                bit_count: if stage == 1 { bit_count } else { 0 },
+                is_inverse: is_inverse as u32,
+            })
+        }).map(|data| Self::create_stage_buffer(device, layout, data)).collect()
+    }
+
+    fn create_output_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+            ],
+            label: Some("Glare output bind group"),
+            layout,
+        })
+    }
+
+    fn create_output_view(device: &wgpu::Device, extent: Ext2u) -> wgpu::TextureView {
+        device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            label: Some("Glare output"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_images(&self, device: &wgpu::Device) -> GlareImages {
+        let grid = Ext2u::new(
+            self.extent.w.div_ceil(GLARE_DOWNSAMPLE).max(1).next_power_of_two(),
+            self.extent.h.div_ceil(GLARE_DOWNSAMPLE).max(1).next_power_of_two(),
+        );
+        let output_view = Self::create_output_view(device, grid);
+
+        GlareImages {
+            grid,
+            images: std::array::from_fn(|_| ComplexImage::new(device, &self.image_bind_group_layout, grid)),
+            kernel: ComplexImage::new(device, &self.image_bind_group_layout, grid),
+            forward_stages: Self::create_stages(device, &self.stage_bind_group_layout, grid, false),
+            inverse_stages: Self::create_stages(device, &self.stage_bind_group_layout, grid, true),
+            output_bind_group: Self::create_output_bind_group(device, &self.output_bind_group_layout, &output_view, &self.sampler),
+            output_view,
+            kernel_aperture: None,
+        }
+    }
+
+    /// Collector bind group layout is used to read presented image
+    pub fn new(device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, extent: Ext2u) -> Self {
+        let image_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Glare image bind group layout"),
+            entries: &[0, 1].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                count: None,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                visibility: wgpu::ShaderStages::FRAGMENT,
+            }),
+        });
+
+        let stage_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Glare stage bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let output_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Glare output bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glare output sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glare resolve UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<ResolveData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let resolve_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: resolve_buffer.as_entire_binding() }],
+            label: Some("Glare resolve bind group"),
+            layout: &stage_bind_group_layout,
+        });
+
+        let disabled_bind_group = Self::create_output_bind_group(device, &output_bind_group_layout, &Self::create_output_view(device, Ext2u::new(1, 1)), &sampler);
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Glare Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/glare.wgsl")))
+        });
+
+        let create_pipeline = |label: &str, entry_point: &str, bind_group_layouts: &[&wgpu::BindGroupLayout], targets: &[Option<wgpu::ColorTargetState>]| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts,
+                ..Default::default()
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                depth_stencil: None,
+                fragment: Some(wgpu::FragmentState {
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    entry_point,
+                    module: &shader_module,
+                    targets,
+                }),
+                layout: Some(&layout),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    entry_point: "vs_main",
+                    module: &shader_module,
+                }
+            })
+        };
+
+        let image_target = Some(wgpu::ColorTargetState {
+            blend: None,
+            format: IMAGE_FORMAT,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let image_targets = [image_target.clone(), image_target];
+        let output_targets = [Some(wgpu::ColorTargetState {
+            blend: None,
+            format: OUTPUT_FORMAT,
+            write_mask: wgpu::ColorWrites::ALL,
+        })];
+
+        let downsample_pipeline = create_pipeline("Glare downsample pipeline", "fs_downsample", &[collector_bind_group_layout], &image_targets);
+        let fft_pipeline = create_pipeline("Glare FFT pipeline", "fs_fft", &[&image_bind_group_layout, &stage_bind_group_layout], &image_targets);
+        let multiply_pipeline = create_pipeline("Glare multiply pipeline", "fs_multiply", &[&image_bind_group_layout, &image_bind_group_layout], &image_targets);
+        let resolve_pipeline = create_pipeline("Glare resolve pipeline", "fs_resolve", &[&image_bind_group_layout, &stage_bind_group_layout], &output_targets);
+
+        Self {
+            downsample_pipeline,
+            fft_pipeline,
+            multiply_pipeline,
+            resolve_pipeline,
+            image_bind_group_layout,
+            stage_bind_group_layout,
+            output_bind_group_layout,
+            sampler,
+            resolve_buffer,
+            resolve_bind_group,
+            disabled_bind_group,
+            pattern: None,
+            extent,
+            images: None,
+        }
+    } // fn new
+
+    pub fn resize(&mut self, extent: Ext2u) {
+        self.extent = extent;
+        self.images = None;
+    }
+
+    /// Layout of output bind group, place pass reads glare through it
+    pub fn output_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.output_bind_group_layout
+    }
+
+    /// Glare computed by last `render` call, black image if glare is disabled
+    pub fn output_bind_group(&self, is_enabled: bool) -> &wgpu::BindGroup {
+        match &self.images {
+            Some(images) if is_enabled => &images.output_bind_group,
+            _ => &self.disabled_bind_group,
+        }
+    }
+
+    fn run_pass(encoder: &mut wgpu::CommandEncoder, targets: &[&wgpu::TextureView], pipeline: &wgpu::RenderPipeline, bind_groups: &[&wgpu::BindGroup]) {
+        let color_attachments = targets.iter().map(|view| Some(wgpu::RenderPassColorAttachment {
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+            resolve_target: None,
+            view,
+        })).collect::<Vec<_>>();
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &color_attachments,
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            render_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Run FFT stages over ping-pong images, starting from `source` one. Returns index of image with result.
+    fn run_stages(&self, encoder: &mut wgpu::CommandEncoder, images: &GlareImages, stages: &[wgpu::BindGroup], mut source: usize) -> usize {
+        for stage in stages {
+            let [target_rg, target_b] = &images.images[1 - source].views;
+            Self::run_pass(encoder, &[target_rg, target_b], &self.fft_pipeline, &[&images.images[source].bind_group, stage]);
+            source = 1 - source;
+        }
+        source
+    }
+
+    /// Compute kernel spectrum of aperture with given blade count and rotation in radians
+    fn update_kernel(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, images: &mut GlareImages, pattern: &[f32], aperture: (u32, f32)) {
+        let grid = images.grid;
+        let kernel_images = kernel_images(pattern, grid);
+        for (texture, data) in images.images[0].textures.iter().zip(&kernel_images) {
+            queue.write_texture(
+                texture.as_image_copy(),
+                super::slice_as_bytes(data),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(grid.w * std::mem::size_of::<[f32; 4]>() as u32),
+                    rows_per_image: None,
+                },
+                wgpu::Extent3d { width: grid.w, height: grid.h, depth_or_array_layers: 1 }
+            );
+        }
+
+        let result = self.run_stages(encoder, images, &images.forward_stages, 0);
+        for (source, target) in images.images[result].textures.iter().zip(&images.kernel.textures) {
+            encoder.copy_texture_to_texture(source.as_image_copy(), target.as_image_copy(), source.size());
+        }
+        images.kernel_aperture = Some(aperture);
+    } // fn update_kernel
+
+    /// Compute glare of collector contents for aperture with given blade count and rotation in radians.
+    /// Glare radiance is scaled by `strength`.
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        collector_bind_group: &wgpu::BindGroup,
+        aperture: (u32, f32),
+        strength: f32
+    ) {
+        let mut images = match self.images.take() {
+            Some(images) => images,
+            None => self.create_images(device),
+        };
+        // Pattern is kept for kernel updates on resize
+        if images.kernel_aperture != Some(aperture) {
+            let pattern = match self.pattern.take() {
+                Some((pattern_aperture, pattern)) if pattern_aperture == aperture => pattern,
+                _ => diffraction_pattern(aperture.0, aperture.1),
+            };
+            self.update_kernel(queue, encoder, &mut images, &pattern, aperture);
+            self.pattern = Some((aperture, pattern));
+        }
+        queue.write_buffer(&self.resolve_buffer, 0, as_bytes(&ResolveData { strength, ..Default::default() }));
+
+        let [image_rg, image_b] = &images.images[0].views;
+        Self::run_pass(encoder, &[image_rg, image_b], &self.downsample_pipeline, &[collector_bind_group]);
+
+        let spectrum = self.run_stages(encoder, &images, &images.forward_stages, 0);
+        let [target_rg, target_b] = &images.images[1 - spectrum].views;
+        Self::run_pass(encoder, &[target_rg, target_b], &self.multiply_pipeline, &[&images.images[spectrum].bind_group, &images.kernel.bind_group]);
+
+        let result = self.run_stages(encoder, &images, &images.inverse_stages, 1 - spectrum);
+        Self::run_pass(encoder, &[&images.output_view], &self.resolve_pipeline, &[&images.images[result].bind_group, &self.resolve_bind_group]);
+
+        self.images = Some(images);
+    } // fn render
+}
@@ -0,0 +1,75 @@
+/// Render pass duration measured by timestamp queries, exists only if device supports them
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    /// Nanoseconds per timestamp tick
+    period: f32,
+}
+
+/// Pass start and end timestamps
+const QUERY_COUNT: u32 = 2;
+const QUERY_BUFFER_SIZE: u64 = QUERY_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+
+impl GpuTimer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        Some(Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("Pass timestamp query set"),
+                count: QUERY_COUNT,
+                ty: wgpu::QueryType::Timestamp,
+            }),
+            resolve_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp resolve buffer"),
+                mapped_at_creation: false,
+                size: QUERY_BUFFER_SIZE,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            }),
+            readback_buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Timestamp readback buffer"),
+                mapped_at_creation: false,
+                size: QUERY_BUFFER_SIZE,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            }),
+            period: queue.get_timestamp_period(),
+        })
+    }
+
+    /// Timestamps written at start and end of measured pass
+    pub fn timestamp_writes(&self) -> wgpu::RenderPassTimestampWrites<'_> {
+        wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(0),
+            end_of_pass_write_index: Some(1),
+        }
+    }
+
+    /// Copy timestamps of measured pass to readback buffer, must follow the pass in the same encoder
+    pub fn resolve(&self, encoder: &mut wgpu::CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..QUERY_COUNT, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(&self.resolve_buffer, 0, &self.readback_buffer, 0, QUERY_BUFFER_SIZE);
+    }
+
+    /// Wait for submitted pass and get its duration in milliseconds
+    pub fn read(&self, device: &wgpu::Device) -> f64 {
+        let slice = self.readback_buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+
+        let duration = {
+            let mapped = slice.get_mapped_range();
+            let [start, end] = [0, 1].map(|index| {
+                let offset = index * std::mem::size_of::<u64>();
+                u64::from_ne_bytes(std::array::from_fn(|byte| mapped[offset + byte]))
+            });
+            end.saturating_sub(start) as f64 * self.period as f64 / 1_000_000.0
+        };
+        self.readback_buffer.unmap();
+
+        duration
+    } // fn read
+}
@@ -0,0 +1,119 @@
+use std::{any::Any, collections::HashMap};
+
+use super::{shader_include, RenderSettings};
+
+/// Shader file `render.wgsl` includes selected integrator by, it's generated instead of being read
+pub const INTEGRATOR_SHADER: &str = "selected_integrator.wgsl";
+
+/// Light transport algorithm traced by main pipeline for every camera ray. Source is compiled together with
+/// `render.wgsl`, so it can use its declarations and include embedded shader files (`sampling.wgsl`,
+/// `intersect.wgsl`, `bsdf.wgsl`, `lights.wgsl`) as the built-in path tracer does.
+pub trait Integrator: 'static {
+    /// Integrator parameters outside of `RenderSettings`, changed by `Render::set_integrator_settings`
+    type Settings: 'static;
+
+    /// Name in logs and errors
+    fn name(&self) -> &str;
+
+    /// WGSL source of integrator
+    fn source(&self) -> String;
+
+    /// Function of source tracing camera ray, `fn(Ray) -> Trace`
+    fn entry_point(&self) -> &str;
+
+    /// Values of `override` declarations of source for integrator and renderer settings.
+    /// Pipeline is rebuilt on their change.
+    fn constants(&self, settings: &Self::Settings, render_settings: &RenderSettings) -> HashMap<String, f64>;
+}
+
+/// Integrator together with its settings, the form `Render` holds any integrator in
+pub(super) trait ConfiguredIntegrator {
+    fn name(&self) -> &str;
+    fn source(&self) -> String;
+    fn entry_point(&self) -> &str;
+    fn constants(&self, render_settings: &RenderSettings) -> HashMap<String, f64>;
+    /// `Configured` of concrete integrator type, settings are found by downcast
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+pub(super) struct Configured<I: Integrator> {
+    pub integrator: I,
+    pub settings: I::Settings,
+}
+
+impl<I: Integrator> ConfiguredIntegrator for Configured<I> {
+    fn name(&self) -> &str {
+        self.integrator.name()
+    }
+
+    fn source(&self) -> String {
+        self.integrator.source()
+    }
+
+    fn entry_point(&self) -> &str {
+        self.integrator.entry_point()
+    }
+
+    fn constants(&self, render_settings: &RenderSettings) -> HashMap<String, f64> {
+        self.integrator.constants(&self.settings, render_settings)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Built-in unidirectional path tracer with next event estimation, see `integrator.wgsl`.
+/// Its parameters are part of `RenderSettings`, so it has no settings of its own.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    type Settings = ();
+
+    fn name(&self) -> &str {
+        "path tracer"
+    }
+
+    fn source(&self) -> String {
+        "#include \"integrator.wgsl\"".to_string()
+    }
+
+    fn entry_point(&self) -> &str {
+        "trace"
+    }
+
+    fn constants(&self, _settings: &(), render_settings: &RenderSettings) -> HashMap<String, f64> {
+        HashMap::from([
+            ("MAX_DEPTH".to_string(), render_settings.max_depth as f64),
+            ("NEXT_EVENT_ESTIMATION".to_string(), render_settings.next_event_estimation as u32 as f64),
+            ("SHOW_VERTEX_COLORS".to_string(), render_settings.show_vertex_colors as u32 as f64),
+            ("SHOW_FACE_ORIENTATION".to_string(), render_settings.show_face_orientation as u32 as f64),
+        ])
+    }
+}
+
+/// Override constants of main pipeline, ones of `render.wgsl` and of integrator
+pub(super) fn main_constants(integrator: &dyn ConfiguredIntegrator, settings: &RenderSettings) -> HashMap<String, f64> {
+    let mut constants = integrator.constants(settings);
+    constants.insert("SEPARATE_DIFFUSE".to_string(), settings.denoise_components as u32 as f64);
+    constants.extend(super::render_module_constants());
+    constants
+}
+
+/// Compose `render.wgsl` with `integrator`, other files are read by `load`
+pub(super) fn compose_render_shader(integrator: &dyn ConfiguredIntegrator, load: &mut dyn FnMut(&str) -> Option<String>) -> Result<String, String> {
+    shader_include::compose("render.wgsl", &mut |name| match name {
+        INTEGRATOR_SHADER => Some(format!(
+            "{}\nfn trace_path(ray: Ray) -> Trace {{\n    return {}(ray);\n}}\n",
+            integrator.source(),
+            integrator.entry_point()
+        )),
+        _ => load(name),
+    })
+}
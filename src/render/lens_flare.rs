@@ -0,0 +1,269 @@
+use crate::{math::{Ext2u, Vec3f}, scene::LensFlare};
+
+use super::{as_bytes, output_constants, shader_include};
+
+/// Surface pixels per bright image pixel along each axis, matches lens_flare.wgsl
+const BRIGHT_DOWNSAMPLE: u32 = 4;
+
+/// Thresholded presented image, filterable to sample ghosts smoothly
+const BRIGHT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct FlareData {
+    tint: Vec3f,
+    intensity: f32,
+    exposure: f32,
+    threshold: f32,
+    ghost_count: u32,
+    ghost_spacing: f32,
+    halo_radius: f32,
+    chromatic_shift: f32,
+    _pad0: [f32; 2],
+}
+
+/// Lens flare post effect. Presented image is thresholded into downsampled bright image,
+/// whose ghosts and halo are added to surface over place pass output.
+pub struct LensFlarePass {
+    bright_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    bright_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    buffer: wgpu::Buffer,
+    flare_bind_group: wgpu::BindGroup,
+
+    bright_view: wgpu::TextureView,
+    bright_bind_group: wgpu::BindGroup,
+}
+
+impl LensFlarePass {
+    fn create_bright_view(device: &wgpu::Device, extent: Ext2u) -> wgpu::TextureView {
+        device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: BRIGHT_FORMAT,
+            label: Some("Lens flare bright image"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d {
+                width: extent.w.div_ceil(BRIGHT_DOWNSAMPLE).max(1),
+                height: extent.h.div_ceil(BRIGHT_DOWNSAMPLE).max(1),
+                depth_or_array_layers: 1,
+            },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_bright_bind_group(device: &wgpu::Device, layout: &wgpu::BindGroupLayout, view: &wgpu::TextureView, sampler: &wgpu::Sampler) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+            ],
+            label: Some("Lens flare bright bind group"),
+            layout,
+        })
+    }
+
+    /// Collector bind group layout is used to read presented image
+    pub fn new(device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, surface_format: wgpu::TextureFormat, extent: Ext2u) -> Self {
+        let flare_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lens flare bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let bright_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Lens flare bright bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Lens flare UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<FlareData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let flare_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() },
+            ],
+            label: Some("Lens flare bind group"),
+            layout: &flare_bind_group_layout,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Lens flare bright image sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Lens Flare Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shader_include::compose_embedded("lens_flare.wgsl")))
+        });
+
+        let constants = output_constants(surface_format);
+        let create_pipeline = |label: &str, entry_point: &str, bind_group_layouts: &[&wgpu::BindGroupLayout], target: wgpu::ColorTargetState| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                bind_group_layouts,
+                ..Default::default()
+            });
+
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                depth_stencil: None,
+                fragment: Some(wgpu::FragmentState {
+                    compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                    entry_point,
+                    module: &shader_module,
+                    targets: &[Some(target)],
+                }),
+                layout: Some(&layout),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    ..Default::default()
+                },
+                vertex: wgpu::VertexState {
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                    entry_point: "vs_main",
+                    module: &shader_module,
+                }
+            })
+        };
+
+        let bright_pipeline = create_pipeline(
+            "Lens flare bright pipeline",
+            "fs_bright",
+            &[collector_bind_group_layout, &flare_bind_group_layout],
+            wgpu::ColorTargetState {
+                blend: None,
+                format: BRIGHT_FORMAT,
+                write_mask: wgpu::ColorWrites::ALL,
+            }
+        );
+
+        // Flare is added to surface contents. Addition is linear for sRGB and float surfaces only,
+        // surfaces encoded by shader get slightly stronger flare over bright image parts.
+        let composite_pipeline = create_pipeline(
+            "Lens flare composite pipeline",
+            "fs_composite",
+            &[collector_bind_group_layout, &flare_bind_group_layout, &bright_bind_group_layout],
+            wgpu::ColorTargetState {
+                blend: Some(wgpu::BlendState {
+                    color: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::One,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                    alpha: wgpu::BlendComponent {
+                        src_factor: wgpu::BlendFactor::Zero,
+                        dst_factor: wgpu::BlendFactor::One,
+                        operation: wgpu::BlendOperation::Add,
+                    },
+                }),
+                format: surface_format,
+                write_mask: wgpu::ColorWrites::ALL,
+            }
+        );
+
+        let bright_view = Self::create_bright_view(device, extent);
+        let bright_bind_group = Self::create_bright_bind_group(device, &bright_bind_group_layout, &bright_view, &sampler);
+
+        Self {
+            bright_pipeline,
+            composite_pipeline,
+            bright_bind_group_layout,
+            sampler,
+            buffer,
+            flare_bind_group,
+            bright_view,
+            bright_bind_group,
+        }
+    } // fn new
+
+    pub fn resize(&mut self, device: &wgpu::Device, extent: Ext2u) {
+        self.bright_view = Self::create_bright_view(device, extent);
+        self.bright_bind_group = Self::create_bright_bind_group(device, &self.bright_bind_group_layout, &self.bright_view, &self.sampler);
+    }
+
+    /// Add flare of presented collector contents to `target` of the same size
+    pub fn render(
+        &self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        collector_bind_group: &wgpu::BindGroup,
+        target: &wgpu::TextureView,
+        exposure: f32,
+        flare: &LensFlare
+    ) {
+        queue.write_buffer(&self.buffer, 0, as_bytes(&FlareData {
+            tint: flare.tint,
+            intensity: flare.intensity,
+            exposure,
+            threshold: flare.threshold,
+            ghost_count: flare.ghost_count,
+            ghost_spacing: flare.ghost_spacing,
+            halo_radius: flare.halo_radius,
+            chromatic_shift: flare.chromatic_shift,
+            ..Default::default()
+        }));
+
+        let mut run_pass = |target: &wgpu::TextureView, pipeline: &wgpu::RenderPipeline, bright_bind_group: Option<&wgpu::BindGroup>| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    resolve_target: None,
+                    view: target,
+                })],
+                ..Default::default()
+            });
+
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, collector_bind_group, &[]);
+            render_pass.set_bind_group(1, &self.flare_bind_group, &[]);
+            if let Some(bright_bind_group) = bright_bind_group {
+                render_pass.set_bind_group(2, bright_bind_group, &[]);
+            }
+            render_pass.draw(0..4, 0..1);
+        };
+
+        run_pass(&self.bright_view, &self.bright_pipeline, None);
+        run_pass(target, &self.composite_pipeline, Some(&self.bright_bind_group));
+    } // fn render
+}
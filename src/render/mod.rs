@@ -1,18 +1,201 @@
-use std::rc::Rc;
+use std::{collections::BTreeSet, rc::Rc, sync::Arc};
 
-use crate::math::{Ext2f, Ext2u, Vec3f};
+use crate::{math::{Ext2f, Ext2u, Mat4f, Vec2f, Vec2u, Vec3d, Vec3f, Vec4f}, scene::{FisheyeMapping, Instance, LensFlare, Projection, Scene, Sky, TexturedMaterial, Tolerances}, theme::{FalseColorPalette, Theme}};
+
+mod scene_data;
+mod outline;
+mod denoise;
+mod aov;
+mod blue_noise;
+mod gpu_timer;
+mod overlay;
+mod path_overlay;
+mod resample;
+mod shader_reload;
+mod shader_include;
+mod buffer_view;
+mod lens_flare;
+mod glare;
+mod sharpen;
+mod convergence;
+mod auto_exposure;
+mod watchdog;
+mod context;
+mod builder;
+mod integrator;
+mod post_process;
+mod path_record;
+mod frame_graph;
+
+pub use aov::{Aov, AovImage, AovPixel};
+pub use context::{device_features, AdapterSelector, GpuContext};
+pub use builder::{BuildError, CollectorPrecision, RenderBuilder};
+pub use integrator::{Integrator, PathTracer};
+pub use overlay::OverlayFrame;
+pub use post_process::{PluginError, PostProcessPlugin};
+pub use path_record::{write_paths_json, write_paths_obj, PathEvent, PathVertex, RecordedPath};
+pub use shader_reload::ShaderReload;
+pub use buffer_view::{Buffer, BufferView, Channels, Normalization};
+pub use frame_graph::{FrameGraph, FramePass};
+
+/// Reinterpret value as byte slice for GPU upload
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts((value as *const T).cast::<u8>(), std::mem::size_of::<T>()) }
+}
+
+/// Reinterpret slice as byte slice for GPU upload
+fn slice_as_bytes<T>(values: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(values.as_ptr().cast::<u8>(), std::mem::size_of_val(values)) }
+}
+
+/// Value of half precision float bits
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10 & 0x1F) as i32;
+    let mantissa = (bits & 0x3FF) as f32;
+    sign * match exponent {
+        0 => mantissa * 2.0f32.powi(-24),
+        0x1F if mantissa == 0.0 => f32::INFINITY,
+        0x1F => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2.0f32.powi(exponent - 15),
+    }
+}
+
+/// Texture layer copied into mappable buffer, rows are padded to copy alignment
+struct TextureReadback {
+    extent: Ext2u,
+    /// Bytes per channel, 4 for 32-bit and 2 for 16-bit float textures, 1 for 8-bit and packed 32-bit ones
+    channel_size: u32,
+    bytes_per_row: u32,
+    buffer: wgpu::Buffer,
+}
+
+impl TextureReadback {
+    /// Copy layer of four-channel 32-bit or 16-bit float texture, copy is submitted but not awaited
+    fn new(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, layer: u32) -> Self {
+        let size = texture.size();
+        Self::region(device, queue, texture, layer, Vec2u::new(0, 0), Ext2u::new(size.width, size.height))
+    }
+
+    /// Copy rectangle of texture layer, rectangle must lie inside of texture
+    fn region(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, layer: u32, origin: Vec2u, extent: Ext2u) -> Self {
+        let channel_size = texture.format().block_copy_size(None).unwrap_or(16) / 4;
+        let bytes_per_row = (extent.w * channel_size * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Texture readback buffer"),
+            mapped_at_creation: false,
+            size: (bytes_per_row * extent.h) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: origin.x, y: origin.y, z: layer },
+                texture,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+        );
+        queue.submit([encoder.finish()]);
+
+        Self { extent, channel_size, bytes_per_row, buffer }
+    } // fn region
+
+    /// Decode every pixel of mapped buffer from its bytes
+    fn map_pixels<T>(&self, decode: impl Fn(&[u8]) -> T) -> Vec<T> {
+        let mapped = self.buffer.slice(..).get_mapped_range();
+        let pixel_size = self.channel_size as usize * 4;
+        mapped
+            .chunks(self.bytes_per_row as usize)
+            .flat_map(|row| row[..self.extent.w as usize * pixel_size].chunks(pixel_size))
+            .map(decode)
+            .collect()
+    }
+
+    /// Unpack pixels of mapped buffer
+    fn pixels(&self) -> Vec<[f32; 4]> {
+        let channel_size = self.channel_size as usize;
+        self.map_pixels(|bytes| std::array::from_fn(|index| {
+            let bytes = &bytes[index * channel_size..(index + 1) * channel_size];
+            match *bytes {
+                [b0, b1] => f16_to_f32(u16::from_ne_bytes([b0, b1])),
+                [b0, b1, b2, b3] => f32::from_ne_bytes([b0, b1, b2, b3]),
+                _ => 0.0,
+            }
+        }))
+    }
+}
+
+/// Read layer of four-channel float texture, rows are top to bottom
+fn read_rgba_texture(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, layer: u32) -> (Ext2u, Vec<[f32; 4]>) {
+    let readback = TextureReadback::new(device, queue, texture, layer);
+    readback.buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+
+    (readback.extent, readback.pixels())
+} // fn read_rgba_texture
+
+/// Read single pixel of four-channel float texture layer, pixel must lie inside of texture
+fn read_rgba_pixel(device: &wgpu::Device, queue: &wgpu::Queue, texture: &wgpu::Texture, layer: u32, pixel: Vec2u) -> [f32; 4] {
+    let readback = TextureReadback::region(device, queue, texture, layer, pixel, Ext2u::new(1, 1));
+    readback.buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+    device.poll(wgpu::Maintain::Wait);
+
+    readback.pixels()[0]
+}
 
 pub struct CameraDescriptor {
-    pub location: Vec3f,
-    pub at: Vec3f,
+    pub location: Vec3d,
+    pub at: Vec3d,
     pub dir: Vec3f,
     pub right: Vec3f,
     pub up: Vec3f,
     pub projection_extent: Ext2f,
     pub near: f32,
+    /// Projection extent is in scene units for orthographic projection, in mapping distances for fisheye
+    /// and is ignored by panorama
+    pub projection: Projection,
+    /// Thin lens radius, zero gives pinhole camera without defocus blur
+    pub aperture_radius: f32,
+    /// Distance from camera to plane in focus, along view direction
+    pub focus_distance: f32,
+    /// Number of aperture blades, round aperture if less than 3
+    pub aperture_blades: u32,
+    /// Aperture polygon rotation, in degrees
+    pub aperture_rotation: f32,
+    /// Scene data texture with aperture shape, overrides blades
+    pub bokeh_texture: Option<u32>,
+    /// Exposure compensation in stops, applied on presentation
+    pub exposure: f32,
+    /// Image plane shift along right (X) and up (Y) axes, in fractions of projection size
+    pub lens_shift: Vec2f,
+    /// Focus plane rotation around right (X) and up (Y) axes, in degrees
+    pub lens_tilt: Vec2f,
+}
+
+/// Ray generation mode index, matches PROJECTION_* constants of camera.wgsl
+fn projection_index(projection: Projection) -> u32 {
+    match projection {
+        Projection::Perspective => 0,
+        Projection::Orthographic { .. } => 1,
+        Projection::Panorama => 2,
+        Projection::Fisheye(FisheyeMapping::Equidistant) => 3,
+        Projection::Fisheye(FisheyeMapping::Stereographic) => 4,
+    }
 }
 
-#[repr(packed)]
+#[derive(Copy, Clone, Default)]
+#[repr(C, packed)]
 #[allow(unused)]
 struct CameraData {
     location: Vec3f,
@@ -23,51 +206,741 @@ struct CameraData {
     projection_width: f32,
     up: Vec3f,
     projection_height: f32,
+    aperture_radius: f32,
+    focus_distance: f32,
+    /// See `projection_index`
+    projection: u32,
+    _pad1: f32,
+    /// Camera basis at previous frame, interpolated with current one for motion blur
+    previous_location: Vec3f,
+    _pad2: f32,
+    previous_dir: Vec3f,
+    _pad3: f32,
+    previous_right: Vec3f,
+    _pad4: f32,
+    previous_up: Vec3f,
+    aperture_blades: u32,
+    /// In radians
+    aperture_rotation: f32,
+    /// Data texture layer, `u32::MAX` for polygonal or round aperture
+    bokeh_texture: u32,
+    lens_shift: Vec2f,
+    /// In radians
+    lens_tilt: Vec2f,
+    _pad5: [u32; 2],
+}
+
+impl CameraData {
+    fn is_moving(&self) -> bool {
+        let current = [self.location, self.dir, self.right, self.up];
+        let previous = [self.previous_location, self.previous_dir, self.previous_right, self.previous_up];
+
+        current.iter().zip(previous.iter()).any(|(current, previous)| {
+            current.x != previous.x || current.y != previous.y || current.z != previous.z
+        })
+    }
+
+    /// Make previous camera basis equal to current one
+    fn stop(&mut self) {
+        self.previous_location = self.location;
+        self.previous_dir = self.dir;
+        self.previous_right = self.right;
+        self.previous_up = self.up;
+    }
 }
 
 #[derive(Default)]
-#[repr(packed)]
+#[repr(C, packed)]
 #[allow(unused)]
 struct SystemData {
     resolution: Ext2f,
     time: f32,
     static_frame_index: u32,
     texel_size: Ext2f,
+    rr_start_depth: u32,
+    rr_threshold: f32,
+    shutter_open: f32,
+    shutter_close: f32,
+    /// Presented image scale
+    exposure: f32,
+    sampler_kind: u32,
+    max_indirect_radiance: f32,
+    min_indirect_roughness: f32,
+    adaptive_threshold: f32,
+    /// Presented heatmap, see `Heatmap`
+    heatmap: u32,
+    tile_size: u32,
+    /// Number of tiles traced in turn, one if tiling is disabled
+    tile_count: u32,
+    watertight_intersection: u32,
+    /// Scene tolerances, in scene units
+    ray_min_distance: f32,
+    intersection_tolerance: f32,
+    /// Standard deviation of presented film grain, in display-encoded units
+    film_grain: f32,
+    /// Dither pattern of presented image, see `Dither`
+    dither: u32,
+    _pad0: u32,
+    /// Evenly spaced stops of false color debug views
+    false_color: [Vec4f; FALSE_COLOR_STOP_COUNT],
+}
+
+const FALSE_COLOR_STOP_COUNT: usize = 8;
+
+/// False color view presented instead of rendered image, matches place.wgsl
+#[derive(Copy, Clone)]
+enum Heatmap {
+    None,
+    SampleCount,
+    Variance,
+}
+
+/// Distance from world origin at which camera-relative mode moves origin to camera
+const WORLD_REBASE_DISTANCE: f64 = 16.0;
+
+/// Samples per pixel traced by accumulation pass, passed to render.wgsl as override constant
+const SAMPLES_PER_PASS: u32 = 4;
+
+/// Override constants required by every pipeline of render.wgsl, whatever its entry point is
+fn render_module_constants() -> std::collections::HashMap<String, f64> {
+    std::collections::HashMap::from([("SAMPLES_PER_PASS".to_string(), SAMPLES_PER_PASS as f64)])
+}
+
+/// Accumulation progress towards target sample count or target error
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RenderProgress {
+    /// Samples per pixel accumulated since camera and instances stopped
+    pub sample_count: u32,
+    /// Zero if accumulation is unlimited
+    pub target_sample_count: u32,
+    /// Mean relative luminance change of pixels sampled by recent accumulation pass,
+    /// None until it's measured after accumulation restart
+    pub convergence: Option<f32>,
+    /// Zero if accumulation isn't stopped by convergence
+    pub target_error: f32,
+}
+
+impl RenderProgress {
+    /// Either target is reached
+    pub fn is_complete(&self) -> bool {
+        let is_sample_count_reached = self.target_sample_count != 0 && self.sample_count >= self.target_sample_count;
+        let is_error_reached = self.target_error > 0.0 && self.convergence.is_some_and(|convergence| convergence <= self.target_error);
+        is_sample_count_reached || is_error_reached
+    }
+}
+
+fn false_color_stops(palette: FalseColorPalette) -> [Vec4f; FALSE_COLOR_STOP_COUNT] {
+    std::array::from_fn(|index| {
+        let color = palette.sample(index as f32 / (FALSE_COLOR_STOP_COUNT - 1) as f32);
+        Vec4f::new(color.x, color.y, color.z, 1.0)
+    })
+}
+
+fn default_max_depth() -> u32 {
+    8
+}
+
+fn default_rr_start_depth() -> u32 {
+    3
+}
+
+fn default_rr_threshold() -> f32 {
+    0.5
 }
 
-pub struct Kernel<'t> {
-    surface: wgpu::Surface<'t>,
-    queue: wgpu::Queue,
-    device: wgpu::Device,
+fn default_shutter() -> f32 {
+    1.0
 }
 
+fn default_temporal_reprojection() -> bool {
+    true
+}
+
+fn default_next_event_estimation() -> bool {
+    true
+}
+
+fn default_samples_per_frame() -> u32 {
+    1
+}
+
+fn default_auto_exposure_min_ev() -> f32 {
+    -4.0
+}
+
+fn default_auto_exposure_max_ev() -> f32 {
+    4.0
+}
+
+fn default_auto_exposure_speed() -> f32 {
+    1.5
+}
+
+/// Source of path sample dimensions
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SamplerKind {
+    /// Independent pseudorandom numbers
+    Random,
+    /// Owen-scrambled Sobol sequence, pixels are decorrelated by scrambling seeds
+    #[default]
+    Sobol,
+    /// Tiled blue noise texture, rotated by golden ratio sequence between samples
+    BlueNoise,
+}
+
+/// Noise added to presented image before its quantization by surface format, hides banding of smooth gradients.
+/// Matches place.wgsl.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Dither {
+    #[default]
+    None,
+    /// 8×8 Bayer matrix
+    Ordered,
+    /// Tiled blue noise texture, less visible pattern than ordered one
+    BlueNoise,
+}
+
+impl Dither {
+    pub const ALL: [Dither; 3] = [Dither::None, Dither::Ordered, Dither::BlueNoise];
+}
+
+/// Path tracing integrator parameters
+#[derive(Copy, Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct RenderSettings {
+    /// Maximal number of surface bounces of path
+    #[serde(default = "default_max_depth")]
+    pub max_depth: u32,
+    /// Number of bounces before Russian roulette starts
+    #[serde(default = "default_rr_start_depth")]
+    pub rr_start_depth: u32,
+    /// Paths with maximal throughput component below threshold are terminated randomly,
+    /// survivors are reweighted. Zero disables Russian roulette.
+    #[serde(default = "default_rr_threshold")]
+    pub rr_threshold: f32,
+    /// Shutter interval for motion blur, as fraction of time between previous (0) and current (1) frames.
+    /// Equal values give instant shutter without blur.
+    #[serde(default = "default_shutter")]
+    pub shutter_open: f32,
+    #[serde(default = "default_shutter")]
+    pub shutter_close: f32,
+    /// Reproject accumulated image on camera movement and resample it on resize instead of restarting accumulation
+    #[serde(default = "default_temporal_reprojection")]
+    pub temporal_reprojection: bool,
+    /// Filter accumulated image by spatial denoiser before presentation
+    #[serde(default)]
+    pub denoise: bool,
+    /// Accumulate diffuse radiance separately, so denoiser filters albedo-demodulated diffuse and remaining
+    /// specular components jointly and recombines them. Preserves highlights and textures better.
+    #[serde(default)]
+    pub denoise_components: bool,
+    /// Sample sequence used by path tracer
+    #[serde(default)]
+    pub sampler: SamplerKind,
+    /// Sample lights and environment at diffuse hits and weight them against scattering by MIS.
+    /// Disabled, lights are found by scattering only, which is slower to converge but unbiased reference.
+    #[serde(default = "default_next_event_estimation")]
+    pub next_event_estimation: bool,
+    /// Present primary hit vertex colors instead of path traced image
+    #[serde(default)]
+    pub show_vertex_colors: bool,
+    /// Maximal component of light contribution reaching camera after two or more bounces.
+    /// Removes fireflies at the cost of energy loss, zero disables clamping.
+    #[serde(default)]
+    pub max_indirect_radiance: f32,
+    /// Minimal roughness of glossy lobes after diffuse bounce, blurs caustics that can't be
+    /// sampled efficiently. Zero disables regularization.
+    #[serde(default)]
+    pub min_indirect_roughness: f32,
+    /// Relative standard error of pixel luminance below which pixel stops receiving samples.
+    /// Zero disables adaptive sampling.
+    #[serde(default)]
+    pub adaptive_threshold: f32,
+    /// Present number of samples accumulated by each pixel instead of rendered image
+    #[serde(default)]
+    pub show_sample_count: bool,
+    /// Present relative standard error of pixel luminance instead of rendered image. Adaptive
+    /// threshold (or 1% if adaptive sampling is disabled) is in the middle of color map.
+    #[serde(default)]
+    pub show_variance: bool,
+    /// Present camera hits colored by surface side, blue for front faces and red for back ones
+    #[serde(default)]
+    pub show_face_orientation: bool,
+    /// Side of square tiles in pixels. Each frame traces single tile in round-robin order,
+    /// so slow GPUs stay responsive at high resolutions. Zero traces whole image every frame.
+    #[serde(default)]
+    pub tile_size: u32,
+    /// Intersect triangles by watertight algorithm, so rays don't leak through edges shared by triangles
+    #[serde(default)]
+    pub watertight_intersection: bool,
+    /// Number of accumulation passes per presented frame, each of them traces `SAMPLES_PER_PASS` samples per pixel.
+    /// Passes of the same frame aren't presented, so convergence on fast GPUs isn't limited by display refresh.
+    #[serde(default = "default_samples_per_frame")]
+    pub samples_per_frame: u32,
+    /// Rebase world around camera, so GPU works with small camera-relative coordinates.
+    /// Removes precision jitter in huge scenes placed far from world origin.
+    #[serde(default)]
+    pub camera_relative: bool,
+    /// Samples per pixel after which accumulation stops and completion callbacks are fired.
+    /// Zero accumulates indefinitely.
+    #[serde(default)]
+    pub target_sample_count: u32,
+    /// Convergence metric (mean relative luminance change of pixels by accumulation pass) at which accumulation
+    /// stops and completion callbacks are fired, whichever of it and target sample count is reached first.
+    /// Zero disables it.
+    #[serde(default)]
+    pub target_error: f32,
+    /// Strength of glare by diffraction on camera aperture blades, added to presented image before its
+    /// clamping to display range. One is physically plausible for bright sources, zero disables glare.
+    #[serde(default)]
+    pub glare: f32,
+    /// Strength of contrast-adaptive sharpening applied after denoising, in [0, 1] range. Zero disables it.
+    #[serde(default)]
+    pub sharpen: f32,
+    /// Standard deviation of animated film grain added to presented image, as fraction of display range.
+    /// Grain fades out in shadows and highlights, zero disables it.
+    #[serde(default)]
+    pub film_grain: f32,
+    /// Dithering of presented image, hides banding of smooth gradients on 8-bit displays
+    #[serde(default)]
+    pub dither: Dither,
+    /// Scale presented image by mean luminance of its pixels between 10th and 90th percentiles, so it matches
+    /// middle grey. Camera exposure is applied on top of it as compensation.
+    #[serde(default)]
+    pub auto_exposure: bool,
+    /// Range of metered image brightness automatic exposure compensates, in EV relative to middle grey.
+    /// Brighter images are scaled down by at most `auto_exposure_max_ev` stops, darker ones are scaled up
+    /// by at most `-auto_exposure_min_ev` stops.
+    #[serde(default = "default_auto_exposure_min_ev")]
+    pub auto_exposure_min_ev: f32,
+    #[serde(default = "default_auto_exposure_max_ev")]
+    pub auto_exposure_max_ev: f32,
+    /// Rate of automatic exposure adaptation, inverse of time in seconds exposure covers 63% of change in
+    #[serde(default = "default_auto_exposure_speed")]
+    pub auto_exposure_speed: f32,
+}
+
+/// Tile size set by first lightening of untiled settings, in pixels
+const MAX_LIGHTENED_TILE_SIZE: u32 = 512;
+
+/// Tiles aren't lightened below this size, as smaller ones barely fill GPU
+const MIN_LIGHTENED_TILE_SIZE: u32 = 64;
+
+impl RenderSettings {
+    /// Settings capped for software adapters, so they stay interactive and finish test renders in reasonable time
+    pub fn reduced(self) -> Self {
+        Self {
+            max_depth: self.max_depth.min(4),
+            rr_start_depth: self.rr_start_depth.min(2),
+            samples_per_frame: 1,
+            ..self
+        }
+    }
+
+    /// Settings with less GPU work per frame: half of `samples_per_frame`, then tiles of decreasing size.
+    /// None if work is already at minimum.
+    pub fn lightened(self) -> Option<Self> {
+        if self.samples_per_frame > 1 {
+            return Some(Self { samples_per_frame: self.samples_per_frame / 2, ..self });
+        }
+        let tile_size = match self.tile_size {
+            0 => MAX_LIGHTENED_TILE_SIZE,
+            tile_size if tile_size > MIN_LIGHTENED_TILE_SIZE => (tile_size / 2).max(MIN_LIGHTENED_TILE_SIZE),
+            _ => return None,
+        };
+        Some(Self { tile_size, ..self })
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            max_depth: default_max_depth(),
+            rr_start_depth: default_rr_start_depth(),
+            rr_threshold: default_rr_threshold(),
+            shutter_open: default_shutter(),
+            shutter_close: default_shutter(),
+            temporal_reprojection: default_temporal_reprojection(),
+            denoise: false,
+            denoise_components: false,
+            sampler: SamplerKind::default(),
+            next_event_estimation: default_next_event_estimation(),
+            show_vertex_colors: false,
+            max_indirect_radiance: 0.0,
+            min_indirect_roughness: 0.0,
+            adaptive_threshold: 0.0,
+            show_sample_count: false,
+            show_variance: false,
+            show_face_orientation: false,
+            tile_size: 0,
+            watertight_intersection: false,
+            samples_per_frame: default_samples_per_frame(),
+            camera_relative: false,
+            target_sample_count: 0,
+            target_error: 0.0,
+            glare: 0.0,
+            sharpen: 0.0,
+            film_grain: 0.0,
+            dither: Dither::None,
+            auto_exposure: false,
+            auto_exposure_min_ev: default_auto_exposure_min_ev(),
+            auto_exposure_max_ev: default_auto_exposure_max_ev(),
+            auto_exposure_speed: default_auto_exposure_speed(),
+        }
+    }
+}
+
+/// Preetham sky parameters, see `scene::sky::SkyModel`
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct SkyData {
+    sun_direction: Vec3f,
+    is_enabled: u32,
+    sun_radiance: Vec3f,
+    sun_cos_angular_radius: f32,
+    perez_a: Vec3f,
+    _pad0: f32,
+    perez_b: Vec3f,
+    _pad1: f32,
+    perez_c: Vec3f,
+    _pad2: f32,
+    perez_d: Vec3f,
+    _pad3: f32,
+    perez_e: Vec3f,
+    _pad4: f32,
+    zenith: Vec3f,
+    _pad5: f32,
+}
+
+/// Accumulated radiance sum (RGB) with sample count (A), and primary hit features used for reprojection and denoising
 struct Collector {
+    /// Texture shared by all collectors, one layer per collector
+    texture: Rc<wgpu::Texture>,
+    layer: u32,
     view: wgpu::TextureView,
+    /// Octahedral-encoded primary hit normal (XY), primary hit distance (Z) and luminance second moment sum (W)
+    feature_texture: Rc<wgpu::Texture>,
+    feature_view: wgpu::TextureView,
+    /// Radiance sum of paths scattered by diffuse lobe at primary hit (RGB), single pixel if it isn't separated
+    diffuse_view: wgpu::TextureView,
     bind_group: wgpu::BindGroup,
+    /// Diffuse radiance as storage texture, it's written by accumulation and resample passes
+    diffuse_bind_group: wgpu::BindGroup,
+}
+
+/// Scene element hit by primary ray at picked pixel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PickHit {
+    /// Scene instance index and triangle index in instance mesh
+    Instance { instance: u32, triangle: u32 },
+    /// Scene primitive index
+    Primitive(u32),
+}
+
+/// Raw values of single pixel of last rendered frame, see `Render::inspect_pixel`
+#[derive(Copy, Clone, Debug)]
+pub struct PixelInfo {
+    /// Radiance summed over accumulation passes, without exposure
+    pub radiance_sum: Vec3f,
+    /// Number of accumulation passes, each of them averages several samples
+    pub pass_count: f32,
+    /// Sum of squared pass luminances, used to estimate pixel variance
+    pub luminance_moment: f32,
+    pub aovs: AovPixel,
+}
+
+impl PixelInfo {
+    /// Mean radiance, without exposure
+    pub fn radiance(&self) -> Vec3f {
+        self.radiance_sum / self.pass_count.max(1.0)
+    }
+}
+
+/// Swapchain format choice. Formats without hardware sRGB encoding get it from shaders.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum SurfaceFormat {
+    /// First 8-bit sRGB format supported by surface, any supported one if there are none
+    #[default]
+    Auto,
+    /// 8 bits per channel with hardware sRGB encoding
+    Srgb8,
+    /// 10 bits per color channel, less banding in dark gradients
+    Rgb10a2,
+    /// Linear half float, for outputs in extended sRGB color space
+    Rgba16Float,
+}
+
+impl SurfaceFormat {
+    /// Texture formats of choice in order of preference, any format matches `Auto`
+    fn candidates(self) -> &'static [wgpu::TextureFormat] {
+        match self {
+            SurfaceFormat::Auto => &[],
+            SurfaceFormat::Srgb8 => &[wgpu::TextureFormat::Bgra8UnormSrgb, wgpu::TextureFormat::Rgba8UnormSrgb],
+            SurfaceFormat::Rgb10a2 => &[wgpu::TextureFormat::Rgb10a2Unorm],
+            SurfaceFormat::Rgba16Float => &[wgpu::TextureFormat::Rgba16Float],
+        }
+    }
+
+    /// Format of headless renderer target
+    fn headless_format(self) -> wgpu::TextureFormat {
+        match self {
+            SurfaceFormat::Auto | SurfaceFormat::Srgb8 => wgpu::TextureFormat::Rgba8UnormSrgb,
+            SurfaceFormat::Rgb10a2 => wgpu::TextureFormat::Rgb10a2Unorm,
+            SurfaceFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        }
+    }
+
+    pub fn matches(self, format: wgpu::TextureFormat) -> bool {
+        self == SurfaceFormat::Auto || self.candidates().contains(&format)
+    }
+
+    /// Pick one of surface `formats`, None if none of them matches
+    fn select(self, formats: &[wgpu::TextureFormat]) -> Option<wgpu::TextureFormat> {
+        match self {
+            SurfaceFormat::Auto => formats.iter()
+                .find(|format| format.is_srgb() && format.has_color_aspect() && format.components() == 4)
+                .or(formats.first())
+                .copied(),
+            _ => self.candidates().iter().find(|format| formats.contains(format)).copied(),
+        }
+    }
+}
+
+/// Override constants of pipelines writing to surface of `format`, see `output.wgsl`
+fn output_constants(format: wgpu::TextureFormat) -> std::collections::HashMap<String, f64> {
+    // Float formats are linear, unorm ones without sRGB suffix expect encoded values
+    let is_srgb_encoded = !format.is_srgb() && format != wgpu::TextureFormat::Rgba16Float;
+    // Float formats don't band, so they aren't dithered
+    let max_code = match format {
+        wgpu::TextureFormat::Rgba16Float => 0,
+        wgpu::TextureFormat::Rgb10a2Unorm => 1023,
+        _ => 255,
+    };
+    std::collections::HashMap::from([
+        ("OUTPUT_SRGB_ENCODE".to_string(), is_srgb_encoded as u32 as f64),
+        ("OUTPUT_MAX_CODE".to_string(), max_code as f64),
+    ])
+}
+
+/// Passes presenting accumulated image. Frame encoding and `frame_graph` share them, so the graph
+/// shows passes encoded with current settings.
+struct PresentationPasses {
+    /// Denoiser also runs while its buffers are viewed, passes below are replaced by buffer view then
+    is_denoised: bool,
+    is_sharpened: bool,
+    is_post_processed: bool,
+    is_glare_enabled: bool,
+    is_lens_flare_enabled: bool,
+}
+
+/// Image presented frames are written to
+enum Target<'t> {
+    Surface(wgpu::Surface<'t>),
+    /// Texture of headless renderer, it has format and size of surface configuration
+    Texture(wgpu::Texture),
+}
+
+impl Target<'_> {
+    fn create_texture(device: &wgpu::Device, configuration: &wgpu::SurfaceConfiguration) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: configuration.format,
+            label: Some("Headless target"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: configuration.width, height: configuration.height, depth_or_array_layers: 1 },
+            usage: configuration.usage | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    /// Apply changed configuration, texture is recreated
+    fn configure(&mut self, device: &wgpu::Device, configuration: &wgpu::SurfaceConfiguration) {
+        match self {
+            Target::Surface(surface) => surface.configure(device, configuration),
+            Target::Texture(texture) => *texture = Self::create_texture(device, configuration),
+        }
+    }
 }
 
 pub struct Render<'t> {
-    kernel: Rc<Kernel<'t>>,
+    context: Arc<GpuContext>,
+    target: Target<'t>,
     surface_configuration: wgpu::SurfaceConfiguration,
+    /// Format of accumulated radiance, see `CollectorPrecision`
+    collector_format: wgpu::TextureFormat,
+    /// Sample count limit of collector precision, it caps target sample count of settings
+    max_sample_count: Option<u32>,
 
     camera_buffer: wgpu::Buffer,
     system_buffer: wgpu::Buffer,
+    sky_buffer: wgpu::Buffer,
     static_frame_index: u32,
+    settings: RenderSettings,
+    /// Linear exposure scale of camera
+    exposure: f32,
+    false_color: [Vec4f; FALSE_COLOR_STOP_COUNT],
+    tolerances: Tolerances,
+
+    /// Camera and instances are kept to collapse motion blur once they stop moving
+    camera_data: CameraData,
+    is_camera_updated: bool,
+    instances: Vec<Instance>,
+    previous_instance_transforms: Vec<Mat4f>,
+    is_instances_updated: bool,
+    /// World point that GPU-side coordinates are relative to, nonzero only in camera-relative mode
+    world_origin: Vec3d,
+    camera_location: Vec3d,
+
+    /// Passes accumulated since last restart or motion, they count towards target sample count
+    still_pass_count: u32,
+    completion_callbacks: Vec<Box<dyn FnOnce(RenderProgress)>>,
+
+    adapter_info: wgpu::AdapterInfo,
+    gpu_timer: Option<gpu_timer::GpuTimer>,
 
     collector_bind_group_layout: wgpu::BindGroupLayout,
+    diffuse_bind_group_layout: wgpu::BindGroupLayout,
     render_bind_group: wgpu::BindGroup,
+    scene_bind_group_layout: wgpu::BindGroupLayout,
+    scene_resources: scene_data::SceneResources,
+    scene_bind_group: wgpu::BindGroup,
+    texture_sampler: wgpu::Sampler,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    /// Render pipeline layout with target collector diffuse radiance, written by main pipeline only
+    main_pipeline_layout: wgpu::PipelineLayout,
+    /// Integrator main shader module is composed with
+    integrator: Box<dyn integrator::ConfiguredIntegrator>,
+    /// Main pipeline is specialized by integrator settings, so it's rebuilt from the module on their change
+    render_shader_module: wgpu::ShaderModule,
     render_pipeline: wgpu::RenderPipeline,
 
+    place_pipeline_layout: wgpu::PipelineLayout,
     place_pipeline: wgpu::RenderPipeline,
+    /// Shader files pipelines are rebuilt from on change, set only if hot reload is enabled
+    shader_watch: Option<shader_reload::ShaderWatch>,
     collectors: [Collector; 2],
+
+    outline: outline::Outline,
+    denoiser: denoise::Denoiser,
+    aovs: aov::Aovs,
+    overlay: overlay::Overlay,
+    resampler: resample::Resampler,
+    buffer_presenter: buffer_view::BufferPresenter,
+    lens_flare_pass: lens_flare::LensFlarePass,
+    sharpener: sharpen::Sharpener,
+    path_recorder: path_record::PathRecorder,
+    path_overlay: path_overlay::PathOverlay,
+    convergence: convergence::ConvergenceMeter,
+    auto_exposure: auto_exposure::AutoExposure,
+    watchdog: watchdog::Watchdog,
+    /// Duration of frame reported by watchdog, taken by `take_slow_frame`
+    slow_frame: Option<std::time::Duration>,
+    glare: glare::Glare,
+    /// Plugin passes applied after sharpening in order of addition
+    post_processes: Vec<post_process::PostProcessPass>,
+    /// Lens flare of current scene
+    lens_flare: Option<LensFlare>,
+    /// Internal texture presented instead of place pass output, debug only
+    buffer_view: Option<BufferView>,
+    /// Read collector of size before resize and camera projection extent it was traced with,
+    /// it's resampled into new collectors by next render
+    resize_source: Option<(Collector, Vec2f)>,
 }
 
 impl<'t> Render<'t> {
-    fn create_collectors<const N: usize>(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout, extent: Ext2u) -> [Collector; N] {
-        let collector_target_texture = device.create_texture(&wgpu::TextureDescriptor {
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba32Float,
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        render_pipeline_layout: &wgpu::PipelineLayout,
+        render_shader_module: &wgpu::ShaderModule,
+        collector_format: wgpu::TextureFormat,
+        constants: &std::collections::HashMap<String, f64>
+    ) -> wgpu::RenderPipeline {
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Main pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants, ..Default::default() },
+                entry_point: "fs_main",
+                module: render_shader_module,
+                targets: &[
+                    Some(wgpu::ColorTargetState {
+                        blend: None,
+                        format: collector_format,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                    Some(wgpu::ColorTargetState {
+                        blend: None,
+                        format: wgpu::TextureFormat::Rgba32Float,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }),
+                ]
+            }),
+            layout: Some(render_pipeline_layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions { constants, ..Default::default() },
+                entry_point: "vs_main",
+                module: render_shader_module,
+            }
+        })
+    }
+
+    fn create_place_pipeline(
+        device: &wgpu::Device,
+        place_pipeline_layout: &wgpu::PipelineLayout,
+        place_shader_module: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat
+    ) -> wgpu::RenderPipeline {
+        let constants = output_constants(surface_format);
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "fs_main",
+                module: place_shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: surface_format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })]
+            }),
             label: None,
+            layout: Some(place_pipeline_layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: place_shader_module,
+            }
+        })
+    }
+
+    /// Accumulated radiance textures have `format`, features and diffuse radiance are always full precision.
+    /// Diffuse radiance textures are allocated at full `extent` only if `is_diffuse_separated` is set.
+    fn create_collectors<const N: usize>(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        diffuse_bind_group_layout: &wgpu::BindGroupLayout,
+        format: wgpu::TextureFormat,
+        extent: Ext2u,
+        is_diffuse_separated: bool
+    ) -> [Collector; N] {
+        let create_texture = |label: &str, format: wgpu::TextureFormat, extent: Ext2u, usage: wgpu::TextureUsages| device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            label: Some(label),
             mip_level_count: 1,
             sample_count: 1,
             size: wgpu::Extent3d {
@@ -75,78 +948,107 @@ impl<'t> Render<'t> {
                 height: extent.h,
                 depth_or_array_layers: N as u32,
             },
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
-            view_formats: &[wgpu::TextureFormat::Rgba32Float],
+            usage: usage | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
         });
 
+        let attachment_usage = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC;
+        let full_format = wgpu::TextureFormat::Rgba32Float;
+        let collector_target_texture = Rc::new(create_texture("Collector", format, extent, attachment_usage));
+        let collector_feature_texture = Rc::new(create_texture("Collector features", full_format, extent, attachment_usage));
+        let diffuse_extent = if is_diffuse_separated { extent } else { Ext2u::new(1, 1) };
+        let diffuse_texture = create_texture("Collector diffuse", full_format, diffuse_extent, wgpu::TextureUsages::STORAGE_BINDING);
+
         let build_collector = |index: usize| {
-            let view = collector_target_texture.create_view(&wgpu::TextureViewDescriptor {
+            let create_view = |texture: &wgpu::Texture| texture.create_view(&wgpu::TextureViewDescriptor {
                 array_layer_count: Some(1),
-                aspect: wgpu::TextureAspect::All,
                 base_array_layer: index as u32,
-                base_mip_level: 0,
                 dimension: Some(wgpu::TextureViewDimension::D2),
-                format: Some(wgpu::TextureFormat::Rgba32Float),
-                label: None,
-                mip_level_count: None,
+                ..Default::default()
             });
-            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&view),
-                }],
-                label: None,
-                layout: &bind_group_layout,
+
+            let view = create_view(&collector_target_texture);
+            let feature_view = create_view(&collector_feature_texture);
+            let diffuse_view = create_view(&diffuse_texture);
+            let bind_group = Self::create_collector_bind_group(device, bind_group_layout, &view, &feature_view, &diffuse_view);
+            let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                entries: &[wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&diffuse_view) }],
+                label: Some("Collector diffuse bind group"),
+                layout: diffuse_bind_group_layout,
             });
 
-            Collector { view, bind_group }
+            Collector {
+                texture: collector_target_texture.clone(),
+                layer: index as u32,
+                view,
+                feature_texture: collector_feature_texture.clone(),
+                feature_view,
+                diffuse_view,
+                bind_group,
+                diffuse_bind_group,
+            }
         };
 
         std::array::from_fn(build_collector)
     }
 
-    pub fn new(window: impl wgpu::WindowHandle + 't, surface_ext: Ext2u) -> Option<Self> {
-        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
-
-        let surface = instance.create_surface(window).ok()?;
-
-        let adapter = futures::executor::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
-            compatible_surface: Some(&surface),
-            power_preference: wgpu::PowerPreference::HighPerformance,
-            ..Default::default()
-        }))?;
+    fn create_collector_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        feature_view: &wgpu::TextureView,
+        diffuse_view: &wgpu::TextureView
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(feature_view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::TextureView(diffuse_view) },
+            ],
+            label: Some("Collector bind group"),
+            layout,
+        })
+    }
 
-        let (device, queue) = futures::executor::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
-            label: Some("Device"),
-            required_features: wgpu::Features::empty(),
-            required_limits: wgpu::Limits::downlevel_defaults(),
-        }, None)).ok()?;
+    /// Renderer writing presented frames to `target` of `surface_configuration` format and size, see `RenderBuilder`
+    fn new(
+        context: Arc<GpuContext>,
+        target: Target<'t>,
+        surface_configuration: wgpu::SurfaceConfiguration,
+        collector_format: wgpu::TextureFormat
+    ) -> Self {
+        let adapter = &context.adapter;
+        let device = &context.device;
+        let queue = &context.queue;
 
-        let surface_format = {
-            let caps = surface.get_capabilities(&adapter);
-            *caps.formats.iter().find(|f| f.is_srgb() && f.has_color_aspect() && f.components() == 4).unwrap_or(&caps.formats[0])
-        };
-        // Setup surface
-        let surface_configuration = wgpu::SurfaceConfiguration {
-            alpha_mode: wgpu::CompositeAlphaMode::Auto,
-            desired_maximum_frame_latency: 3,
-            present_mode: wgpu::PresentMode::AutoNoVsync,
-            format: surface_format,
-            width: surface_ext.w,
-            height: surface_ext.h,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            view_formats: vec![surface_format]
-        };
-        surface.configure(&device, &surface_configuration);
+        if let Target::Surface(surface) = &target {
+            surface.configure(device, &surface_configuration);
+        }
+        let surface_format = surface_configuration.format;
+        let surface_ext = Ext2u::new(surface_configuration.width, surface_configuration.height);
 
         let collector_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[0, 1, 2].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                count: None,
+                ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: false }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+            }),
+            label: None,
+        });
+
+        let diffuse_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[wgpu::BindGroupLayoutEntry {
                 binding: 0,
                 count: None,
-                ty: wgpu::BindingType::Texture { sample_type: wgpu::TextureSampleType::Float { filterable: false }, view_dimension: wgpu::TextureViewDimension::D2, multisampled: false },
+                ty: wgpu::BindingType::StorageTexture {
+                    access: wgpu::StorageTextureAccess::WriteOnly,
+                    format: wgpu::TextureFormat::Rgba32Float,
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
                 visibility: wgpu::ShaderStages::FRAGMENT,
             }],
-            label: None,
+            label: Some("Collector diffuse bind group layout"),
         });
 
         let camera_buffer = device.create_buffer(&wgpu::BufferDescriptor {
@@ -163,6 +1065,14 @@ impl<'t> Render<'t> {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC,
         });
 
+        let sky_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sky UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<SkyData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        queue.write_buffer(&sky_buffer, 0, as_bytes(&SkyData::default()));
+
         let render_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             label: None,
             entries: &[
@@ -174,7 +1084,8 @@ impl<'t> Render<'t> {
                         min_binding_size: Some(std::num::NonZeroU64::try_from(std::mem::size_of::<CameraData>() as u64).unwrap()),
                         ty: wgpu::BufferBindingType::Uniform
                     },
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // Vertex stage reads camera to project path overlay
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                 },
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
@@ -184,11 +1095,40 @@ impl<'t> Render<'t> {
                         min_binding_size: Some(std::num::NonZeroU64::try_from(std::mem::size_of::<SystemData>() as u64).unwrap()),
                         ty: wgpu::BufferBindingType::Uniform
                     },
-                    visibility: wgpu::ShaderStages::FRAGMENT,
-                }
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: Some(std::num::NonZeroU64::try_from(std::mem::size_of::<SkyData>() as u64).unwrap()),
+                        ty: wgpu::BufferBindingType::Uniform
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                },
             ],
         });
 
+        let blue_noise_view = scene_data::create_float_texture(
+            device,
+            queue,
+            "Blue noise",
+            wgpu::TextureFormat::R32Float,
+            Ext2u::new(blue_noise::SIZE, blue_noise::SIZE),
+            &blue_noise::generate()
+        );
+
         let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
             entries: &[
                 wgpu::BindGroupEntry {
@@ -207,177 +1147,930 @@ impl<'t> Render<'t> {
                         size: None,
                     })
                 },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sky_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&blue_noise_view),
+                },
             ],
             label: None,
             layout: &render_bind_group_layout,
         });
 
+        let scene_bind_group_layout = scene_data::create_bind_group_layout(device);
+
+        let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Scene texture sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let scene_resources = scene_data::SceneResources::new(device, queue, &Scene::default());
+        let scene_bind_group = scene_data::create_bind_group(device, &scene_bind_group_layout, &texture_sampler, &scene_resources, &[], &[], Vec3d::default());
+
         let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&render_bind_group_layout, &collector_bind_group_layout],
+            bind_group_layouts: &[&render_bind_group_layout, &collector_bind_group_layout, &scene_bind_group_layout],
+            ..Default::default()
+        });
+
+        let main_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&render_bind_group_layout, &collector_bind_group_layout, &scene_bind_group_layout, &diffuse_bind_group_layout],
             ..Default::default()
         });
 
+        let integrator = Box::new(integrator::Configured { integrator: PathTracer, settings: () });
         let render_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Main Shader"),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/render.wgsl")))
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(
+                integrator::compose_render_shader(&*integrator, &mut shader_include::load_embedded).expect("embedded shader includes must be embedded too")
+            ))
         });
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Main pipeline"),
-            depth_stencil: None,
-            fragment: Some(wgpu::FragmentState {
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                entry_point: "fs_main",
-                module: &render_shader_module,
-                targets: &[Some(wgpu::ColorTargetState {
-                    blend: None,
-                    format: wgpu::TextureFormat::Rgba32Float,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })]
-            }),
-            layout: Some(&render_pipeline_layout),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                ..Default::default()
-            },
-            vertex: wgpu::VertexState {
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                entry_point: "vs_main",
-                module: &render_shader_module,
-            }
-        });
+        let render_pipeline = Self::create_render_pipeline(
+            device,
+            &main_pipeline_layout,
+            &render_shader_module,
+            collector_format,
+            &integrator::main_constants(&*integrator, &RenderSettings::default())
+        );
+
+        let outline = outline::Outline::new(device, &render_pipeline_layout, &render_shader_module, surface_format, surface_ext);
 
         let place_shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Place Shader"),
-            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/place.wgsl")))
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shader_include::compose_embedded("place.wgsl")))
         });
 
+        let glare = glare::Glare::new(device, &collector_bind_group_layout, surface_ext);
+
         let place_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            bind_group_layouts: &[&render_bind_group_layout, &collector_bind_group_layout],
+            bind_group_layouts: &[&render_bind_group_layout, &collector_bind_group_layout, glare.output_bind_group_layout()],
             ..Default::default()
         });
 
-        let place_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            depth_stencil: None,
-            fragment: Some(wgpu::FragmentState {
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                entry_point: "fs_main",
-                module: &place_shader_module,
-                targets: &[Some(wgpu::ColorTargetState {
-                    blend: None,
-                    format: surface_format,
-                    write_mask: wgpu::ColorWrites::ALL,
-                })]
-            }),
-            label: None,
-            layout: Some(&place_pipeline_layout),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
-                ..Default::default()
-            },
-            vertex: wgpu::VertexState {
-                buffers: &[],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-                entry_point: "vs_main",
-                module: &place_shader_module,
-            }
-        });
+        let place_pipeline = Self::create_place_pipeline(device, &place_pipeline_layout, &place_shader_module, surface_format);
 
-        Some(Self {
-            collectors: Self::create_collectors(&device, &collector_bind_group_layout, surface_ext),
-            kernel: Rc::new(Kernel {
-                device,
-                queue,
-                surface,
-            }),
+        let denoiser = denoise::Denoiser::new(device, &collector_bind_group_layout, surface_ext);
+        let aovs = aov::Aovs::new(device, &render_pipeline_layout, &render_shader_module, surface_ext);
+        let gpu_timer = gpu_timer::GpuTimer::new(device, queue);
+        let overlay = overlay::Overlay::new(device, surface_format);
+        let resampler = resample::Resampler::new(device, &collector_bind_group_layout, &diffuse_bind_group_layout, collector_format);
+        let buffer_presenter = buffer_view::BufferPresenter::new(device, surface_format);
+        let lens_flare_pass = lens_flare::LensFlarePass::new(device, &collector_bind_group_layout, surface_format, surface_ext);
+        let path_recorder = path_record::PathRecorder::new(device, &render_bind_group_layout, &collector_bind_group_layout, &scene_bind_group_layout);
+        let path_overlay = path_overlay::PathOverlay::new(device, &render_bind_group_layout, surface_format);
+        let convergence = convergence::ConvergenceMeter::new(device, &collector_bind_group_layout, surface_ext);
+        let auto_exposure = auto_exposure::AutoExposure::new(device, &collector_bind_group_layout);
+
+        let collectors = Self::create_collectors(device, &collector_bind_group_layout, &diffuse_bind_group_layout, collector_format, surface_ext, false);
+        let sharpener = sharpen::Sharpener::new(device, &collector_bind_group_layout, &collectors, surface_ext);
+        let adapter_info = adapter.get_info();
+        let watchdog = watchdog::Watchdog::new(context.clone());
+
+        Self {
+            context,
+            collectors,
+            target,
+            collector_format,
+            max_sample_count: None,
             render_bind_group,
+            scene_bind_group_layout,
+            scene_resources,
+            scene_bind_group,
+            texture_sampler,
             camera_buffer,
             system_buffer,
+            sky_buffer,
+            render_pipeline_layout,
+            main_pipeline_layout,
+            integrator,
+            render_shader_module,
             render_pipeline,
+            place_pipeline_layout,
             place_pipeline,
+            shader_watch: None,
             static_frame_index: 0,
+            settings: RenderSettings::default(),
+            exposure: 1.0,
+            false_color: false_color_stops(FalseColorPalette::default()),
+            tolerances: Tolerances::default(),
+            camera_data: CameraData::default(),
+            is_camera_updated: false,
+            instances: Vec::new(),
+            previous_instance_transforms: Vec::new(),
+            is_instances_updated: false,
+            world_origin: Vec3d::default(),
+            camera_location: Vec3d::default(),
+            still_pass_count: 0,
+            completion_callbacks: Vec::new(),
+            adapter_info,
+            gpu_timer,
             collector_bind_group_layout,
+            diffuse_bind_group_layout,
             surface_configuration,
-        })
+            outline,
+            denoiser,
+            aovs,
+            overlay,
+            resampler,
+            buffer_presenter,
+            lens_flare_pass,
+            sharpener,
+            path_recorder,
+            path_overlay,
+            convergence,
+            auto_exposure,
+            watchdog,
+            slow_frame: None,
+            lens_flare: None,
+            glare,
+            post_processes: Vec::new(),
+            buffer_view: None,
+            resize_source: None,
+        }
     }
 
-    /// Render resize function
-    pub fn resize(&mut self, new_extent: Ext2u) {
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    /// Set integrator parameters, accumulation restarts on change of anything but presentation toggles and targets
+    pub fn set_settings(&mut self, settings: RenderSettings) {
+        crate::crash::set_render_settings(&settings);
+        let kept = RenderSettings {
+            denoise: settings.denoise,
+            show_sample_count: settings.show_sample_count,
+            show_variance: settings.show_variance,
+            target_sample_count: settings.target_sample_count,
+            target_error: settings.target_error,
+            auto_exposure: settings.auto_exposure,
+            auto_exposure_min_ev: settings.auto_exposure_min_ev,
+            auto_exposure_max_ev: settings.auto_exposure_max_ev,
+            auto_exposure_speed: settings.auto_exposure_speed,
+            sharpen: settings.sharpen,
+            glare: settings.glare,
+            film_grain: settings.film_grain,
+            dither: settings.dither,
+            ..self.settings
+        };
+        if kept != settings {
+            self.static_frame_index = 0;
+        }
+        let constants = integrator::main_constants(&*self.integrator, &settings);
+        if constants != integrator::main_constants(&*self.integrator, &self.settings) {
+            self.render_pipeline = Self::create_render_pipeline(&self.context.device, &self.main_pipeline_layout, &self.render_shader_module, self.collector_format, &constants);
+        }
+        if !settings.auto_exposure {
+            self.auto_exposure.reset();
+        }
+        if settings.denoise_components != self.settings.denoise_components {
+            self.collectors = Self::create_collectors(&self.context.device, &self.collector_bind_group_layout, &self.diffuse_bind_group_layout, self.collector_format, self.extent(), settings.denoise_components);
+            self.resize_source = None;
+            self.sharpener.bind_collectors(&self.context.device, &self.collector_bind_group_layout, &self.collectors);
+            for pass in &mut self.post_processes {
+                pass.bind_collectors(&self.context.device, &self.collector_bind_group_layout, &self.collectors);
+            }
+        }
+        self.settings = settings;
+        self.update_world_origin();
+    } // fn set_settings
+
+    /// Report frames whose GPU work takes longer than `timeout`, None disables reports.
+    /// Software adapters have no driver timeout, so they are never reported.
+    pub fn set_watchdog_timeout(&mut self, timeout: Option<std::time::Duration>) {
+        self.watchdog.set_timeout(timeout.filter(|_| !self.is_software()));
+    }
+
+    /// GPU duration of last frame exceeded watchdog timeout, frame may be still running.
+    /// Frames are checked without waiting, so report lags behind `render` calls.
+    pub fn take_slow_frame(&mut self) -> Option<std::time::Duration> {
+        self.slow_frame.take()
+    }
+
+    /// Rebuild pipelines from shader files on disk on their change, done by `reload_shaders`
+    pub fn enable_shader_hot_reload(&mut self, directory: &std::path::Path) {
+        self.shader_watch = Some(shader_reload::ShaderWatch::new(directory));
+    }
+
+    /// Append post-process pass of plugin, it's skipped on shader compilation error
+    pub fn add_post_process(&mut self, plugin: &PostProcessPlugin) -> Result<(), String> {
+        let device = &self.context.device;
+        let queue = &self.context.queue;
+        let layout = &self.collector_bind_group_layout;
+        let collectors = &self.collectors;
+        let extent = self.extent();
+        let (_, pass) = self.try_create("Post-process Shader", &plugin.source, |module| {
+            post_process::PostProcessPass::new(device, queue, layout, plugin, module, collectors, extent)
+        })?;
+        self.post_processes.push(pass);
+        Ok(())
+    }
+
+    /// Compile shader module and pipelines built of it, errors are caught instead of reaching uncaptured error handler
+    fn try_create<T>(&self, label: &str, source: &str, create: impl FnOnce(&wgpu::ShaderModule) -> T) -> Result<(wgpu::ShaderModule, T), String> {
+        let device = &self.context.device;
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(source)),
+        });
+        let value = create(&module);
+        match futures::executor::block_on(device.pop_error_scope()) {
+            Some(error) => Err(error.to_string()),
+            None => Ok((module, value)),
+        }
+    }
+
+    /// Replace main shader module and pipelines built of it, they are kept on compilation error.
+    /// Accumulation restarts on success.
+    fn rebuild_render_shader(&mut self, source: &str) -> Result<(), String> {
+        let device = &self.context.device;
+        let layout = &self.render_pipeline_layout;
+        let main_layout = &self.main_pipeline_layout;
+        let constants = integrator::main_constants(&*self.integrator, &self.settings);
+        let collector_format = self.collector_format;
+        let (module, (render_pipeline, id_pipeline, aov_pipeline)) = self.try_create("Main Shader", source, |module| (
+            Self::create_render_pipeline(device, main_layout, module, collector_format, &constants),
+            outline::Outline::create_id_pipeline(device, layout, module),
+            aov::Aovs::create_pipeline(device, layout, module),
+        ))?;
+
+        self.render_shader_module = module;
+        self.render_pipeline = render_pipeline;
+        self.outline.set_id_pipeline(id_pipeline);
+        self.aovs.set_pipeline(aov_pipeline);
+        self.static_frame_index = 0;
+        Ok(())
+    }
+
+    /// Name of integrator traced by main pipeline
+    pub fn integrator_name(&self) -> &str {
+        self.integrator.name()
+    }
+
+    /// Settings of current integrator, None if it isn't of type `I`
+    pub fn integrator_settings<I: Integrator>(&self) -> Option<&I::Settings> {
+        self.integrator.as_any().downcast_ref::<integrator::Configured<I>>().map(|configured| &configured.settings)
+    }
+
+    /// Trace camera rays by `integrator` with its `settings`, current one is kept if its shader fails to compile.
+    /// Shader files are read from hot reload directory if it's enabled.
+    pub fn set_integrator<I: Integrator>(&mut self, integrator: I, settings: I::Settings) -> Result<(), String> {
+        self.set_configured_integrator(Box::new(integrator::Configured { integrator, settings }))
+    }
+
+    fn set_configured_integrator(&mut self, integrator: Box<dyn integrator::ConfiguredIntegrator>) -> Result<(), String> {
+        let source = match &self.shader_watch {
+            Some(watch) => integrator::compose_render_shader(&*integrator, &mut |name| shader_reload::load(&watch.directory, name)),
+            None => integrator::compose_render_shader(&*integrator, &mut shader_include::load_embedded),
+        }?;
+
+        let previous = std::mem::replace(&mut self.integrator, integrator);
+        self.rebuild_render_shader(&source).inspect_err(|_| self.integrator = previous)
+    }
+
+    /// Change settings of current integrator, false if it isn't of type `I`. Accumulation restarts,
+    /// main pipeline is rebuilt if integrator constants change.
+    pub fn set_integrator_settings<I: Integrator>(&mut self, settings: I::Settings) -> bool {
+        let previous_constants = integrator::main_constants(&*self.integrator, &self.settings);
+        let Some(configured) = self.integrator.as_any_mut().downcast_mut::<integrator::Configured<I>>() else {
+            return false;
+        };
+        configured.settings = settings;
+
+        let constants = integrator::main_constants(&*self.integrator, &self.settings);
+        if constants != previous_constants {
+            self.render_pipeline = Self::create_render_pipeline(&self.context.device, &self.main_pipeline_layout, &self.render_shader_module, self.collector_format, &constants);
+        }
         self.static_frame_index = 0;
-        self.collectors = Self::create_collectors(&self.kernel.device, &self.collector_bind_group_layout, new_extent.clone());
+        true
+    }
+
+    /// Rebuild pipelines of shader files changed since last call if hot reload is enabled,
+    /// pipelines of shader failed to compile are kept. Accumulation restarts on main shader change.
+    pub fn reload_shaders(&mut self) -> Vec<ShaderReload> {
+        let Some(watch) = &mut self.shader_watch else {
+            return Vec::new();
+        };
+        let mut results = Vec::new();
+        let mut read = |file: &shader_reload::ShaderFile, source: Option<Result<String, String>>| match source? {
+            Ok(source) => Some((file.path.clone(), source)),
+            Err(error) => {
+                results.push(ShaderReload::Failed(file.path.clone(), error));
+                None
+            }
+        };
+        let integrator = &*self.integrator;
+        let render_source = watch.render.read_changed_with(|load| integrator::compose_render_shader(integrator, load));
+        let render_source = read(&watch.render, render_source);
+        let place_source = watch.place.read_changed();
+        let place_source = read(&watch.place, place_source);
+
+        if let Some((path, source)) = render_source {
+            match self.rebuild_render_shader(&source) {
+                Ok(()) => results.push(ShaderReload::Reloaded(path)),
+                Err(error) => results.push(ShaderReload::Failed(path, error)),
+            }
+        }
+
+        if let Some((path, source)) = place_source {
+            let device = &self.context.device;
+            let layout = &self.place_pipeline_layout;
+            let format = self.surface_configuration.format;
+            match self.try_create("Place Shader", &source, |module| Self::create_place_pipeline(device, layout, module, format)) {
+                Ok((_, place_pipeline)) => {
+                    self.place_pipeline = place_pipeline;
+                    results.push(ShaderReload::Reloaded(path));
+                }
+                Err(error) => results.push(ShaderReload::Failed(path, error)),
+            }
+        }
+
+        results
+    } // fn reload_shaders
+
+    pub fn buffer_view(&self) -> Option<BufferView> {
+        self.buffer_view
+    }
+
+    /// Present internal texture instead of image, None restores image presentation
+    pub fn set_buffer_view(&mut self, buffer_view: Option<BufferView>) {
+        self.buffer_view = buffer_view;
+    }
+
+    pub fn surface_format(&self) -> wgpu::TextureFormat {
+        self.surface_configuration.format
+    }
+
+    /// Resize image. Accumulated image is rescaled to new size and kept with reduced weight
+    /// if temporal reprojection is enabled, camera projection may change with it.
+    pub fn resize(&mut self, new_extent: Ext2u) {
+        let new_collectors = Self::create_collectors(&self.context.device, &self.collector_bind_group_layout, &self.diffuse_bind_group_layout, self.collector_format, new_extent, self.settings.denoise_components);
+        let collectors = std::mem::replace(&mut self.collectors, new_collectors);
+
+        // Source of repeated resizes between renders is the oldest collector, new ones are empty yet
+        if self.static_frame_index == 0 || !self.settings.temporal_reprojection {
+            self.static_frame_index = 0;
+            self.resize_source = None;
+        } else if self.resize_source.is_none() {
+            let projection_extent = Vec2f::new(self.camera_data.projection_width, self.camera_data.projection_height);
+            let [even, odd] = collectors;
+            self.resize_source = Some((if self.static_frame_index & 1 == 0 { even } else { odd }, projection_extent));
+
+            let sample_count = (self.progress().sample_count as f32 * resample::HISTORY_WEIGHT) as u32;
+            self.still_pass_count = sample_count / SAMPLES_PER_PASS * self.tile_count(new_extent);
+        }
+
+        self.outline.resize(&self.context.device, new_extent);
+        self.denoiser.resize(&self.context.device, new_extent);
+        self.lens_flare_pass.resize(&self.context.device, new_extent);
+        self.sharpener.resize(&self.context.device, &self.collector_bind_group_layout, &self.collectors, new_extent);
+        self.convergence.resize(&self.context.device, new_extent);
+        self.glare.resize(new_extent);
+        for pass in &mut self.post_processes {
+            pass.resize(&self.context.device, &self.collector_bind_group_layout, &self.collectors, new_extent);
+        }
+        self.aovs.resize(&self.context.device, new_extent);
         self.surface_configuration.width = new_extent.w;
         self.surface_configuration.height = new_extent.h;
-        self.kernel.surface.configure(&self.kernel.device, &self.surface_configuration);
+        self.target.configure(&self.context.device, &self.surface_configuration);
     } // fn resize
 
     pub fn set_camera(&mut self, camera_data: &CameraDescriptor) {
-        self.kernel.queue.write_buffer(&self.camera_buffer, 0, unsafe {
-            std::slice::from_raw_parts(std::mem::transmute(&CameraData {
-                _pad0: 0.0,
-                dir: camera_data.dir,
-                location: camera_data.location,
-                near: camera_data.near,
-                projection_height: camera_data.projection_extent.h,
-                projection_width: camera_data.projection_extent.w,
-                right: camera_data.right,
-                up: camera_data.up,
-            }), std::mem::size_of::<CameraData>())
-        });
-        self.static_frame_index = 0;
-    } // fn set_camera
+        self.camera_location = camera_data.location;
+        self.update_world_origin();
 
-    pub fn render(&mut self) {
-        let image = match self.kernel.surface.get_current_texture() {
-            Ok(v) => v,
-            Err(_) => return,
+        let mut data = CameraData {
+            dir: camera_data.dir,
+            location: (camera_data.location - self.world_origin).to_f32(),
+            near: camera_data.near,
+            projection_height: camera_data.projection_extent.h,
+            projection_width: camera_data.projection_extent.w,
+            right: camera_data.right,
+            up: camera_data.up,
+            aperture_radius: camera_data.aperture_radius,
+            focus_distance: camera_data.focus_distance,
+            projection: projection_index(camera_data.projection),
+            aperture_blades: camera_data.aperture_blades,
+            aperture_rotation: camera_data.aperture_rotation.to_radians(),
+            bokeh_texture: camera_data.bokeh_texture.unwrap_or(u32::MAX),
+            lens_shift: camera_data.lens_shift,
+            lens_tilt: Vec2f::new(camera_data.lens_tilt.x.to_radians(), camera_data.lens_tilt.y.to_radians()),
+            ..Default::default()
         };
-        let image_view = image.texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        self.kernel.queue.write_buffer(&self.system_buffer, 0, unsafe {
-            let s = image.texture.size();
-            let resolution = Ext2f::new(s.width as f32, s.height as f32);
-            let texel_size = Ext2f::new(1.0 / resolution.w, 1.0 / resolution.h);
-            std::slice::from_raw_parts(std::mem::transmute(&SystemData {
-                resolution,
-                texel_size,
-                time: std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).map(|v| {
-                    (v.as_millis() & 0xFFFFFF) as f32 / 1000.0
-                }).unwrap_or(0.0),
-                static_frame_index: self.static_frame_index,
+        data.stop();
+
+        // Previous basis is camera at last frame, initial camera (with zero near) has no motion
+        if self.is_camera_updated {
+            data.previous_location = self.camera_data.previous_location;
+            data.previous_dir = self.camera_data.previous_dir;
+            data.previous_right = self.camera_data.previous_right;
+            data.previous_up = self.camera_data.previous_up;
+        } else if self.camera_data.near != 0.0 {
+            data.previous_location = self.camera_data.location;
+            data.previous_dir = self.camera_data.dir;
+            data.previous_right = self.camera_data.right;
+            data.previous_up = self.camera_data.up;
+        }
+
+        // Only camera movement is reprojected, projection and lens changes restart accumulation.
+        // Projection extent change of resize is handled by resampling.
+        let old = self.camera_data;
+        let is_projection_changed = [
+            old.near, old.aperture_radius, old.focus_distance, old.aperture_rotation,
+            old.lens_shift.x, old.lens_shift.y, old.lens_tilt.x, old.lens_tilt.y,
+        ] != [
+            data.near, data.aperture_radius, data.focus_distance, data.aperture_rotation,
+            data.lens_shift.x, data.lens_shift.y, data.lens_tilt.x, data.lens_tilt.y,
+        ]
+            || [old.aperture_blades, old.bokeh_texture, old.projection] != [data.aperture_blades, data.bokeh_texture, data.projection];
+        let is_extent_changed = [old.projection_width, old.projection_height] != [data.projection_width, data.projection_height];
+
+        if is_projection_changed || (is_extent_changed && self.resize_source.is_none()) || !self.settings.temporal_reprojection {
+            self.static_frame_index = 0;
+        }
+
+        self.camera_data = data;
+        self.exposure = camera_data.exposure.exp2();
+        self.context.queue.write_buffer(&self.camera_buffer, 0, as_bytes(&self.camera_data));
+        self.is_camera_updated = true;
+    } // fn set_camera
+
+    /// Upload scene geometry and rebuild acceleration structure
+    pub fn set_scene(&mut self, scene: &Scene) {
+        self.scene_resources = scene_data::SceneResources::new(&self.context.device, &self.context.queue, scene);
+        self.tolerances = scene.tolerances;
+        // New scene instances have no motion
+        self.instances.clear();
+        self.set_instances(&scene.instances);
+        self.set_sky(scene.sky.as_ref());
+        self.set_lens_flare(scene.lens_flare.as_ref());
+    } // fn set_scene
+
+    /// Set lens flare post effect. It is added to presented image only, heatmaps, buffer views and AOVs are left without it.
+    pub fn set_lens_flare(&mut self, lens_flare: Option<&LensFlare>) {
+        self.lens_flare = lens_flare.copied();
+    }
+
+    /// Update parameters of current scene materials, material count must not change
+    pub fn set_materials(&mut self, materials: &[TexturedMaterial]) {
+        self.scene_resources.set_materials(&self.context.queue, materials);
+        // Emission changes light selection probabilities
+        self.update_scene_bind_group();
+    }
+
+    /// Set procedural sky, it lights the scene only if scene has no environment map
+    pub fn set_sky(&mut self, sky: Option<&Sky>) {
+        let data = sky.map(|sky| {
+            let model = sky.model();
+            let [perez_a, perez_b, perez_c, perez_d, perez_e] = model.perez;
+
+            SkyData {
+                sun_direction: model.sun_direction,
+                is_enabled: 1,
+                sun_radiance: model.sun_radiance,
+                sun_cos_angular_radius: model.sun_cos_angular_radius,
+                perez_a,
+                perez_b,
+                perez_c,
+                perez_d,
+                perez_e,
+                zenith: model.zenith,
                 ..Default::default()
-            }), std::mem::size_of::<SystemData>())
-        });
+            }
+        }).unwrap_or_default();
+
+        self.context.queue.write_buffer(&self.sky_buffer, 0, as_bytes(&data));
+        self.static_frame_index = 0;
+    } // fn set_sky
+
+    /// Replace instances of current scene, only top-level acceleration structure is rebuilt.
+    /// Transforms of replaced instances are used as previous-frame ones for motion blur.
+    pub fn set_instances(&mut self, instances: &[Instance]) {
+        if self.instances.len() != instances.len() {
+            self.previous_instance_transforms = instances.iter().map(|instance| instance.transform).collect();
+        } else if !self.is_instances_updated {
+            self.previous_instance_transforms = self.instances.iter().map(|instance| instance.transform).collect();
+        }
+        self.instances = instances.to_vec();
+        self.is_instances_updated = true;
+        self.update_scene_bind_group();
+    } // fn set_instances
+
+    fn update_scene_bind_group(&mut self) {
+        self.scene_bind_group = scene_data::create_bind_group(
+            &self.context.device,
+            &self.scene_bind_group_layout,
+            &self.texture_sampler,
+            &self.scene_resources,
+            &self.instances,
+            &self.previous_instance_transforms,
+            self.world_origin
+        );
+        self.static_frame_index = 0;
+    } // fn update_scene_bind_group
+
+    /// Move world origin to camera once camera gets too far from it, or back to zero if camera-relative mode is off.
+    /// Rebasing keeps accumulation, as image doesn't change.
+    fn update_world_origin(&mut self) {
+        let origin = if !self.settings.camera_relative {
+            Vec3d::default()
+        } else if (self.camera_location - self.world_origin).length() > WORLD_REBASE_DISTANCE {
+            self.camera_location
+        } else {
+            self.world_origin
+        };
+
+        if origin == self.world_origin {
+            return;
+        }
+
+        // Camera of last frame is moved too, so motion blur and reprojection stay continuous
+        let shift = (self.world_origin - origin).to_f32();
+        let CameraData { location, previous_location, .. } = self.camera_data;
+        self.camera_data.location = location + shift;
+        self.camera_data.previous_location = previous_location + shift;
+        self.context.queue.write_buffer(&self.camera_buffer, 0, as_bytes(&self.camera_data));
+
+        self.world_origin = origin;
+        self.path_overlay.upload(&self.context.device, origin);
+        let static_frame_index = self.static_frame_index;
+        self.update_scene_bind_group();
+        self.static_frame_index = static_frame_index;
+    } // fn update_world_origin
+
+    /// Camera and instances not updated since last frame are stopped, so accumulation converges to sharp image
+    fn is_instances_moving(&self) -> bool {
+        self.instances.iter().zip(&self.previous_instance_transforms).any(|(instance, previous)| instance.transform != *previous)
+    }
+
+    fn stop_motion(&mut self) {
+        if !self.is_camera_updated && self.camera_data.is_moving() {
+            self.camera_data.stop();
+            self.context.queue.write_buffer(&self.camera_buffer, 0, as_bytes(&self.camera_data));
+
+            // Reprojected history is sharp only with instant shutter
+            if self.settings.shutter_open != self.settings.shutter_close {
+                self.static_frame_index = 0;
+            }
+        }
+
+        if !self.is_instances_updated && self.is_instances_moving() {
+            self.previous_instance_transforms = self.instances.iter().map(|instance| instance.transform).collect();
+            self.update_scene_bind_group();
+        }
+
+        self.is_camera_updated = false;
+        self.is_instances_updated = false;
+    } // fn stop_motion
 
-        let mut encoder = self.kernel.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+    /// Select scene instances to outline
+    pub fn set_selection(&mut self, selection: &BTreeSet<u32>) {
+        self.outline.set_selection(&self.context.device, selection);
+    } // fn set_selection
 
+    /// Set UI overlay appearance
+    pub fn set_theme(&mut self, theme: &Theme) {
+        self.outline.set_theme(&self.context.queue, theme);
+        self.false_color = false_color_stops(theme.false_color);
+    } // fn set_theme
 
+    /// Set UI drawn over presented image
+    pub fn set_overlay(&mut self, frame: OverlayFrame) {
+        self.overlay.set_frame(frame);
+    }
+
+    /// Show or hide selection box, corners are in pixels
+    pub fn set_selection_box(&mut self, selection_box: Option<(Vec2u, Vec2u)>) {
+        self.outline.set_selection_box(&self.context.queue, selection_box);
+    } // fn set_selection_box
+
+    /// Get indices of scene instances visible in pixel rectangle, corners are inclusive
+    pub fn pick_instances(&self, min: Vec2u, max: Vec2u) -> BTreeSet<u32> {
+        self.outline.read_ids(&self.context.device, &self.context.queue, min, max)
+    } // fn pick_instances
+
+    /// Scene element visible at pixel center in last rendered frame, None if there is no element
+    pub fn pick(&self, x: u32, y: u32) -> Option<PickHit> {
+        let pixel = Vec2u::new(x, y);
+        let [instance_id, primitive_id] = *self.outline.read_id_pixels(&self.context.device, &self.context.queue, pixel, pixel).first()?;
+        let primitive_index = primitive_id.checked_sub(1)?;
+
+        Some(match instance_id.checked_sub(1) {
+            Some(instance) => PickHit::Instance { instance, triangle: self.scene_resources.mesh_triangle_index(primitive_index)? },
+            None => PickHit::Primitive(primitive_index),
+        })
+    }
+
+    /// Raw accumulated values and AOVs of pixel in last rendered frame, None if pixel is outside of image.
+    /// Pixel is read synchronously, so it stalls GPU and is meant for debugging.
+    pub fn inspect_pixel(&self, x: u32, y: u32) -> Option<PixelInfo> {
+        let extent = self.extent();
+        if x >= extent.w || y >= extent.h {
+            return None;
+        }
+        let pixel = Vec2u::new(x, y);
+
+        let collector = &self.collectors[self.static_frame_index as usize & 1];
+        let [r, g, b, pass_count] = read_rgba_pixel(&self.context.device, &self.context.queue, &collector.texture, collector.layer, pixel);
+        let features = read_rgba_pixel(&self.context.device, &self.context.queue, &collector.feature_texture, collector.layer, pixel);
+
+        Some(PixelInfo {
+            radiance_sum: Vec3f::new(r, g, b),
+            pass_count,
+            luminance_moment: features[3],
+            aovs: self.aovs.read_pixel(&self.context.device, &self.context.queue, pixel),
+        })
+    } // fn inspect_pixel
+
+    /// Depth along view direction of surface seen at pixel center in last rendered frame, None if nothing was
+    /// hit there or pixel is outside of image. Pixel is read synchronously, so it stalls GPU.
+    pub fn depth_at(&self, x: u32, y: u32) -> Option<f32> {
+        let extent = self.extent();
+        if x >= extent.w || y >= extent.h {
+            return None;
+        }
+        let depth = self.aovs.read_pixel(&self.context.device, &self.context.queue, Vec2u::new(x, y)).depth;
+        (depth > 0.0).then_some(depth)
+    }
+
+    /// Trace `path_count` paths through pixel with their vertices recorded, None if pixel is outside of image.
+    /// Paths are traced by integrator of main pipeline synchronously, so it stalls GPU and is meant for debugging.
+    pub fn record_paths(&mut self, x: u32, y: u32, path_count: u32) -> Option<Vec<RecordedPath>> {
+        let extent = self.extent();
+        if x >= extent.w || y >= extent.h {
+            return None;
+        }
+
+        // Pipeline is built on each call, so it always matches main one
+        let constants = integrator::main_constants(&*self.integrator, &self.settings);
+        let pipeline = self.path_recorder.create_pipeline(&self.context.device, &self.render_shader_module, &constants);
+        let bind_groups = [&self.render_bind_group, &self.collectors[0].bind_group, &self.scene_bind_group];
+        let mut paths = self.path_recorder.record(&self.context.device, &self.context.queue, &pipeline, bind_groups, Vec2u::new(x, y), path_count);
+        for vertex in paths.iter_mut().flat_map(|path| path.vertices.iter_mut()) {
+            vertex.position += self.world_origin;
+        }
+        Some(paths)
+    } // fn record_paths
+
+    /// Draw recorded paths over image until cleared, replacing previously shown ones
+    pub fn set_path_overlay(&mut self, paths: &[RecordedPath]) {
+        self.path_overlay.set_paths(&self.context.device, paths, self.world_origin);
+    }
+
+    pub fn clear_path_overlay(&mut self) {
+        self.path_overlay.clear();
+    }
+
+    /// Read auxiliary image of last rendered frame
+    pub fn read_aov(&self, aov: Aov) -> AovImage {
+        if aov != Aov::Beauty {
+            return self.aovs.read(&self.context.device, &self.context.queue, aov);
+        }
+
+        // Last frame is accumulated into collector that is read by next one
+        let collector = &self.collectors[self.static_frame_index as usize & 1];
+        let (extent, pixels) = read_rgba_texture(&self.context.device, &self.context.queue, &collector.texture, collector.layer);
+        let data = pixels.into_iter().flat_map(|[r, g, b, count]| {
+            let scale = self.presented_exposure() / count.max(1.0);
+            [r * scale, g * scale, b * scale]
+        }).collect();
+
+        AovImage { extent, channel_count: 3, data }
+    } // fn read_aov
+
+    /// Read last rendered frame as it's presented, after denoiser, post-processing, tone mapping, film grain
+    /// and dithering, without outline and overlays. RGB is linear display value decoded from surface format.
+    /// Image is presented again to separate texture, so it stalls GPU.
+    pub fn read_presented(&mut self) -> AovImage {
+        let format = self.surface_configuration.format;
+        let texture = Target::create_texture(&self.context.device, &self.surface_configuration);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Last frame is accumulated into collector that is read by next one
+        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        self.present(&mut encoder, self.static_frame_index as usize & 1, &view);
+        self.context.queue.submit([encoder.finish()]);
+
+        let readback = TextureReadback::new(&self.context.device, &self.context.queue, &texture, 0);
+        readback.buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        self.context.device.poll(wgpu::Maintain::Wait);
+
+        // Unorm formats hold sRGB-encoded values, see `output_constants`
+        let decode = |code: u32, max_code: f32| {
+            let value = code as f32 / max_code;
+            if value <= 0.04045 { value / 12.92 } else { ((value + 0.055) / 1.055).powf(2.4) }
+        };
+        let data = readback.map_pixels(|bytes| match format {
+            wgpu::TextureFormat::Rgba16Float => [0, 2, 4].map(|offset| f16_to_f32(u16::from_ne_bytes([bytes[offset], bytes[offset + 1]]))),
+            wgpu::TextureFormat::Rgb10a2Unorm => {
+                let bits = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                [0, 10, 20].map(|shift| decode((bits >> shift) & 1023, 1023.0))
+            }
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => [2, 1, 0].map(|index| decode(bytes[index] as u32, 255.0)),
+            _ => [0, 1, 2].map(|index| decode(bytes[index] as u32, 255.0)),
+        }).into_iter().flatten().collect();
+
+        AovImage { extent: readback.extent, channel_count: 3, data }
+    } // fn read_presented
+
+    /// Resolution of rendered image
+    pub fn extent(&self) -> Ext2u {
+        Ext2u::new(self.surface_configuration.width, self.surface_configuration.height)
+    }
+
+    /// Read raw accumulated image of last rendered frame, rows are top to bottom.
+    /// RGB is radiance summed over accumulation passes without exposure, alpha is number of passes.
+    /// Device is polled while future is pending, so it doesn't depend on rendering of next frames.
+    /// Empty if buffer mapping fails.
+    pub async fn read_accumulator(&self) -> Vec<[f32; 4]> {
+        let collector = &self.collectors[self.static_frame_index as usize & 1];
+        let readback = TextureReadback::new(&self.context.device, &self.context.queue, &collector.texture, collector.layer);
+
+        let (sender, mut receiver) = futures::channel::oneshot::channel();
+        readback.buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| _ = sender.send(result));
+
+        let result = futures::future::poll_fn(|context| {
+            self.context.device.poll(wgpu::Maintain::Poll);
+            let poll = futures::FutureExt::poll_unpin(&mut receiver, context);
+            if poll.is_pending() {
+                context.waker().wake_by_ref();
+            }
+            poll
+        }).await;
+
+        match result {
+            Ok(Ok(())) => readback.pixels(),
+            _ => Vec::new(),
+        }
+    } // fn read_accumulator
+
+    /// Number of passes that trace every pixel once
+    fn tile_count(&self, extent: Ext2u) -> u32 {
+        match self.settings.tile_size {
+            0 => 1,
+            tile_size => extent.w.div_ceil(tile_size) * extent.h.div_ceil(tile_size),
+        }
+    }
+
+    /// Samples per pixel accumulated since last restart or motion
+    pub fn progress(&self) -> RenderProgress {
+        RenderProgress {
+            sample_count: self.still_pass_count / self.tile_count(self.extent()) * SAMPLES_PER_PASS,
+            target_sample_count: match (self.settings.target_sample_count, self.max_sample_count) {
+                (target, Some(max)) if target == 0 || target > max => max,
+                (target, _) => target,
+            },
+            convergence: self.convergence.value(),
+            target_error: self.settings.target_error,
+        }
+    }
+
+    /// Call `callback` once accumulation reaches target sample count
+    pub fn on_complete(&mut self, callback: impl FnOnce(RenderProgress) + 'static) {
+        self.completion_callbacks.push(Box::new(callback));
+    }
+
+    /// Future resolved once accumulation reaches target sample count, canceled if renderer is dropped first
+    pub fn completion(&mut self) -> futures::channel::oneshot::Receiver<RenderProgress> {
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        self.on_complete(move |progress| _ = sender.send(progress));
+        receiver
+    }
+
+    /// Linear scale of presented image, camera exposure with automatic one if it's enabled
+    fn presented_exposure(&self) -> f32 {
+        if self.settings.auto_exposure {
+            self.exposure * self.auto_exposure.scale()
+        } else {
+            self.exposure
+        }
+    }
+
+    /// Upload system data of accumulation pass with current frame index
+    fn write_system_data(&self, extent: Ext2u) {
+        let resolution = Ext2f::new(extent.w as f32, extent.h as f32);
+        let texel_size = Ext2f::new(1.0 / resolution.w, 1.0 / resolution.h);
+        let tile_count = self.tile_count(extent);
+
+        self.context.queue.write_buffer(&self.system_buffer, 0, as_bytes(&SystemData {
+            resolution,
+            texel_size,
+            time: std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).map(|v| {
+                (v.as_millis() & 0xFFFFFF) as f32 / 1000.0
+            }).unwrap_or(0.0),
+            static_frame_index: self.static_frame_index,
+            rr_start_depth: self.settings.rr_start_depth,
+            rr_threshold: self.settings.rr_threshold,
+            shutter_open: self.settings.shutter_open,
+            shutter_close: self.settings.shutter_close,
+            exposure: self.presented_exposure(),
+            sampler_kind: self.settings.sampler as u32,
+            max_indirect_radiance: self.settings.max_indirect_radiance,
+            min_indirect_roughness: self.settings.min_indirect_roughness,
+            adaptive_threshold: self.settings.adaptive_threshold,
+            heatmap: if self.settings.show_sample_count {
+                Heatmap::SampleCount
+            } else if self.settings.show_variance {
+                Heatmap::Variance
+            } else {
+                Heatmap::None
+            } as u32,
+            tile_size: self.settings.tile_size,
+            tile_count,
+            watertight_intersection: self.settings.watertight_intersection as u32,
+            ray_min_distance: self.tolerances.ray_min_distance,
+            intersection_tolerance: self.tolerances.intersection_tolerance,
+            film_grain: self.settings.film_grain,
+            dither: self.settings.dither as u32,
+            false_color: self.false_color,
+            ..Default::default()
+        }));
+    } // fn write_system_data
+
+    /// Trace one accumulation pass into next collector
+    fn accumulate(&self, encoder: &mut wgpu::CommandEncoder, timestamp_writes: Option<wgpu::RenderPassTimestampWrites>) {
         let read_collector = &self.collectors[self.static_frame_index as usize & 1];
         let target_collector = &self.collectors[(self.static_frame_index + 1) as usize & 1];
 
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: wgpu::StoreOp::Store,
-                },
-                resolve_target: None,
-                view: &target_collector.view,
-            })],
+            color_attachments: &[
+                Some(wgpu::RenderPassColorAttachment {
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    resolve_target: None,
+                    view: &target_collector.view,
+                }),
+                Some(wgpu::RenderPassColorAttachment {
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                    resolve_target: None,
+                    view: &target_collector.feature_view,
+                }),
+            ],
+            timestamp_writes,
             ..Default::default()
         });
 
         render_pass.set_pipeline(&self.render_pipeline);
         render_pass.set_bind_group(0, &self.render_bind_group, &[]);
         render_pass.set_bind_group(1, &read_collector.bind_group, &[]);
+        render_pass.set_bind_group(2, &self.scene_bind_group, &[]);
+        render_pass.set_bind_group(3, &target_collector.diffuse_bind_group, &[]);
         render_pass.draw(0..4, 0..1);
+    } // fn accumulate
+
+    /// GPU context of renderer, resources of other users of it can be exchanged with renderer
+    pub fn context(&self) -> &Arc<GpuContext> {
+        &self.context
+    }
+
+    pub fn adapter_name(&self) -> &str {
+        &self.adapter_info.name
+    }
+
+    pub fn adapter_info(&self) -> &wgpu::AdapterInfo {
+        &self.adapter_info
+    }
+
+    /// Adapter runs on CPU, e.g. llvmpipe or WARP
+    pub fn is_software(&self) -> bool {
+        self.adapter_info.device_type == wgpu::DeviceType::Cpu
+    }
+
+    /// Trace one accumulation pass without presenting and wait for it. Returns GPU time of the pass
+    /// in milliseconds if device supports timestamp queries.
+    pub fn trace_pass(&mut self) -> Option<f64> {
+        self.stop_motion();
+        let extent = self.extent();
+        self.write_system_data(extent);
 
-        drop(render_pass);
+        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        self.accumulate(&mut encoder, self.gpu_timer.as_ref().map(|timer| timer.timestamp_writes()));
+        if let Some(timer) = &self.gpu_timer {
+            timer.resolve(&mut encoder);
+        }
+        self.context.queue.submit([encoder.finish()]);
+        self.context.device.poll(wgpu::Maintain::Wait);
 
+        self.static_frame_index += 1;
+        self.still_pass_count += 1;
+
+        self.gpu_timer.as_ref().map(|timer| timer.read(&self.context.device))
+    }
+
+    /// Present collector image, tone mapped and converted to surface format
+    fn place(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, collector_bind_group: &wgpu::BindGroup, is_glare_enabled: bool) {
         let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
                 ops: wgpu::Operations {
@@ -385,21 +2078,261 @@ impl<'t> Render<'t> {
                     store: wgpu::StoreOp::Store,
                 },
                 resolve_target: None,
-                view: &image_view,
+                view: target,
             })],
             ..Default::default()
         });
 
         render_pass.set_pipeline(&self.place_pipeline);
         render_pass.set_bind_group(0, &self.render_bind_group, &[]);
-        render_pass.set_bind_group(1, &target_collector.bind_group, &[]);
+        render_pass.set_bind_group(1, collector_bind_group, &[]);
+        render_pass.set_bind_group(2, self.glare.output_bind_group(is_glare_enabled), &[]);
         render_pass.draw(0..4, 0..1);
+    }
 
-        drop(render_pass);
+    /// Passes of presentation enabled by current settings
+    fn presentation_passes(&self) -> PresentationPasses {
+        // Heatmaps read sample counts and moments from unfiltered collector
+        let is_heatmap_shown = self.settings.show_sample_count || self.settings.show_variance;
+        let is_denoiser_viewed = self.buffer_view.is_some_and(|view| view.buffer.is_denoiser());
+        PresentationPasses {
+            is_denoised: is_denoiser_viewed || self.settings.denoise && !is_heatmap_shown,
+            is_sharpened: self.settings.sharpen > 0.0 && !is_heatmap_shown,
+            is_post_processed: !self.post_processes.is_empty() && !is_heatmap_shown,
+            is_glare_enabled: self.settings.glare > 0.0 && !is_heatmap_shown,
+            is_lens_flare_enabled: self.lens_flare.is_some() && !is_heatmap_shown,
+        }
+    }
 
-        self.kernel.queue.submit([encoder.finish()]);
-        image.present();
+    /// Present collector through denoiser and post-processing chain, tone mapped and converted to surface format.
+    /// Buffer view replaces image if it's active.
+    fn present(&mut self, encoder: &mut wgpu::CommandEncoder, collector_index: usize, target: &wgpu::TextureView) {
+        let presented_collector = &self.collectors[collector_index];
+        let passes = self.presentation_passes();
 
-        self.static_frame_index += 1;
-    }
+        let denoised_bind_group = passes.is_denoised
+            .then(|| {
+                let albedo = self.settings.denoise_components.then(|| &self.aovs.views()[0]);
+                self.denoiser.render(&self.context.device, encoder, &self.collector_bind_group_layout, presented_collector, albedo)
+            });
+
+        if let Some(view) = &self.buffer_view {
+            let source = match view.buffer {
+                Buffer::CollectorA => &self.collectors[0].view,
+                Buffer::CollectorB => &self.collectors[1].view,
+                Buffer::FeaturesA => &self.collectors[0].feature_view,
+                Buffer::FeaturesB => &self.collectors[1].feature_view,
+                Buffer::AovAlbedoDepth => &self.aovs.views()[0],
+                Buffer::AovNormalMotion => &self.aovs.views()[1],
+                Buffer::DenoiserA => &self.denoiser.views()[0],
+                Buffer::DenoiserB => &self.denoiser.views()[1],
+            };
+            self.buffer_presenter.render(&self.context.device, &self.context.queue, encoder, source, target, view);
+        } else {
+            let mut collector_bind_group = denoised_bind_group.as_ref().unwrap_or(&presented_collector.bind_group);
+
+            if passes.is_sharpened {
+                self.sharpener.render(&self.context.queue, encoder, collector_bind_group, self.settings.sharpen);
+                collector_bind_group = self.sharpener.output_bind_group(collector_index);
+            }
+
+            if passes.is_post_processed {
+                for pass in &self.post_processes {
+                    pass.render(encoder, collector_bind_group);
+                    collector_bind_group = pass.output_bind_group(collector_index);
+                }
+            }
+
+            if passes.is_glare_enabled {
+                // Bokeh textures have no blades, glare of round aperture is used for them
+                let aperture = if self.camera_data.bokeh_texture == u32::MAX { (self.camera_data.aperture_blades, self.camera_data.aperture_rotation) } else { (0, 0.0) };
+                self.glare.render(&self.context.device, &self.context.queue, encoder, collector_bind_group, aperture, self.settings.glare);
+            }
+            self.place(encoder, target, collector_bind_group, passes.is_glare_enabled);
+
+            if let Some(lens_flare) = self.lens_flare.as_ref().filter(|_| passes.is_lens_flare_enabled) {
+                self.lens_flare_pass.render(&self.context.queue, encoder, collector_bind_group, target, self.presented_exposure(), lens_flare);
+            }
+        }
+    } // fn present
+
+    pub fn render(&mut self) {
+        // IDs change together with camera, scene or resolution, all but camera movement restart accumulation
+        let is_id_update_required = self.static_frame_index == 0 || self.is_camera_updated || self.resize_source.is_some();
+        // Motion vectors also change when camera stops
+        let is_aov_update_required = is_id_update_required || self.camera_data.is_moving();
+        self.stop_motion();
+
+        let (image, image_view) = match &self.target {
+            Target::Surface(surface) => {
+                let Ok(image) = surface.get_current_texture() else {
+                    return;
+                };
+                let image_view = image.texture.create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(image), image_view)
+            }
+            // Headless target has no image to present
+            Target::Texture(texture) => (None, texture.create_view(&wgpu::TextureViewDescriptor::default())),
+        };
+        let extent = self.extent();
+
+        let is_moving = self.camera_data.is_moving() || self.is_instances_moving();
+        self.convergence.poll(&self.context.device);
+        if self.settings.auto_exposure {
+            self.auto_exposure.poll(&self.context.device);
+            self.auto_exposure.adapt(&self.settings);
+        }
+        if let Some(duration) = self.watchdog.poll() {
+            self.slow_frame = Some(duration);
+        }
+        // Watchdog times GPU work from first submission of frame
+        let mut frame_start = None;
+        if is_moving || self.static_frame_index == 0 {
+            self.still_pass_count = 0;
+            self.convergence.reset();
+        }
+
+        // Accumulation restarted after resize has no use of old image
+        if let Some((source, projection_extent)) = self.resize_source.take().filter(|_| self.static_frame_index != 0) {
+            let scale = Vec2f::new(self.camera_data.projection_width / projection_extent.x, self.camera_data.projection_height / projection_extent.y);
+            let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            self.resampler.render(&self.context.queue, &mut encoder, &source, &self.collectors[self.static_frame_index as usize & 1], extent, scale);
+            frame_start.get_or_insert_with(std::time::Instant::now);
+            self.context.queue.submit([encoder.finish()]);
+        }
+
+        // Extra accumulation passes are traced without presentation. Moving scenes get single pass,
+        // as motion is defined between presented frames. Completed image is only presented.
+        let pass_count = if self.progress().is_complete() {
+            0
+        } else if is_moving {
+            1
+        } else {
+            self.settings.samples_per_frame.max(1)
+        };
+        for _ in 1..pass_count {
+            self.write_system_data(extent);
+            let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+            self.accumulate(&mut encoder, None);
+            frame_start.get_or_insert_with(std::time::Instant::now);
+            self.context.queue.submit([encoder.finish()]);
+            self.static_frame_index += 1;
+            self.still_pass_count += 1;
+        }
+
+        self.write_system_data(extent);
+
+        let mut encoder = self.context.device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+
+        let read_collector = &self.collectors[self.static_frame_index as usize & 1];
+        let target_collector = &self.collectors[(self.static_frame_index + 1) as usize & 1];
+
+        if is_id_update_required {
+            self.outline.render_ids(&mut encoder, &[&self.render_bind_group, &read_collector.bind_group, &self.scene_bind_group]);
+        }
+        if is_aov_update_required {
+            self.aovs.render(&mut encoder, &[&self.render_bind_group, &read_collector.bind_group, &self.scene_bind_group]);
+        }
+
+        let presented_index = if pass_count > 0 {
+            self.accumulate(&mut encoder, None);
+            if !is_moving {
+                self.convergence.measure(&mut encoder, &read_collector.bind_group, &target_collector.bind_group);
+            }
+            (self.static_frame_index + 1) as usize & 1
+        } else {
+            self.static_frame_index as usize & 1
+        };
+        if self.settings.auto_exposure {
+            self.auto_exposure.measure(&mut encoder, &self.collectors[presented_index].bind_group, extent);
+        }
+
+        self.present(&mut encoder, presented_index, &image_view);
+
+        self.outline.render(&mut encoder, &image_view);
+        self.path_overlay.render(&mut encoder, &image_view, &self.render_bind_group);
+        self.overlay.render(&self.context.device, &self.context.queue, &mut encoder, &image_view, extent);
+
+        let frame_start = *frame_start.get_or_insert_with(std::time::Instant::now);
+        self.context.queue.submit([encoder.finish()]);
+        self.convergence.submit();
+        self.auto_exposure.submit();
+        self.watchdog.submit(&self.context.queue, frame_start);
+        if let Some(image) = image {
+            image.present();
+        }
+
+        if pass_count > 0 {
+            self.static_frame_index += 1;
+            self.still_pass_count += 1;
+        }
+
+        let progress = self.progress();
+        if progress.is_complete() {
+            for callback in self.completion_callbacks.drain(..) {
+                callback(progress);
+            }
+        }
+    } // fn render
+
+    /// Passes of still frame presented by `render` with current settings, in encoding order.
+    /// Must be kept in sync with `render`.
+    pub fn frame_graph(&self) -> FrameGraph {
+        const SYSTEM_DATA: &str = "System data";
+        const SCENE: &str = "Scene";
+        const READ_COLLECTOR: &str = "Read collector";
+        const TARGET_COLLECTOR: &str = "Target collector";
+        const ALBEDO_DEPTH: &str = "AOV albedo and depth";
+        const NORMAL_MOTION: &str = "AOV normal and motion";
+        const INSTANCE_IDS: &str = "Instance IDs";
+        const SURFACE: &str = "Surface";
+
+        let presentation = self.presentation_passes();
+        let mut passes = vec![
+            FramePass::new("Resample", &["Collector before resize"], &[READ_COLLECTOR]).condition("after resize"),
+            FramePass::new("Instance IDs", &[SYSTEM_DATA, SCENE], &[INSTANCE_IDS]).condition("on camera, scene or size change"),
+            FramePass::new("AOVs", &[SYSTEM_DATA, SCENE], &[ALBEDO_DEPTH, NORMAL_MOTION]).condition("on camera change"),
+            FramePass::new(
+                format!("{} accumulation x{}", self.integrator.name(), self.settings.samples_per_frame.max(1)),
+                &[SYSTEM_DATA, SCENE, READ_COLLECTOR],
+                &[TARGET_COLLECTOR],
+            ).condition("until target is reached, single while moving"),
+            FramePass::new("Convergence", &[READ_COLLECTOR, TARGET_COLLECTOR], &["Convergence readback"]).condition("while still"),
+            FramePass::new("Auto exposure histogram", &[TARGET_COLLECTOR], &["Exposure histogram"]).enabled(self.settings.auto_exposure),
+        ];
+
+        let denoiser_reads: &[&str] = if self.settings.denoise_components { &[TARGET_COLLECTOR, ALBEDO_DEPTH] } else { &[TARGET_COLLECTOR] };
+        passes.push(FramePass::new("Denoiser", denoiser_reads, &["Denoised image"]).enabled(presentation.is_denoised));
+        let mut image = if presentation.is_denoised { "Denoised image".to_string() } else { TARGET_COLLECTOR.to_string() };
+
+        if let Some(view) = &self.buffer_view {
+            passes.push(FramePass::new("Buffer view", &[&format!("{:?}", view.buffer)], &[SURFACE]));
+        } else {
+            passes.push(FramePass::new("Sharpen", &[&image], &["Sharpened image"]).enabled(presentation.is_sharpened));
+            if presentation.is_sharpened {
+                image = "Sharpened image".to_string();
+            }
+
+            for pass in &self.post_processes {
+                let output = format!("{} image", pass.name);
+                passes.push(FramePass::new(pass.name.clone(), &[&image], &[&output]).enabled(presentation.is_post_processed));
+                if presentation.is_post_processed {
+                    image = output;
+                }
+            }
+
+            passes.push(FramePass::new("Glare", &[&image], &["Glare image"]).enabled(presentation.is_glare_enabled));
+            let place_reads: &[&str] = if presentation.is_glare_enabled { &[SYSTEM_DATA, &image, "Glare image"] } else { &[SYSTEM_DATA, &image] };
+            passes.push(FramePass::new("Tone mapping", place_reads, &[SURFACE]));
+            passes.push(FramePass::new("Lens flare", &[&image], &[SURFACE]).enabled(presentation.is_lens_flare_enabled));
+        }
+
+        passes.extend([
+            FramePass::new("Selection outline", &[INSTANCE_IDS], &[SURFACE]).condition("while anything is selected"),
+            FramePass::new("Path overlay", &[SYSTEM_DATA, "Recorded paths"], &[SURFACE]).condition("while paths are shown"),
+            FramePass::new("UI overlay", &[], &[SURFACE]),
+        ]);
+
+        FrameGraph { passes }
+    } // fn frame_graph
 }
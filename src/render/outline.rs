@@ -0,0 +1,388 @@
+use std::collections::BTreeSet;
+
+use crate::{math::{Ext2u, Vec2u, Vec3f}, theme::Theme};
+
+use super::{as_bytes, output_constants, shader_include, slice_as_bytes};
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct OutlineData {
+    color: Vec3f,
+    is_box_enabled: u32,
+    box_min: Vec2u,
+    box_max: Vec2u,
+    box_color: Vec3f,
+    /// Outline width in pixels
+    width: u32,
+}
+
+/// Outline width at unit theme scale, in pixels
+const OUTLINE_WIDTH: f32 = 2.0;
+
+/// Instance ID and primitive index of primary hit, see `fs_id`
+const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg32Uint;
+
+/// Selection outline overlay. Instance IDs of primary hits are rendered into separate ID buffer,
+/// which is then edge-detected on top of presented image, so accumulated image stays untouched.
+/// Same ID buffer is read back to pick instances and primitives under cursor or selection box.
+pub struct Outline {
+    id_pipeline: wgpu::RenderPipeline,
+    id_texture: wgpu::Texture,
+    id_view: wgpu::TextureView,
+
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    buffer: wgpu::Buffer,
+    /// Per-instance selection flags
+    selection_buffer: wgpu::Buffer,
+
+    data: OutlineData,
+    is_selection_empty: bool,
+}
+
+impl Outline {
+    fn create_id_texture(device: &wgpu::Device, extent: Ext2u) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: ID_FORMAT,
+            label: Some("ID buffer"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    fn create_selection_buffer(device: &wgpu::Device, flags: &[u32]) -> wgpu::Buffer {
+        // Zero-sized bindings are not allowed
+        let flags = if flags.is_empty() { &[0][..] } else { flags };
+        let bytes = slice_as_bytes(flags);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Selection SSBO"),
+            mapped_at_creation: true,
+            size: bytes.len() as u64,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        buffer.slice(..).get_mapped_range_mut().copy_from_slice(bytes);
+        buffer.unmap();
+        buffer
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        id_view: &wgpu::TextureView,
+        buffer: &wgpu::Buffer,
+        selection_buffer: &wgpu::Buffer
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(id_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: selection_buffer.as_entire_binding() },
+            ],
+            label: Some("Outline bind group"),
+            layout,
+        })
+    }
+
+    /// ID pass uses `fs_id` entry point of path tracing shader with the same pipeline layout
+    pub fn create_id_pipeline(device: &wgpu::Device, render_pipeline_layout: &wgpu::PipelineLayout, render_shader_module: &wgpu::ShaderModule) -> wgpu::RenderPipeline {
+        let constants = super::render_module_constants();
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("ID pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "fs_id",
+                module: render_shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: ID_FORMAT,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })]
+            }),
+            layout: Some(render_pipeline_layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "vs_main",
+                module: render_shader_module,
+            }
+        })
+    }
+
+    /// Replace ID pipeline, e.g. by one of reloaded shader
+    pub fn set_id_pipeline(&mut self, id_pipeline: wgpu::RenderPipeline) {
+        self.id_pipeline = id_pipeline;
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        render_pipeline_layout: &wgpu::PipelineLayout,
+        render_shader_module: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        extent: Ext2u
+    ) -> Self {
+        let id_pipeline = Self::create_id_pipeline(device, render_pipeline_layout, render_shader_module);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Outline bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Uint,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Outline Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shader_include::compose_embedded("outline.wgsl")))
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[&bind_group_layout],
+            ..Default::default()
+        });
+
+        let constants = output_constants(surface_format);
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Outline pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "fs_main",
+                module: &shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: surface_format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })]
+            }),
+            layout: Some(&pipeline_layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: &shader_module,
+            }
+        });
+
+        let theme = Theme::default();
+        let data = OutlineData {
+            color: theme.outline_color,
+            box_color: theme.box_color,
+            width: OUTLINE_WIDTH as u32,
+            ..Default::default()
+        };
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Outline UBO"),
+            mapped_at_creation: true,
+            size: std::mem::size_of::<OutlineData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        buffer.slice(..).get_mapped_range_mut().copy_from_slice(as_bytes(&data));
+        buffer.unmap();
+
+        let selection_buffer = Self::create_selection_buffer(device, &[]);
+        let id_texture = Self::create_id_texture(device, extent);
+        let id_view = id_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = Self::create_bind_group(device, &bind_group_layout, &id_view, &buffer, &selection_buffer);
+
+        Self {
+            id_pipeline,
+            id_texture,
+            id_view,
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            buffer,
+            selection_buffer,
+            data,
+            is_selection_empty: true,
+        }
+    } // fn new
+
+    pub fn resize(&mut self, device: &wgpu::Device, extent: Ext2u) {
+        self.id_texture = Self::create_id_texture(device, extent);
+        self.id_view = self.id_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.id_view, &self.buffer, &self.selection_buffer);
+    }
+
+    /// Set selected scene instance indices
+    pub fn set_selection(&mut self, device: &wgpu::Device, selection: &BTreeSet<u32>) {
+        let mut flags = vec![0u32; selection.last().map(|index| *index as usize + 1).unwrap_or(0)];
+        for index in selection {
+            flags[*index as usize] = 1;
+        }
+
+        self.is_selection_empty = selection.is_empty();
+        self.selection_buffer = Self::create_selection_buffer(device, &flags);
+        self.bind_group = Self::create_bind_group(device, &self.bind_group_layout, &self.id_view, &self.buffer, &self.selection_buffer);
+    }
+
+    /// Set outline and selection box colors and outline width
+    pub fn set_theme(&mut self, queue: &wgpu::Queue, theme: &Theme) {
+        self.data.color = theme.outline_color;
+        self.data.box_color = theme.box_color;
+        self.data.width = (OUTLINE_WIDTH * theme.font_scale).round().max(1.0) as u32;
+        queue.write_buffer(&self.buffer, 0, as_bytes(&self.data));
+    }
+
+    /// Set selection box corners (inclusive, in pixels) to draw
+    pub fn set_selection_box(&mut self, queue: &wgpu::Queue, selection_box: Option<(Vec2u, Vec2u)>) {
+        let (box_min, box_max) = selection_box.unwrap_or_default();
+
+        self.data.is_box_enabled = selection_box.is_some() as u32;
+        self.data.box_min = box_min;
+        self.data.box_max = box_max;
+        queue.write_buffer(&self.buffer, 0, as_bytes(&self.data));
+    }
+
+    /// Read ID buffer rectangle (corners are inclusive), pixels are instance ID and primitive index + 1
+    pub fn read_id_pixels(&self, device: &wgpu::Device, queue: &wgpu::Queue, min: Vec2u, max: Vec2u) -> Vec<[u32; 2]> {
+        const PIXEL_SIZE: u32 = 8;
+
+        let size = self.id_texture.size();
+        if size.width == 0 || size.height == 0 || min.x >= size.width || min.y >= size.height {
+            return Vec::new();
+        }
+        let max = Vec2u::new(max.x.min(size.width - 1), max.y.min(size.height - 1));
+        let extent = Ext2u::new(max.x - min.x + 1, max.y - min.y + 1);
+
+        let bytes_per_row = (extent.w * PIXEL_SIZE).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ID readback buffer"),
+            mapped_at_creation: false,
+            size: (bytes_per_row * extent.h) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: min.x, y: min.y, z: 0 },
+                texture: &self.id_texture,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+        );
+        queue.submit([encoder.finish()]);
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+
+        let data = slice.get_mapped_range();
+        data
+            .chunks(bytes_per_row as usize)
+            .flat_map(|row| row[..(extent.w * PIXEL_SIZE) as usize].chunks(PIXEL_SIZE as usize))
+            .map(|bytes| [0, 4].map(|offset| u32::from_ne_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])))
+            .collect()
+    } // fn read_id_pixels
+
+    /// Read instance indices present in ID buffer rectangle (corners are inclusive)
+    pub fn read_ids(&self, device: &wgpu::Device, queue: &wgpu::Queue, min: Vec2u, max: Vec2u) -> BTreeSet<u32> {
+        self.read_id_pixels(device, queue, min, max)
+            .into_iter()
+            // ID buffer stores instance index + 1, zero is reserved for 'no instance'
+            .filter_map(|[id, _]| id.checked_sub(1))
+            .collect()
+    }
+
+    /// Render instance IDs, path tracing bind groups are expected in pipeline layout order
+    pub fn render_ids(&self, encoder: &mut wgpu::CommandEncoder, bind_groups: &[&wgpu::BindGroup]) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: &self.id_view,
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.id_pipeline);
+        for (index, bind_group) in bind_groups.iter().enumerate() {
+            render_pass.set_bind_group(index as u32, bind_group, &[]);
+        }
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Draw outline and selection box over target if there is any
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        if self.is_selection_empty && self.data.is_box_enabled == 0 {
+            return;
+        }
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: target,
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::math::Ext2u;
+
+/// Tessellated egui output of single frame
+pub struct OverlayFrame {
+    pub primitives: Vec<egui::ClippedPrimitive>,
+    pub textures_delta: egui::TexturesDelta,
+    pub pixels_per_point: f32,
+}
+
+/// egui pass, drawn over presented image after place and outline passes, so accumulated image stays untouched
+pub struct Overlay {
+    renderer: egui_wgpu::Renderer,
+    primitives: Vec<egui::ClippedPrimitive>,
+    /// Texture changes not uploaded yet, they pile up while frames aren't presented
+    textures_delta: egui::TexturesDelta,
+    pixels_per_point: f32,
+}
+
+impl Overlay {
+    pub fn new(device: &wgpu::Device, target_format: wgpu::TextureFormat) -> Self {
+        Self {
+            renderer: egui_wgpu::Renderer::new(device, target_format, None, 1),
+            primitives: Vec::new(),
+            textures_delta: egui::TexturesDelta::default(),
+            pixels_per_point: 1.0,
+        }
+    }
+
+    /// Set frame drawn by following renders
+    pub fn set_frame(&mut self, frame: OverlayFrame) {
+        self.primitives = frame.primitives;
+        self.textures_delta.append(frame.textures_delta);
+        self.pixels_per_point = frame.pixels_per_point;
+    }
+
+    pub fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, extent: Ext2u) {
+        let textures_delta = std::mem::take(&mut self.textures_delta);
+        for (id, image_delta) in &textures_delta.set {
+            self.renderer.update_texture(device, queue, *id, image_delta);
+        }
+
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [extent.w, extent.h],
+            pixels_per_point: self.pixels_per_point,
+        };
+        // Returned command buffers come from paint callbacks, overlay doesn't use them
+        self.renderer.update_buffers(device, queue, encoder, &self.primitives, &screen_descriptor);
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Overlay pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: target,
+            })],
+            ..Default::default()
+        });
+        self.renderer.render(&mut render_pass, &self.primitives, &screen_descriptor);
+        drop(render_pass);
+
+        for id in &textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+    } // fn render
+}
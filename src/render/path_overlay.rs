@@ -0,0 +1,170 @@
+use crate::math::{Vec3d, Vec3f};
+
+use super::{output_constants, path_record::{self, PathEvent, RecordedPath}, shader_include, slice_as_bytes};
+
+/// Pieces every path segment is split into, so segments bend along nonlinear projections
+const SEGMENT_SUBDIVISION: usize = 16;
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct LineVertex {
+    position: Vec3f,
+    /// Other end of line, see path_overlay.wgsl
+    other_position: Vec3f,
+    color: Vec3f,
+}
+
+/// Color of path segment by event at its start
+fn event_color(event: PathEvent) -> Vec3f {
+    match event {
+        PathEvent::Camera => Vec3f::new(1.0, 1.0, 1.0),
+        PathEvent::Diffuse => Vec3f::new(1.0, 0.35, 0.05),
+        PathEvent::Reflection => Vec3f::new(0.1, 0.8, 1.0),
+        PathEvent::Transmission => Vec3f::new(0.2, 1.0, 0.2),
+        PathEvent::Absorbed => Vec3f::new(1.0, 0.1, 0.1),
+        PathEvent::Escaped => Vec3f::new(1.0, 0.9, 0.1),
+    }
+}
+
+/// Recorded paths drawn as lines over presented image, colored by scattering event of segment start
+pub struct PathOverlay {
+    pipeline: wgpu::RenderPipeline,
+    /// World-space segment ends and colors, uploaded relative to world origin
+    segments: Vec<(Vec3d, Vec3d, Vec3f)>,
+    vertex_buffer: Option<wgpu::Buffer>,
+    vertex_count: u32,
+}
+
+impl PathOverlay {
+    /// Camera is read from render bind group
+    pub fn new(device: &wgpu::Device, render_bind_group_layout: &wgpu::BindGroupLayout, surface_format: wgpu::TextureFormat) -> Self {
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Path Overlay Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Owned(shader_include::compose_embedded("path_overlay.wgsl")))
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[render_bind_group_layout],
+            ..Default::default()
+        });
+
+        let constants = output_constants(surface_format);
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Path overlay pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+                entry_point: "fs_main",
+                module: &shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: surface_format,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })]
+            }),
+            layout: Some(&layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<LineVertex>() as u64,
+                    step_mode: wgpu::VertexStepMode::Vertex,
+                    attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x3],
+                }],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: &shader_module,
+            }
+        });
+
+        Self {
+            pipeline,
+            segments: Vec::new(),
+            vertex_buffer: None,
+            vertex_count: 0,
+        }
+    } // fn new
+
+    /// Show `paths`, previously shown ones are replaced
+    pub fn set_paths(&mut self, device: &wgpu::Device, paths: &[RecordedPath], world_origin: Vec3d) {
+        let escape_length = path_record::escape_length(paths);
+
+        self.segments.clear();
+        for path in paths {
+            let points = path_record::path_points(path, escape_length);
+            for (ends, vertex) in points.windows(2).zip(&path.vertices) {
+                let color = event_color(vertex.event);
+                let step = (ends[1] - ends[0]) * (1.0 / SEGMENT_SUBDIVISION as f64);
+                self.segments.extend((0..SEGMENT_SUBDIVISION).map(|index| {
+                    let start = ends[0] + step * index as f64;
+                    (start, start + step, color)
+                }));
+            }
+        }
+
+        self.upload(device, world_origin);
+    }
+
+    pub fn clear(&mut self) {
+        self.segments.clear();
+        self.vertex_buffer = None;
+        self.vertex_count = 0;
+    }
+
+    /// Upload segments relative to `world_origin`, done on its change
+    pub fn upload(&mut self, device: &wgpu::Device, world_origin: Vec3d) {
+        if self.segments.is_empty() {
+            return;
+        }
+
+        let vertices = self.segments.iter().flat_map(|&(start, end, color)| {
+            let start = (start - world_origin).to_f32();
+            let end = (end - world_origin).to_f32();
+            [
+                LineVertex { position: start, other_position: end, color },
+                LineVertex { position: end, other_position: start, color },
+            ]
+        }).collect::<Vec<_>>();
+        let bytes = slice_as_bytes(&vertices);
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Path overlay VBO"),
+            mapped_at_creation: true,
+            size: bytes.len() as u64,
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        buffer.slice(..).get_mapped_range_mut().copy_from_slice(bytes);
+        buffer.unmap();
+
+        self.vertex_buffer = Some(buffer);
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    /// Draw paths over target if there are any
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView, render_bind_group: &wgpu::BindGroup) {
+        let Some(vertex_buffer) = self.vertex_buffer.as_ref() else {
+            return;
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: target,
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, render_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.draw(0..self.vertex_count, 0..1);
+    }
+}
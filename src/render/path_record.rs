@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use crate::math::{Vec2u, Vec3d, Vec3f};
+
+use super::as_bytes;
+
+/// Vertices of recorded path beyond this number are dropped, matches render.wgsl
+const MAX_RECORDED_VERTICES: usize = 32;
+
+/// Paths per workgroup of recording pass, matches render.wgsl
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Scattering event at path vertex
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub enum PathEvent {
+    /// Path start on camera lens
+    Camera,
+    /// Scattering by diffuse lobe
+    Diffuse,
+    /// Specular or glossy reflection
+    Reflection,
+    Transmission,
+    /// Surface hit without scattering
+    Absorbed,
+    /// Ray left scene, vertex is its origin
+    Escaped,
+}
+
+impl PathEvent {
+    /// Event of PATH_EVENT_* constant of render.wgsl
+    fn from_index(index: u32) -> Self {
+        match index {
+            0 => PathEvent::Camera,
+            1 => PathEvent::Diffuse,
+            2 => PathEvent::Reflection,
+            3 => PathEvent::Transmission,
+            4 => PathEvent::Absorbed,
+            _ => PathEvent::Escaped,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, serde::Serialize)]
+pub struct PathVertex {
+    /// World-space position
+    pub position: Vec3d,
+    pub event: PathEvent,
+    /// Direction of ray leaving vertex
+    pub direction: Vec3f,
+    /// Path throughput after vertex
+    pub throughput: Vec3f,
+    /// Radiance gathered by path up to and including vertex
+    pub radiance: Vec3f,
+}
+
+/// Path traced through pixel with vertices reported by integrator
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct RecordedPath {
+    /// Radiance path contributes to pixel
+    pub radiance: Vec3f,
+    pub vertices: Vec<PathVertex>,
+}
+
+/// Write paths as JSON array
+pub fn write_paths_json(paths: &[RecordedPath], writer: impl std::io::Write) -> std::io::Result<()> {
+    serde_json::to_writer_pretty(writer, paths).map_err(std::io::Error::other)
+}
+
+/// Length escaped rays are drawn with, the longest distance of path vertex from camera
+pub fn escape_length(paths: &[RecordedPath]) -> f64 {
+    paths.iter()
+        .filter_map(|path| {
+            let start = path.vertices.first()?.position;
+            path.vertices.iter().map(|vertex| (vertex.position - start).length()).reduce(f64::max)
+        })
+        .fold(0.0, f64::max)
+        .max(1.0)
+}
+
+/// Polyline of path vertex positions, escaped ray is ended `escape_length` away from its origin
+pub fn path_points(path: &RecordedPath, escape_length: f64) -> Vec<Vec3d> {
+    let mut points = path.vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>();
+    if let Some(last) = path.vertices.last().filter(|vertex| vertex.event == PathEvent::Escaped) {
+        points.push(last.position + last.direction.to_f64() * escape_length);
+    }
+    points
+}
+
+/// Write paths as OBJ polylines, one object per path. Escaped rays are drawn with `escape_length`.
+pub fn write_paths_obj(paths: &[RecordedPath], mut writer: impl std::io::Write) -> std::io::Result<()> {
+    let escape_length = escape_length(paths);
+    let mut vertex_count = 0;
+
+    for (index, path) in paths.iter().enumerate() {
+        writeln!(writer, "o path_{}", index)?;
+        let points = path_points(path, escape_length);
+
+        for point in &points {
+            writeln!(writer, "v {} {} {}", point.x, point.y, point.z)?;
+        }
+        if points.len() >= 2 {
+            let indices = (vertex_count + 1..=vertex_count + points.len()).map(|index| index.to_string()).collect::<Vec<_>>();
+            writeln!(writer, "l {}", indices.join(" "))?;
+        }
+        vertex_count += points.len();
+    }
+    Ok(())
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct RecordParams {
+    pixel: Vec2u,
+    path_count: u32,
+    seed: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct PathVertexData {
+    position: Vec3f,
+    event: u32,
+    direction: Vec3f,
+    _pad0: u32,
+    throughput: Vec3f,
+    _pad1: u32,
+    radiance: Vec3f,
+    _pad2: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct RecordedPathData {
+    radiance: Vec3f,
+    vertex_count: u32,
+    vertices: [PathVertexData; MAX_RECORDED_VERTICES],
+}
+
+/// Traces paths through single pixel by main shader module with vertex recording enabled
+pub struct PathRecorder {
+    bind_group_layout: wgpu::BindGroupLayout,
+    pipeline_layout: wgpu::PipelineLayout,
+    params_buffer: wgpu::Buffer,
+    /// Recordings made, so repeated recordings of pixel trace different paths
+    seed: u32,
+}
+
+impl PathRecorder {
+    /// Layouts are ones of render pipeline, record bind group is bound after them
+    pub fn new(
+        device: &wgpu::Device,
+        render_bind_group_layout: &wgpu::BindGroupLayout,
+        collector_bind_group_layout: &wgpu::BindGroupLayout,
+        scene_bind_group_layout: &wgpu::BindGroupLayout
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Path record bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                    },
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[render_bind_group_layout, collector_bind_group_layout, scene_bind_group_layout, &bind_group_layout],
+            ..Default::default()
+        });
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Path record params UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<RecordParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        Self { bind_group_layout, pipeline_layout, params_buffer, seed: 0 }
+    } // fn new
+
+    /// Recording pipeline of main shader module specialized by main pipeline `constants`
+    pub fn create_pipeline(&self, device: &wgpu::Device, shader_module: &wgpu::ShaderModule, constants: &HashMap<String, f64>) -> wgpu::ComputePipeline {
+        let mut constants = constants.clone();
+        constants.insert("RECORD_PATH".to_string(), 1.0);
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Path record pipeline"),
+            layout: Some(&self.pipeline_layout),
+            module: shader_module,
+            entry_point: "cs_record_paths",
+            compilation_options: wgpu::PipelineCompilationOptions { constants: &constants, ..Default::default() },
+        })
+    }
+
+    /// Trace `path_count` paths through `pixel` by `pipeline` and read them back. GPU is waited for,
+    /// so it's meant for debugging. Positions are relative to world origin.
+    pub fn record(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        pipeline: &wgpu::ComputePipeline,
+        bind_groups: [&wgpu::BindGroup; 3],
+        pixel: Vec2u,
+        path_count: u32
+    ) -> Vec<RecordedPath> {
+        let size = path_count as u64 * std::mem::size_of::<RecordedPathData>() as u64;
+        let path_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Recorded path buffer"),
+            mapped_at_creation: false,
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Recorded path readback buffer"),
+            mapped_at_creation: false,
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 1, resource: path_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: self.params_buffer.as_entire_binding() },
+            ],
+            label: Some("Path record bind group"),
+            layout: &self.bind_group_layout,
+        });
+
+        self.seed = self.seed.wrapping_add(1);
+        queue.write_buffer(&self.params_buffer, 0, as_bytes(&RecordParams { pixel, path_count, seed: self.seed }));
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Path record encoder") });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Path record pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(pipeline);
+            for (index, bind_group) in bind_groups.into_iter().enumerate() {
+                compute_pass.set_bind_group(index as u32, bind_group, &[]);
+            }
+            compute_pass.set_bind_group(3, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(path_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&path_buffer, 0, &readback_buffer, 0, size);
+        queue.submit([encoder.finish()]);
+
+        readback_buffer.slice(..).map_async(wgpu::MapMode::Read, |_| {});
+        device.poll(wgpu::Maintain::Wait);
+
+        let mut paths = vec![RecordedPathData::default(); path_count as usize];
+        {
+            let mapped = readback_buffer.slice(..).get_mapped_range();
+            // Buffer is copied into properly aligned values
+            unsafe { std::ptr::copy_nonoverlapping(mapped.as_ptr(), paths.as_mut_ptr().cast::<u8>(), mapped.len()) };
+        }
+        readback_buffer.unmap();
+
+        paths.iter().map(|path| RecordedPath {
+            radiance: path.radiance,
+            vertices: path.vertices[..(path.vertex_count as usize).min(MAX_RECORDED_VERTICES)].iter().map(|vertex| PathVertex {
+                position: vertex.position.to_f64(),
+                event: PathEvent::from_index(vertex.event),
+                direction: vertex.direction,
+                throughput: vertex.throughput,
+                radiance: vertex.radiance,
+            }).collect(),
+        }).collect()
+    } // fn record
+}
@@ -0,0 +1,312 @@
+use std::path::{Path, PathBuf};
+
+use crate::{locale::tr_args, math::Ext2u};
+
+use wgpu::naga;
+
+use super::{shader_include, Collector, Render};
+
+/// Processed mean radiance with unit sample count
+const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+/// Extension of plugin descriptor files
+const PLUGIN_EXTENSION: &str = "ron";
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Plugin file contents
+#[derive(serde::Deserialize)]
+struct PluginDescriptor {
+    name: String,
+    /// Shader file, relative to plugin file
+    shader: String,
+    /// Uniform block field names and values
+    #[serde(default)]
+    parameters: Vec<(String, f32)>,
+    #[serde(default = "default_enabled")]
+    enabled: bool,
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, String),
+    /// Plugin path and shader composition or parsing error
+    Shader(PathBuf, String),
+    /// Plugin path and parameter name missing among uniform block fields
+    UnknownParameter(PathBuf, String),
+    /// Plugin path and uniform block field name, field isn't `f32` or has no parameter value
+    InvalidField(PathBuf, String),
+}
+
+impl std::fmt::Display for PluginError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let message = match self {
+            PluginError::Io(path, error) => format!("{}: {}", path.display(), error),
+            PluginError::Parse(path, error) => format!("{}: {}", path.display(), error),
+            PluginError::Shader(path, error) => tr_args("error.plugin_shader", &[&path.display(), error]),
+            PluginError::UnknownParameter(path, name) => tr_args("error.plugin_unknown_parameter", &[&path.display(), name]),
+            PluginError::InvalidField(path, name) => tr_args("error.plugin_field", &[&path.display(), name]),
+        };
+        f.write_str(&message)
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Post-process pass described by plugin file. Passes run after accumulation and denoising, each one
+/// reads mean radiance written by previous one, and the last one is presented.
+#[derive(Clone, Debug)]
+pub struct PostProcessPlugin {
+    pub name: String,
+    /// Plugin file
+    pub path: PathBuf,
+    /// WGSL source with `post_process.wgsl` prelude and included files
+    pub source: String,
+    /// Uniform block field names and values
+    pub parameters: Vec<(String, f32)>,
+    /// Uniform block contents, parameters are placed at offsets of fields of the same name.
+    /// Empty if shader has no uniform block.
+    parameter_bytes: Vec<u8>,
+}
+
+/// Uniform block of `source` with `parameters` written to its fields
+fn layout_parameters(path: &Path, source: &str, parameters: &[(String, f32)]) -> Result<Vec<u8>, PluginError> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|error| PluginError::Shader(path.to_path_buf(), error.emit_to_string(source)))?;
+    let block = module.global_variables.iter()
+        .map(|(_, variable)| variable)
+        .find(|variable| variable.space == naga::AddressSpace::Uniform && variable.binding == Some(naga::ResourceBinding { group: 1, binding: 0 }));
+
+    let Some(block) = block else {
+        return match parameters.first() {
+            Some((name, _)) => Err(PluginError::UnknownParameter(path.to_path_buf(), name.clone())),
+            None => Ok(Vec::new()),
+        };
+    };
+    let naga::TypeInner::Struct { members, span } = &module.types[block.ty].inner else {
+        return Err(PluginError::InvalidField(path.to_path_buf(), block.name.clone().unwrap_or_default()));
+    };
+
+    if let Some((name, _)) = parameters.iter().find(|(name, _)| !members.iter().any(|member| member.name.as_ref() == Some(name))) {
+        return Err(PluginError::UnknownParameter(path.to_path_buf(), name.clone()));
+    }
+
+    let mut bytes = vec![0; *span as usize];
+    for member in members {
+        let name = member.name.clone().unwrap_or_default();
+        let value = parameters.iter().find(|(parameter, _)| *parameter == name).map(|(_, value)| *value);
+        let (naga::TypeInner::Scalar(naga::Scalar::F32), Some(value)) = (&module.types[member.ty].inner, value) else {
+            return Err(PluginError::InvalidField(path.to_path_buf(), name));
+        };
+        let offset = member.offset as usize;
+        bytes[offset..offset + 4].copy_from_slice(&value.to_ne_bytes());
+    }
+    Ok(bytes)
+} // fn layout_parameters
+
+impl PostProcessPlugin {
+    /// Load plugin file, None if plugin is disabled. Shader `#include` directives are resolved among files
+    /// of plugin directory, then among embedded shaders.
+    pub fn load(path: &Path) -> Result<Option<Self>, PluginError> {
+        let text = std::fs::read_to_string(path).map_err(|error| PluginError::Io(path.to_path_buf(), error))?;
+        let descriptor = ron::from_str::<PluginDescriptor>(&text).map_err(|error| PluginError::Parse(path.to_path_buf(), error.to_string()))?;
+        if !descriptor.enabled {
+            return Ok(None);
+        }
+
+        let directory = path.parent().unwrap_or(Path::new("."));
+        let shader = shader_include::compose(&descriptor.shader, &mut |name| {
+            std::fs::read_to_string(directory.join(name)).ok().or_else(|| shader_include::load_embedded(name))
+        }).map_err(|error| PluginError::Shader(path.to_path_buf(), error))?;
+
+        let source = format!("{}\n{}", include_str!("shaders/post_process.wgsl"), shader);
+        let parameter_bytes = layout_parameters(path, &source, &descriptor.parameters)?;
+
+        Ok(Some(Self {
+            name: descriptor.name,
+            path: path.to_path_buf(),
+            source,
+            parameters: descriptor.parameters,
+            parameter_bytes,
+        }))
+    }
+
+    /// Enabled plugins of directory in file name order, so passes are ordered by prefixing names.
+    /// Missing directory has no plugins.
+    pub fn load_directory(directory: &Path) -> Vec<Result<Self, PluginError>> {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return Vec::new();
+        };
+        let mut paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == PLUGIN_EXTENSION))
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        paths.iter().filter_map(|path| Self::load(path).transpose()).collect()
+    }
+}
+
+/// Compiled pass of post-process plugin
+pub struct PostProcessPass {
+    /// Plugin name
+    pub name: String,
+    pipeline: wgpu::RenderPipeline,
+    parameter_bind_group: wgpu::BindGroup,
+
+    view: wgpu::TextureView,
+    /// Output bind groups with features of each collector
+    output_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl PostProcessPass {
+    fn create_view(device: &wgpu::Device, extent: Ext2u) -> wgpu::TextureView {
+        device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: IMAGE_FORMAT,
+            label: Some("Post-processed image"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_output_bind_groups(
+        device: &wgpu::Device,
+        collector_bind_group_layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        collectors: &[Collector; 2]
+    ) -> [wgpu::BindGroup; 2] {
+        collectors.each_ref().map(|collector| {
+            Render::create_collector_bind_group(device, collector_bind_group_layout, view, &collector.feature_view, &collector.diffuse_view)
+        })
+    }
+
+    /// Pass of `plugin` with pipeline built of `shader_module` composed of its source.
+    /// Collector bind group layout is used to read presented image.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        collector_bind_group_layout: &wgpu::BindGroupLayout,
+        plugin: &PostProcessPlugin,
+        shader_module: &wgpu::ShaderModule,
+        collectors: &[Collector; 2],
+        extent: Ext2u
+    ) -> Self {
+        let parameter_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post-process parameter bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: std::num::NonZeroU64::new(plugin.parameter_bytes.len() as u64),
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        // Buffer is kept for shaders without uniform block, so bind group layout stays the same
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Post-process parameter UBO"),
+            mapped_at_creation: false,
+            size: (plugin.parameter_bytes.len() as u64).max(1).next_multiple_of(16),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        queue.write_buffer(&buffer, 0, &plugin.parameter_bytes);
+
+        let parameter_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() },
+            ],
+            label: Some("Post-process parameter bind group"),
+            layout: &parameter_bind_group_layout,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[collector_bind_group_layout, &parameter_bind_group_layout],
+            ..Default::default()
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Post-process pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "fs_main",
+                module: shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: IMAGE_FORMAT,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })]
+            }),
+            layout: Some(&layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: shader_module,
+            }
+        });
+
+        let view = Self::create_view(device, extent);
+        Self {
+            name: plugin.name.clone(),
+            pipeline,
+            parameter_bind_group,
+            output_bind_groups: Self::create_output_bind_groups(device, collector_bind_group_layout, &view, collectors),
+            view,
+        }
+    } // fn new
+
+    /// Resize output image, `collectors` are ones of new extent
+    pub fn resize(&mut self, device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, collectors: &[Collector; 2], extent: Ext2u) {
+        self.view = Self::create_view(device, extent);
+        self.bind_collectors(device, collector_bind_group_layout, collectors);
+    }
+
+    /// Rebuild output bind groups of recreated collectors
+    pub fn bind_collectors(&mut self, device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, collectors: &[Collector; 2]) {
+        self.output_bind_groups = Self::create_output_bind_groups(device, collector_bind_group_layout, &self.view, collectors);
+    }
+
+    /// Process image of collector bind group
+    pub fn render(&self, encoder: &mut wgpu::CommandEncoder, collector_bind_group: &wgpu::BindGroup) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Post-process pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: &self.view,
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, collector_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.parameter_bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Bind group of last `render` result with collector layout, features are taken from collector of `collector_index`
+    pub fn output_bind_group(&self, collector_index: usize) -> &wgpu::BindGroup {
+        &self.output_bind_groups[collector_index]
+    }
+}
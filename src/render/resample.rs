@@ -0,0 +1,134 @@
+use crate::math::{Ext2u, Vec2f};
+
+use super::{as_bytes, Collector};
+
+/// Weight of resampled history, so blur of rescaling fades quickly under new samples
+pub const HISTORY_WEIGHT: f32 = 0.5;
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct ResampleData {
+    /// Ratio of new projection extent to old one
+    scale: Vec2f,
+    target_size: Vec2f,
+    weight: f32,
+    _pad0: f32,
+}
+
+/// Accumulated image kept over resize: old collector is rescaled into new one by bilinear filter,
+/// and accumulation continues from it with reduced weight instead of restarting. Diffuse radiance is rescaled too.
+pub struct Resampler {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Resampler {
+    /// Collectors have radiance of `collector_format`, features are always full precision
+    pub fn new(
+        device: &wgpu::Device,
+        collector_bind_group_layout: &wgpu::BindGroupLayout,
+        diffuse_bind_group_layout: &wgpu::BindGroupLayout,
+        collector_format: wgpu::TextureFormat
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Resample bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Resample UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<ResampleData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() }],
+            label: Some("Resample bind group"),
+            layout: &bind_group_layout,
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Resample Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/resample.wgsl")))
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[collector_bind_group_layout, &bind_group_layout, diffuse_bind_group_layout],
+            ..Default::default()
+        });
+
+        let target = |format| Some(wgpu::ColorTargetState {
+            blend: None,
+            format,
+            write_mask: wgpu::ColorWrites::ALL,
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Resample pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "fs_main",
+                module: &shader_module,
+                targets: &[target(collector_format), target(wgpu::TextureFormat::Rgba32Float)],
+            }),
+            layout: Some(&layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: &shader_module,
+            }
+        });
+
+        Self { pipeline, buffer, bind_group }
+    } // fn new
+
+    /// Rescale `source` contents into `target` of `target_extent`. `scale` is ratio of new projection extent to old one.
+    pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, source: &Collector, target: &Collector, target_extent: Ext2u, scale: Vec2f) {
+        queue.write_buffer(&self.buffer, 0, as_bytes(&ResampleData {
+            scale,
+            target_size: Vec2f::new(target_extent.w as f32, target_extent.h as f32),
+            weight: HISTORY_WEIGHT,
+            ..Default::default()
+        }));
+
+        let attachment = |view| Some(wgpu::RenderPassColorAttachment {
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: wgpu::StoreOp::Store,
+            },
+            resolve_target: None,
+            view,
+        });
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Resample pass"),
+            color_attachments: &[attachment(&target.view), attachment(&target.feature_view)],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &source.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_bind_group(2, &target.diffuse_bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    } // fn render
+}
@@ -0,0 +1,760 @@
+use crate::{accel::{Aabb, Bvh, FlatBvhNode}, locale::tr_args, math::{Ext2u, Mat4f, Vec2f, Vec3d, Vec3f, Vec4f}, scene::{environment::luminance, Instance, Material, Mesh, Scene, Shape, Texture, TexturedMaterial, Triangle}};
+
+use super::{as_bytes, slice_as_bytes};
+
+/// Triangle with per-vertex shading normals. Flat-shaded triangles repeat geometric normal.
+/// Vertex colors are packed as sRGB RGBA8, white for meshes without them.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct TriangleData {
+    v0: Vec3f,
+    _pad0: u32,
+    v1: Vec3f,
+    _pad1: u32,
+    v2: Vec3f,
+    _pad2: u32,
+    n0: Vec3f,
+    _pad3: u32,
+    n1: Vec3f,
+    _pad4: u32,
+    n2: Vec3f,
+    _pad5: u32,
+    uv0: Vec2f,
+    uv1: Vec2f,
+    uv2: Vec2f,
+    c0: u32,
+    c1: u32,
+    c2: u32,
+    _pad6: [u32; 3],
+}
+
+/// Mesh instance. Leaf nodes of top-level BVH reference instances.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct InstanceData {
+    world_to_object: Mat4f,
+    /// Used to bring tangents back to world space
+    object_to_world: Mat4f,
+    /// Transform at previous frame, interpolated with current one for motion blur
+    previous_object_to_world: Mat4f,
+    /// Index of instance mesh BVH root in common node array
+    blas_root: u32,
+    material_index: u32,
+    /// Index of instance in scene, instances are reordered by top-level BVH build
+    id: u32,
+    /// Area sampling pdf of instance triangles if they are lights, zero otherwise
+    light_area_pdf: f32,
+    custom_data: Vec4f,
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct DecalData {
+    position: Vec3f,
+    texture_index: u32,
+    right: Vec3f,
+    opacity: f32,
+    up: Vec3f,
+    _pad0: f32,
+    forward: Vec3f,
+    _pad1: f32,
+}
+
+/// Analytic primitive. Meaning of points and radii depends on primitive kind.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct PrimitiveData {
+    p0: Vec3f,
+    r0: f32,
+    p1: Vec3f,
+    r1: f32,
+    kind: u32,
+    material_index: u32,
+    /// Area sampling pdf if primitive is a light, zero otherwise
+    light_area_pdf: f32,
+    _pad0: u32,
+}
+
+// Zero is reserved for empty primitive buffer placeholder
+const PRIMITIVE_KIND_SPHERE: u32 = 1;
+const PRIMITIVE_KIND_PLANE: u32 = 2;
+const PRIMITIVE_KIND_BOX: u32 = 3;
+const PRIMITIVE_KIND_CYLINDER: u32 = 4;
+const PRIMITIVE_KIND_CONE: u32 = 5;
+const PRIMITIVE_KIND_CAPSULE: u32 = 6;
+const PRIMITIVE_KIND_TORUS: u32 = 7;
+
+impl PrimitiveData {
+    fn from_shape(shape: &Shape) -> Self {
+        let zero = Vec3f::default();
+
+        let (kind, p0, r0, p1, r1) = match *shape {
+            Shape::Sphere { center, radius } => (PRIMITIVE_KIND_SPHERE, center, radius, zero, 0.0),
+            Shape::Plane { point, normal, extent } => (PRIMITIVE_KIND_PLANE, point, extent, normal.normalized(), 0.0),
+            Shape::Box { min, max } => (PRIMITIVE_KIND_BOX, min.min(max), 0.0, min.max(max), 0.0),
+            Shape::Cylinder { a, b, radius } => (PRIMITIVE_KIND_CYLINDER, a, radius, b, 0.0),
+            Shape::Cone { a, b, radius_a, radius_b } => (PRIMITIVE_KIND_CONE, a, radius_a, b, radius_b),
+            Shape::Capsule { a, b, radius } => (PRIMITIVE_KIND_CAPSULE, a, radius, b, 0.0),
+            Shape::Torus { center, axis, major_radius, minor_radius } => (PRIMITIVE_KIND_TORUS, center, major_radius, axis.normalized(), minor_radius),
+        };
+
+        Self { p0, r0, p1, r1, kind, ..Default::default() }
+    }
+
+    /// Primitive with points relative to `origin`, `p1` of other kinds is axis or unused
+    fn relative_to(&self, origin: Vec3d) -> Self {
+        let is_p1_point = matches!(self.kind, PRIMITIVE_KIND_BOX | PRIMITIVE_KIND_CYLINDER | PRIMITIVE_KIND_CONE | PRIMITIVE_KIND_CAPSULE);
+
+        Self {
+            p0: self.p0.relative_to(origin),
+            p1: if is_p1_point { self.p1.relative_to(origin) } else { self.p1 },
+            ..*self
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct MaterialData {
+    color: Vec3f,
+    kind: u32,
+    emission: Vec3f,
+    roughness: f32,
+    ior: f32,
+    metallic: f32,
+    specular: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    transmission: f32,
+    /// Texture array layers, `NO_TEXTURE` if map is not used
+    albedo_texture: u32,
+    roughness_texture: u32,
+    metallic_texture: u32,
+    normal_texture: u32,
+    /// Nonzero if color is multiplied by instance custom data
+    instance_tint: u32,
+    /// `Backface` variant index
+    backface: u32,
+}
+
+const NO_TEXTURE: u32 = u32::MAX;
+
+const MATERIAL_KIND_LAMBERTIAN: u32 = 0;
+const MATERIAL_KIND_METAL: u32 = 1;
+const MATERIAL_KIND_DIELECTRIC: u32 = 2;
+const MATERIAL_KIND_EMISSIVE: u32 = 3;
+const MATERIAL_KIND_PRINCIPLED: u32 = 4;
+
+impl MaterialData {
+    fn from_textured_material(textured_material: &TexturedMaterial) -> Self {
+        let maps = &textured_material.maps;
+
+        Self {
+            albedo_texture: maps.albedo.unwrap_or(NO_TEXTURE),
+            roughness_texture: maps.roughness.unwrap_or(NO_TEXTURE),
+            metallic_texture: maps.metallic.unwrap_or(NO_TEXTURE),
+            normal_texture: maps.normal.unwrap_or(NO_TEXTURE),
+            instance_tint: textured_material.instance_tint as u32,
+            backface: textured_material.backface as u32,
+            ..Self::from_material(&textured_material.material)
+        }
+    }
+
+    fn from_material(material: &Material) -> Self {
+        match *material {
+            Material::Lambertian { color } => Self { color, kind: MATERIAL_KIND_LAMBERTIAN, ..Default::default() },
+            Material::Metal { color, roughness } => Self { color, kind: MATERIAL_KIND_METAL, roughness, ..Default::default() },
+            Material::Dielectric { ior, color } => Self { color, kind: MATERIAL_KIND_DIELECTRIC, ior, ..Default::default() },
+            Material::Emissive { color, intensity } => Self { kind: MATERIAL_KIND_EMISSIVE, emission: color * intensity, ..Default::default() },
+            Material::Principled { base_color, metallic, roughness, specular, clearcoat, clearcoat_roughness, transmission, ior } => Self {
+                color: base_color,
+                kind: MATERIAL_KIND_PRINCIPLED,
+                roughness,
+                ior,
+                metallic,
+                specular,
+                clearcoat,
+                clearcoat_roughness,
+                transmission,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct EnvironmentData {
+    rotation: f32,
+    intensity: f32,
+    /// Converts pixel luminance to solid angle sampling pdf
+    pdf_normalization: f32,
+    /// Zero if scene has no environment map or it's completely black
+    is_enabled: u32,
+}
+
+/// Emitter for next event estimation. Lights are selected proportionally to their power,
+/// so area sampling pdf only depends on emitted luminance.
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct LightData {
+    /// Sphere center or first triangle vertex
+    p0: Vec3f,
+    kind: u32,
+    /// Sphere radius is stored in x component
+    p1: Vec3f,
+    material_index: u32,
+    p2: Vec3f,
+    /// Selection probability of this and all previous lights
+    cdf: f32,
+    area_pdf: f32,
+    _pad0: [u32; 3],
+}
+
+// Zero is reserved for empty light buffer placeholder
+const LIGHT_KIND_SPHERE: u32 = 1;
+const LIGHT_KIND_TRIANGLE: u32 = 2;
+
+/// Collect emitting spheres and triangles of emitting instances into light list and
+/// write area pdfs of primitives. Returns light list and area pdfs of scene instances.
+fn build_lights(
+    primitives: &mut [PrimitiveData],
+    materials: &[MaterialData],
+    mesh_triangles: &[Vec<Triangle>],
+    instances: &[Instance]
+) -> (Vec<LightData>, Vec<f32>) {
+    let emitted_luminance = |material_index: u32| materials.get(material_index as usize).map(|material| luminance(material.emission)).unwrap_or(0.0);
+    let mut lights = Vec::<(LightData, f32)>::new();
+
+    // Other primitive kinds are not sampled explicitly
+    for primitive in primitives.iter().filter(|primitive| primitive.kind == PRIMITIVE_KIND_SPHERE) {
+        let power = emitted_luminance(primitive.material_index) * 4.0 * std::f32::consts::PI * primitive.r0 * primitive.r0;
+
+        if power > 0.0 {
+            lights.push((LightData {
+                p0: primitive.p0,
+                kind: LIGHT_KIND_SPHERE,
+                p1: Vec3f::new(primitive.r0, 0.0, 0.0),
+                material_index: primitive.material_index,
+                ..Default::default()
+            }, power));
+        }
+    }
+
+    for instance in instances.iter().filter(|instance| emitted_luminance(instance.material_index) > 0.0) {
+        let luminance = emitted_luminance(instance.material_index);
+
+        for triangle in &mesh_triangles[instance.mesh_index as usize] {
+            let [v0, v1, v2] = [triangle.v0, triangle.v1, triangle.v2].map(|v| instance.transform.transform_point(v));
+            let area = ((v1 - v0) % (v2 - v0)).length() / 2.0;
+
+            if area > 0.0 {
+                lights.push((LightData {
+                    p0: v0,
+                    kind: LIGHT_KIND_TRIANGLE,
+                    p1: v1,
+                    material_index: instance.material_index,
+                    p2: v2,
+                    ..Default::default()
+                }, luminance * area));
+            }
+        }
+    }
+
+    let total_power = lights.iter().map(|(_, power)| *power).sum::<f32>();
+    if total_power <= 0.0 {
+        return (Vec::new(), vec![0.0; instances.len()]);
+    }
+
+    for primitive in primitives.iter_mut().filter(|primitive| primitive.kind == PRIMITIVE_KIND_SPHERE) {
+        primitive.light_area_pdf = emitted_luminance(primitive.material_index) / total_power;
+    }
+
+    let instance_area_pdfs = instances
+        .iter()
+        .map(|instance| emitted_luminance(instance.material_index) / total_power)
+        .collect::<Vec<_>>();
+
+    let mut cumulative_power = 0.0;
+    let lights = lights
+        .into_iter()
+        .map(|(light, power)| {
+            cumulative_power += power;
+            LightData {
+                cdf: cumulative_power / total_power,
+                area_pdf: emitted_luminance(light.material_index) / total_power,
+                ..light
+            }
+        })
+        .collect::<Vec<_>>();
+
+    (lights, instance_area_pdfs)
+} // fn build_lights
+
+fn create_storage_buffer<T: Copy + Default>(device: &wgpu::Device, label: &str, data: &[T]) -> wgpu::Buffer {
+    // Zero-sized bindings are not allowed, so empty arrays are replaced by single default element
+    let default = [T::default()];
+    let data = if data.is_empty() { &default[..] } else { data };
+    let bytes = slice_as_bytes(data);
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        mapped_at_creation: true,
+        size: bytes.len() as u64,
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    buffer.slice(..).get_mapped_range_mut().copy_from_slice(bytes);
+    buffer.unmap();
+    buffer
+}
+
+/// Upload textures into single texture array
+fn create_texture_array(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, format: wgpu::TextureFormat, textures: &[Texture]) -> wgpu::Texture {
+    let extent = textures.first().map(|t| t.extent).unwrap_or(Ext2u::new(1, 1));
+
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        label: Some(label),
+        mip_level_count: 1,
+        sample_count: 1,
+        size: wgpu::Extent3d {
+            width: extent.w,
+            height: extent.h,
+            // Single-layer arrays may be treated as plain 2D textures by some backends
+            depth_or_array_layers: textures.len().max(2) as u32,
+        },
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    for (layer, scene_texture) in textures.iter().enumerate() {
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                texture: &texture,
+            },
+            &scene_texture.data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(extent.w * 4),
+                rows_per_image: Some(extent.h),
+            },
+            wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+        );
+    }
+
+    texture
+} // fn create_texture_array
+
+/// Upload single-layer 32-bit float texture, which is read by texel loads only
+pub fn create_float_texture(device: &wgpu::Device, queue: &wgpu::Queue, label: &str, format: wgpu::TextureFormat, extent: Ext2u, data: &[f32]) -> wgpu::TextureView {
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        dimension: wgpu::TextureDimension::D2,
+        format,
+        label: Some(label),
+        mip_level_count: 1,
+        sample_count: 1,
+        size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let bytes = slice_as_bytes(data);
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            texture: &texture,
+        },
+        bytes,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes.len() as u32 / extent.h),
+            rows_per_image: Some(extent.h),
+        },
+        wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+    );
+
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Environment bindings: parameters, radiance, conditional and marginal CDFs
+struct EnvironmentResources {
+    buffer: wgpu::Buffer,
+    radiance_view: wgpu::TextureView,
+    conditional_cdf_view: wgpu::TextureView,
+    marginal_cdf_view: wgpu::TextureView,
+}
+
+fn create_environment_resources(device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) -> EnvironmentResources {
+    let (data, radiance, distribution) = match &scene.environment {
+        Some(environment) => {
+            let start_time = std::time::Instant::now();
+            let distribution = environment.build_distribution();
+            crate::crash::info!("Environment distribution built in {:.3} ms", start_time.elapsed().as_secs_f64() * 1000.0);
+
+            let pixel_count = (environment.extent.w * environment.extent.h) as f32;
+            let data = EnvironmentData {
+                rotation: environment.rotation,
+                intensity: environment.intensity,
+                pdf_normalization: pixel_count / (distribution.total_weight * 2.0 * std::f32::consts::PI * std::f32::consts::PI),
+                is_enabled: (distribution.total_weight > 0.0) as u32,
+            };
+
+            (data, Some((environment.extent, &environment.data[..])), Some(distribution))
+        }
+        None => (EnvironmentData::default(), None, None),
+    };
+
+    let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Environment UBO"),
+        mapped_at_creation: true,
+        size: std::mem::size_of::<EnvironmentData>() as u64,
+        usage: wgpu::BufferUsages::UNIFORM,
+    });
+    buffer.slice(..).get_mapped_range_mut().copy_from_slice(as_bytes(&data));
+    buffer.unmap();
+
+    // Disabled environment is bound as black 1x1 texture
+    let (extent, radiance) = radiance.unwrap_or((Ext2u::new(1, 1), &[0.0; 4]));
+    let (conditional_cdf, marginal_cdf) = distribution
+        .map(|distribution| (distribution.conditional_cdf, distribution.marginal_cdf))
+        .unwrap_or((vec![1.0], vec![1.0]));
+
+    EnvironmentResources {
+        buffer,
+        radiance_view: create_float_texture(device, queue, "Environment radiance", wgpu::TextureFormat::Rgba32Float, extent, radiance),
+        conditional_cdf_view: create_float_texture(device, queue, "Environment conditional CDF", wgpu::TextureFormat::R32Float, extent, &conditional_cdf),
+        marginal_cdf_view: create_float_texture(device, queue, "Environment marginal CDF", wgpu::TextureFormat::R32Float, Ext2u::new(extent.h, 1), &marginal_cdf),
+    }
+} // fn create_environment_resources
+
+/// Bottom-level BVHs of all scene meshes. Child offsets of interior nodes are relative to the first
+/// mesh node, as top-level BVH is placed before mesh BVHs and its size depends on instances.
+struct MeshAccelerationData {
+    nodes: Vec<FlatBvhNode>,
+    triangles: Vec<TriangleData>,
+    /// Index of each triangle in its mesh
+    triangle_indices: Vec<u32>,
+    /// Relative root node index and bounds of each mesh BVH
+    roots: Vec<(u32, Aabb)>,
+}
+
+/// Pack sRGB color into RGBA8 with opaque alpha
+fn pack_color(color: Vec3f) -> u32 {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u32;
+    channel(color.x) | channel(color.y) << 8 | channel(color.z) << 16 | 0xFF << 24
+}
+
+fn build_mesh_acceleration_data(meshes: &[Mesh]) -> MeshAccelerationData {
+    let start_time = std::time::Instant::now();
+
+    let mut nodes = Vec::<FlatBvhNode>::new();
+    let mut triangles = Vec::<TriangleData>::new();
+    let mut triangle_indices = Vec::<u32>::new();
+    let mut roots = Vec::<(u32, Aabb)>::with_capacity(meshes.len());
+
+    for mesh in meshes {
+        let blas = Bvh::build(&mesh.triangles().collect::<Vec<_>>());
+        let node_base = nodes.len() as u32;
+        let triangle_base = triangles.len() as u32;
+
+        roots.push((node_base, blas.bounds()));
+        nodes.extend(blas.flatten().into_iter().map(|mut node| {
+            node.offset += if node.count != 0 { triangle_base } else { node_base };
+            node
+        }));
+
+        // Reorder triangles to make leaf ranges contiguous
+        triangle_indices.extend_from_slice(&blas.indices);
+        triangles.extend(blas.indices.iter().map(|index| {
+            let indices = mesh.indices[*index as usize];
+            let [v0, v1, v2] = indices.map(|i| mesh.positions[i as usize]);
+            let [n0, n1, n2] = if mesh.normals.is_empty() {
+                [((v1 - v0) % (v2 - v0)).normalized(); 3]
+            } else {
+                indices.map(|i| mesh.normals[i as usize])
+            };
+            let [uv0, uv1, uv2] = if mesh.uvs.is_empty() {
+                [Vec2f::default(); 3]
+            } else {
+                indices.map(|i| mesh.uvs[i as usize])
+            };
+            let [c0, c1, c2] = if mesh.colors.is_empty() {
+                [u32::MAX; 3]
+            } else {
+                indices.map(|i| pack_color(mesh.colors[i as usize]))
+            };
+
+            TriangleData { v0, v1, v2, n0, n1, n2, uv0, uv1, uv2, c0, c1, c2, ..Default::default() }
+        }));
+    }
+
+    crate::crash::info!(
+        "Mesh BVHs built in {:.3} ms ({} meshes, {} triangles, {} nodes)",
+        start_time.elapsed().as_secs_f64() * 1000.0,
+        meshes.len(),
+        triangles.len(),
+        nodes.len()
+    );
+
+    MeshAccelerationData { nodes, triangles, triangle_indices, roots }
+} // fn build_mesh_acceleration_data
+
+/// Top-level BVH over instances followed by rebased mesh BVHs, and instances in top-level BVH leaf order.
+/// Instance bounds cover both current and previous transforms.
+fn build_instance_acceleration_data(
+    mesh_nodes: &[FlatBvhNode],
+    mesh_roots: &[(u32, Aabb)],
+    instances: &[Instance],
+    previous_transforms: &[Mat4f]
+) -> (Vec<FlatBvhNode>, Vec<InstanceData>) {
+    let instances = instances
+        .iter()
+        .enumerate()
+        .filter_map(|(id, instance)| {
+            let world_to_object = instance.transform.inversed();
+            if world_to_object.is_none() {
+                crate::crash::error!("{}", tr_args("error.singular_instance", &[&instance.mesh_index]));
+            }
+            Some((id, instance, world_to_object?))
+        })
+        .collect::<Vec<_>>();
+
+    let tlas = Bvh::build(&instances
+        .iter()
+        .map(|(id, instance, _)| {
+            let bounds = mesh_roots[instance.mesh_index as usize].1;
+            let mut instance_bounds = bounds.transformed(&instance.transform);
+            instance_bounds.extend(&bounds.transformed(&previous_transforms[*id]));
+            instance_bounds
+        })
+        .collect::<Vec<_>>()
+    );
+
+    let mut nodes = tlas.flatten();
+    let mesh_node_base = nodes.len() as u32;
+
+    nodes.extend(mesh_nodes.iter().map(|node| {
+        let mut node = *node;
+        if node.count == 0 {
+            node.offset += mesh_node_base;
+        }
+        node
+    }));
+
+    let instances = tlas.indices
+        .iter()
+        .map(|index| {
+            let (id, instance, world_to_object) = instances[*index as usize];
+            InstanceData {
+                world_to_object,
+                object_to_world: instance.transform,
+                previous_object_to_world: previous_transforms[id],
+                blas_root: mesh_node_base + mesh_roots[instance.mesh_index as usize].0,
+                material_index: instance.material_index,
+                id: id as u32,
+                custom_data: instance.custom_data,
+                ..Default::default()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    (nodes, instances)
+} // fn build_instance_acceleration_data
+
+/// Scene data that doesn't depend on instance placement. Renderer keeps it,
+/// so moving instances only rebuilds top-level BVH.
+pub struct SceneResources {
+    mesh_nodes: Vec<FlatBvhNode>,
+    mesh_roots: Vec<(u32, Aabb)>,
+    /// Mesh triangles in object space, emitting instances are turned into lights
+    mesh_triangles: Vec<Vec<Triangle>>,
+    /// Mesh triangle index of each triangle in triangle buffer
+    triangle_indices: Vec<u32>,
+    /// Primitives and materials are kept to build light list
+    primitives: Vec<PrimitiveData>,
+    materials: Vec<MaterialData>,
+    triangle_buffer: wgpu::Buffer,
+    /// Decals are rebased with primitives and instances
+    decals: Vec<DecalData>,
+    material_buffer: wgpu::Buffer,
+    texture_array_view: wgpu::TextureView,
+    data_texture_array_view: wgpu::TextureView,
+    environment: EnvironmentResources,
+}
+
+impl SceneResources {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) -> Self {
+        let mesh_data = build_mesh_acceleration_data(&scene.meshes);
+
+        let decals = scene.decals.iter().map(|decal| DecalData {
+            position: decal.position,
+            texture_index: decal.texture_index,
+            // Axes are prescaled to map decal box into [-1, 1] cube
+            right: decal.right * (2.0 / decal.size.x),
+            opacity: decal.opacity,
+            up: decal.up * (2.0 / decal.size.y),
+            forward: decal.forward * (2.0 / decal.size.z),
+            ..Default::default()
+        }).collect::<Vec<_>>();
+
+        let primitives = scene.primitives.iter().map(|primitive| PrimitiveData {
+            material_index: primitive.material_index,
+            ..PrimitiveData::from_shape(&primitive.shape)
+        }).collect::<Vec<_>>();
+
+        let materials = scene.materials.iter().map(MaterialData::from_textured_material).collect::<Vec<_>>();
+
+        let array_view_descriptor = wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        };
+
+        Self {
+            triangle_buffer: create_storage_buffer(device, "Triangle SSBO", &mesh_data.triangles),
+            mesh_nodes: mesh_data.nodes,
+            mesh_roots: mesh_data.roots,
+            triangle_indices: mesh_data.triangle_indices,
+            mesh_triangles: scene.meshes.iter().map(|mesh| mesh.triangles().collect()).collect(),
+            decals,
+            material_buffer: create_storage_buffer(device, "Material SSBO", &materials),
+            primitives,
+            materials,
+            texture_array_view: create_texture_array(device, queue, "Scene texture array", wgpu::TextureFormat::Rgba8UnormSrgb, &scene.textures)
+                .create_view(&array_view_descriptor),
+            data_texture_array_view: create_texture_array(device, queue, "Scene data texture array", wgpu::TextureFormat::Rgba8Unorm, &scene.data_textures)
+                .create_view(&array_view_descriptor),
+            environment: create_environment_resources(device, queue, scene),
+        }
+    } // fn new
+
+    /// Index in its mesh of triangle at `index` in triangle buffer
+    pub fn mesh_triangle_index(&self, index: u32) -> Option<u32> {
+        self.triangle_indices.get(index as usize).copied()
+    }
+
+    /// Replace materials with the same number of new ones, light list is rebuilt with next bind group
+    pub fn set_materials(&mut self, queue: &wgpu::Queue, materials: &[TexturedMaterial]) {
+        self.materials = materials.iter().map(MaterialData::from_textured_material).collect();
+        queue.write_buffer(&self.material_buffer, 0, slice_as_bytes(&self.materials));
+    }
+}
+
+pub fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Scene bind group layout"),
+        entries: &[0, 1, 2, 3, 4, 7, 13]
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                count: None,
+                ty: wgpu::BindingType::Buffer {
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                },
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+            })
+            .into_iter()
+            .chain([5, 8].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                count: None,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    multisampled: false,
+                },
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+            }))
+            .chain([
+                wgpu::BindGroupLayoutEntry {
+                    binding: 6,
+                    count: None,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 9,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                },
+            ])
+            .chain([10, 11, 12].map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                count: None,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+            }))
+            .collect::<Vec<_>>(),
+    })
+} // fn create_bind_group_layout
+
+/// Build top-level BVH over `instances` and bind it together with scene resources.
+/// World-space data is made relative to `origin`.
+pub fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    sampler: &wgpu::Sampler,
+    resources: &SceneResources,
+    instances: &[Instance],
+    previous_transforms: &[Mat4f],
+    origin: Vec3d
+) -> wgpu::BindGroup {
+    let mut primitives = resources.primitives.iter().map(|primitive| primitive.relative_to(origin)).collect::<Vec<_>>();
+    let instances = instances
+        .iter()
+        .map(|instance| Instance { transform: instance.transform.relative_to(origin), ..*instance })
+        .collect::<Vec<_>>();
+    let previous_transforms = previous_transforms.iter().map(|transform| transform.relative_to(origin)).collect::<Vec<_>>();
+    let decals = resources.decals
+        .iter()
+        .map(|decal| DecalData { position: decal.position.relative_to(origin), ..*decal })
+        .collect::<Vec<_>>();
+
+    let (lights, instance_light_area_pdfs) = build_lights(&mut primitives, &resources.materials, &resources.mesh_triangles, &instances);
+
+    let (nodes, mut instances) = build_instance_acceleration_data(&resources.mesh_nodes, &resources.mesh_roots, &instances, &previous_transforms);
+    for instance in &mut instances {
+        instance.light_area_pdf = instance_light_area_pdfs[instance.id as usize];
+    }
+
+    let bvh_buffer = create_storage_buffer(device, "BVH SSBO", &nodes);
+    let instance_buffer = create_storage_buffer(device, "Instance SSBO", &instances);
+    let primitive_buffer = create_storage_buffer(device, "Primitive SSBO", &primitives);
+    let light_buffer = create_storage_buffer(device, "Light SSBO", &lights);
+    let decal_buffer = create_storage_buffer(device, "Decal SSBO", &decals);
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: bvh_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: resources.triangle_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 2, resource: instance_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 3, resource: decal_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 4, resource: primitive_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 5, resource: wgpu::BindingResource::TextureView(&resources.texture_array_view) },
+            wgpu::BindGroupEntry { binding: 6, resource: wgpu::BindingResource::Sampler(sampler) },
+            wgpu::BindGroupEntry { binding: 7, resource: resources.material_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 8, resource: wgpu::BindingResource::TextureView(&resources.data_texture_array_view) },
+            wgpu::BindGroupEntry { binding: 9, resource: resources.environment.buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 10, resource: wgpu::BindingResource::TextureView(&resources.environment.radiance_view) },
+            wgpu::BindGroupEntry { binding: 11, resource: wgpu::BindingResource::TextureView(&resources.environment.conditional_cdf_view) },
+            wgpu::BindGroupEntry { binding: 12, resource: wgpu::BindingResource::TextureView(&resources.environment.marginal_cdf_view) },
+            wgpu::BindGroupEntry { binding: 13, resource: light_buffer.as_entire_binding() },
+        ],
+        label: Some("Scene bind group"),
+        layout,
+    })
+} // fn create_bind_group
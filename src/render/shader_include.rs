@@ -0,0 +1,59 @@
+/// Shader files embedded into binary, `#include` directives of embedded shaders are resolved among them
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+    ("render.wgsl", include_str!("shaders/render.wgsl")),
+    ("sampling.wgsl", include_str!("shaders/sampling.wgsl")),
+    ("intersect.wgsl", include_str!("shaders/intersect.wgsl")),
+    ("bsdf.wgsl", include_str!("shaders/bsdf.wgsl")),
+    ("lights.wgsl", include_str!("shaders/lights.wgsl")),
+    ("integrator.wgsl", include_str!("shaders/integrator.wgsl")),
+    ("place.wgsl", include_str!("shaders/place.wgsl")),
+    ("outline.wgsl", include_str!("shaders/outline.wgsl")),
+    ("buffer_view.wgsl", include_str!("shaders/buffer_view.wgsl")),
+    ("lens_flare.wgsl", include_str!("shaders/lens_flare.wgsl")),
+    ("output.wgsl", include_str!("shaders/output.wgsl")),
+    ("camera.wgsl", include_str!("shaders/camera.wgsl")),
+    ("path_overlay.wgsl", include_str!("shaders/path_overlay.wgsl")),
+];
+
+pub fn load_embedded(name: &str) -> Option<String> {
+    EMBEDDED_SHADERS.iter().find(|(file_name, _)| *file_name == name).map(|(_, source)| source.to_string())
+}
+
+/// File name of `#include "name"` directive line
+fn parse_include(line: &str) -> Option<Result<&str, String>> {
+    let argument = line.trim().strip_prefix("#include")?.trim();
+    Some(argument.strip_prefix('"').and_then(|name| name.strip_suffix('"')).ok_or_else(|| format!("invalid include directive: {}", line.trim())))
+}
+
+fn compose_file(name: &str, load: &mut dyn FnMut(&str) -> Option<String>, included: &mut Vec<String>, output: &mut String) -> Result<(), String> {
+    let source = load(name).ok_or_else(|| format!("shader file {} not found", name))?;
+    included.push(name.to_string());
+
+    for (index, line) in source.lines().enumerate() {
+        match parse_include(line) {
+            Some(Ok(include)) => if !included.iter().any(|file| file == include) {
+                compose_file(include, load, included, output).map_err(|error| format!("{}\n    included at {}:{}", error, name, index + 1))?;
+            },
+            Some(Err(error)) => return Err(format!("{} at {}:{}", error, name, index + 1)),
+            None => {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Build WGSL module of `name` file by substitution of `#include "file"` lines with file contents.
+/// Every file is included once, so shared parts can be included by several files. Files are read by `load`.
+pub fn compose(name: &str, load: &mut dyn FnMut(&str) -> Option<String>) -> Result<String, String> {
+    let mut output = String::new();
+    compose_file(name, load, &mut Vec::new(), &mut output)?;
+    Ok(output)
+}
+
+/// Compose embedded shader, its includes are checked to exist at development time
+pub fn compose_embedded(name: &str) -> String {
+    compose(name, &mut load_embedded).expect("embedded shader includes must be embedded too")
+}
@@ -0,0 +1,85 @@
+use std::{path::{Path, PathBuf}, time::SystemTime};
+
+use super::shader_include;
+
+fn modification_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Shader file polled for changes of it and files it includes by modification time
+pub struct ShaderFile {
+    pub path: PathBuf,
+    directory: PathBuf,
+    name: &'static str,
+    /// Files loaded by last composition with their modification times, empty if file wasn't read yet.
+    /// Missing included files have no time, so their creation triggers composition too.
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl ShaderFile {
+    fn new(directory: &Path, name: &'static str) -> Self {
+        Self {
+            path: directory.join(name),
+            directory: directory.to_path_buf(),
+            name,
+            files: Vec::new(),
+        }
+    }
+
+    /// Compose shader if any of its files changed since last read. Failed version isn't reread until next save.
+    pub fn read_changed(&mut self) -> Option<Result<String, String>> {
+        let name = self.name;
+        self.read_changed_with(|load| shader_include::compose(name, load))
+    }
+
+    /// Compose shader by `compose` from files it loads if any of them changed since last read
+    pub fn read_changed_with(&mut self, compose: impl FnOnce(&mut dyn FnMut(&str) -> Option<String>) -> Result<String, String>) -> Option<Result<String, String>> {
+        let is_unchanged = !self.files.is_empty() && self.files.iter().all(|(path, time)| modification_time(path) == *time);
+        if is_unchanged || !self.path.exists() {
+            return None;
+        }
+
+        // Include set is rescanned by every composition, so files included by edits are watched too
+        let mut files = Vec::new();
+        let source = compose(&mut |name| {
+            let path = self.directory.join(name);
+            let time = modification_time(&path);
+            let source = time.and_then(|_| std::fs::read_to_string(&path).ok());
+            files.push((path, time));
+            source
+        });
+        self.files = files;
+        Some(source)
+    }
+}
+
+/// Shader file of hot reload `directory`, embedded one if it isn't there
+pub fn load(directory: &Path, name: &str) -> Option<String> {
+    std::fs::read_to_string(directory.join(name)).ok().or_else(|| shader_include::load_embedded(name))
+}
+
+/// Shaders rebuilt on change: `render.wgsl` with its includes is used by main, ID and AOV pipelines, `place.wgsl` by place one
+pub struct ShaderWatch {
+    /// Embedded shaders are kept if it doesn't exist
+    pub directory: PathBuf,
+    pub render: ShaderFile,
+    pub place: ShaderFile,
+}
+
+impl ShaderWatch {
+    /// Files aren't read yet, so first poll replaces embedded shaders by ones on disk
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+            render: ShaderFile::new(directory, "render.wgsl"),
+            place: ShaderFile::new(directory, "place.wgsl"),
+        }
+    }
+}
+
+/// Result of shader file rebuild
+pub enum ShaderReload {
+    Reloaded(PathBuf),
+    /// Compilation error, pipelines of previous version are kept
+    Failed(PathBuf, String),
+}
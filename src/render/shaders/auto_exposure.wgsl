@@ -0,0 +1,54 @@
+/// P_TR Project
+/// `File` render/auto_exposure.wgsl
+/// `Description` Luminance histogram module of automatic exposure.
+/// `Author` TioT2
+
+@group(0) @binding(0) var collector: texture_2d<f32>; // radiance sum and sample count
+
+// Pixel count per log2 luminance bin, bin 0 counts pixels darker than histogram range
+@group(1) @binding(0) var<storage, read_write> histogram: array<atomic<u32>, BIN_COUNT>;
+
+// Match auto_exposure.rs
+const BIN_COUNT: u32 = 256;
+const MIN_LOG_LUMINANCE: f32 = -16.0;
+const MAX_LOG_LUMINANCE: f32 = 16.0;
+
+const WORKGROUP_SIZE: u32 = 16;
+
+var<workgroup> shared_histogram: array<atomic<u32>, BIN_COUNT>;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+fn bin_index(luminance: f32) -> u32 {
+    let log_luminance = log2(luminance);
+    if luminance <= 0.0 || log_luminance < MIN_LOG_LUMINANCE {
+        return 0u;
+    }
+    let position = (log_luminance - MIN_LOG_LUMINANCE) / (MAX_LOG_LUMINANCE - MIN_LOG_LUMINANCE);
+    return clamp(u32(position * f32(BIN_COUNT - 1u)) + 1u, 1u, BIN_COUNT - 1u);
+}
+
+// Histogram of mean pixel luminance, workgroup histograms are added to global one
+@compute @workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn cs_histogram(@builtin(global_invocation_id) pixel: vec3u, @builtin(local_invocation_index) index: u32) {
+    atomicStore(&shared_histogram[index], 0u);
+    workgroupBarrier();
+
+    if all(pixel.xy < textureDimensions(collector)) {
+        let collected = textureLoad(collector, pixel.xy, 0);
+        // Pixels without samples aren't metered
+        if collected.a > 0.0 {
+            atomicAdd(&shared_histogram[bin_index(luminance(collected.rgb / collected.a))], 1u);
+        }
+    }
+    workgroupBarrier();
+
+    let count = atomicLoad(&shared_histogram[index]);
+    if count != 0u {
+        atomicAdd(&histogram[index], count);
+    }
+} // fn cs_histogram
+
+// file auto_exposure.wgsl
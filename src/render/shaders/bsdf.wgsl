@@ -0,0 +1,220 @@
+/// P_TR Project
+/// `File` render/bsdf.wgsl
+/// `Description` Materials and scattering module.
+/// `Author` TioT2
+
+struct Decal {
+    position: vec3f,
+    texture_index: u32,
+    right: vec3f, // axes are scaled to map decal box into [-1, 1] cube
+    opacity: f32,
+    up: vec3f,
+    forward: vec3f,
+}
+
+@group(2) @binding(3) var<storage, read> decals: array<Decal>;
+@group(2) @binding(5) var scene_textures: texture_2d_array<f32>;
+@group(2) @binding(6) var scene_sampler: sampler;
+
+// Blend all decals whose boxes contain the point over the base color
+fn apply_decals(point: vec3f, normal: vec3f, base_color: vec3f) -> vec3f {
+    var color = base_color;
+
+    for (var i = 0u; i < arrayLength(&decals); i++) {
+        let decal = decals[i];
+
+        // Project only onto surfaces facing the projector
+        if decal.opacity <= 0.0 || dot(normal, decal.forward) >= 0.0 {
+            continue;
+        }
+
+        let delta = point - decal.position;
+        let local = vec3f(dot(delta, decal.right), dot(delta, decal.up), dot(delta, decal.forward));
+
+        if any(abs(local) > vec3f(1.0)) {
+            continue;
+        }
+
+        let texel = textureSampleLevel(
+            scene_textures,
+            scene_sampler,
+            vec2f(local.x, -local.y) * 0.5 + 0.5,
+            decal.texture_index,
+            0.0
+        );
+        color = mix(color, texel.rgb, texel.a * decal.opacity);
+    }
+
+    return color;
+}
+
+const MATERIAL_KIND_LAMBERTIAN: u32 = 0;
+const MATERIAL_KIND_METAL: u32 = 1;
+const MATERIAL_KIND_DIELECTRIC: u32 = 2;
+const MATERIAL_KIND_EMISSIVE: u32 = 3;
+const MATERIAL_KIND_PRINCIPLED: u32 = 4;
+
+struct Material {
+    color: vec3f,
+    kind: u32,
+    emission: vec3f,
+    roughness: f32,
+    ior: f32,
+    metallic: f32,
+    specular: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    transmission: f32,
+    albedo_texture: u32, // scene texture layer
+    roughness_texture: u32, // data texture layers
+    metallic_texture: u32,
+    normal_texture: u32,
+    instance_tint: u32, // nonzero if color is multiplied by instance custom data
+    backface: u32, // BACKFACE_* constant
+}
+
+const BACKFACE_CLOSED: u32 = 0; // back side is the inside of closed surface
+const BACKFACE_TWO_SIDED: u32 = 1; // both sides are front ones
+const BACKFACE_CULL: u32 = 2; // back side is invisible
+const BACKFACE_FLIP: u32 = 3; // sides are swapped
+
+fn is_backface_culled(material_index: u32) -> bool {
+    return materials[material_index].backface == BACKFACE_CULL;
+}
+
+const NO_TEXTURE: u32 = 0xFFFFFFFFu;
+
+@group(2) @binding(7) var<storage, read> materials: array<Material>;
+@group(2) @binding(8) var data_textures: texture_2d_array<f32>;
+
+// Modulate material parameters by its texture maps, vertex colors and instance data. Returns normal perturbed by normal map.
+fn apply_material_maps(material: ptr<function, Material>, uv: vec2f, normal: vec3f, tangent: vec4f, custom_data: vec4f, vertex_color: vec3f) -> vec3f {
+    // Texture V axis points up
+    let tex_coord = vec2f(uv.x, 1.0 - uv.y);
+
+    (*material).color *= vertex_color;
+
+    if (*material).instance_tint != 0u {
+        (*material).color *= custom_data.rgb;
+    }
+
+    if (*material).albedo_texture != NO_TEXTURE {
+        (*material).color *= textureSampleLevel(scene_textures, scene_sampler, tex_coord, (*material).albedo_texture, 0.0).rgb;
+    }
+    if (*material).roughness_texture != NO_TEXTURE {
+        (*material).roughness *= textureSampleLevel(data_textures, scene_sampler, tex_coord, (*material).roughness_texture, 0.0).g;
+    }
+    if (*material).metallic_texture != NO_TEXTURE {
+        (*material).metallic *= textureSampleLevel(data_textures, scene_sampler, tex_coord, (*material).metallic_texture, 0.0).b;
+    }
+    if (*material).normal_texture == NO_TEXTURE {
+        return normal;
+    }
+
+    let t = normalize(tangent.xyz - normal * dot(normal, tangent.xyz));
+    let b = cross(normal, t) * tangent.w;
+    let n = textureSampleLevel(data_textures, scene_sampler, tex_coord, (*material).normal_texture, 0.0).xyz * 2.0 - 1.0;
+    return normalize(t * n.x + b * n.y + normal * n.z);
+}
+
+struct ScatterResult {
+    direction: vec3f,
+    weight: vec3f, // BSDF value multiplied by cosine and divided by sampling pdf
+    pdf: f32, // solid angle pdf for diffuse scattering, zero for other lobes
+    is_scattered: bool,
+}
+
+fn sample_cosine_hemisphere(normal: vec3f) -> vec3f {
+    let r = sqrt(rand_f32());
+    let phi = 2.0 * PI * rand_f32();
+    return orthonormal_basis(normal) * vec3f(r * cos(phi), r * sin(phi), sqrt(max(1.0 - r * r, 0.0)));
+}
+
+fn fresnel_schlick(f0: vec3f, cos_theta: f32) -> vec3f {
+    return f0 + (1.0 - f0) * pow(1.0 - clamp(cos_theta, 0.0, 1.0), 5.0);
+}
+
+// Smith masking term for GGX distribution
+fn ggx_g1(n_dot_x: f32, alpha2: f32) -> f32 {
+    return 2.0 * n_dot_x / (n_dot_x + sqrt(alpha2 + (1.0 - alpha2) * n_dot_x * n_dot_x));
+}
+
+fn lambertian_scatter(color: vec3f, normal: vec3f) -> ScatterResult {
+    let direction = sample_cosine_hemisphere(normal);
+    return ScatterResult(direction, color, max(dot(direction, normal), 0.0) / PI, true);
+}
+
+// GGX microfacet reflection, half vector is sampled proportionally to D(h) * dot(n, h)
+fn ggx_reflect_scatter(f0: vec3f, roughness: f32, normal: vec3f, direction: vec3f) -> ScatterResult {
+    let alpha = max(roughness * roughness, 1e-4);
+    let alpha2 = alpha * alpha;
+
+    let u = rand_f32();
+    let phi = 2.0 * PI * rand_f32();
+    let cos_theta = sqrt((1.0 - u) / (1.0 + (alpha2 - 1.0) * u));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let half_vector = orthonormal_basis(normal) * vec3f(sin_theta * cos(phi), sin_theta * sin(phi), cos_theta);
+
+    var result: ScatterResult;
+    result.direction = reflect(direction, half_vector);
+
+    let n_dot_l = dot(normal, result.direction);
+    let n_dot_v = max(-dot(normal, direction), 1e-4);
+    let v_dot_h = max(-dot(direction, half_vector), 0.0);
+
+    result.is_scattered = n_dot_l > 0.0;
+    result.weight = fresnel_schlick(f0, v_dot_h) * ggx_g1(n_dot_l, alpha2) * ggx_g1(n_dot_v, alpha2) * v_dot_h / (n_dot_v * cos_theta);
+    return result;
+}
+
+// Smooth dielectric, reflection or refraction is chosen by Fresnel reflectance
+fn dielectric_scatter(ior: f32, color: vec3f, normal: vec3f, direction: vec3f, is_front_face: bool) -> ScatterResult {
+    let eta = select(ior, 1.0 / ior, is_front_face);
+    let refracted = refract(direction, normal, eta);
+    let is_total_reflection = all(refracted == vec3f(0.0));
+
+    // Schlick approximation uses angle on the optically thinner side
+    let cos_theta = select(-dot(direction, normal), -dot(refracted, normal), eta > 1.0);
+    let r0 = (1.0 - ior) / (1.0 + ior);
+    let reflectance = fresnel_schlick(vec3f(r0 * r0), cos_theta).x;
+
+    let direction_out = select(refracted, reflect(direction, normal), is_total_reflection || rand_f32() < reflectance);
+    return ScatterResult(direction_out, color, 0.0, true);
+}
+
+// Principled BSDF. Layers are picked stochastically: clearcoat over either metal,
+// smooth transmission or dielectric specular over diffuse base.
+// Layers chosen with Fresnel probability get unit F0, so Fresnel term is not counted twice.
+fn principled_scatter(material: Material, color: vec3f, normal: vec3f, direction: vec3f, is_front_face: bool) -> ScatterResult {
+    let n_dot_v = max(-dot(normal, direction), 0.0);
+
+    if is_front_face && rand_f32() < material.clearcoat * fresnel_schlick(vec3f(0.04), n_dot_v).x {
+        return ggx_reflect_scatter(vec3f(1.0), material.clearcoat_roughness, normal, direction);
+    }
+
+    if rand_f32() < material.metallic {
+        return ggx_reflect_scatter(color, material.roughness, normal, direction);
+    }
+
+    if rand_f32() < material.transmission {
+        return dielectric_scatter(material.ior, color, normal, direction, is_front_face);
+    }
+
+    if is_front_face && rand_f32() < fresnel_schlick(vec3f(0.08 * material.specular), n_dot_v).x {
+        return ggx_reflect_scatter(vec3f(1.0), material.roughness, normal, direction);
+    }
+
+    return lambertian_scatter(color, normal);
+}
+
+fn material_scatter(material: Material, color: vec3f, normal: vec3f, direction: vec3f, is_front_face: bool) -> ScatterResult {
+    switch material.kind {
+        case MATERIAL_KIND_LAMBERTIAN: { return lambertian_scatter(color, normal); }
+        case MATERIAL_KIND_METAL: { return ggx_reflect_scatter(color, material.roughness, normal, direction); }
+        case MATERIAL_KIND_DIELECTRIC: { return dielectric_scatter(material.ior, color, normal, direction, is_front_face); }
+        case MATERIAL_KIND_PRINCIPLED: { return principled_scatter(material, color, normal, direction, is_front_face); }
+        default: { return ScatterResult(direction, vec3f(0.0), 0.0, false); }
+    }
+}
+
+// file bsdf.wgsl
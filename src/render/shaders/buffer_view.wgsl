@@ -0,0 +1,66 @@
+/// P_TR Project
+/// `File` render/buffer_view.wgsl
+/// `Description` Raw internal texture presentation module.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+#include "output.wgsl"
+
+@group(0) @binding(0) var buffer: texture_2d<f32>;
+
+struct View {
+    channels: u32, // all color channels or single channel index + 1
+    normalization: u32,
+    scale: f32, // applied before normalization mapping
+}
+
+@group(0) @binding(1) var<uniform> view: View;
+
+const CHANNELS_RGB: u32 = 0;
+
+const NORMALIZATION_PER_SAMPLE: u32 = 1;
+const NORMALIZATION_SIGNED: u32 = 2;
+const NORMALIZATION_LOG: u32 = 3;
+
+// Color of NaN and infinite values, so they stand out of any buffer
+const NON_FINITE_COLOR: vec3f = vec3f(1.0, 0.0, 1.0);
+
+const F32_MAX: f32 = 3.40282347e38;
+
+@fragment
+fn fs_main(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let value = textureLoad(buffer, vec2i(frag_coord_4f.xy), 0);
+
+    // NaN fails any comparison, infinity fails the bound one
+    if !all(abs(value) <= vec4f(F32_MAX)) {
+        return encode_output(NON_FINITE_COLOR);
+    }
+
+    var color = value.rgb;
+    if view.channels != CHANNELS_RGB {
+        color = vec3f(value[view.channels - 1u]);
+    }
+
+    if view.normalization == NORMALIZATION_PER_SAMPLE {
+        color = select(vec3f(0.0), color / value.a, value.a > 0.0);
+    }
+    color *= view.scale;
+
+    switch view.normalization {
+        case NORMALIZATION_SIGNED: {
+            color = color * 0.5 + 0.5;
+        }
+        case NORMALIZATION_LOG: {
+            color = sign(color) * log2(1.0 + abs(color));
+        }
+        default: {}
+    }
+
+    return encode_output(color);
+} // fn fs_main
+
+// file buffer_view.wgsl
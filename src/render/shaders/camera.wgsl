@@ -0,0 +1,38 @@
+/// P_TR Project
+/// `File` render/camera.wgsl
+/// `Description` Camera uniform layout and projection modes, shared by ray generation and overlays.
+/// `Author` TioT2
+
+// Matches `CameraData`
+struct Camera {
+    location: vec3f,
+    direction: vec3f,
+    near: f32,
+    right: vec3f,
+    projection_width: f32,
+    up: vec3f,
+    projection_height: f32,
+    aperture_radius: f32, // zero for pinhole camera
+    focus_distance: f32, // distance to plane in focus along view direction
+    projection: u32, // PROJECTION_* constant
+    // Camera basis at previous frame, for motion blur
+    previous_location: vec3f,
+    previous_direction: vec3f,
+    previous_right: vec3f,
+    previous_up: vec3f,
+    aperture_blades: u32, // round aperture if less than 3
+    aperture_rotation: f32, // radians
+    bokeh_texture: u32, // data texture with aperture shape, NO_TEXTURE if not used
+    lens_shift: vec2f, // image plane shift, in fractions of projection size
+    lens_tilt: vec2f, // focus plane rotation around right and up axes, radians
+}
+
+// Ray generation modes, match `Projection`
+const PROJECTION_PERSPECTIVE: u32 = 0u;
+const PROJECTION_ORTHOGRAPHIC: u32 = 1u; // rays are parallel, projection size is in scene units
+const PROJECTION_PANORAMA: u32 = 2u; // equirectangular map of all directions, projection size is ignored
+// Fisheye mappings of angle to view direction to image plane distance, projection size is in these distances
+const PROJECTION_FISHEYE_EQUIDISTANT: u32 = 3u;
+const PROJECTION_FISHEYE_STEREOGRAPHIC: u32 = 4u;
+
+// file camera.wgsl
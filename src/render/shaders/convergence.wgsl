@@ -0,0 +1,80 @@
+/// P_TR Project
+/// `File` render/convergence.wgsl
+/// `Description` Accumulation convergence measurement module.
+/// `Author` TioT2
+
+@group(0) @binding(0) var previous: texture_2d<f32>; // radiance sum and sample count before last pass
+@group(1) @binding(0) var current: texture_2d<f32>; // radiance sum and sample count after last pass
+
+// Relative change sum (X) and number of sampled pixels (Y), per workgroup and total
+@group(2) @binding(0) var<storage, read_write> partial_sums: array<vec2f>;
+@group(2) @binding(1) var<storage, read_write> total_sum: vec2f;
+
+// Change of dark pixels is measured relative to this luminance, so they don't dominate metric
+const MIN_LUMINANCE: f32 = 1e-2;
+
+const PARTIAL_WORKGROUP_SIZE: u32 = 8;
+const TOTAL_WORKGROUP_SIZE: u32 = 256;
+
+var<workgroup> shared_sums: array<vec2f, TOTAL_WORKGROUP_SIZE>;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+// Sum of shared values of first `count` invocations into the first one, `count` is power of two
+fn reduce_shared(index: u32, count: u32) {
+    for (var stride = count / 2u; stride > 0u; stride /= 2u) {
+        workgroupBarrier();
+        if index < stride {
+            shared_sums[index] += shared_sums[index + stride];
+        }
+    }
+}
+
+// Relative luminance change of pixels sampled by last pass, summed over workgroup
+@compute @workgroup_size(PARTIAL_WORKGROUP_SIZE, PARTIAL_WORKGROUP_SIZE)
+fn cs_partial(
+    @builtin(global_invocation_id) pixel: vec3u,
+    @builtin(local_invocation_index) index: u32,
+    @builtin(workgroup_id) workgroup: vec3u,
+    @builtin(num_workgroups) workgroup_count: vec3u
+) {
+    var sum = vec2f(0.0);
+    if all(pixel.xy < textureDimensions(current)) {
+        let before = textureLoad(previous, pixel.xy, 0);
+        let after = textureLoad(current, pixel.xy, 0);
+
+        // Pixels without history or new samples (converged or outside of current tile) aren't measured
+        if before.a > 0.0 && after.a > before.a {
+            let before_luminance = luminance(before.rgb / before.a);
+            let after_luminance = luminance(after.rgb / after.a);
+            sum = vec2f(abs(after_luminance - before_luminance) / max(after_luminance, MIN_LUMINANCE), 1.0);
+        }
+    }
+
+    shared_sums[index] = sum;
+    reduce_shared(index, PARTIAL_WORKGROUP_SIZE * PARTIAL_WORKGROUP_SIZE);
+
+    if index == 0u {
+        partial_sums[workgroup.y * workgroup_count.x + workgroup.x] = shared_sums[0];
+    }
+} // fn cs_partial
+
+// Sum of all workgroup sums, dispatched as single workgroup
+@compute @workgroup_size(TOTAL_WORKGROUP_SIZE)
+fn cs_total(@builtin(local_invocation_index) index: u32) {
+    var sum = vec2f(0.0);
+    for (var i = index; i < arrayLength(&partial_sums); i += TOTAL_WORKGROUP_SIZE) {
+        sum += partial_sums[i];
+    }
+
+    shared_sums[index] = sum;
+    reduce_shared(index, TOTAL_WORKGROUP_SIZE);
+
+    if index == 0u {
+        total_sum = shared_sums[0];
+    }
+} // fn cs_total
+
+// file convergence.wgsl
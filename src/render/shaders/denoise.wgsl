@@ -0,0 +1,197 @@
+/// P_TR Project
+/// `File` render/denoise.wgsl
+/// `Description` SVGF-style variance-guided à-trous denoiser module.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@group(0) @binding(0) var collector: texture_2d<f32>; // radiance sum and sample count
+@group(0) @binding(1) var features: texture_2d<f32>; // octahedral normal, primary hit distance, luminance second moment sum
+@group(0) @binding(2) var diffuse: texture_2d<f32>; // diffuse radiance sum, component mode only
+
+struct Iteration {
+    step: u32,
+    is_last: u32,
+}
+
+@group(1) @binding(0) var image: texture_2d<f32>; // filtered color and variance, irradiance in component mode
+@group(1) @binding(1) var<uniform> iteration: Iteration;
+@group(1) @binding(2) var specular_image: texture_2d<f32>; // filtered specular radiance and variance, component mode only
+@group(1) @binding(3) var albedo: texture_2d<f32>; // primary hit albedo and depth, component mode only
+
+const SKY_DEPTH: f32 = -1.0;
+
+// Edge-stopping function parameters
+const SIGMA_DEPTH: f32 = 0.05;
+const SIGMA_NORMAL: f32 = 128.0;
+const SIGMA_LUMINANCE: f32 = 4.0;
+const SIGMA_ALBEDO: f32 = 0.1;
+
+// Diffuse radiance is divided by albedo clamped from below, so dark surfaces don't amplify noise
+const MIN_ALBEDO: f32 = 0.01;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+fn octahedral_decode(p: vec2f) -> vec3f {
+    var n = vec3f(p, 1.0 - abs(p.x) - abs(p.y));
+    if n.z < 0.0 {
+        n = vec3f((1.0 - abs(n.yx)) * select(vec2f(-1.0), vec2f(1.0), n.xy >= vec2f(0.0)), n.z);
+    }
+    return normalize(n);
+}
+
+// Mean color and variance of the mean, it decreases as samples accumulate
+@fragment
+fn fs_prepare(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(frag_coord_4f.xy);
+    let collected = textureLoad(collector, pixel, 0);
+    let count = max(collected.a, 1.0);
+
+    let mean = collected.rgb / count;
+    let mean_luminance = luminance(mean);
+    let moment = textureLoad(features, pixel, 0).w / count;
+
+    return vec4f(mean, max(moment - mean_luminance * mean_luminance, 0.0) / count);
+} // fn fs_prepare
+
+// B3 spline kernel weight
+fn kernel_weight(offset: i32) -> f32 {
+    switch abs(offset) {
+        case 0: { return 3.0 / 8.0; }
+        case 1: { return 1.0 / 4.0; }
+        default: { return 1.0 / 16.0; }
+    }
+}
+
+// Variance blurred by 3x3 gaussian, raw one is too noisy to guide filter
+fn filtered_variance(source: texture_2d<f32>, pixel: vec2i, max_pixel: vec2i) -> f32 {
+    var variance = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let weight = 0.25 / f32(1u << u32(abs(x) + abs(y)));
+            variance += weight * textureLoad(source, clamp(pixel + vec2i(x, y), vec2i(0), max_pixel), 0).a;
+        }
+    }
+    return variance;
+}
+
+// Single à-trous iteration over color and variance of `source`, albedo `guide` also stops filter on texture edges.
+// Guide is a parameter, so pipelines without albedo binding can pass any texture with guiding disabled.
+fn filter_image(source: texture_2d<f32>, guide: texture_2d<f32>, pixel: vec2i, is_albedo_guided: bool) -> vec4f {
+    let max_pixel = vec2i(textureDimensions(source)) - 1;
+    let center = textureLoad(source, pixel, 0);
+    let center_features = textureLoad(features, pixel, 0);
+    let depth = center_features.z;
+
+    if depth == SKY_DEPTH {
+        return center;
+    }
+
+    let normal = octahedral_decode(center_features.xy);
+    let center_luminance = luminance(center.rgb);
+    let center_albedo = select(vec3f(0.0), textureLoad(guide, pixel, 0).rgb, is_albedo_guided);
+    let luminance_scale = SIGMA_LUMINANCE * sqrt(filtered_variance(source, pixel, max_pixel)) + 1e-6;
+    let step = i32(iteration.step);
+
+    var color_sum = vec3f(0.0);
+    var variance_sum = 0.0;
+    var weight_sum = 0.0;
+
+    for (var y = -2; y <= 2; y++) {
+        for (var x = -2; x <= 2; x++) {
+            let sample_pixel = pixel + vec2i(x, y) * step;
+            if any(sample_pixel < vec2i(0)) || any(sample_pixel > max_pixel) {
+                continue;
+            }
+
+            let sample_features = textureLoad(features, sample_pixel, 0);
+            if sample_features.z == SKY_DEPTH {
+                continue;
+            }
+            let sample = textureLoad(source, sample_pixel, 0);
+
+            let depth_weight = exp(-abs(depth - sample_features.z) / (SIGMA_DEPTH * depth * length(vec2f(vec2i(x, y) * step)) + 1e-6));
+            let normal_weight = pow(max(dot(normal, octahedral_decode(sample_features.xy)), 0.0), SIGMA_NORMAL);
+            let luminance_weight = exp(-abs(center_luminance - luminance(sample.rgb)) / luminance_scale);
+            var weight = kernel_weight(x) * kernel_weight(y) * depth_weight * normal_weight * luminance_weight;
+
+            if is_albedo_guided {
+                let albedo_difference = abs(center_albedo - textureLoad(guide, sample_pixel, 0).rgb);
+                weight *= exp(-max(albedo_difference.r, max(albedo_difference.g, albedo_difference.b)) / SIGMA_ALBEDO);
+            }
+
+            color_sum += sample.rgb * weight;
+            variance_sum += sample.a * weight * weight;
+            weight_sum += weight;
+        }
+    }
+
+    // Center tap always has nonzero weight
+    return vec4f(color_sum / weight_sum, variance_sum / (weight_sum * weight_sum));
+} // fn filter_image
+
+@fragment
+fn fs_iteration(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let filtered = filter_image(image, image, vec2i(frag_coord_4f.xy), false);
+
+    // Last iteration output is presented as accumulated image with unit sample count
+    return vec4f(filtered.rgb, select(filtered.a, 1.0, iteration.is_last != 0u));
+} // fn fs_iteration
+
+struct Components {
+    @location(0) irradiance: vec4f, // diffuse radiance divided by albedo and its variance
+    @location(1) specular: vec4f, // remaining radiance and its variance
+}
+
+fn demodulation_albedo(pixel: vec2i) -> vec3f {
+    return max(textureLoad(albedo, pixel, 0).rgb, vec3f(MIN_ALBEDO));
+}
+
+// Mean irradiance and specular radiance with variances of their means
+@fragment
+fn fs_prepare_components(@builtin(position) frag_coord_4f: vec4f) -> Components {
+    let pixel = vec2i(frag_coord_4f.xy);
+    let collected = textureLoad(collector, pixel, 0);
+    let count = max(collected.a, 1.0);
+
+    let mean = collected.rgb / count;
+    let mean_luminance = luminance(mean);
+    let moment = textureLoad(features, pixel, 0).w / count;
+    let variance = max(moment - mean_luminance * mean_luminance, 0.0) / count;
+
+    let diffuse_mean = textureLoad(diffuse, pixel, 0).rgb / count;
+    let specular_mean = max(mean - diffuse_mean, vec3f(0.0));
+    let albedo = demodulation_albedo(pixel);
+
+    // Only total luminance moment is accumulated, so variance is split by component luminance shares
+    let diffuse_share = clamp(luminance(diffuse_mean) / max(mean_luminance, 1e-6), 0.0, 1.0);
+    let albedo_luminance = luminance(albedo);
+
+    return Components(
+        vec4f(diffuse_mean / albedo, variance * diffuse_share * diffuse_share / (albedo_luminance * albedo_luminance)),
+        vec4f(specular_mean, variance * (1.0 - diffuse_share) * (1.0 - diffuse_share)),
+    );
+} // fn fs_prepare_components
+
+// Both components are filtered with shared geometry and albedo guides
+@fragment
+fn fs_iteration_components(@builtin(position) frag_coord_4f: vec4f) -> Components {
+    let pixel = vec2i(frag_coord_4f.xy);
+    return Components(filter_image(image, albedo, pixel, true), filter_image(specular_image, albedo, pixel, true));
+}
+
+// Filtered components recombined into image with unit sample count
+@fragment
+fn fs_combine(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(frag_coord_4f.xy);
+    let irradiance = textureLoad(image, pixel, 0).rgb;
+    let specular = textureLoad(specular_image, pixel, 0).rgb;
+    return vec4f(demodulation_albedo(pixel) * irradiance + specular, 1.0);
+}
+
+// file denoise.wgsl
@@ -0,0 +1,136 @@
+/// P_TR Project
+/// `File` render/glare.wgsl
+/// `Description` Aperture diffraction glare module, FFT convolution of image by diffraction kernel.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+const PI: f32 = 3.14159265358979;
+
+// Surface pixels per glare image pixel along each axis, matches glare.rs
+const GLARE_DOWNSAMPLE: u32 = 2;
+
+// Complex image, (Re R, Im R, Re G, Im G) and (Re B, Im B, 0, 0)
+struct Complex {
+    @location(0) rg: vec4f,
+    @location(1) b: vec4f,
+}
+
+struct Stage {
+    axis: u32, // 0 for rows, 1 for columns
+    span: u32, // butterfly span of FFT stage
+    bit_count: u32, // log2 of axis size at first stage of axis, input is read in bit-reversed order; 0 at other stages
+    is_inverse: u32,
+}
+
+struct Resolve {
+    strength: f32,
+}
+
+// Entry points bind different resources to the same slots
+@group(0) @binding(0) var light_collector: texture_2d<f32>;
+
+@group(0) @binding(0) var image_rg: texture_2d<f32>;
+@group(0) @binding(1) var image_b: texture_2d<f32>;
+
+@group(1) @binding(0) var<uniform> stage: Stage;
+@group(1) @binding(0) var<uniform> resolve: Resolve;
+
+@group(1) @binding(0) var kernel_rg: texture_2d<f32>;
+@group(1) @binding(1) var kernel_b: texture_2d<f32>;
+
+fn load_complex(position: vec2u) -> Complex {
+    return Complex(textureLoad(image_rg, position, 0), textureLoad(image_b, position, 0));
+}
+
+// Product of complex pairs stored in XY and ZW
+fn complex_mul(a: vec4f, b: vec4f) -> vec4f {
+    return vec4f(
+        a.x * b.x - a.y * b.y,
+        a.x * b.y + a.y * b.x,
+        a.z * b.z - a.w * b.w,
+        a.z * b.w + a.w * b.z,
+    );
+}
+
+// Mean presented radiance over GLARE_DOWNSAMPLE^2 block, zero in padding outside of image
+@fragment
+fn fs_downsample(@builtin(position) frag_coord_4f: vec4f) -> Complex {
+    let extent = textureDimensions(light_collector);
+    let origin = vec2u(frag_coord_4f.xy) * GLARE_DOWNSAMPLE;
+
+    var sum = vec3f(0.0);
+    for (var y = 0u; y < GLARE_DOWNSAMPLE; y++) {
+        for (var x = 0u; x < GLARE_DOWNSAMPLE; x++) {
+            let position = origin + vec2u(x, y);
+            if all(position < extent) {
+                let collected = textureLoad(light_collector, position, 0);
+                sum += collected.rgb / max(collected.a, 1.0);
+            }
+        }
+    }
+
+    let color = sum / f32(GLARE_DOWNSAMPLE * GLARE_DOWNSAMPLE);
+    return Complex(vec4f(color.r, 0.0, color.g, 0.0), vec4f(color.b, 0.0, 0.0, 0.0));
+} // fn fs_downsample
+
+// Radix-2 decimation in time FFT stage along pass axis, every output element is computed independently
+@fragment
+fn fs_fft(@builtin(position) frag_coord_4f: vec4f) -> Complex {
+    let position = vec2u(frag_coord_4f.xy);
+    let index = position[stage.axis];
+    let half_span = stage.span / 2u;
+    let offset = index % stage.span;
+    let is_upper = offset >= half_span;
+    let twiddle_index = offset % half_span;
+
+    // Even and odd elements of butterfly
+    var even_position = position;
+    var odd_position = position;
+    even_position[stage.axis] = index - offset + twiddle_index;
+    odd_position[stage.axis] = even_position[stage.axis] + half_span;
+
+    // The first stage reorders input
+    if stage.bit_count != 0u {
+        even_position[stage.axis] = reverseBits(even_position[stage.axis]) >> (32u - stage.bit_count);
+        odd_position[stage.axis] = reverseBits(odd_position[stage.axis]) >> (32u - stage.bit_count);
+    }
+
+    let angle = select(-2.0, 2.0, stage.is_inverse != 0u) * PI * f32(twiddle_index) / f32(stage.span);
+    let twiddle = vec4f(cos(angle), sin(angle), cos(angle), sin(angle));
+
+    let even = load_complex(even_position);
+    let odd = load_complex(odd_position);
+    let sign = select(1.0, -1.0, is_upper);
+
+    return Complex(
+        even.rg + sign * complex_mul(twiddle, odd.rg),
+        even.b + sign * complex_mul(twiddle, odd.b),
+    );
+} // fn fs_fft
+
+// Image spectrum multiplied by kernel one, convolution in spatial domain
+@fragment
+fn fs_multiply(@builtin(position) frag_coord_4f: vec4f) -> Complex {
+    let position = vec2u(frag_coord_4f.xy);
+    let image = load_complex(position);
+
+    return Complex(
+        complex_mul(image.rg, textureLoad(kernel_rg, position, 0)),
+        complex_mul(image.b, textureLoad(kernel_b, position, 0)),
+    );
+} // fn fs_multiply
+
+// Real part of convolved image, scaled by glare strength
+@fragment
+fn fs_resolve(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let image = load_complex(vec2u(frag_coord_4f.xy));
+
+    // Negative values are ringing of truncated kernel
+    return vec4f(max(vec3f(image.rg.x, image.rg.z, image.b.x) * resolve.strength, vec3f(0.0)), 1.0);
+} // fn fs_resolve
+
+// file glare.wgsl
@@ -0,0 +1,205 @@
+/// P_TR Project
+/// `File` render/integrator.wgsl
+/// `Description` Path tracing integrator module.
+/// `Author` TioT2
+
+// Integrator options specialized by pipeline, so their branches are compiled out instead of being taken per pixel
+override MAX_DEPTH: u32 = 8u; // maximal number of bounces
+override NEXT_EVENT_ESTIMATION: bool = true; // sample lights and environment at diffuse hits
+override SHOW_VERTEX_COLORS: bool = false; // primary hit vertex colors are presented instead of radiance
+override SHOW_FACE_ORIENTATION: bool = false; // camera hits are colored by surface side instead of radiance
+
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    return pdf * pdf / (pdf * pdf + other_pdf * other_pdf);
+}
+
+const OFFSET_ORIGIN: f32 = 1.0 / 32.0;
+const OFFSET_FLOAT_SCALE: f32 = 1.0 / 65536.0;
+const OFFSET_INT_SCALE: f32 = 256.0;
+
+// Wachter and Binder 'A Fast and Robust Method for Avoiding Self-Intersection'. Point is moved along
+// normal by several ulps, so offset follows coordinate magnitude; points near origin get fixed offset.
+fn offset_ray_origin(point: vec3f, normal: vec3f) -> vec3f {
+    let int_offset = vec3i(normal * OFFSET_INT_SCALE);
+    let int_point = bitcast<vec3f>(bitcast<vec3i>(point) + select(int_offset, -int_offset, point < vec3f(0.0)));
+
+    return select(int_point, point + normal * OFFSET_FLOAT_SCALE, abs(point) < vec3f(OFFSET_ORIGIN));
+}
+
+fn is_occluded(origin: vec3f, direction: vec3f) -> bool {
+    return intersect_scene(Ray(direction, origin)).is_hit;
+}
+
+fn is_occluded_within(origin: vec3f, direction: vec3f, max_distance: f32) -> bool {
+    let result = intersect_scene(Ray(direction, origin));
+    return result.is_hit && result.distance < max_distance;
+}
+
+// Clamp contribution of light that reached camera after `bounce_count` scattering events.
+// Direct lighting is kept as is, so clamping only darkens rare bright indirect paths.
+fn clamp_contribution(contribution: vec3f, bounce_count: u32) -> vec3f {
+    let max_component = max(contribution.x, max(contribution.y, contribution.z));
+
+    if bounce_count < 2u || system.max_indirect_radiance <= 0.0 || max_component <= system.max_indirect_radiance {
+        return contribution;
+    }
+    return contribution * (system.max_indirect_radiance / max_component);
+}
+
+const FACE_ORIENTATION_FRONT_COLOR: vec3f = vec3f(0.1, 0.3, 0.9);
+const FACE_ORIENTATION_BACK_COLOR: vec3f = vec3f(0.9, 0.1, 0.1);
+
+fn trace(init_ray: Ray) -> Trace {
+    var ray_color = vec3f(1.0, 1.0, 1.0);
+    var incoming_light = vec3f(0.0, 0.0, 0.0);
+    var ray = init_ray;
+
+    // Radiance gathered before primary hit scattering, path is diffuse if its first scattering is
+    var primary_light = vec3f(0.0);
+    var is_diffuse = false;
+
+    var depth = 0u;
+
+    // Pdf of the last diffuse scattering, zero after other lobes
+    var scatter_pdf = 0.0;
+
+    // Minimal roughness of glossy lobes, raised after diffuse scattering
+    var min_roughness = 0.0;
+
+    while depth <= MAX_DEPTH {
+        let result = intersect_scene(ray);
+
+        // Vertex color view shows camera hits unlit
+        if SHOW_VERTEX_COLORS {
+            return Trace(select(vec3f(0.0), result.vertex_color, result.is_hit), vec3f(0.0));
+        }
+
+        // Face orientation view shows sides as seen by shading, after material backface mode
+        if SHOW_FACE_ORIENTATION {
+            let side_color = select(FACE_ORIENTATION_BACK_COLOR, FACE_ORIENTATION_FRONT_COLOR, result.is_front_face);
+            return Trace(select(vec3f(0.0), side_color * (0.3 + 0.7 * abs(dot(result.normal, ray.direction))), result.is_hit), vec3f(0.0));
+        }
+
+        if !result.is_hit {
+            // Diffuse scattering directions are weighted against environment (or sun) sampling
+            var weight = 1.0;
+
+            if is_sky_enabled() {
+                if scatter_pdf > 0.0 {
+                    weight = power_heuristic(scatter_pdf, sun_pdf());
+                }
+
+                incoming_light += clamp_contribution((sky_radiance(ray.direction) + sun_radiance_at(ray.direction) * weight) * ray_color, depth);
+                record_vertex(ray.origin, PATH_EVENT_ESCAPED, ray.direction, ray_color, incoming_light);
+                break;
+            }
+
+            if scatter_pdf > 0.0 && environment.is_enabled != 0u {
+                weight = power_heuristic(scatter_pdf, environment_pdf(ray.direction));
+            }
+
+            incoming_light += clamp_contribution(environment_radiance_at(ray.direction) * ray_color * weight, depth);
+            record_vertex(ray.origin, PATH_EVENT_ESCAPED, ray.direction, ray_color, incoming_light);
+            break;
+        }
+
+        var material = materials[result.material_index];
+        let normal = apply_material_maps(&material, result.uv, result.normal, result.tangent, result.custom_data, result.vertex_color);
+        let hit_point = ray.origin + ray.direction * result.distance;
+        let color = apply_decals(hit_point, result.normal, material.color);
+
+        material.roughness = max(material.roughness, min_roughness);
+        material.clearcoat_roughness = max(material.clearcoat_roughness, min_roughness);
+
+        // Lights hit by diffuse scattering are weighted against light sampling
+        var emission_weight = 1.0;
+        if scatter_pdf > 0.0 && result.light_area_pdf > 0.0 {
+            let cos_light = abs(dot(result.normal, ray.direction));
+            emission_weight = power_heuristic(scatter_pdf, result.light_area_pdf * result.distance * result.distance / max(cos_light, 1e-6));
+        }
+
+        incoming_light += clamp_contribution(material.emission * ray_color * emission_weight, depth);
+
+        let scatter = material_scatter(material, color, normal, ray.direction, result.is_front_face);
+
+        if !scatter.is_scattered {
+            record_vertex(hit_point, PATH_EVENT_ABSORBED, ray.direction, vec3f(0.0), incoming_light);
+            break;
+        }
+
+        if depth == 0u {
+            primary_light = incoming_light;
+            is_diffuse = scatter.pdf > 0.0;
+        }
+
+        // Next event estimation of environment (or sun) for diffuse scattering
+        if NEXT_EVENT_ESTIMATION && scatter.pdf > 0.0 && (environment.is_enabled != 0u || is_sky_enabled()) {
+            var light: EnvironmentSample;
+            if environment.is_enabled != 0u {
+                light = sample_environment();
+            } else {
+                light = sample_sun();
+            }
+            let cos_theta = dot(normal, light.direction);
+
+            if cos_theta > 0.0 && light.pdf > 0.0 && dot(result.normal, light.direction) > 0.0 && !is_occluded(offset_ray_origin(hit_point, result.geometric_normal), light.direction) {
+                let weight = power_heuristic(light.pdf, cos_theta / PI);
+                incoming_light += clamp_contribution(ray_color * color / PI * cos_theta * light.radiance / light.pdf * weight, depth + 1u);
+            }
+        }
+
+        // Next event estimation of scene lights
+        if NEXT_EVENT_ESTIMATION && scatter.pdf > 0.0 && has_lights() {
+            let light = sample_light();
+            let origin = offset_ray_origin(hit_point, result.geometric_normal);
+            let to_light = light.point - origin;
+            let distance = length(to_light);
+            let direction = to_light / distance;
+            let cos_theta = dot(normal, direction);
+            let cos_light = abs(dot(light.normal, direction));
+
+            if cos_theta > 0.0 && cos_light > 0.0 && dot(result.normal, direction) > 0.0 && !is_occluded_within(origin, direction, distance - system.intersection_tolerance) {
+                let pdf = light.area_pdf * distance * distance / cos_light;
+                let weight = power_heuristic(pdf, cos_theta / PI);
+                incoming_light += clamp_contribution(ray_color * color / PI * cos_theta * light.radiance / pdf * weight, depth + 1u);
+            }
+        }
+
+        if RECORD_PATH {
+            var event = PATH_EVENT_DIFFUSE;
+            if scatter.pdf <= 0.0 {
+                let is_transmitted = dot(scatter.direction, result.geometric_normal) * dot(ray.direction, result.geometric_normal) > 0.0;
+                event = select(PATH_EVENT_REFLECTION, PATH_EVENT_TRANSMISSION, is_transmitted);
+            }
+            record_vertex(hit_point, event, scatter.direction, ray_color * scatter.weight, incoming_light);
+        }
+
+        // Transmitted rays continue from the other side of the surface
+        ray.origin = offset_ray_origin(hit_point, result.geometric_normal * sign(dot(scatter.direction, result.geometric_normal)));
+        ray.direction = scatter.direction;
+        ray_color *= scatter.weight;
+        // Without light sampling, hits of lights by scattering are the only estimate and are kept unweighted
+        scatter_pdf = select(0.0, scatter.pdf, NEXT_EVENT_ESTIMATION);
+        if scatter.pdf > 0.0 {
+            min_roughness = system.min_indirect_roughness;
+        }
+
+        // Russian roulette, low-throughput paths survive with probability proportional to throughput
+        if depth >= system.rr_start_depth && system.rr_threshold > 0.0 {
+            let survival = max(ray_color.x, max(ray_color.y, ray_color.z)) / system.rr_threshold;
+
+            if survival < 1.0 {
+                if rand_f32() >= survival {
+                    break;
+                }
+                ray_color /= survival;
+            }
+        }
+
+        depth = depth + 1;
+    }
+
+    return Trace(incoming_light, select(vec3f(0.0), incoming_light - primary_light, SEPARATE_DIFFUSE && is_diffuse));
+}
+
+// file integrator.wgsl
@@ -0,0 +1,860 @@
+/// P_TR Project
+/// `File` render/intersect.wgsl
+/// `Description` Ray/scene intersection module.
+/// `Author` TioT2
+
+struct BvhNode {
+    min: vec3f,
+    offset: u32, // right child index for inner nodes, first triangle index for leaves
+    max: vec3f,
+    count: u32, // zero for inner nodes
+}
+
+struct Triangle {
+    v0: vec3f,
+    v1: vec3f,
+    v2: vec3f,
+    n0: vec3f,
+    n1: vec3f,
+    n2: vec3f,
+    uv0: vec2f,
+    uv1: vec2f,
+    uv2: vec2f,
+    c0: u32, // sRGB RGBA8 vertex colors
+    c1: u32,
+    c2: u32,
+}
+
+struct Instance {
+    world_to_object: mat4x4f,
+    object_to_world: mat4x4f,
+    previous_object_to_world: mat4x4f, // transform at previous frame, for motion blur
+    blas_root: u32, // instance mesh BVH root node index
+    material_index: u32,
+    id: u32, // index of instance in scene
+    light_area_pdf: f32, // area sampling pdf of emitting instance, zero if instance is not a light
+    custom_data: vec4f, // user data for shading
+}
+
+// Node array starts with top-level BVH over instances, followed by mesh BVHs
+@group(2) @binding(0) var<storage, read> bvh_nodes: array<BvhNode>;
+@group(2) @binding(1) var<storage, read> triangles: array<Triangle>;
+@group(2) @binding(2) var<storage, read> instances: array<Instance>;
+
+struct Ray {
+    direction: vec3f,
+    origin: vec3f,
+}
+
+
+const PRIMITIVE_KIND_SPHERE: u32 = 1;
+const PRIMITIVE_KIND_PLANE: u32 = 2;
+const PRIMITIVE_KIND_BOX: u32 = 3;
+const PRIMITIVE_KIND_CYLINDER: u32 = 4;
+const PRIMITIVE_KIND_CONE: u32 = 5;
+const PRIMITIVE_KIND_CAPSULE: u32 = 6;
+const PRIMITIVE_KIND_TORUS: u32 = 7;
+
+struct Primitive {
+    p0: vec3f,
+    r0: f32,
+    p1: vec3f,
+    r1: f32,
+    kind: u32,
+    material_index: u32,
+    light_area_pdf: f32, // area sampling pdf of emitting primitive, zero if primitive is not a light
+}
+
+@group(2) @binding(4) var<storage, read> primitives: array<Primitive>;
+
+struct PrimitiveIntersectResult {
+    normal: vec3f,
+    distance: f32,
+    uv: vec2f,
+    is_hit: bool,
+}
+
+fn primitive_no_hit() -> PrimitiveIntersectResult {
+    var result: PrimitiveIntersectResult;
+    result.is_hit = false;
+    return result;
+}
+
+fn dot2(v: vec3f) -> f32 {
+    return dot(v, v);
+}
+
+// Duff et al. 'Building an Orthonormal Basis, Revisited'. Third column is the normal itself.
+fn orthonormal_basis(n: vec3f) -> mat3x3f {
+    let s = select(-1.0, 1.0, n.z >= 0.0);
+    let a = -1.0 / (s + n.z);
+    let b = n.x * n.y * a;
+    return mat3x3f(
+        vec3f(1.0 + s * n.x * n.x * a, s * b, -s * n.x),
+        vec3f(b, s + n.y * n.y * a, -n.y),
+        n
+    );
+}
+
+// Angle around segment axis and relative height along it
+fn axial_uv(point: vec3f, a: vec3f, b: vec3f) -> vec2f {
+    let ba = b - a;
+    let basis = orthonormal_basis(normalize(ba));
+    let local = (point - a) * basis;
+    return vec2f(atan2(local.y, local.x) / (2.0 * PI) + 0.5, dot(point - a, ba) / dot(ba, ba));
+}
+
+fn sphere_intersect_check(center: vec3f, radius: f32, ray: Ray) -> PrimitiveIntersectResult {
+    let oc = ray.origin - center;
+    let b = dot(oc, ray.direction);
+    let c = dot(oc, oc) - radius * radius;
+    var h = b * b - c;
+
+    if h < 0.0 {
+        return primitive_no_hit();
+    }
+    h = sqrt(h);
+
+    var result: PrimitiveIntersectResult;
+    result.distance = select(-b + h, -b - h, -b - h > system.ray_min_distance);
+    result.is_hit = result.distance > system.ray_min_distance;
+    result.normal = (oc + ray.direction * result.distance) / radius;
+    result.uv = vec2f(atan2(result.normal.z, result.normal.x) / (2.0 * PI) + 0.5, acos(clamp(result.normal.y, -1.0, 1.0)) / PI);
+    return result;
+}
+
+fn plane_intersect_check(point: vec3f, normal: vec3f, extent: f32, ray: Ray) -> PrimitiveIntersectResult {
+    var result: PrimitiveIntersectResult;
+
+    result.distance = dot(point - ray.origin, normal) / dot(normal, ray.direction);
+    result.normal = normal;
+
+    let basis = orthonormal_basis(normal);
+    let local = (ray.origin + ray.direction * result.distance - point) * basis;
+    result.uv = local.xy;
+    result.is_hit = result.distance > system.ray_min_distance && (extent <= 0.0 || max(abs(local.x), abs(local.y)) <= extent);
+    return result;
+}
+
+fn box_intersect_check(p0: vec3f, p1: vec3f, ray: Ray) -> PrimitiveIntersectResult {
+    let utv0 = (p0 - ray.origin) / ray.direction;
+    let utv1 = (p1 - ray.origin) / ray.direction;
+    let tv0 = min(utv0, utv1);
+    let tv1 = max(utv0, utv1);
+    let t_near = max(max(tv0.x, tv0.y), tv0.z);
+    let t_far = min(min(tv1.x, tv1.y), tv1.z);
+
+    var result: PrimitiveIntersectResult;
+    result.is_hit = t_far >= max(t_near, system.ray_min_distance);
+    result.distance = select(t_far, t_near, t_near > system.ray_min_distance);
+    result.normal = select(
+        vec3f(tv1 == vec3f(t_far)) * sign(ray.direction),
+        vec3f(tv0 == vec3f(t_near)) * -sign(ray.direction),
+        t_near > system.ray_min_distance
+    );
+
+    let local = (ray.origin + ray.direction * result.distance - p0) / (p1 - p0);
+    let axis = abs(result.normal);
+    result.uv = select(select(local.xy, local.xz, axis.y > 0.5), local.zy, axis.x > 0.5);
+    return result;
+}
+
+// Capped cylinder, Inigo Quilez
+fn cylinder_intersect_check(a: vec3f, b: vec3f, radius: f32, ray: Ray) -> PrimitiveIntersectResult {
+    let ba = b - a;
+    let oc = ray.origin - a;
+    let baba = dot(ba, ba);
+    let bard = dot(ba, ray.direction);
+    let baoc = dot(ba, oc);
+    let k2 = baba - bard * bard;
+    let k1 = baba * dot(oc, ray.direction) - baoc * bard;
+    let k0 = baba * dot(oc, oc) - baoc * baoc - radius * radius * baba;
+    var h = k1 * k1 - k2 * k0;
+
+    if h < 0.0 {
+        return primitive_no_hit();
+    }
+    h = sqrt(h);
+
+    var result: PrimitiveIntersectResult;
+
+    // Body, nearest and farthest hits for rays started inside
+    for (var i = 0; i < 2; i++) {
+        let t = (-k1 + select(h, -h, i == 0)) / k2;
+        let y = baoc + t * bard;
+
+        if t > system.ray_min_distance && y > 0.0 && y < baba {
+            result.distance = t;
+            result.normal = (oc + ray.direction * t - ba * y / baba) / radius;
+            result.is_hit = true;
+            break;
+        }
+    }
+
+    // Caps
+    for (var i = 0; i < 2; i++) {
+        let t = (select(baba, 0.0, i == 0) - baoc) / bard;
+
+        if t > system.ray_min_distance && abs(k1 + k2 * t) < h && (!result.is_hit || t < result.distance) {
+            result.distance = t;
+            result.normal = ba * select(1.0, -1.0, i == 0) / sqrt(baba);
+            result.is_hit = true;
+        }
+    }
+
+    result.uv = axial_uv(ray.origin + ray.direction * result.distance, a, b);
+    return result;
+}
+
+// Capped cone, Inigo Quilez
+fn cone_intersect_check(pa: vec3f, pb: vec3f, ra: f32, rb: f32, ray: Ray) -> PrimitiveIntersectResult {
+    let ba = pb - pa;
+    let oa = ray.origin - pa;
+    let ob = ray.origin - pb;
+    let m0 = dot(ba, ba);
+    let m1 = dot(oa, ba);
+    let m2 = dot(ray.direction, ba);
+    let m3 = dot(ray.direction, oa);
+    let m5 = dot(oa, oa);
+    let m9 = dot(ob, ba);
+
+    var result: PrimitiveIntersectResult;
+
+    // Caps
+    if m1 < 0.0 {
+        if dot2(oa * m2 - ray.direction * m1) < ra * ra * m2 * m2 && -m1 / m2 > system.ray_min_distance {
+            result.distance = -m1 / m2;
+            result.normal = -ba * inverseSqrt(m0);
+            result.is_hit = true;
+        }
+    } else if m9 > 0.0 {
+        let t = -m9 / m2;
+        if dot2(ob + ray.direction * t) < rb * rb && t > system.ray_min_distance {
+            result.distance = t;
+            result.normal = ba * inverseSqrt(m0);
+            result.is_hit = true;
+        }
+    }
+
+    if !result.is_hit {
+        // Body
+        let rr = ra - rb;
+        let hy = m0 + rr * rr;
+        let k2 = m0 * m0 - m2 * m2 * hy;
+        let k1 = m0 * m0 * m3 - m1 * m2 * hy + m0 * ra * (rr * m2 * 1.0);
+        let k0 = m0 * m0 * m5 - m1 * m1 * hy + m0 * ra * (rr * m1 * 2.0 - m0 * ra);
+        let h = k1 * k1 - k2 * k0;
+
+        if h < 0.0 {
+            return primitive_no_hit();
+        }
+
+        let t = (-k1 - sqrt(h)) / k2;
+        let y = m1 + t * m2;
+
+        if t <= system.ray_min_distance || y < 0.0 || y > m0 {
+            return primitive_no_hit();
+        }
+
+        result.distance = t;
+        result.normal = normalize(m0 * (m0 * (oa + t * ray.direction) + rr * ba * ra) - ba * hy * y);
+        result.is_hit = true;
+    }
+
+    result.uv = axial_uv(ray.origin + ray.direction * result.distance, pa, pb);
+    return result;
+}
+
+// Capsule, Inigo Quilez
+fn capsule_intersect_check(pa: vec3f, pb: vec3f, radius: f32, ray: Ray) -> PrimitiveIntersectResult {
+    let ba = pb - pa;
+    let oa = ray.origin - pa;
+    let baba = dot(ba, ba);
+    let bard = dot(ba, ray.direction);
+    let baoa = dot(ba, oa);
+    let rdoa = dot(ray.direction, oa);
+    let oaoa = dot(oa, oa);
+    let a = baba - bard * bard;
+    var b = baba * rdoa - baoa * bard;
+    var c = baba * oaoa - baoa * baoa - radius * radius * baba;
+    var h = b * b - a * c;
+
+    if h < 0.0 {
+        return primitive_no_hit();
+    }
+
+    var result: PrimitiveIntersectResult;
+    var t = (-b - sqrt(h)) / a;
+    let y = baoa + t * bard;
+
+    if y > 0.0 && y < baba {
+        // Body
+        result.distance = t;
+    } else {
+        // Caps
+        let oc = select(ray.origin - pb, oa, y <= 0.0);
+        b = dot(ray.direction, oc);
+        c = dot(oc, oc) - radius * radius;
+        h = b * b - c;
+        if h <= 0.0 {
+            return primitive_no_hit();
+        }
+        result.distance = -b - sqrt(h);
+    }
+
+    let point = ray.origin + ray.direction * result.distance;
+    let pap = point - pa;
+    result.normal = (pap - clamp(dot(pap, ba) / baba, 0.0, 1.0) * ba) / radius;
+    result.is_hit = result.distance > system.ray_min_distance;
+    result.uv = axial_uv(point, pa, pb);
+    return result;
+}
+
+// Torus around local Z axis, Inigo Quilez
+fn torus_intersect_local(ro: vec3f, rd: vec3f, major_radius: f32, minor_radius: f32) -> f32 {
+    var po = 1.0;
+    let ra2 = major_radius * major_radius;
+    let rb2 = minor_radius * minor_radius;
+    let m = dot(ro, ro);
+    let n = dot(ro, rd);
+
+    // Bounding sphere
+    if n * n - m + (major_radius + minor_radius) * (major_radius + minor_radius) < 0.0 {
+        return -1.0;
+    }
+
+    // Quartic equation
+    let k = (m - rb2 - ra2) / 2.0;
+    var k3 = n;
+    var k2 = n * n + ra2 * rd.z * rd.z + k;
+    var k1 = k * n + ra2 * ro.z * rd.z;
+    var k0 = k * k + ra2 * ro.z * ro.z - ra2 * rb2;
+
+    // Prevent |c1| from being too close to zero
+    if abs(k3 * (k3 * k3 - k2) + k1) < 0.01 {
+        po = -1.0;
+        let tmp = k1;
+        k1 = k3;
+        k3 = tmp;
+        k0 = 1.0 / k0;
+        k1 = k1 * k0;
+        k2 = k2 * k0;
+        k3 = k3 * k0;
+    }
+
+    var c2 = 2.0 * k2 - 3.0 * k3 * k3;
+    var c1 = k3 * (k3 * k3 - k2) + k1;
+    var c0 = k3 * (k3 * (-3.0 * k3 * k3 + 4.0 * k2) - 8.0 * k1) + 4.0 * k0;
+    c2 /= 3.0;
+    c1 *= 2.0;
+    c0 /= 3.0;
+    let q = c2 * c2 + c0;
+    let r = 3.0 * c0 * c2 - c2 * c2 * c2 - c1 * c1;
+    var h = r * r - q * q * q;
+    var z = 0.0;
+
+    if h < 0.0 {
+        let sq = sqrt(q);
+        z = 2.0 * sq * cos(acos(r / (sq * q)) / 3.0);
+    } else {
+        let sq = pow(sqrt(h) + abs(r), 1.0 / 3.0);
+        z = sign(r) * abs(sq + q / sq);
+    }
+    z = c2 - z;
+
+    var d1 = z - 3.0 * c2;
+    var d2 = z * z - 3.0 * c0;
+
+    if abs(d1) < 1.0e-4 {
+        if d2 < 0.0 {
+            return -1.0;
+        }
+        d2 = sqrt(d2);
+    } else {
+        if d1 < 0.0 {
+            return -1.0;
+        }
+        d1 = sqrt(d1 / 2.0);
+        d2 = c1 / d1;
+    }
+
+    var result = 1e20;
+
+    h = d1 * d1 - z + d2;
+    if h > 0.0 {
+        h = sqrt(h);
+        var t1 = -d1 - h - k3;
+        var t2 = -d1 + h - k3;
+        t1 = select(t1, 2.0 / t1, po < 0.0);
+        t2 = select(t2, 2.0 / t2, po < 0.0);
+        if t1 > system.ray_min_distance { result = t1; }
+        if t2 > system.ray_min_distance { result = min(result, t2); }
+    }
+
+    h = d1 * d1 - z - d2;
+    if h > 0.0 {
+        h = sqrt(h);
+        var t1 = d1 - h - k3;
+        var t2 = d1 + h - k3;
+        t1 = select(t1, 2.0 / t1, po < 0.0);
+        t2 = select(t2, 2.0 / t2, po < 0.0);
+        if t1 > system.ray_min_distance { result = min(result, t1); }
+        if t2 > system.ray_min_distance { result = min(result, t2); }
+    }
+
+    return select(result, -1.0, result >= 1e20);
+}
+
+fn torus_intersect_check(center: vec3f, axis: vec3f, major_radius: f32, minor_radius: f32, ray: Ray) -> PrimitiveIntersectResult {
+    let basis = orthonormal_basis(axis);
+    let ro = (ray.origin - center) * basis;
+    let rd = ray.direction * basis;
+    let t = torus_intersect_local(ro, rd, major_radius, minor_radius);
+
+    if t <= 0.0 {
+        return primitive_no_hit();
+    }
+
+    let pos = ro + rd * t;
+    let local_normal = normalize(pos * (dot(pos, pos) - minor_radius * minor_radius - major_radius * major_radius * vec3f(1.0, 1.0, -1.0)));
+
+    var result: PrimitiveIntersectResult;
+    result.distance = t;
+    result.normal = basis * local_normal;
+    result.uv = vec2f(
+        atan2(pos.y, pos.x) / (2.0 * PI) + 0.5,
+        atan2(pos.z, length(pos.xy) - major_radius) / (2.0 * PI) + 0.5,
+    );
+    result.is_hit = true;
+    return result;
+}
+
+fn primitive_intersect_check(primitive: Primitive, ray: Ray) -> PrimitiveIntersectResult {
+    switch primitive.kind {
+        case PRIMITIVE_KIND_SPHERE: { return sphere_intersect_check(primitive.p0, primitive.r0, ray); }
+        case PRIMITIVE_KIND_PLANE: { return plane_intersect_check(primitive.p0, primitive.p1, primitive.r0, ray); }
+        case PRIMITIVE_KIND_BOX: { return box_intersect_check(primitive.p0, primitive.p1, ray); }
+        case PRIMITIVE_KIND_CYLINDER: { return cylinder_intersect_check(primitive.p0, primitive.p1, primitive.r0, ray); }
+        case PRIMITIVE_KIND_CONE: { return cone_intersect_check(primitive.p0, primitive.p1, primitive.r0, primitive.r1, ray); }
+        case PRIMITIVE_KIND_CAPSULE: { return capsule_intersect_check(primitive.p0, primitive.p1, primitive.r0, ray); }
+        case PRIMITIVE_KIND_TORUS: { return torus_intersect_check(primitive.p0, primitive.p1, primitive.r0, primitive.r1, ray); }
+        default: { return primitive_no_hit(); }
+    }
+}
+
+struct TriangleIntersectResult {
+    normal: vec3f,
+    distance: f32,
+    geometric_normal: vec3f, // unit normal on the side defined by winding
+
+    tangent: vec4f, // w is bitangent sign
+    uv: vec2f,
+    barycentric: vec2f, // weights of v1 and v2
+    is_hit: bool,
+}
+
+struct TriangleHit {
+    barycentric: vec2f, // weights of v1 and v2
+    distance: f32,
+    is_hit: bool,
+}
+
+// Moller-Trumbore ray/triangle intersection
+fn moller_trumbore_hit(triangle: Triangle, ray: Ray) -> TriangleHit {
+    var hit: TriangleHit;
+    hit.is_hit = false;
+
+    let edge1 = triangle.v1 - triangle.v0;
+    let edge2 = triangle.v2 - triangle.v0;
+    let p = cross(ray.direction, edge2);
+    let det = dot(edge1, p);
+
+    if abs(det) < system.intersection_tolerance * system.intersection_tolerance {
+        return hit;
+    }
+
+    let inv_det = 1.0 / det;
+    let t = ray.origin - triangle.v0;
+    let u = dot(t, p) * inv_det;
+    let q = cross(t, edge1);
+    let v = dot(ray.direction, q) * inv_det;
+
+    hit.barycentric = vec2f(u, v);
+    hit.distance = dot(edge2, q) * inv_det;
+    hit.is_hit = u >= 0.0 && v >= 0.0 && u + v <= 1.0 && hit.distance > system.ray_min_distance;
+    return hit;
+}
+
+// Woop et al. 'Watertight Ray/Triangle Intersection'. Edge tests are done in 2D space of ray
+// and are exact for shared edges, so rays can't leak between adjacent triangles.
+fn watertight_hit(triangle: Triangle, ray: Ray) -> TriangleHit {
+    var hit: TriangleHit;
+    hit.is_hit = false;
+
+    // Ray direction dominant axis becomes Z, winding is kept by swapping X and Y for negative Z
+    let d = abs(ray.direction);
+    var kz = 2u;
+    if d.x > d.y && d.x > d.z {
+        kz = 0u;
+    } else if d.y > d.z {
+        kz = 1u;
+    }
+    var kx = (kz + 1u) % 3u;
+    var ky = (kx + 1u) % 3u;
+    if ray.direction[kz] < 0.0 {
+        let k = kx;
+        kx = ky;
+        ky = k;
+    }
+
+    let shear = vec3f(ray.direction[kx], ray.direction[ky], 1.0) / ray.direction[kz];
+    let a = triangle.v0 - ray.origin;
+    let b = triangle.v1 - ray.origin;
+    let c = triangle.v2 - ray.origin;
+    let ax = a[kx] - shear.x * a[kz];
+    let ay = a[ky] - shear.y * a[kz];
+    let bx = b[kx] - shear.x * b[kz];
+    let by = b[ky] - shear.y * b[kz];
+    let cx = c[kx] - shear.x * c[kz];
+    let cy = c[ky] - shear.y * c[kz];
+
+    // Scaled barycentric coordinates
+    let u = cx * by - cy * bx;
+    let v = ax * cy - ay * cx;
+    let w = bx * ay - by * ax;
+
+    if (u < 0.0 || v < 0.0 || w < 0.0) && (u > 0.0 || v > 0.0 || w > 0.0) {
+        return hit;
+    }
+
+    let det = u + v + w;
+    if det == 0.0 {
+        return hit;
+    }
+
+    let t = (u * a[kz] + v * b[kz] + w * c[kz]) * shear.z;
+
+    hit.barycentric = vec2f(v, w) / det;
+    hit.distance = t / det;
+    hit.is_hit = hit.distance > system.ray_min_distance;
+    return hit;
+}
+
+fn triangle_intersect_check(triangle: Triangle, ray: Ray) -> TriangleIntersectResult {
+    var result: TriangleIntersectResult;
+
+    var hit: TriangleHit;
+    if system.watertight_intersection != 0u {
+        hit = watertight_hit(triangle, ray);
+    } else {
+        hit = moller_trumbore_hit(triangle, ray);
+    }
+
+    result.is_hit = hit.is_hit;
+    if !hit.is_hit {
+        return result;
+    }
+
+    result.distance = hit.distance;
+    let u = hit.barycentric.x;
+    let v = hit.barycentric.y;
+
+    // Shading normal is interpolated and put to the side defined by winding
+    let edge1 = triangle.v1 - triangle.v0;
+    let edge2 = triangle.v2 - triangle.v0;
+    let geometric_normal = cross(edge1, edge2);
+    let shading_normal = normalize(triangle.n0 * (1.0 - u - v) + triangle.n1 * u + triangle.n2 * v);
+    result.normal = shading_normal * sign(dot(shading_normal, geometric_normal));
+    result.geometric_normal = normalize(geometric_normal);
+    result.uv = triangle.uv0 * (1.0 - u - v) + triangle.uv1 * u + triangle.uv2 * v;
+    result.barycentric = vec2f(u, v);
+
+    return result;
+}
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    return select(pow((color + 0.055) / 1.055, vec3f(2.4)), color / 12.92, color <= vec3f(0.04045));
+}
+
+// Linear vertex color interpolated at barycentric point
+fn triangle_vertex_color(triangle: Triangle, barycentric: vec2f) -> vec3f {
+    let c0 = srgb_to_linear(unpack4x8unorm(triangle.c0).rgb);
+    let c1 = srgb_to_linear(unpack4x8unorm(triangle.c1).rgb);
+    let c2 = srgb_to_linear(unpack4x8unorm(triangle.c2).rgb);
+
+    return c0 * (1.0 - barycentric.x - barycentric.y) + c1 * barycentric.x + c2 * barycentric.y;
+}
+
+// Tangent along U texture axis, arbitrary one for triangles without texture coordinates
+fn triangle_tangent(triangle: Triangle, normal: vec3f) -> vec4f {
+    let edge1 = triangle.v1 - triangle.v0;
+    let edge2 = triangle.v2 - triangle.v0;
+    let duv1 = triangle.uv1 - triangle.uv0;
+    let duv2 = triangle.uv2 - triangle.uv0;
+    let det = duv1.x * duv2.y - duv1.y * duv2.x;
+
+    if abs(det) < 1e-12 {
+        return vec4f(orthonormal_basis(normal)[0], 1.0);
+    }
+
+    let tangent = (edge1 * duv2.y - edge2 * duv1.y) / det;
+    let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / det;
+    return vec4f(tangent, sign(dot(cross(normal, tangent), bitangent)));
+}
+
+// Distance to BVH node bounds or 'no hit' distance
+fn bvh_node_distance(node: BvhNode, ray: Ray, inv_direction: vec3f) -> f32 {
+    let utv0 = (node.min - ray.origin) * inv_direction;
+    let utv1 = (node.max - ray.origin) * inv_direction;
+    let t_near = max(max(min(utv0.x, utv1.x), min(utv0.y, utv1.y)), min(utv0.z, utv1.z));
+    let t_far = min(min(max(utv0.x, utv1.x), max(utv0.y, utv1.y)), max(utv0.z, utv1.z));
+
+    return select(BVH_NO_HIT, max(t_near, 0.0), t_far >= max(t_near, 0.0));
+}
+
+const BVH_NO_HIT: f32 = 1e30;
+// BVH depth is limited by builder so traversal never overflows stack, see `accel::MAX_DEPTH`
+const BVH_STACK_SIZE: u32 = 32;
+
+struct BlasIntersectionResult {
+    normal: vec3f,
+    distance: f32,
+    geometric_normal: vec3f,
+    tangent: vec4f,
+    uv: vec2f,
+    barycentric: vec2f,
+    vertex_color: vec3f,
+    triangle_index: u32,
+    is_hit: bool,
+}
+
+// Traverse mesh BVH in object space, back faces are skipped if `cull_backfaces` is set
+fn intersect_blas(root: u32, ray: Ray, max_distance: f32, cull_backfaces: bool) -> BlasIntersectionResult {
+    var result: BlasIntersectionResult;
+    result.is_hit = false;
+    result.distance = max_distance;
+
+    let inv_direction = 1.0 / ray.direction;
+    var stack: array<u32, BVH_STACK_SIZE>;
+    // Root of empty BVH has inverted bounds, slab test would swap its planes into unbounded box
+    var stack_size = select(0u, 1u, all(bvh_nodes[root].min <= bvh_nodes[root].max));
+    stack[0] = root;
+
+    while stack_size > 0u {
+        stack_size -= 1u;
+        let node_index = stack[stack_size];
+        let node = bvh_nodes[node_index];
+
+        if bvh_node_distance(node, ray, inv_direction) >= result.distance {
+            continue;
+        }
+
+        if node.count != 0u {
+            for (var i = node.offset; i < node.offset + node.count; i++) {
+                let t = triangle_intersect_check(triangles[i], ray);
+
+                // Triangle normal points to the side defined by winding
+                if t.is_hit && t.distance < result.distance && !(cull_backfaces && dot(t.normal, ray.direction) > 0.0) {
+                    result.is_hit = true;
+                    result.distance = t.distance;
+                    result.normal = t.normal;
+                    result.geometric_normal = t.geometric_normal;
+                    result.uv = t.uv;
+                    result.barycentric = t.barycentric;
+                    result.triangle_index = i;
+                }
+            }
+        } else {
+            stack[stack_size] = node.offset;
+            stack[stack_size + 1u] = node_index + 1u;
+            stack_size += 2u;
+        }
+    }
+
+    // Tangent and vertex color are required for the closest hit only
+    if result.is_hit {
+        let triangle = triangles[result.triangle_index];
+        result.tangent = triangle_tangent(triangle, result.normal);
+        result.vertex_color = triangle_vertex_color(triangle, result.barycentric);
+    }
+
+    return result;
+}
+
+struct InstanceIntersectionResult {
+    normal: vec3f,
+    distance: f32,
+    geometric_normal: vec3f,
+    tangent: vec4f,
+    uv: vec2f,
+    vertex_color: vec3f,
+    instance_index: u32,
+    triangle_index: u32, // index in triangles array
+    is_hit: bool,
+}
+
+// Traverse top-level BVH and intersect mesh instances
+// Time of current path within frame, 0 is previous frame and 1 is current one
+var<private> _ray_time: f32 = 1.0;
+
+// Inverse of matrix without projective part
+fn affine_inverse(m: mat4x4f) -> mat4x4f {
+    let a = m[0].xyz;
+    let b = m[1].xyz;
+    let c = m[2].xyz;
+    let inverse_determinant = 1.0 / dot(a, cross(b, c));
+
+    // Rows of inverse 3x3 part are cross products of columns
+    let inverse = transpose(mat3x3f(cross(b, c), cross(c, a), cross(a, b))) * inverse_determinant;
+
+    return mat4x4f(
+        vec4f(inverse[0], 0.0),
+        vec4f(inverse[1], 0.0),
+        vec4f(inverse[2], 0.0),
+        vec4f(-(inverse * m[3].xyz), 1.0),
+    );
+}
+
+fn intersect_instances(ray: Ray, max_distance: f32) -> InstanceIntersectionResult {
+    var result: InstanceIntersectionResult;
+    result.is_hit = false;
+    result.distance = max_distance;
+
+    let inv_direction = 1.0 / ray.direction;
+    var stack: array<u32, BVH_STACK_SIZE>;
+    // Root of empty BVH has inverted bounds, slab test would swap its planes into unbounded box
+    var stack_size = select(0u, 1u, all(bvh_nodes[0u].min <= bvh_nodes[0u].max));
+    stack[0] = 0u;
+
+    while stack_size > 0u {
+        stack_size -= 1u;
+        let node_index = stack[stack_size];
+        let node = bvh_nodes[node_index];
+
+        if bvh_node_distance(node, ray, inv_direction) >= result.distance {
+            continue;
+        }
+
+        if node.count != 0u {
+            for (var i = node.offset; i < node.offset + node.count; i++) {
+                let instance = instances[i];
+
+                // Moving instances are placed at ray time
+                var world_to_object = instance.world_to_object;
+                var object_to_world = instance.object_to_world;
+                if _ray_time < 1.0 {
+                    object_to_world = instance.previous_object_to_world * (1.0 - _ray_time) + instance.object_to_world * _ray_time;
+                    world_to_object = affine_inverse(object_to_world);
+                }
+
+                // Direction is not normalized to keep distances equal in both spaces
+                var object_ray: Ray;
+                object_ray.origin = (world_to_object * vec4f(ray.origin, 1.0)).xyz;
+                object_ray.direction = (world_to_object * vec4f(ray.direction, 0.0)).xyz;
+
+                let t = intersect_blas(instance.blas_root, object_ray, result.distance, is_backface_culled(instance.material_index));
+
+                if t.is_hit {
+                    result.is_hit = true;
+                    result.distance = t.distance;
+                    result.normal = normalize((transpose(world_to_object) * vec4f(t.normal, 0.0)).xyz);
+                    result.geometric_normal = normalize((transpose(world_to_object) * vec4f(t.geometric_normal, 0.0)).xyz);
+                    result.tangent = vec4f(normalize((object_to_world * vec4f(t.tangent.xyz, 0.0)).xyz), t.tangent.w);
+                    result.uv = t.uv;
+                    result.vertex_color = t.vertex_color;
+                    result.instance_index = i;
+                    result.triangle_index = t.triangle_index;
+                }
+            }
+        } else {
+            stack[stack_size] = node.offset;
+            stack[stack_size + 1u] = node_index + 1u;
+            stack_size += 2u;
+        }
+    }
+
+    return result;
+}
+
+struct SceneIntersectionResult {
+    normal: vec3f,
+    distance: f32,
+    geometric_normal: vec3f, // faces ray origin as shading normal does, used to offset secondary rays
+    tangent: vec4f,
+    uv: vec2f,
+    material_index: u32,
+    instance_id: u32, // scene instance index + 1, zero for primitives
+    instance_index: u32, // index in instances array, valid only if instance_id is nonzero
+    primitive_index: u32, // primitive index, or index in triangles array for instances
+    custom_data: vec4f, // instance custom data, all ones for primitives
+    vertex_color: vec3f, // linear, white for primitives and meshes without vertex colors
+    light_area_pdf: f32,
+    is_hit: bool,
+    is_front_face: bool, // false for rays leaving closed surfaces
+}
+
+fn intersect_scene(ray: Ray) -> SceneIntersectionResult {
+    var result: SceneIntersectionResult;
+
+    result.is_hit = false;
+    result.distance = 100000000.0;
+    result.instance_id = 0u;
+    result.light_area_pdf = 0.0;
+    result.custom_data = vec4f(1.0);
+    result.vertex_color = vec3f(1.0);
+
+    for (var index = 0u; index < arrayLength(&primitives); index++) {
+        let primitive = primitives[index];
+        let i = primitive_intersect_check(primitive, ray);
+
+        if i.is_hit && i.distance < result.distance && !(is_backface_culled(primitive.material_index) && dot(i.normal, ray.direction) > 0.0) {
+            result.is_hit = true;
+            result.distance = i.distance;
+            result.material_index = primitive.material_index;
+            result.primitive_index = index;
+            result.light_area_pdf = primitive.light_area_pdf;
+            result.normal = i.normal;
+            result.geometric_normal = i.normal;
+            result.tangent = vec4f(orthonormal_basis(i.normal)[0], 1.0);
+            result.uv = i.uv;
+        }
+    }
+
+    {
+        let i = intersect_instances(ray, result.distance);
+
+        if i.is_hit {
+            let instance = instances[i.instance_index];
+
+            result.is_hit = true;
+            result.distance = i.distance;
+            result.material_index = instance.material_index;
+            result.instance_id = instance.id + 1u;
+            result.instance_index = i.instance_index;
+            result.primitive_index = i.triangle_index;
+            result.custom_data = instance.custom_data;
+            result.vertex_color = i.vertex_color;
+            result.light_area_pdf = instance.light_area_pdf;
+            result.normal = i.normal;
+            result.geometric_normal = i.geometric_normal;
+            result.tangent = i.tangent;
+            result.uv = i.uv;
+        }
+    }
+
+    // Shading expects normal facing towards ray origin
+    result.is_front_face = dot(result.normal, ray.direction) < 0.0;
+    if result.is_hit {
+        switch materials[result.material_index].backface {
+            case BACKFACE_TWO_SIDED: { result.is_front_face = true; }
+            case BACKFACE_FLIP: { result.is_front_face = !result.is_front_face; }
+            default: {}
+        }
+    }
+    result.normal = faceForward(result.normal, ray.direction, result.normal);
+    result.geometric_normal = faceForward(result.geometric_normal, ray.direction, result.geometric_normal);
+
+    return result;
+}
+
+// file intersect.wgsl
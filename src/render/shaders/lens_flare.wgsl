@@ -0,0 +1,106 @@
+/// P_TR Project
+/// `File` render/lens_flare.wgsl
+/// `Description` Procedural lens flare post effect module.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+#include "output.wgsl"
+
+struct Flare {
+    tint: vec3f,
+    intensity: f32,
+    exposure: f32,
+    threshold: f32,
+    ghost_count: u32,
+    ghost_spacing: f32,
+    halo_radius: f32, // in image half-heights
+    chromatic_shift: f32, // in image half-heights
+}
+
+@group(0) @binding(0) var light_collector: texture_2d<f32>;
+@group(1) @binding(0) var<uniform> flare: Flare;
+@group(2) @binding(0) var bright: texture_2d<f32>;
+@group(2) @binding(1) var bright_sampler: sampler;
+
+// Surface pixels per bright image pixel along each axis
+const BRIGHT_DOWNSAMPLE: u32 = 4;
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+// Presented image with luminance above threshold, averaged over BRIGHT_DOWNSAMPLE^2 blocks
+@fragment
+fn fs_bright(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let origin = vec2i(frag_coord_4f.xy) * i32(BRIGHT_DOWNSAMPLE);
+    let extent = vec2i(textureDimensions(light_collector)) - 1;
+
+    var sum = vec3f(0.0);
+    for (var y = 0; y < i32(BRIGHT_DOWNSAMPLE); y++) {
+        for (var x = 0; x < i32(BRIGHT_DOWNSAMPLE); x++) {
+            let collected = textureLoad(light_collector, min(origin + vec2i(x, y), extent), 0);
+            let color = collected.rgb * (flare.exposure / max(collected.a, 1.0));
+            let l = luminance(color);
+
+            // Only excess over threshold casts flare, so flare fades in smoothly with brightness
+            sum += color * (max(l - flare.threshold, 0.0) / max(l, 1e-6));
+        }
+    }
+
+    return vec4f(sum / f32(BRIGHT_DOWNSAMPLE * BRIGHT_DOWNSAMPLE), 1.0);
+} // fn fs_bright
+
+// Bright image at `uv` with red and blue channels shifted along `direction`
+fn sample_dispersed(uv: vec2f, direction: vec2f) -> vec3f {
+    return vec3f(
+        textureSampleLevel(bright, bright_sampler, uv + direction, 0.0).r,
+        textureSampleLevel(bright, bright_sampler, uv, 0.0).g,
+        textureSampleLevel(bright, bright_sampler, uv - direction, 0.0).b,
+    );
+}
+
+// Weight of sample by its distance to image center, in [0, 1], so image border doesn't cut flares sharply
+fn center_weight(uv: vec2f) -> f32 {
+    return pow(max(1.0 - length(uv - 0.5) / length(vec2f(0.5)), 0.0), 4.0);
+}
+
+// Distinct tint of ghost by its index, as of coated lens elements
+fn ghost_color(index: u32) -> vec3f {
+    return 0.6 + 0.4 * cos(6.2831853 * (f32(index) * 0.17 + vec3f(0.0, 0.33, 0.67)));
+}
+
+// Flare added to presented image
+@fragment
+fn fs_composite(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    // Ghosts are images of bright pixels mirrored through center, so they are sampled from mirrored coordinate
+    let resolution = vec2f(textureDimensions(light_collector));
+    let uv = 1.0 - frag_coord_4f.xy / resolution;
+    let aspect = vec2f(resolution.y / resolution.x, 1.0);
+    let to_center = 0.5 - uv;
+    let to_center_length = length(to_center / aspect);
+    let direction = select(vec2f(0.0), to_center / to_center_length, to_center_length > 0.0);
+    let dispersion = direction * (0.5 * flare.chromatic_shift);
+
+    var color = vec3f(0.0);
+    for (var index = 0u; index < flare.ghost_count; index++) {
+        let ghost_uv = fract(uv + to_center * (flare.ghost_spacing * f32(index)));
+        color += sample_dispersed(ghost_uv, dispersion) * ghost_color(index) * center_weight(ghost_uv);
+    }
+
+    // Halo ring around image center is lit by sources at fixed distance behind it
+    if flare.halo_radius > 0.0 {
+        let radius = 0.5 * flare.halo_radius;
+        let halo_uv = uv + direction * radius;
+        let ring = 1.0 - min(abs(to_center_length - radius) / radius, 1.0);
+        let is_inside = all(halo_uv == clamp(halo_uv, vec2f(0.0), vec2f(1.0)));
+        color += sample_dispersed(halo_uv, dispersion) * select(0.0, pow(ring, 5.0), is_inside);
+    }
+
+    return encode_output(color * flare.tint * flare.intensity);
+} // fn fs_composite
+
+// file lens_flare.wgsl
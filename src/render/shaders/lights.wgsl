@@ -0,0 +1,238 @@
+/// P_TR Project
+/// `File` render/lights.wgsl
+/// `Description` Environment, sky and light sampling module.
+/// `Author` TioT2
+
+struct Environment {
+    rotation: f32,
+    intensity: f32,
+    pdf_normalization: f32, // converts pixel luminance to solid angle pdf
+    is_enabled: u32,
+}
+
+@group(2) @binding(9) var<uniform> environment: Environment;
+@group(2) @binding(10) var environment_radiance: texture_2d<f32>;
+@group(2) @binding(11) var environment_conditional_cdf: texture_2d<f32>; // row CDFs
+@group(2) @binding(12) var environment_marginal_cdf: texture_2d<f32>; // CDF over rows, stored as single row
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+// Equirectangular mapping, V axis goes from top to bottom
+fn environment_direction_to_uv(direction: vec3f) -> vec2f {
+    let phi = atan2(direction.x, -direction.z) - environment.rotation;
+    return vec2f(fract(phi / (2.0 * PI) + 0.5), acos(clamp(direction.y, -1.0, 1.0)) / PI);
+}
+
+fn environment_uv_to_direction(uv: vec2f) -> vec3f {
+    let phi = (uv.x - 0.5) * 2.0 * PI + environment.rotation;
+    let theta = uv.y * PI;
+    return vec3f(sin(theta) * sin(phi), cos(theta), -sin(theta) * cos(phi));
+}
+
+fn environment_texel(uv: vec2f) -> vec2i {
+    let extent = vec2i(textureDimensions(environment_radiance));
+    return clamp(vec2i(uv * vec2f(extent)), vec2i(0), extent - 1);
+}
+
+// Solid angle pdf of pixel sample with given direction. Sampling weights contain row sine, direction's own sine converts to solid angle.
+fn environment_texel_pdf(texel: vec2i, uv: vec2f) -> f32 {
+    let sin_theta = sin(uv.y * PI);
+
+    if sin_theta <= 0.0 {
+        return 0.0;
+    }
+
+    let row_sin_theta = sin(PI * (f32(texel.y) + 0.5) / f32(textureDimensions(environment_radiance).y));
+    return luminance(textureLoad(environment_radiance, texel, 0).rgb) * row_sin_theta * environment.pdf_normalization / sin_theta;
+}
+
+// Radiance of rays escaping the scene
+fn environment_radiance_at(direction: vec3f) -> vec3f {
+    if environment.is_enabled == 0u {
+        return vec3f(0.0);
+    }
+
+    return textureLoad(environment_radiance, environment_texel(environment_direction_to_uv(direction)), 0).rgb * environment.intensity;
+}
+
+fn environment_pdf(direction: vec3f) -> f32 {
+    let uv = environment_direction_to_uv(direction);
+    return environment_texel_pdf(environment_texel(uv), uv);
+}
+
+// Index of the first CDF entry greater than value
+fn cdf_search(cdf: texture_2d<f32>, row: i32, count: i32, value: f32) -> i32 {
+    var low = 0;
+    var high = count - 1;
+
+    while low < high {
+        let middle = (low + high) / 2;
+        if textureLoad(cdf, vec2i(middle, row), 0).r > value {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+
+    return low;
+}
+
+struct EnvironmentSample {
+    direction: vec3f,
+    pdf: f32,
+    radiance: vec3f,
+}
+
+// Importance sample environment by luminance
+fn sample_environment() -> EnvironmentSample {
+    let extent = vec2i(textureDimensions(environment_radiance));
+    let y = cdf_search(environment_marginal_cdf, 0, extent.y, rand_f32());
+    let x = cdf_search(environment_conditional_cdf, y, extent.x, rand_f32());
+    let uv = (vec2f(f32(x), f32(y)) + vec2f(rand_f32(), rand_f32())) / vec2f(extent);
+
+    var result: EnvironmentSample;
+    result.direction = environment_uv_to_direction(uv);
+    result.pdf = environment_texel_pdf(vec2i(x, y), uv);
+    result.radiance = textureLoad(environment_radiance, vec2i(x, y), 0).rgb * environment.intensity;
+    return result;
+}
+
+struct Sky {
+    sun_direction: vec3f,
+    is_enabled: u32,
+    sun_radiance: vec3f,
+    sun_cos_angular_radius: f32,
+    perez_a: vec3f, // Perez function coefficients for luminance Y and chromaticities x, y
+    perez_b: vec3f,
+    perez_c: vec3f,
+    perez_d: vec3f,
+    perez_e: vec3f,
+    zenith: vec3f, // Zenith Y, x, y divided by Perez function at zenith
+}
+
+@group(0) @binding(2) var<uniform> sky: Sky;
+
+const SKY_GROUND_ALBEDO: f32 = 0.3;
+
+// Procedural sky lights the scene only if there is no environment map
+fn is_sky_enabled() -> bool {
+    return sky.is_enabled != 0u && environment.is_enabled == 0u;
+}
+
+fn sky_perez(cos_theta: f32, gamma: f32) -> vec3f {
+    let cos_gamma = cos(gamma);
+    return (1.0 + sky.perez_a * exp(sky.perez_b / cos_theta)) * (1.0 + sky.perez_c * exp(sky.perez_d * gamma) + sky.perez_e * cos_gamma * cos_gamma);
+}
+
+// Preetham sky radiance without sun disk. Directions below horizon see ground lit by horizon.
+fn sky_radiance(direction: vec3f) -> vec3f {
+    let sky_direction = normalize(vec3f(direction.x, max(direction.y, 0.001), direction.z));
+    let gamma = acos(clamp(dot(sky_direction, sky.sun_direction), -1.0, 1.0));
+    let luminance_xy = sky.zenith * sky_perez(sky_direction.y, gamma);
+
+    let luminance = luminance_xy.x;
+    let chromaticity = luminance_xy.yz;
+    let xyz = vec3f(chromaticity.x, chromaticity.y, 1.0 - chromaticity.x - chromaticity.y) * (luminance / chromaticity.y);
+    let rgb = max(mat3x3f(
+        vec3f(3.2406, -0.9689, 0.0557),
+        vec3f(-1.5372, 1.8758, -0.2040),
+        vec3f(-0.4986, 0.0415, 1.0570),
+    ) * xyz, vec3f(0.0));
+
+    return select(rgb, rgb * SKY_GROUND_ALBEDO, direction.y < 0.0);
+}
+
+fn sun_pdf() -> f32 {
+    return 1.0 / (2.0 * PI * (1.0 - sky.sun_cos_angular_radius));
+}
+
+fn sun_radiance_at(direction: vec3f) -> vec3f {
+    return select(vec3f(0.0), sky.sun_radiance, dot(direction, sky.sun_direction) >= sky.sun_cos_angular_radius);
+}
+
+// Uniformly sample sun disk cone
+fn sample_sun() -> EnvironmentSample {
+    let cos_theta = 1.0 - rand_f32() * (1.0 - sky.sun_cos_angular_radius);
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = 2.0 * PI * rand_f32();
+
+    var result: EnvironmentSample;
+    result.direction = orthonormal_basis(sky.sun_direction) * vec3f(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+    result.pdf = sun_pdf();
+    result.radiance = sky.sun_radiance;
+    return result;
+}
+
+const LIGHT_KIND_SPHERE: u32 = 1;
+const LIGHT_KIND_TRIANGLE: u32 = 2;
+
+struct Light {
+    p0: vec3f, // sphere center or first triangle vertex
+    kind: u32,
+    p1: vec3f, // x is sphere radius
+    material_index: u32,
+    p2: vec3f,
+    cdf: f32, // selection probability of this and all previous lights
+    area_pdf: f32,
+}
+
+// Emitting spheres and triangles, selected proportionally to power. Empty list contains single zero-kind light.
+@group(2) @binding(13) var<storage, read> lights: array<Light>;
+
+struct LightSample {
+    point: vec3f,
+    area_pdf: f32,
+    normal: vec3f,
+    radiance: vec3f,
+}
+
+fn has_lights() -> bool {
+    return lights[0].kind != 0u;
+}
+
+fn light_search(value: f32) -> u32 {
+    var low = 0u;
+    var high = arrayLength(&lights) - 1u;
+
+    while low < high {
+        let middle = (low + high) / 2u;
+        if lights[middle].cdf > value {
+            high = middle;
+        } else {
+            low = middle + 1u;
+        }
+    }
+
+    return low;
+}
+
+// Select light by power and sample point on its surface uniformly by area
+fn sample_light() -> LightSample {
+    let light = lights[light_search(rand_f32())];
+    let u = vec2f(rand_f32(), rand_f32());
+
+    var result: LightSample;
+    result.area_pdf = light.area_pdf;
+    result.radiance = materials[light.material_index].emission;
+
+    if light.kind == LIGHT_KIND_SPHERE {
+        let z = 1.0 - 2.0 * u.x;
+        let r = sqrt(max(1.0 - z * z, 0.0));
+        let phi = 2.0 * PI * u.y;
+
+        result.normal = vec3f(r * cos(phi), r * sin(phi), z);
+        result.point = light.p0 + result.normal * light.p1.x;
+    } else {
+        let s = sqrt(u.x);
+        let b = vec2f(1.0 - s, u.y * s);
+
+        result.point = light.p0 * b.x + light.p1 * b.y + light.p2 * (1.0 - b.x - b.y);
+        result.normal = normalize(cross(light.p1 - light.p0, light.p2 - light.p0));
+    }
+
+    return result;
+}
+
+// file lights.wgsl
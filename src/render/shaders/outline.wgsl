@@ -0,0 +1,67 @@
+/// P_TR Project
+/// `File` render/outline.wgsl
+/// `Description` Selection outline overlay module.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+struct Outline {
+    color: vec3f,
+    is_box_enabled: u32,
+    box_min: vec2u, // Selection box corners, inclusive
+    box_max: vec2u,
+    box_color: vec3f,
+    width: u32, // outline width in pixels
+}
+
+#include "output.wgsl"
+
+@group(0) @binding(0) var id_buffer: texture_2d<u32>;
+@group(0) @binding(1) var<uniform> outline: Outline;
+@group(0) @binding(2) var<storage, read> selection: array<u32>; // Selection flag per scene instance
+
+// ID buffer stores instance index + 1, zero is reserved for 'no instance'
+fn is_selected(id: u32) -> bool {
+    return id != 0u && id <= arrayLength(&selection) && selection[id - 1u] != 0u;
+}
+
+fn is_box_border(pixel: vec2u) -> bool {
+    let is_inside = all(pixel >= outline.box_min) && all(pixel <= outline.box_max);
+    let is_on_edge = any(pixel == outline.box_min) || any(pixel == outline.box_max);
+
+    return outline.is_box_enabled != 0u && is_inside && is_on_edge;
+}
+
+// Outline is drawn outside of selected instance silhouette, so instance itself stays untouched
+@fragment
+fn fs_main(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(frag_coord_4f.xy);
+    let max_pixel = vec2i(textureDimensions(id_buffer)) - 1;
+
+    if is_box_border(vec2u(pixel)) {
+        return encode_output(outline.box_color);
+    }
+
+    if is_selected(textureLoad(id_buffer, pixel, 0).r) {
+        discard;
+    }
+
+    let width = i32(outline.width);
+    var is_edge = false;
+    for (var y = -width; y <= width; y++) {
+        for (var x = -width; x <= width; x++) {
+            is_edge = is_edge || is_selected(textureLoad(id_buffer, clamp(pixel + vec2i(x, y), vec2i(0), max_pixel), 0).r);
+        }
+    }
+
+    if !is_edge {
+        discard;
+    }
+
+    return encode_output(outline.color);
+} // fn fs_main
+
+// file outline.wgsl
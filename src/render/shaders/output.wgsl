@@ -0,0 +1,33 @@
+/// P_TR Project
+/// `File` render/output.wgsl
+/// `Description` Surface output encoding module.
+/// `Author` TioT2
+
+// Set for surface formats without hardware sRGB encoding, e.g. 10-bit ones
+override OUTPUT_SRGB_ENCODE: bool = false;
+
+fn linear_to_srgb(color: vec3f) -> vec3f {
+    let c = max(color, vec3f(0.0));
+    return select(1.055 * pow(c, vec3f(1.0 / 2.4)) - 0.055, c * 12.92, c <= vec3f(0.0031308));
+}
+
+// Largest channel code of surface format, zero for float formats that need no dithering
+override OUTPUT_MAX_CODE: f32 = 255.0;
+
+fn srgb_to_linear(color: vec3f) -> vec3f {
+    let c = max(color, vec3f(0.0));
+    return select(pow((c + 0.055) / 1.055, vec3f(2.4)), c / 12.92, c <= vec3f(0.04045));
+}
+
+// Linear color written to surface
+fn encode_output(color: vec3f) -> vec4f {
+    return vec4f(select(color, linear_to_srgb(color), OUTPUT_SRGB_ENCODE), 1.0);
+}
+
+// Linear color written to surface, `offset` is added to its display-encoded value
+fn encode_output_offset(color: vec3f, offset: vec3f) -> vec4f {
+    let encoded = linear_to_srgb(color) + offset;
+    return vec4f(select(srgb_to_linear(encoded), encoded, OUTPUT_SRGB_ENCODE), 1.0);
+}
+
+// file output.wgsl
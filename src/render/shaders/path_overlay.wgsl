@@ -0,0 +1,84 @@
+/// P_TR Project
+/// `File` render/path_overlay.wgsl
+/// `Description` Recorded path overlay module, draws path segments over presented image.
+/// `Author` TioT2
+
+#include "output.wgsl"
+#include "camera.wgsl"
+
+const PI: f32 = 3.14159265358979;
+
+@group(0) @binding(0) var<uniform> camera: Camera;
+
+struct VsIn {
+    @location(0) position: vec3f, // relative to world origin
+    @location(1) other_position: vec3f, // other end of line
+    @location(2) color: vec3f,
+}
+
+struct VsOut {
+    @builtin(position) clip_position: vec4f,
+    @location(0) color: vec3f,
+}
+
+fn to_local(position: vec3f) -> vec3f {
+    let to_point = position - camera.location;
+    return vec3f(dot(to_point, camera.right), dot(to_point, camera.up), dot(to_point, camera.direction));
+}
+
+// Texture coordinate of point in camera basis for panorama and fisheye projections, inverse of ray generation of render.wgsl
+fn wide_tex_coord(local: vec3f) -> vec2f {
+    if camera.projection == PROJECTION_PANORAMA {
+        let direction = normalize(local);
+        let tex_coord = vec2f(atan2(direction.x, direction.z) / (2.0 * PI) + 0.5, 1.0 - acos(clamp(direction.y, -1.0, 1.0)) / PI);
+        return tex_coord - camera.lens_shift;
+    }
+
+    let side = length(local.xy);
+    let angle = atan2(side, local.z);
+    var radius = angle;
+    if camera.projection == PROJECTION_FISHEYE_STEREOGRAPHIC {
+        radius = 2.0 * tan(angle * 0.5);
+    }
+    var coord = vec2f(0.0);
+    if side > 0.0 {
+        coord = local.xy / side * radius / vec2f(camera.projection_width, camera.projection_height);
+    }
+    return coord * 0.5 + 0.5 - camera.lens_shift;
+}
+
+@vertex
+fn vs_main(input: VsIn) -> VsOut {
+    let local = to_local(input.position);
+    let size = vec2f(camera.projection_width, camera.projection_height);
+    var clip_position: vec4f;
+
+    switch camera.projection {
+        case PROJECTION_ORTHOGRAPHIC: {
+            clip_position = vec4f(local.xy / size - 2.0 * camera.lens_shift, 0.0, 1.0);
+        }
+        case PROJECTION_PANORAMA, PROJECTION_FISHEYE_EQUIDISTANT, PROJECTION_FISHEYE_STEREOGRAPHIC: {
+            let tex_coord = wide_tex_coord(local);
+            clip_position = vec4f(tex_coord * 2.0 - 1.0, 0.0, 1.0);
+
+            // Line crossing panorama seam would span whole image, both its ends are moved out of view
+            let other_tex_coord = wide_tex_coord(to_local(input.other_position));
+            if camera.projection == PROJECTION_PANORAMA && abs(tex_coord.x - other_tex_coord.x) > 0.5 {
+                clip_position = vec4f(2.0, 2.0, 0.0, 1.0);
+            }
+        }
+        default: {
+            // Homogeneous, so lines are clipped by near plane
+            clip_position = vec4f(local.xy * camera.near / size - 2.0 * camera.lens_shift * local.z, 0.0, local.z);
+        }
+    }
+
+    return VsOut(clip_position, input.color);
+}
+
+@fragment
+fn fs_main(@location(0) color: vec3f) -> @location(0) vec4f {
+    return encode_output(color);
+}
+
+// file path_overlay.wgsl
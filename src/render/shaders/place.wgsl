@@ -22,19 +22,159 @@ fn vs_main(@builtin(vertex_index) index: u32) -> VsOut {
     );
 }
 
+#include "output.wgsl"
+
+const FALSE_COLOR_STOP_COUNT: u32 = 8;
+
 struct System {
     resolution: vec2f,
     time: f32,
     static_frame_index: u32,
     texel_size: vec2f,
+    rr_start_depth: u32,
+    rr_threshold: f32,
+    shutter_open: f32,
+    shutter_close: f32,
+    exposure: f32, // presented image scale
+    sampler_kind: u32,
+    max_indirect_radiance: f32,
+    min_indirect_roughness: f32,
+    adaptive_threshold: f32,
+    heatmap: u32,
+    tile_size: u32,
+    tile_count: u32,
+    watertight_intersection: u32,
+    ray_min_distance: f32,
+    intersection_tolerance: f32,
+    film_grain: f32, // presented grain standard deviation, in display-encoded units
+    dither: u32,
+    _pad0: u32,
+    false_color: array<vec4f, FALSE_COLOR_STOP_COUNT>, // evenly spaced false color map stops
 }
 
 @group(0) @binding(1) var<uniform> system: System;
+// Tiled threshold map
+@group(0) @binding(3) var blue_noise: texture_2d<f32>;
 @group(1) @binding(0) var light_collector: texture_2d<f32>;
+// Luminance second moment sum is stored in W
+@group(1) @binding(1) var features: texture_2d<f32>;
+// Aperture diffraction glare radiance at reduced resolution, black if glare is disabled
+@group(2) @binding(0) var glare: texture_2d<f32>;
+@group(2) @binding(1) var glare_sampler: sampler;
+
+// Surface pixels per glare pixel along each axis, matches glare.wgsl
+const GLARE_DOWNSAMPLE: f32 = 2.0;
+
+const DITHER_ORDERED: u32 = 1;
+const DITHER_BLUE_NOISE: u32 = 2;
+
+const HEATMAP_SAMPLE_COUNT: u32 = 1;
+const HEATMAP_VARIANCE: u32 = 2;
+
+// Matches render.wgsl
+const ADAPTIVE_MIN_LUMINANCE: f32 = 0.01;
+// Variance heatmap reference error if adaptive sampling is disabled
+const DEFAULT_VARIANCE_REFERENCE: f32 = 0.01;
+
+// Color of `t` in [0, 1] range, linearly interpolated between false color map stops
+fn false_color(t: f32) -> vec3f {
+    let position = clamp(t, 0.0, 1.0) * f32(FALSE_COLOR_STOP_COUNT - 1u);
+    let index = min(u32(position), FALSE_COLOR_STOP_COUNT - 2u);
+
+    return mix(system.false_color[index].rgb, system.false_color[index + 1u].rgb, position - f32(index));
+}
+
+fn luminance(color: vec3f) -> f32 {
+    return dot(color, vec3f(0.2126, 0.7152, 0.0722));
+}
+
+fn hash_u32(value: u32) -> u32 {
+    var x = value * 747796405u + 2891336453u;
+    x = ((x >> ((x >> 28u) + 4u)) ^ x) * 277803737u;
+    return (x >> 22u) ^ x;
+}
+
+fn hash_unorm(value: u32) -> f32 {
+    return f32(hash_u32(value) >> 8u) / 16777216.0;
+}
+
+// Threshold of 8x8 Bayer matrix, in [0, 1)
+fn bayer(pixel: vec2u) -> f32 {
+    let x = pixel.x ^ pixel.y;
+    var index = 0u;
+    for (var bit = 0u; bit < 3u; bit++) {
+        index = (index << 2u) | (((x >> bit) & 1u) << 1u) | ((pixel.y >> bit) & 1u);
+    }
+    return (f32(index) + 0.5) / 64.0;
+}
+
+// Dither offset in [-0.5, 0.5] channel codes, channels are decorrelated by shifted patterns
+fn dither_codes(pixel: vec2u) -> vec3f {
+    if system.dither == DITHER_ORDERED {
+        return vec3f(bayer(pixel), bayer(pixel + vec2u(4u, 0u)), bayer(pixel + vec2u(0u, 4u))) - 0.5;
+    }
+    if system.dither == DITHER_BLUE_NOISE {
+        let size = textureDimensions(blue_noise);
+        return vec3f(
+            textureLoad(blue_noise, pixel % size, 0).r,
+            textureLoad(blue_noise, (pixel + size / 3u) % size, 0).r,
+            textureLoad(blue_noise, (pixel + size * 2u / 3u) % size, 0).r,
+        ) - 0.5;
+    }
+    return vec3f(0.0);
+}
+
+// Monochrome grain with unit variance, changes every frame
+fn grain(pixel: vec2u) -> f32 {
+    let seed = hash_u32(pixel.x ^ hash_u32(pixel.y ^ bitcast<u32>(system.time)));
+
+    // Sum of four uniform values is close to gaussian, its variance is 4/12
+    var sum = 0.0;
+    for (var index = 0u; index < 4u; index++) {
+        sum += hash_unorm(seed + index);
+    }
+    return (sum - 2.0) * sqrt(3.0);
+}
+
+// Presented color with film grain and dithering, grain is the strongest in midtones
+fn present(color: vec3f, pixel: vec2u) -> vec4f {
+    // Float surfaces don't band
+    let is_dithered = system.dither != 0u && OUTPUT_MAX_CODE > 0.0;
+    if !is_dithered && system.film_grain <= 0.0 {
+        return encode_output(color);
+    }
+
+    var offset = select(vec3f(0.0), dither_codes(pixel) / max(OUTPUT_MAX_CODE, 1.0), is_dithered);
+    if system.film_grain > 0.0 {
+        let encoded = linear_to_srgb(vec3f(luminance(clamp(color, vec3f(0.0), vec3f(1.0))))).x;
+        offset += grain(pixel) * system.film_grain * 2.0 * sqrt(encoded * (1.0 - encoded));
+    }
+    return encode_output_offset(color, offset);
+}
 
 @fragment
 fn fs_main(@builtin(position) frag_coord_4f: vec4f, @location(0) tex_coord: vec2f) -> @location(0) vec4f {
-    return textureLoad(light_collector, vec2i(frag_coord_4f.xy), 0) / f32(system.static_frame_index + 1);
+    // Sample count is stored in alpha, it differs per pixel after reprojection and adaptive sampling
+    let collected = textureLoad(light_collector, vec2i(frag_coord_4f.xy), 0);
+
+    // Pixel sampled at every pass over tiles since accumulation start is shown by the last map color
+    if system.heatmap == HEATMAP_SAMPLE_COUNT {
+        return encode_output(false_color(collected.a / f32(system.static_frame_index / system.tile_count + 1u)));
+    }
+    // Relative standard error of mean luminance, as estimated by adaptive sampling.
+    // Pixels at adaptive threshold are shown by the middle map color.
+    if system.heatmap == HEATMAP_VARIANCE {
+        let count = max(collected.a, 1.0);
+        let mean = luminance(collected.rgb) / count;
+        let variance = max(textureLoad(features, vec2i(frag_coord_4f.xy), 0).w / count - mean * mean, 0.0);
+        let error = sqrt(variance / count) / max(mean, ADAPTIVE_MIN_LUMINANCE);
+        let reference = select(DEFAULT_VARIANCE_REFERENCE, system.adaptive_threshold, system.adaptive_threshold > 0.0);
+        return encode_output(false_color(0.5 * error / reference));
+    }
+    // Glare image is padded, so it's addressed by pixel size
+    let glare_uv = frag_coord_4f.xy / (GLARE_DOWNSAMPLE * vec2f(textureDimensions(glare)));
+    let radiance = collected.rgb / max(collected.a, 1.0) + textureSampleLevel(glare, glare_sampler, glare_uv, 0.0).rgb;
+    return present(radiance * system.exposure, vec2u(frag_coord_4f.xy));
 } // fn fs_main
 
 // file shader.wgsl
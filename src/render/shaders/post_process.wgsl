@@ -0,0 +1,25 @@
+/// P_TR Project
+/// `File` render/post_process.wgsl
+/// `Description` Post-process plugin prelude module, prepended to shaders of plugins.
+/// `Author` TioT2
+
+// Plugin shader defines `fs_main` returning processed mean radiance (RGB) and unit sample count (A) of pixel.
+// Plugin parameters are bound as uniform block of `f32` fields named after parameters, every field needs a value:
+// `@group(1) @binding(0) var<uniform> parameters: Parameters;`
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@group(0) @binding(0) var collector: texture_2d<f32>; // radiance sum and sample count
+// Primary hit octahedral-encoded normal (XY) and distance (Z, negative if nothing was hit)
+@group(0) @binding(1) var features: texture_2d<f32>;
+
+// Mean radiance of pixel, image border is extended
+fn load_mean(pixel: vec2i) -> vec3f {
+    let collected = textureLoad(collector, clamp(pixel, vec2i(0), vec2i(textureDimensions(collector)) - 1), 0);
+    return collected.rgb / max(collected.a, 1.0);
+}
+
+// file post_process.wgsl
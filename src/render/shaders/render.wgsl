@@ -22,236 +22,554 @@ fn vs_main(@builtin(vertex_index) index: u32) -> VsOut {
     );
 }
 
-struct Camera {
-    location: vec3f,
-    direction: vec3f,
-    near: f32,
-    right: vec3f,
-    projection_width: f32,
-    up: vec3f,
-    projection_height: f32,
-}
+#include "camera.wgsl"
 
 struct System {
     resolution: vec2f,
     time: f32,
     static_frame_index: u32,
     texel_size: vec2f,
+    rr_start_depth: u32, // first bounce with Russian roulette
+    rr_threshold: f32, // throughput below which paths are terminated randomly, zero disables roulette
+    shutter_open: f32, // shutter interval, 0 is previous frame and 1 is current one
+    shutter_close: f32,
+    exposure: f32, // presented image scale
+    sampler_kind: u32, // SAMPLER_* constant
+    max_indirect_radiance: f32, // clamp of light arriving after two or more bounces, zero if disabled
+    min_indirect_roughness: f32, // minimal glossy roughness after diffuse bounce, zero if disabled
+    adaptive_threshold: f32, // relative error of converged pixels, zero if adaptive sampling is disabled
+    heatmap: u32, // presentation only, see place.wgsl
+    tile_size: u32, // side of square tiles traced in turn, in pixels
+    tile_count: u32, // one if tiling is disabled
+    watertight_intersection: u32, // nonzero if triangles are intersected by watertight algorithm
+    ray_min_distance: f32, // hits closer to ray origin are ignored, in scene units
+    intersection_tolerance: f32, // shadow ray shortening and degenerate triangle size, in scene units
+    film_grain: f32, // presented grain standard deviation, in display-encoded units
+    dither: u32,
+    _pad0: u32,
+    false_color: array<vec4f, 8>, // false color map stops, used by place shader
+}
+
+override SEPARATE_DIFFUSE: bool = false; // radiance of paths scattered by diffuse lobe at primary hit is accumulated separately
+
+// Result of integrator tracing camera ray
+struct Trace {
+    radiance: vec3f,
+    diffuse: vec3f, // part of radiance scattered by diffuse lobe at primary hit, used if SEPARATE_DIFFUSE is set
 }
 
 @group(0) @binding(0) var<uniform> camera: Camera;
 @group(0) @binding(1) var<uniform> system: System;
-@group(1) @binding(0) var read_collector: texture_2d<f32>;
+@group(1) @binding(0) var read_collector: texture_2d<f32>; // radiance sum and sample count
+// Primary hit octahedral-encoded normal (XY) and distance (Z, SKY_DEPTH if nothing was hit), luminance second moment sum (W)
+@group(1) @binding(1) var read_features: texture_2d<f32>;
+// Radiance sum of paths scattered by diffuse lobe at primary hit (RGB), written only if SEPARATE_DIFFUSE is set
+@group(1) @binding(2) var read_diffuse: texture_2d<f32>;
+@group(3) @binding(0) var write_diffuse: texture_storage_2d<rgba32float, write>;
+
+#include "sampling.wgsl"
+#include "intersect.wgsl"
+#include "bsdf.wgsl"
+#include "lights.wgsl"
+override RECORD_PATH: bool = false; // integrator vertices are recorded by `record_vertex`, set by path recording pipeline only
+
+// Path vertex events, match `PathEvent`
+const PATH_EVENT_CAMERA: u32 = 0u;
+const PATH_EVENT_DIFFUSE: u32 = 1u; // scattering by diffuse lobe
+const PATH_EVENT_REFLECTION: u32 = 2u; // specular or glossy reflection
+const PATH_EVENT_TRANSMISSION: u32 = 3u;
+const PATH_EVENT_ABSORBED: u32 = 4u; // surface hit without scattering
+const PATH_EVENT_ESCAPED: u32 = 5u; // ray left scene, position is its origin
+
+// Vertices of recorded path beyond this number are dropped
+const MAX_RECORDED_VERTICES: u32 = 32u;
+
+struct PathVertex {
+    position: vec3f,
+    event: u32, // PATH_EVENT_* constant
+    direction: vec3f, // ray leaving vertex
+    _pad0: u32,
+    throughput: vec3f, // path throughput after vertex
+    _pad1: u32,
+    radiance: vec3f, // radiance gathered by path up to and including vertex
+    _pad2: u32,
+}
 
-var<private> _rand_seed : u32 = 123456789;
+var<private> _path_vertices: array<PathVertex, MAX_RECORDED_VERTICES>;
+var<private> _path_vertex_count: u32 = 0u;
 
-fn rand_u32() -> u32 {
-    _rand_seed ^= _rand_seed << 13;
-    _rand_seed ^= _rand_seed >> 17;
-    _rand_seed ^= _rand_seed << 5;
-    return _rand_seed;
+// Record vertex of traced path, does nothing outside of path recording pipeline
+fn record_vertex(position: vec3f, event: u32, direction: vec3f, throughput: vec3f, radiance: vec3f) {
+    if RECORD_PATH && _path_vertex_count < MAX_RECORDED_VERTICES {
+        _path_vertices[_path_vertex_count] = PathVertex(position, event, direction, 0u, throughput, 0u, radiance, 0u);
+        _path_vertex_count++;
+    }
 }
 
-fn rand_f32() -> f32 {
-    return f32(rand_u32()) / 4294967295.0;
+// Generated by renderer, defines `trace_path` calling selected integrator
+#include "selected_integrator.wgsl"
+
+fn is_fisheye() -> bool {
+    return camera.projection == PROJECTION_FISHEYE_EQUIDISTANT || camera.projection == PROJECTION_FISHEYE_STEREOGRAPHIC;
 }
 
-fn rand_vec3() -> vec3f {
-    let theta = 2 * 3.14159265367989 * rand_f32();
-    let phi = acos(1.0 - 2.0 * rand_f32());
-    return vec3f(
-        sin(phi) * cos(theta),
-        cos(phi),
-        sin(phi) * sin(theta),
-    );
+// Angle of ray to view direction at fisheye image plane distance from center
+fn fisheye_angle(radius: f32) -> f32 {
+    if camera.projection == PROJECTION_FISHEYE_STEREOGRAPHIC {
+        return 2.0 * atan(radius * 0.5);
+    }
+    return radius;
+}
+
+fn fisheye_radius(angle: f32) -> f32 {
+    if camera.projection == PROJECTION_FISHEYE_STEREOGRAPHIC {
+        return 2.0 * tan(angle * 0.5);
+    }
+    return angle;
+}
+
+// Fisheye image plane point of texture coordinate, in mapping distances
+fn fisheye_point(tex_coord: vec2f) -> vec2f {
+    return ((tex_coord + camera.lens_shift) * 2.0 - 1.0) * vec2f(camera.projection_width, camera.projection_height);
 }
 
-struct Ray {
-    direction: vec3f,
-    origin: vec3f,
+// Texture coordinate is traced, fisheye image points beyond 180 degrees off view direction are outside of image circle
+fn is_in_image_circle(tex_coord: vec2f) -> bool {
+    return !is_fisheye() || fisheye_angle(length(fisheye_point(tex_coord))) <= PI;
 }
 
-struct SphereIntersectResult {
-    normal: vec3f,
-    distance: f32,
-    is_hit: bool,
+// Direction of equirectangular panorama texture coordinate in camera basis (right, up, forward).
+// Image center is forward and top is up, as in environment maps.
+fn panorama_direction(tex_coord: vec2f) -> vec3f {
+    let phi = (tex_coord.x - 0.5) * 2.0 * PI;
+    let theta = (1.0 - tex_coord.y) * PI;
+    return vec3f(sin(theta) * sin(phi), cos(theta), sin(theta) * cos(phi));
 }
 
-fn sphere_intersect_check(center: vec3f, radius: f32, ray: Ray) -> SphereIntersectResult {
-    var result: SphereIntersectResult;
+fn panorama_tex_coord(direction: vec3f) -> vec2f {
+    return vec2f(atan2(direction.x, direction.z) / (2.0 * PI) + 0.5, 1.0 - acos(clamp(direction.y, -1.0, 1.0)) / PI);
+}
 
-    let delta = center - ray.origin;
-    let delta_proj_len = dot(delta, ray.direction);
-    let delta_proj = ray.direction * delta_proj_len;
-    let h = distance(delta, delta_proj);
+// Camera basis is interpolated between previous and current frames at ray time
+fn tex_coord_to_ray(tex_coord: vec2f) -> Ray {
+    let coord = (tex_coord + camera.lens_shift) * 2.0 - 1.0;
+    let direction = mix(camera.previous_direction, camera.direction, _ray_time);
+    let right = mix(camera.previous_right, camera.right, _ray_time);
+    let up = mix(camera.previous_up, camera.up, _ray_time);
 
-    result.is_hit = delta_proj_len > 0.0 && h <= radius;
-    let d = sqrt(radius * radius - h * h);
-    result.distance = delta_proj_len - d;
-    result.normal = (delta_proj - delta - ray.direction * d) / radius;
+    let plane_offset = right * camera.projection_width * coord.x + up * camera.projection_height * coord.y;
 
-    return result;
+    var ray: Ray;
+    ray.origin = mix(camera.previous_location, camera.location, _ray_time);
+    switch camera.projection {
+        case PROJECTION_ORTHOGRAPHIC: {
+            ray.origin += plane_offset;
+            ray.direction = normalize(direction);
+        }
+        case PROJECTION_PANORAMA: {
+            let local = panorama_direction(tex_coord + camera.lens_shift);
+            ray.direction = normalize(right * local.x + up * local.y + direction * local.z);
+        }
+        case PROJECTION_FISHEYE_EQUIDISTANT, PROJECTION_FISHEYE_STEREOGRAPHIC: {
+            // Points outside of image circle are clamped to it
+            let point = fisheye_point(tex_coord);
+            let radius = length(point);
+            let angle = min(fisheye_angle(radius), PI);
+            var side = vec2f(0.0);
+            if radius > 0.0 {
+                side = point / radius * sin(angle);
+            }
+            ray.direction = normalize(right * side.x + up * side.y + direction * cos(angle));
+        }
+        default: {
+            ray.direction = normalize(direction * camera.near + plane_offset);
+        }
+    }
+    return ray;
 }
 
-struct PlaneIntersectResult {
-    distance: f32,
-    is_hit: bool,
+// Maximal number of rejection sampling attempts for bokeh texture
+const BOKEH_SAMPLE_ATTEMPTS: u32 = 16;
+
+fn sample_unit_disk() -> vec2f {
+    let radius = sqrt(rand_f32());
+    let angle = 2.0 * PI * rand_f32();
+    return vec2f(cos(angle), sin(angle)) * radius;
 }
 
-fn plane_intersect_check(point: vec3f, normal: vec3f, ray: Ray) -> PlaneIntersectResult {
-    var result: PlaneIntersectResult;
+// Point on aperture in [-1, 1] square, distributed by aperture shape
+fn sample_aperture() -> vec2f {
+    // Texture luminance is lens transmittance, so points are rejection sampled by it
+    if camera.bokeh_texture != NO_TEXTURE {
+        for (var i = 0u; i < BOKEH_SAMPLE_ATTEMPTS; i++) {
+            let point = vec2f(rand_f32(), rand_f32()) * 2.0 - 1.0;
+            let tex_coord = vec2f(point.x, -point.y) * 0.5 + 0.5;
+            let transmittance = dot(textureSampleLevel(data_textures, scene_sampler, tex_coord, camera.bokeh_texture, 0.0).rgb, vec3f(0.2126, 0.7152, 0.0722));
+
+            if rand_f32() < transmittance {
+                return point;
+            }
+        }
+        return sample_unit_disk();
+    }
+
+    if camera.aperture_blades < 3u {
+        return sample_unit_disk();
+    }
+
+    // Regular polygon consists of equal triangles, so triangle is selected uniformly
+    let blade_angle = 2.0 * PI / f32(camera.aperture_blades);
+    let angle = camera.aperture_rotation + blade_angle * floor(rand_f32() * f32(camera.aperture_blades));
+    let v0 = vec2f(cos(angle), sin(angle));
+    let v1 = vec2f(cos(angle + blade_angle), sin(angle + blade_angle));
 
-    result.distance = dot(point - ray.origin, normal) / dot(normal, ray.direction);
-    result.is_hit = result.distance > 0.0;
-    return result;
+    var u = vec2f(rand_f32(), rand_f32());
+    if u.x + u.y > 1.0 {
+        u = 1.0 - u;
+    }
+
+    return v0 * u.x + v1 * u.y;
 }
 
-struct BoxIntersectionResult {
-    normal: vec3f,
-    distance: f32,
-    is_hit: bool,
+// Thin lens camera ray, origin is moved over lens disk and ray passes through the same point on focus plane
+fn tex_coord_to_lens_ray(tex_coord: vec2f) -> Ray {
+    // Each path has its own time within shutter interval
+    _ray_time = mix(system.shutter_open, system.shutter_close, rand_f32());
+
+    var ray = tex_coord_to_ray(tex_coord);
+
+    // Panorama has no optical axis to focus along
+    if camera.aperture_radius <= 0.0 || camera.projection == PROJECTION_PANORAMA {
+        return ray;
+    }
+
+    // Tilted focus plane passes through focus distance point on optical axis (Scheimpflug principle)
+    let focus_normal = normalize(camera.direction + camera.up * tan(camera.lens_tilt.x) + camera.right * tan(camera.lens_tilt.y));
+    let ray_cos = dot(ray.direction, focus_normal);
+    if ray_cos <= 1e-4 {
+        return ray;
+    }
+    let focus_point = ray.origin + ray.direction * (camera.focus_distance * dot(camera.direction, focus_normal) / ray_cos);
+    let lens_point = sample_aperture() * camera.aperture_radius;
+
+    ray.origin += camera.right * lens_point.x + camera.up * lens_point.y;
+    ray.direction = normalize(focus_point - ray.origin);
+    return ray;
 }
 
-fn box_intersect_check(p0: vec3f, p1: vec3f, ray: Ray) -> BoxIntersectionResult {
-    let utv0 = (p0 - ray.origin) / ray.direction;
-    let utv1 = (p1 - ray.origin) / ray.direction;
-    let tv0 = min(utv0, utv1);
-    let tv1 = max(utv0, utv1);
-    let t_near = max(max(tv0.x, tv0.y), tv0.z);
-    let t_far = min(min(tv1.x, tv1.y), tv1.z);
-    return BoxIntersectionResult(
-        /* normal:   */ vec3f(tv0 == vec3f(t_near)) * -sign(ray.direction),
-        /* distance: */ mix(t_far, t_near, f32(t_near > 0.0)),
-        /* is_hit:   */ t_far >= max(t_near, 0.0),
-    );
+const SKY_DEPTH: f32 = -1.0;
+
+// History is kept if its primary hit distance differs from expected one by less than this fraction
+const REPROJECTION_DEPTH_TOLERANCE: f32 = 0.02;
+
+// Sample count of reprojected history is limited, so disocclusion errors fade out quickly
+const REPROJECTION_MAX_SAMPLES: f32 = 16.0;
+
+fn is_camera_moving() -> bool {
+    return any(camera.previous_location != camera.location)
+        || any(camera.previous_direction != camera.direction)
+        || any(camera.previous_right != camera.right)
+        || any(camera.previous_up != camera.up);
 }
 
-fn box_intersect_test(p0: vec3f, p1: vec3f, ray: Ray) -> bool {
-    let utv0 = (p0 - ray.origin) / ray.direction;
-    let utv1 = (p1 - ray.origin) / ray.direction;
-    let tv0 = min(utv0, utv1);
-    let tv1 = max(utv0, utv1);
-    return min(min(tv1.x, tv1.y), tv1.z) >= max(max(max(tv0.x, tv0.y), tv0.z), 0.0);
+// Point at `to_point` from previous camera location was in image at previous frame
+fn is_in_previous_view(to_point: vec3f) -> bool {
+    return camera.projection == PROJECTION_PANORAMA || is_fisheye() || dot(to_point, camera.previous_direction) > 0.0;
 }
 
-struct SceneIntersectionResult {
-    color: vec3f,
-    distance: f32,
-    emission: vec3f,
-    is_hit: bool,
-    normal: vec3f,
+// Texture coordinate of point at previous frame, it must be in previous view
+fn previous_tex_coord(to_point: vec3f) -> vec2f {
+    let local = vec3f(
+        dot(to_point, camera.previous_right),
+        dot(to_point, camera.previous_up),
+        dot(to_point, camera.previous_direction)
+    );
+    var coord = local.xy / vec2f(camera.projection_width, camera.projection_height);
+    switch camera.projection {
+        case PROJECTION_ORTHOGRAPHIC: {}
+        case PROJECTION_PANORAMA: {
+            return panorama_tex_coord(normalize(local)) - camera.lens_shift;
+        }
+        case PROJECTION_FISHEYE_EQUIDISTANT, PROJECTION_FISHEYE_STEREOGRAPHIC: {
+            let side = length(local.xy);
+            coord = vec2f(0.0);
+            if side > 0.0 {
+                coord = local.xy / side * fisheye_radius(atan2(side, local.z)) / vec2f(camera.projection_width, camera.projection_height);
+            }
+        }
+        default: {
+            coord *= camera.near / local.z;
+        }
+    }
+    return coord * 0.5 + 0.5 - camera.lens_shift;
 }
 
-fn intersect_scene(ray: Ray) -> SceneIntersectionResult {
-    var result: SceneIntersectionResult;
+// Orthographic camera sees the same sky direction in all pixels, so sky has no screen position
+fn is_parallel_sky(depth: f32) -> bool {
+    return depth == SKY_DEPTH && camera.projection == PROJECTION_ORTHOGRAPHIC;
+}
 
-    result.is_hit = false;
-    result.distance = 100000000.0;
+struct History {
+    color: vec4f, // radiance sum and sample count
+    moment: f32, // luminance second moment sum
+    diffuse: vec3f, // diffuse radiance sum
+}
 
-    {
-        let i = sphere_intersect_check(vec3f(0.0, 2.0, -3.0), 1.0, ray);
+// Accumulated values of pixel primary hit at previous frame, zero if history is rejected
+fn reproject_history(pixel: vec2i, ray: Ray, depth: f32) -> History {
+    let rejected = History(vec4f(0.0), 0.0, vec3f(0.0));
 
-        if i.is_hit && i.distance < result.distance {
-            result.is_hit = true;
-            result.distance = i.distance;
-            result.color = vec3f(1.0, 1.0, 1.0);
-            result.emission = vec3f(1.0, 1.0, 1.0);
-            result.normal = i.normal;
-            // result.roughness = 1.0;
-        }
+    if system.static_frame_index == 0u {
+        return rejected;
     }
 
-    {
-        let i = sphere_intersect_check(vec3f(1.1, 0.55, -1.1), 0.5, ray);
+    var history_pixel = pixel;
+    var expected_depth = depth;
+    let is_moving = is_camera_moving();
 
-        if i.is_hit && i.distance < result.distance {
-            result.is_hit = true;
-            result.distance = i.distance;
-            result.color = vec3f(0.30, 0.47, 0.80);
-            result.emission = vec3f(0.0, 0.0, 0.0);
-            result.normal = i.normal;
-            // result.roughness = 1.0;
-        }
+    // Parallel sky changes only with view direction
+    if is_moving && is_parallel_sky(depth) && any(camera.previous_direction != camera.direction) {
+        return rejected;
     }
 
-    if box_intersect_test(vec3f(-12.0, -1.001, -12.0), vec3f(12.0, -0.999, 12.0), ray) {
-        let i = plane_intersect_check(vec3f(0.0, -1.0, 0.0), vec3f(0.0, 1.0, 0.0), ray);
+    if is_moving && !is_parallel_sky(depth) {
+        // Sky is infinitely far, so only direction to it matters
+        var to_point = ray.direction;
+        if depth != SKY_DEPTH {
+            to_point = ray.origin + ray.direction * depth - camera.previous_location;
+            expected_depth = length(to_point);
+        }
 
-        if i.is_hit && i.distance < result.distance {
-            result.is_hit = true;
+        if !is_in_previous_view(to_point) {
+            return rejected;
+        }
+        let tex_coord = previous_tex_coord(to_point);
+
+        // Texture coordinate Y axis points up, pixel one points down
+        history_pixel = vec2i(floor(vec2f(tex_coord.x, 1.0 - tex_coord.y) * system.resolution));
 
-            result.distance = i.distance;
-            result.emission = vec3f(0.0, 0.0, 0.0);
-            result.color = vec3f(0.8, 0.4, 0.4);
-            result.normal = vec3f(0.0, 1.0, 0.0);
-            // result.roughness = 0.001;
+        if any(history_pixel < vec2i(0)) || any(history_pixel >= vec2i(system.resolution)) {
+            return rejected;
         }
     }
 
-    {
-        let i = box_intersect_check(vec3f(0.0, 0.0, 0.0), vec3f(1.0, -1.0, 1.0), ray);
+    let history_features = textureLoad(read_features, history_pixel, 0);
+    let history_depth = history_features.z;
+    if (history_depth == SKY_DEPTH) != (depth == SKY_DEPTH) {
+        return rejected;
+    }
+    if depth != SKY_DEPTH && abs(history_depth - expected_depth) > expected_depth * REPROJECTION_DEPTH_TOLERANCE {
+        return rejected;
+    }
 
-        if i.is_hit && i.distance < result.distance {
-            result.is_hit = true;
-            result.distance = i.distance;
-            result.color = vec3f(0.8, 0.8, 0.8);
-            result.emission = vec3f(0.0, 0.0, 0.0);
-            result.normal = i.normal;
-            // result.roughness = 0.001;
-        }
+    var history = History(textureLoad(read_collector, history_pixel, 0), history_features.w, vec3f(0.0));
+    if SEPARATE_DIFFUSE {
+        history.diffuse = textureLoad(read_diffuse, history_pixel, 0).rgb;
+    }
+    if is_moving && history.color.a > REPROJECTION_MAX_SAMPLES {
+        let scale = REPROJECTION_MAX_SAMPLES / history.color.a;
+        history.color *= scale;
+        history.moment *= scale;
+        history.diffuse *= scale;
     }
 
-    return result;
+    return history;
 }
 
-const MAX_BOUNCE: u32 = 8;
+override SAMPLES_PER_PASS: u32; // samples per pixel of accumulation pass, set by Render
 
-fn trace(init_ray: Ray) -> vec3f {
-    var ray_color = vec3f(1.0, 1.0, 1.0);
-    var incoming_light = vec3f(0.0, 0.0, 0.0);
-    var ray = init_ray;
+// Adaptive sampling parameters. Pixels are never skipped before minimal sample count is reached,
+// and all of them are sampled once per refresh period, so falsely converged pixels can recover.
+const ADAPTIVE_MIN_FRAME_COUNT: f32 = 16.0;
+const ADAPTIVE_REFRESH_PERIOD: u32 = 8;
+// Luminance below which absolute error is used, keeps dark pixels from being sampled forever
+const ADAPTIVE_MIN_LUMINANCE: f32 = 0.01;
 
-    var index = MAX_BOUNCE + 1;
+// Number of complete passes over all tiles, equal to frame index if tiling is disabled
+fn tile_pass_index() -> u32 {
+    return system.static_frame_index / system.tile_count;
+}
 
-    while index > 0 {
-        let result = intersect_scene(ray);
+// Check if pixel belongs to tile traced at current frame, tiles are visited row by row
+fn is_in_current_tile(pixel: vec2u) -> bool {
+    if system.tile_count <= 1u {
+        return true;
+    }
 
-        if !result.is_hit {
-            break;
-        }
+    let column_count = (u32(system.resolution.x) + system.tile_size - 1u) / system.tile_size;
+    let tile = pixel / system.tile_size;
+    return tile.y * column_count + tile.x == system.static_frame_index % system.tile_count;
+}
 
-        incoming_light += result.emission * ray_color;
-        ray.origin += ray.direction * result.distance + result.normal * 0.001;
-        ray.direction = rand_vec3();
-        ray.direction *= sign(dot(ray.direction, result.normal));
-        ray_color *= result.color * clamp(dot(result.normal, ray.direction), 0.0, 1.0) * 3.14159265358979;
+// Check if relative standard error of pixel mean luminance is below adaptive sampling threshold
+fn is_converged(history: History) -> bool {
+    let count = history.color.a;
 
-        index = index - 1;
+    if system.adaptive_threshold <= 0.0 || count < ADAPTIVE_MIN_FRAME_COUNT || tile_pass_index() % ADAPTIVE_REFRESH_PERIOD == 0u {
+        return false;
     }
 
-    return incoming_light;
+    let mean = luminance(history.color.rgb) / count;
+    let variance = max(history.moment / count - mean * mean, 0.0);
+    return sqrt(variance / count) < system.adaptive_threshold * max(mean, ADAPTIVE_MIN_LUMINANCE);
 }
 
-fn tex_coord_to_ray(tex_coord: vec2f) -> Ray {
-    let coord = tex_coord * 2.0 - 1.0;
-    var ray: Ray;
-    ray.origin = camera.location;
-    ray.direction = normalize(camera.direction * camera.near + camera.right * camera.projection_width * coord.x + camera.up * camera.projection_height * coord.y);
-    return ray;
+struct CollectorOutput {
+    @location(0) color: vec4f, // radiance sum and sample count
+    @location(1) features: vec4f, // see read_features
+}
+
+// Unit vector to point of [-1, 1] square
+fn octahedral_encode(n: vec3f) -> vec2f {
+    let p = n.xy / (abs(n.x) + abs(n.y) + abs(n.z));
+
+    if n.z >= 0.0 {
+        return p;
+    }
+    return (1.0 - abs(p.yx)) * select(vec2f(-1.0), vec2f(1.0), p >= vec2f(0.0));
 }
 
 @fragment
-fn fs_main(@builtin(position) frag_coord_4f: vec4f, @location(0) tex_coord: vec2f) -> @location(0) vec4f {
-    _rand_seed = u32(tex_coord.x * 3123456.0) * u32(tex_coord.y * 8765345.0) * u32((cos(system.time) + 1.123123) * 324234234.5);
+fn fs_main(@builtin(position) frag_coord_4f: vec4f, @location(0) tex_coord: vec2f) -> CollectorOutput {
+    // Frame index differs between accumulation passes of the same presented frame
+    _rand_seed = u32(tex_coord.x * 3123456.0) * u32(tex_coord.y * 8765345.0) * u32((cos(system.time) + 1.123123) * 324234234.5) ^ hash_u32(system.static_frame_index);
+
+    // Primary hit at pixel center and current frame time is used to find pixel history
+    let center_ray = tex_coord_to_ray(tex_coord);
+    let primary = intersect_scene(center_ray);
+    var depth = SKY_DEPTH;
+    if primary.is_hit {
+        depth = primary.distance;
+    }
+    let history = reproject_history(vec2i(frag_coord_4f.xy), center_ray, depth);
+
+    // Converged pixels and pixels outside of current tile keep their history without new samples
+    if !is_in_current_tile(vec2u(frag_coord_4f.xy)) || is_converged(history) {
+        if SEPARATE_DIFFUSE {
+            textureStore(write_diffuse, vec2u(frag_coord_4f.xy), vec4f(history.diffuse, 0.0));
+        }
+        return CollectorOutput(history.color, vec4f(octahedral_encode(primary.normal), depth, history.moment));
+    }
+
+    var out_color = vec3f(0.0);
+    var out_diffuse = vec3f(0.0);
+    for (var i = 0u; i < SAMPLES_PER_PASS; i++) {
+        start_sample(vec2u(frag_coord_4f.xy), tile_pass_index() * SAMPLES_PER_PASS + i);
+        let sample_tex_coord = tex_coord + system.texel_size * vec2f(rand_f32(), rand_f32());
+        if !is_in_image_circle(sample_tex_coord) {
+            continue;
+        }
+        let traced = trace_path(tex_coord_to_lens_ray(sample_tex_coord));
+        out_color += traced.radiance;
+        out_diffuse += traced.diffuse;
+    }
+    out_color /= f32(SAMPLES_PER_PASS);
+
+    if SEPARATE_DIFFUSE {
+        textureStore(write_diffuse, vec2u(frag_coord_4f.xy), vec4f(history.diffuse + out_diffuse / f32(SAMPLES_PER_PASS), 0.0));
+    }
 
-    let out_color = (
-        trace(tex_coord_to_ray(tex_coord + system.texel_size * vec2f(rand_f32(), rand_f32()))) +
-        trace(tex_coord_to_ray(tex_coord + system.texel_size * vec2f(rand_f32(), rand_f32()))) +
-        trace(tex_coord_to_ray(tex_coord + system.texel_size * vec2f(rand_f32(), rand_f32()))) +
-        trace(tex_coord_to_ray(tex_coord + system.texel_size * vec2f(rand_f32(), rand_f32())))
-    ) / 4.0;
+    let out_luminance = luminance(out_color);
 
-    return vec4f(textureLoad(read_collector, vec2i(frag_coord_4f.xy), 0).xyz * f32(system.static_frame_index != 0) + out_color, 0.0);
+    return CollectorOutput(
+        history.color + vec4f(out_color, 1.0),
+        vec4f(octahedral_encode(primary.normal), depth, history.moment + out_luminance * out_luminance)
+    );
 } // fn fs_main
 
+struct AovOutput {
+    @location(0) albedo_depth: vec4f, // first hit albedo and linear depth, zero for sky
+    @location(1) normal_motion: vec4f, // octahedral shading normal and motion vector in pixels
+}
+
+// Octahedral normal of sky, it lies outside of encoded normal square
+const AOV_NO_NORMAL: vec2f = vec2f(2.0);
+
+// Auxiliary outputs of primary hit at pixel center
+@fragment
+fn fs_aov(@location(0) tex_coord: vec2f) -> AovOutput {
+    let ray = tex_coord_to_ray(tex_coord);
+    let result = intersect_scene(ray);
+
+    var albedo_depth = vec4f(0.0);
+    var normal = AOV_NO_NORMAL;
+    // Sky is infinitely far, so only direction to it matters
+    var to_point = ray.direction;
+
+    if result.is_hit {
+        var material = materials[result.material_index];
+        let shading_normal = apply_material_maps(&material, result.uv, result.normal, result.tangent, result.custom_data, result.vertex_color);
+        var point = ray.origin + ray.direction * result.distance;
+
+        albedo_depth = vec4f(apply_decals(point, result.normal, material.color), result.distance * dot(ray.direction, camera.direction));
+        normal = octahedral_encode(shading_normal);
+
+        // Point of moving instance is moved to its previous-frame position
+        if result.instance_id != 0u {
+            let instance = instances[result.instance_index];
+            point = (instance.previous_object_to_world * (instance.world_to_object * vec4f(point, 1.0))).xyz;
+        }
+        to_point = point - camera.previous_location;
+    }
+
+    var motion = vec2f(0.0);
+    // Parallel sky has no motion
+    if (result.is_hit || camera.projection != PROJECTION_ORTHOGRAPHIC) && is_in_previous_view(to_point) {
+        // Texture coordinate Y axis points up, pixel one points down
+        motion = (previous_tex_coord(to_point) - tex_coord) * vec2f(1.0, -1.0) * system.resolution;
+    }
+
+    return AovOutput(albedo_depth, vec4f(normal, motion));
+} // fn fs_aov
+
+// Instance ID and primitive index + 1 of primary hit at pixel center. Instance ID is zero for
+// primitives and both are zero if nothing was hit.
+@fragment
+fn fs_id(@location(0) tex_coord: vec2f) -> @location(0) vec2u {
+    let hit = intersect_scene(tex_coord_to_ray(tex_coord));
+    if !hit.is_hit {
+        return vec2u(0u);
+    }
+    return vec2u(hit.instance_id, hit.primitive_index + 1u);
+} // fn fs_id
+
+struct RecordParams {
+    pixel: vec2u,
+    path_count: u32,
+    seed: u32, // differs between recordings of the same pixel
+}
+
+struct RecordedPath {
+    radiance: vec3f,
+    vertex_count: u32,
+    vertices: array<PathVertex, MAX_RECORDED_VERTICES>,
+}
+
+@group(3) @binding(1) var<storage, read_write> recorded_paths: array<RecordedPath>;
+@group(3) @binding(2) var<uniform> record_params: RecordParams;
+
+// Trace paths through pixel with vertex recording, one per invocation
+@compute @workgroup_size(64)
+fn cs_record_paths(@builtin(global_invocation_id) id: vec3u) {
+    let index = id.x;
+    if index >= record_params.path_count {
+        return;
+    }
+
+    let pixel = record_params.pixel;
+    let tex_coord = vec2f((f32(pixel.x) + 0.5) * system.texel_size.x, 1.0 - (f32(pixel.y) + 0.5) * system.texel_size.y);
+    _rand_seed = hash_u32(index ^ hash_u32(record_params.seed));
+    start_sample(pixel, record_params.seed * record_params.path_count + index);
+
+    let ray = tex_coord_to_lens_ray(tex_coord + system.texel_size * vec2f(rand_f32(), rand_f32()));
+    record_vertex(ray.origin, PATH_EVENT_CAMERA, ray.direction, vec3f(1.0), vec3f(0.0));
+    let traced = trace_path(ray);
+
+    recorded_paths[index].radiance = traced.radiance;
+    recorded_paths[index].vertex_count = _path_vertex_count;
+    for (var i = 0u; i < _path_vertex_count; i++) {
+        recorded_paths[index].vertices[i] = _path_vertices[i];
+    }
+} // fn cs_record_paths
+
 // file shader.wgsl
@@ -0,0 +1,66 @@
+/// P_TR Project
+/// `File` render/resample.wgsl
+/// `Description` Accumulated image resampling on resize module.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@group(0) @binding(0) var collector: texture_2d<f32>; // radiance sum and sample count of old size
+@group(0) @binding(1) var features: texture_2d<f32>; // octahedral normal, primary hit distance, luminance second moment sum
+@group(0) @binding(2) var diffuse: texture_2d<f32>; // diffuse radiance sum of old size, single pixel if it isn't separated
+
+struct Resample {
+    scale: vec2f, // ratio of new projection extent to old one
+    target_size: vec2f, // new collector size in pixels
+    weight: f32, // scale of history sums, new samples outweigh resampled ones
+}
+
+@group(1) @binding(0) var<uniform> resample: Resample;
+@group(2) @binding(0) var target_diffuse: texture_storage_2d<rgba32float, write>; // stores outside of single pixel texture are discarded
+
+struct CollectorOutput {
+    @location(0) color: vec4f,
+    @location(1) features: vec4f,
+}
+
+@fragment
+fn fs_main(@builtin(position) frag_coord_4f: vec4f) -> CollectorOutput {
+    let source_size = vec2f(textureDimensions(collector));
+
+    // Image is scaled around center, as projection extent is
+    let tex_coord = (frag_coord_4f.xy / resample.target_size - 0.5) * resample.scale + 0.5;
+    if any(tex_coord < vec2f(0.0)) || any(tex_coord > vec2f(1.0)) {
+        textureStore(target_diffuse, vec2u(frag_coord_4f.xy), vec4f(0.0));
+        return CollectorOutput(vec4f(0.0), vec4f(0.0));
+    }
+
+    // Collector format isn't filterable, so taps are blended manually
+    let position = tex_coord * source_size - 0.5;
+    let base = floor(position);
+    let t = position - base;
+    let max_pixel = vec2i(source_size) - 1;
+
+    var color = vec4f(0.0);
+    var moment = 0.0;
+    var diffuse_sum = vec3f(0.0);
+    let max_diffuse_pixel = vec2i(textureDimensions(diffuse)) - 1;
+    for (var i = 0; i < 4; i++) {
+        let offset = vec2i(i % 2, i / 2);
+        let pixel = clamp(vec2i(base) + offset, vec2i(0), max_pixel);
+        let tap_weight = select(1.0 - t.x, t.x, offset.x == 1) * select(1.0 - t.y, t.y, offset.y == 1);
+        color += textureLoad(collector, pixel, 0) * tap_weight;
+        moment += textureLoad(features, pixel, 0).w * tap_weight;
+        diffuse_sum += textureLoad(diffuse, min(pixel, max_diffuse_pixel), 0).rgb * tap_weight;
+    }
+    textureStore(target_diffuse, vec2u(frag_coord_4f.xy), vec4f(diffuse_sum * resample.weight, 0.0));
+
+    // Normal and distance aren't blended, so reprojection still rejects history across edges
+    let nearest = textureLoad(features, clamp(vec2i(tex_coord * source_size), vec2i(0), max_pixel), 0);
+
+    return CollectorOutput(color * resample.weight, vec4f(nearest.xyz, moment * resample.weight));
+} // fn fs_main
+
+// file resample.wgsl
@@ -0,0 +1,126 @@
+/// P_TR Project
+/// `File` render/sampling.wgsl
+/// `Description` Random numbers and sample sequences module.
+/// `Author` TioT2
+
+const PI: f32 = 3.14159265358979;
+
+@group(0) @binding(3) var blue_noise: texture_2d<f32>; // tiled threshold map
+
+var<private> _rand_seed : u32 = 123456789;
+
+fn rand_u32() -> u32 {
+    _rand_seed ^= _rand_seed << 13;
+    _rand_seed ^= _rand_seed >> 17;
+    _rand_seed ^= _rand_seed << 5;
+    return _rand_seed;
+}
+
+const SAMPLER_RANDOM: u32 = 0;
+const SAMPLER_SOBOL: u32 = 1;
+const SAMPLER_BLUE_NOISE: u32 = 2;
+
+// Sobol generator matrix columns of dimensions 1-3, dimension 0 is van der Corput sequence.
+// Constant arrays can't be indexed dynamically, so they are copied to private memory.
+var<private> _sobol_directions: array<u32, 96> = array<u32, 96>(
+    0x80000000u, 0xc0000000u, 0xa0000000u, 0xf0000000u, 0x88000000u, 0xcc000000u, 0xaa000000u, 0xff000000u, 0x80800000u, 0xc0c00000u, 0xa0a00000u, 0xf0f00000u, 0x88880000u, 0xcccc0000u, 0xaaaa0000u, 0xffff0000u,
+    0x80008000u, 0xc000c000u, 0xa000a000u, 0xf000f000u, 0x88008800u, 0xcc00cc00u, 0xaa00aa00u, 0xff00ff00u, 0x80808080u, 0xc0c0c0c0u, 0xa0a0a0a0u, 0xf0f0f0f0u, 0x88888888u, 0xccccccccu, 0xaaaaaaaau, 0xffffffffu,
+    0x80000000u, 0xc0000000u, 0x60000000u, 0x90000000u, 0xe8000000u, 0x5c000000u, 0x8e000000u, 0xc5000000u, 0x68800000u, 0x9cc00000u, 0xee600000u, 0x55900000u, 0x80680000u, 0xc09c0000u, 0x60ee0000u, 0x90550000u,
+    0xe8808000u, 0x5cc0c000u, 0x8e606000u, 0xc5909000u, 0x6868e800u, 0x9c9c5c00u, 0xeeee8e00u, 0x5555c500u, 0x8000e880u, 0xc0005cc0u, 0x60008e60u, 0x9000c590u, 0xe8006868u, 0x5c009c9cu, 0x8e00eeeeu, 0xc5005555u,
+    0x80000000u, 0xc0000000u, 0x20000000u, 0x50000000u, 0xf8000000u, 0x74000000u, 0xa2000000u, 0x93000000u, 0xd8800000u, 0x25400000u, 0x59e00000u, 0xe6d00000u, 0x78080000u, 0xb40c0000u, 0x82020000u, 0xc3050000u,
+    0x208f8000u, 0x51474000u, 0xfbea2000u, 0x75d93000u, 0xa0858800u, 0x914e5400u, 0xdbe79e00u, 0x25db6d00u, 0x58800080u, 0xe54000c0u, 0x79e00020u, 0xb6d00050u, 0x800800f8u, 0xc00c0074u, 0x200200a2u, 0x50050093u,
+);
+
+// Current path sample, see start_sample
+var<private> _sample_pixel: vec2u = vec2u(0);
+var<private> _sample_pixel_seed: u32 = 0;
+var<private> _sample_index: u32 = 0;
+var<private> _sample_dimension: u32 = 0;
+
+fn hash_u32(value: u32) -> u32 {
+    var x = value;
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+// Start sample of pixel, its dimensions are taken by rand_f32 calls in order
+fn start_sample(pixel: vec2u, index: u32) {
+    _sample_pixel = pixel;
+    _sample_pixel_seed = hash_u32(pixel.x ^ hash_u32(pixel.y));
+    _sample_index = index;
+    _sample_dimension = 0u;
+}
+
+fn sobol(index: u32, dimension: u32) -> u32 {
+    if dimension == 0u {
+        return reverseBits(index);
+    }
+
+    // Index is shifted by one bit per step, shifts by 32 bits and more are undefined
+    var result = 0u;
+    var bits = index;
+    for (var bit = 0u; bits != 0u; bit++) {
+        if (bits & 1u) != 0u {
+            result ^= _sobol_directions[(dimension - 1u) * 32u + bit];
+        }
+        bits >>= 1u;
+    }
+    return result;
+}
+
+fn laine_karras_permutation(value: u32, seed: u32) -> u32 {
+    var x = value + seed;
+    x ^= x * 0x6c50b47cu;
+    x ^= x * 0xb82f1e52u;
+    x ^= x * 0xc7afe638u;
+    x ^= x * 0x8d22f6e6u;
+    return x;
+}
+
+// Owen scrambling in base 2
+fn nested_uniform_scramble(value: u32, seed: u32) -> u32 {
+    return reverseBits(laine_karras_permutation(reverseBits(value), seed));
+}
+
+// Dimensions are grouped by four, each group has its own sample order (Burley 2020, hash-based Owen scrambling)
+fn sobol_owen_sample(dimension: u32) -> f32 {
+    let group_seed = hash_u32(_sample_pixel_seed ^ hash_u32(dimension / 4u));
+    let index = nested_uniform_scramble(_sample_index, group_seed);
+    let value = nested_uniform_scramble(sobol(index, dimension % 4u), hash_u32(group_seed + dimension));
+    return f32(value >> 8u) / 16777216.0;
+}
+
+fn blue_noise_sample(dimension: u32) -> f32 {
+    let size = textureDimensions(blue_noise);
+
+    // Dimensions read the texture with different offsets, R2 sequence keeps them apart
+    let offset = vec2u(fract(f32(dimension) * vec2f(0.7548776662, 0.5698402910)) * vec2f(size));
+    let value = textureLoad(blue_noise, (_sample_pixel + offset) % size, 0).r;
+
+    // Golden ratio sequence keeps each pixel stratified over samples
+    return fract(value + f32(_sample_index) * 0.6180339887);
+}
+
+// Next dimension of current sample
+fn rand_f32() -> f32 {
+    let dimension = _sample_dimension;
+    _sample_dimension++;
+
+    switch system.sampler_kind {
+        case SAMPLER_SOBOL: {
+            return sobol_owen_sample(dimension);
+        }
+        case SAMPLER_BLUE_NOISE: {
+            return blue_noise_sample(dimension);
+        }
+        default: {
+            return f32(rand_u32()) / 4294967295.0;
+        }
+    }
+}
+
+// file sampling.wgsl
@@ -0,0 +1,57 @@
+/// P_TR Project
+/// `File` render/sharpen.wgsl
+/// `Description` Contrast-adaptive sharpening module.
+/// `Author` TioT2
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4f {
+    return vec4f(f32(index / 2) * 2.0 - 1.0, f32(index % 2) * 2.0 - 1.0, 0.0, 1.0);
+}
+
+struct Sharpen {
+    strength: f32,
+}
+
+@group(0) @binding(0) var collector: texture_2d<f32>; // radiance sum and sample count
+@group(1) @binding(0) var<uniform> sharpen: Sharpen;
+
+// Mean radiance of pixel, image border is extended
+fn load_mean(pixel: vec2i) -> vec3f {
+    let collected = textureLoad(collector, clamp(pixel, vec2i(0), vec2i(textureDimensions(collector)) - 1), 0);
+    return collected.rgb / max(collected.a, 1.0);
+}
+
+// Mean radiance sharpened by cross-shaped filter, as in AMD FidelityFX CAS. Negative lobe weight follows local
+// contrast, so noisy and high contrast areas aren't oversharpened. Output has unit sample count.
+@fragment
+fn fs_main(@builtin(position) frag_coord_4f: vec4f) -> @location(0) vec4f {
+    let pixel = vec2i(frag_coord_4f.xy);
+
+    // a b c
+    // d e f
+    // g h i
+    let a = load_mean(pixel + vec2i(-1, -1));
+    let b = load_mean(pixel + vec2i( 0, -1));
+    let c = load_mean(pixel + vec2i( 1, -1));
+    let d = load_mean(pixel + vec2i(-1,  0));
+    let e = load_mean(pixel);
+    let f = load_mean(pixel + vec2i( 1,  0));
+    let g = load_mean(pixel + vec2i(-1,  1));
+    let h = load_mean(pixel + vec2i( 0,  1));
+    let i = load_mean(pixel + vec2i( 1,  1));
+
+    // Soft minimum and maximum over cross and whole neighborhood
+    let cross_min = min(min(min(b, d), min(e, f)), h);
+    let cross_max = max(max(max(b, d), max(e, f)), h);
+    let min_rgb = cross_min + min(cross_min, min(min(a, c), min(g, i)));
+    let max_rgb = cross_max + max(cross_max, max(max(a, c), max(g, i)));
+
+    // Radiance isn't limited by display range, so contrast is measured by ratio of extremes only
+    let amount = sqrt(clamp(min_rgb / max(max_rgb, vec3f(1e-6)), vec3f(0.0), vec3f(1.0)));
+    let weight = amount * (-0.2 * sharpen.strength);
+
+    let color = (e + (b + d + f + h) * weight) / (1.0 + 4.0 * weight);
+    return vec4f(max(color, vec3f(0.0)), 1.0);
+} // fn fs_main
+
+// file sharpen.wgsl
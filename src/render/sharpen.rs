@@ -0,0 +1,174 @@
+use crate::math::Ext2u;
+
+use super::{as_bytes, Collector, Render};
+
+/// Sharpened mean radiance with unit sample count
+const IMAGE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+#[derive(Copy, Clone, Default)]
+#[repr(C)]
+struct SharpenData {
+    strength: f32,
+    _pad0: [f32; 3],
+}
+
+/// Contrast-adaptive sharpening of presented image, restores detail softened by denoiser and resampling
+pub struct Sharpener {
+    pipeline: wgpu::RenderPipeline,
+    buffer: wgpu::Buffer,
+    sharpen_bind_group: wgpu::BindGroup,
+
+    view: wgpu::TextureView,
+    /// Output bind groups with features of each collector
+    output_bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl Sharpener {
+    fn create_view(device: &wgpu::Device, extent: Ext2u) -> wgpu::TextureView {
+        device.create_texture(&wgpu::TextureDescriptor {
+            dimension: wgpu::TextureDimension::D2,
+            format: IMAGE_FORMAT,
+            label: Some("Sharpened image"),
+            mip_level_count: 1,
+            sample_count: 1,
+            size: wgpu::Extent3d { width: extent.w, height: extent.h, depth_or_array_layers: 1 },
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        }).create_view(&wgpu::TextureViewDescriptor::default())
+    }
+
+    fn create_output_bind_groups(
+        device: &wgpu::Device,
+        collector_bind_group_layout: &wgpu::BindGroupLayout,
+        view: &wgpu::TextureView,
+        collectors: &[Collector; 2]
+    ) -> [wgpu::BindGroup; 2] {
+        collectors.each_ref().map(|collector| {
+            Render::create_collector_bind_group(device, collector_bind_group_layout, view, &collector.feature_view, &collector.diffuse_view)
+        })
+    }
+
+    /// Collector bind group layout is used to read presented image
+    pub fn new(device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, collectors: &[Collector; 2], extent: Ext2u) -> Self {
+        let sharpen_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Sharpen bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    count: None,
+                    ty: wgpu::BindingType::Buffer {
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                        ty: wgpu::BufferBindingType::Uniform,
+                    },
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                },
+            ],
+        });
+
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Sharpen UBO"),
+            mapped_at_creation: false,
+            size: std::mem::size_of::<SharpenData>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sharpen_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: buffer.as_entire_binding() },
+            ],
+            label: Some("Sharpen bind group"),
+            layout: &sharpen_bind_group_layout,
+        });
+
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Sharpen Shader"),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(include_str!("shaders/sharpen.wgsl")))
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            bind_group_layouts: &[collector_bind_group_layout, &sharpen_bind_group_layout],
+            ..Default::default()
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sharpen pipeline"),
+            depth_stencil: None,
+            fragment: Some(wgpu::FragmentState {
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "fs_main",
+                module: &shader_module,
+                targets: &[Some(wgpu::ColorTargetState {
+                    blend: None,
+                    format: IMAGE_FORMAT,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })]
+            }),
+            layout: Some(&layout),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            vertex: wgpu::VertexState {
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                entry_point: "vs_main",
+                module: &shader_module,
+            }
+        });
+
+        let view = Self::create_view(device, extent);
+        Self {
+            pipeline,
+            buffer,
+            sharpen_bind_group,
+            output_bind_groups: Self::create_output_bind_groups(device, collector_bind_group_layout, &view, collectors),
+            view,
+        }
+    } // fn new
+
+    /// Resize output image, `collectors` are ones of new extent
+    pub fn resize(&mut self, device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, collectors: &[Collector; 2], extent: Ext2u) {
+        self.view = Self::create_view(device, extent);
+        self.bind_collectors(device, collector_bind_group_layout, collectors);
+    }
+
+    /// Rebuild output bind groups of recreated collectors
+    pub fn bind_collectors(&mut self, device: &wgpu::Device, collector_bind_group_layout: &wgpu::BindGroupLayout, collectors: &[Collector; 2]) {
+        self.output_bind_groups = Self::create_output_bind_groups(device, collector_bind_group_layout, &self.view, collectors);
+    }
+
+    /// Sharpen image of collector bind group, `strength` is clamped to [0, 1] range
+    pub fn render(&self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, collector_bind_group: &wgpu::BindGroup, strength: f32) {
+        queue.write_buffer(&self.buffer, 0, as_bytes(&SharpenData {
+            // Filter normalization 1 + 4 * weight reaches zero at strength 1.25
+            strength: strength.clamp(0.0, 1.0),
+            ..Default::default()
+        }));
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+                resolve_target: None,
+                view: &self.view,
+            })],
+            ..Default::default()
+        });
+
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, collector_bind_group, &[]);
+        render_pass.set_bind_group(1, &self.sharpen_bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+    }
+
+    /// Bind group of last `render` result with collector layout, so place pass can present it as is
+    /// Features are taken from collector of `collector_index` the image was sharpened from
+    pub fn output_bind_group(&self, collector_index: usize) -> &wgpu::BindGroup {
+        &self.output_bind_groups[collector_index]
+    }
+}
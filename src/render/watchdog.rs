@@ -0,0 +1,104 @@
+use std::{sync::{mpsc, Arc}, time::{Duration, Instant}};
+
+use super::GpuContext;
+
+/// Interval of device polls while tracked frame runs, precision of measured duration
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Frame submission whose completion is awaited
+struct Submission {
+    start: Instant,
+    /// Completion time sent by callback of submitted work
+    done: mpsc::Receiver<Instant>,
+    /// Submission is already reported as slow while still running
+    is_reported: bool,
+}
+
+/// Detector of frames whose GPU work runs long enough to risk driver timeout (TDR) reset of device.
+/// Single frame is tracked at a time. Device is polled by separate thread while it runs, so completion
+/// time doesn't depend on when render loop gets to check it.
+pub struct Watchdog {
+    context: Arc<GpuContext>,
+    timeout: Option<Duration>,
+    submission: Option<Submission>,
+    /// Poller thread polls device while token it got is shared with completion callback, exits on drop of sender
+    poller: Option<mpsc::Sender<Arc<()>>>,
+}
+
+impl Watchdog {
+    pub fn new(context: Arc<GpuContext>) -> Self {
+        Self { context, timeout: None, submission: None, poller: None }
+    }
+
+    /// None disables detection
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+        if timeout.is_none() {
+            self.submission = None;
+            self.poller = None;
+        }
+    }
+
+    /// Thread polling device until completion callback drops its token, started on first use
+    fn poller(&mut self) -> &mpsc::Sender<Arc<()>> {
+        self.poller.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel::<Arc<()>>();
+            let context = self.context.clone();
+            std::thread::spawn(move || {
+                for token in receiver {
+                    // Callback is also dropped without call on device loss
+                    while Arc::strong_count(&token) > 1 {
+                        context.device.poll(wgpu::Maintain::Poll);
+                        std::thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            });
+            sender
+        })
+    }
+
+    /// Track frame whose work was submitted to `queue` since `start`, skipped while previous one is running
+    pub fn submit(&mut self, queue: &wgpu::Queue, start: Instant) {
+        if self.timeout.is_none() || self.submission.is_some() {
+            return;
+        }
+
+        let token = Arc::new(());
+        let (sender, done) = mpsc::channel();
+        queue.on_submitted_work_done({
+            let token = token.clone();
+            move || {
+                _ = sender.send(Instant::now());
+                drop(token);
+            }
+        });
+        if self.poller().send(token).is_err() {
+            return;
+        }
+        self.submission = Some(Submission { start, done, is_reported: false });
+    }
+
+    /// Duration of tracked frame if it exceeded timeout, frame still running is reported once
+    pub fn poll(&mut self) -> Option<Duration> {
+        let timeout = self.timeout?;
+        let submission = self.submission.as_mut()?;
+
+        let duration = match submission.done.try_recv() {
+            Err(mpsc::TryRecvError::Empty) => {
+                let duration = submission.start.elapsed();
+                if submission.is_reported || duration <= timeout {
+                    return None;
+                }
+                submission.is_reported = true;
+                return Some(duration);
+            }
+            Ok(done_time) => done_time.duration_since(submission.start),
+            // Callback is dropped with device loss, nothing to measure then
+            Err(mpsc::TryRecvError::Disconnected) => Duration::ZERO,
+        };
+        let is_reported = submission.is_reported;
+        self.submission = None;
+
+        (!is_reported && duration > timeout).then_some(duration)
+    } // fn poll
+}
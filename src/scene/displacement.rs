@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+
+use crate::math::{Ext2u, Vec2f, Vec3f};
+
+use super::Mesh;
+
+/// Single-channel height texture
+#[derive(Clone, Debug)]
+pub struct HeightMap {
+    pub extent: Ext2u,
+    pub data: Vec<f32>,
+}
+
+impl HeightMap {
+    /// Heights are taken from image luminance
+    pub fn from_image(image: &image::DynamicImage) -> Self {
+        let luma = image.to_luma32f();
+
+        Self {
+            extent: Ext2u::new(luma.width(), luma.height()),
+            data: luma.into_raw(),
+        }
+    }
+
+    fn texel(&self, x: i64, y: i64) -> f32 {
+        let x = x.rem_euclid(self.extent.w as i64) as usize;
+        let y = y.rem_euclid(self.extent.h as i64) as usize;
+        self.data[y * self.extent.w as usize + x]
+    }
+
+    /// Bilinear sample with repeat addressing, V axis points up
+    pub fn sample(&self, uv: Vec2f) -> f32 {
+        let x = uv.x * self.extent.w as f32 - 0.5;
+        let y = (1.0 - uv.y) * self.extent.h as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0) * (1.0 - tx) + self.texel(x0 + 1, y0) * tx;
+        let bottom = self.texel(x0, y0 + 1) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1) * tx;
+        top * (1.0 - ty) + bottom * ty
+    }
+}
+
+/// Displacement along vertex normals by height map
+#[derive(Clone, Debug)]
+pub struct Displacement {
+    pub height_map: HeightMap,
+    /// Displacement distance of unit height
+    pub scale: f32,
+    /// Height that corresponds to zero displacement
+    pub midlevel: f32,
+    /// Edges longer than this (in mesh space) are split before displacing
+    pub max_edge_length: f32,
+}
+
+/// Tessellation stops at this triangle count even if some edges are still too long
+const MAX_TESSELLATED_TRIANGLE_COUNT: usize = 1 << 22;
+
+impl Displacement {
+    /// Check if edge is long both in mesh space and in height map texels
+    fn should_split(&self, mesh: &Mesh, a: u32, b: u32) -> bool {
+        let (a, b) = (a as usize, b as usize);
+        let uv_delta = mesh.uvs[b] - mesh.uvs[a];
+        let texel_delta = Vec2f::new(uv_delta.x * self.height_map.extent.w as f32, uv_delta.y * self.height_map.extent.h as f32);
+
+        (mesh.positions[b] - mesh.positions[a]).length() > self.max_edge_length && texel_delta.length() > 1.0
+    }
+
+    /// Single tessellation pass. Edges are split independently of triangles, so neighbouring
+    /// triangles always agree on edge midpoints and no T-junctions appear. Split decisions are keyed by
+    /// endpoint positions, so edges duplicated at UV seams are split on both sides. Returns false if nothing was split.
+    fn tessellation_pass(&self, mesh: &mut Mesh) -> bool {
+        let position_key = |position: Vec3f| [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()];
+        let mut splits = HashMap::<([u32; 3], [u32; 3]), bool>::new();
+        let mut midpoints = HashMap::<(u32, u32), u32>::new();
+
+        for triangle in &mesh.indices {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                let key = (a.min(b), a.max(b));
+
+                if midpoints.contains_key(&key) {
+                    continue;
+                }
+
+                let (key_a, key_b) = (position_key(mesh.positions[a as usize]), position_key(mesh.positions[b as usize]));
+                let position_edge = (key_a.min(key_b), key_a.max(key_b));
+                if !*splits.entry(position_edge).or_insert_with(|| self.should_split(mesh, a, b)) {
+                    continue;
+                }
+
+                let (a, b) = (a as usize, b as usize);
+                midpoints.insert(key, mesh.positions.len() as u32);
+                mesh.positions.push((mesh.positions[a] + mesh.positions[b]) / 2.0);
+                mesh.uvs.push((mesh.uvs[a] + mesh.uvs[b]) / 2.0);
+
+                // Opposite normals cancel out, face normal is used then
+                let normal = mesh.normals[a] + mesh.normals[b];
+                mesh.normals.push(if normal.length2() > 1e-6 {
+                    normal.normalized()
+                } else {
+                    let [v0, v1, v2] = triangle.map(|v| mesh.positions[v as usize]);
+                    ((v1 - v0) % (v2 - v0)).normalized()
+                });
+                if !mesh.colors.is_empty() {
+                    mesh.colors.push((mesh.colors[a] + mesh.colors[b]) / 2.0);
+                }
+            }
+        }
+
+        if midpoints.is_empty() {
+            return false;
+        }
+
+        let mut indices = Vec::with_capacity(mesh.indices.len() * 4);
+
+        for v in &mesh.indices {
+            let m = [0, 1, 2].map(|i| midpoints.get(&(v[i].min(v[(i + 1) % 3]), v[i].max(v[(i + 1) % 3]))).copied());
+
+            match m.iter().filter(|m| m.is_some()).count() {
+                0 => indices.push(*v),
+                1 => {
+                    let k = m.iter().position(Option::is_some).unwrap();
+                    let (v0, v1, v2, m01) = (v[k], v[(k + 1) % 3], v[(k + 2) % 3], m[k].unwrap());
+
+                    indices.extend([[v0, m01, v2], [m01, v1, v2]]);
+                }
+                2 => {
+                    // Rotate triangle so that the only unsplit edge is v2-v0
+                    let k = (m.iter().position(Option::is_none).unwrap() + 1) % 3;
+                    let (v0, v1, v2) = (v[k], v[(k + 1) % 3], v[(k + 2) % 3]);
+                    let (m01, m12) = (m[k].unwrap(), m[(k + 1) % 3].unwrap());
+
+                    indices.extend([[v0, m01, m12], [m01, v1, m12], [v0, m12, v2]]);
+                }
+                _ => {
+                    let [m01, m12, m20] = m.map(Option::unwrap);
+
+                    indices.extend([[v[0], m01, m20], [m01, v[1], m12], [m20, m12, v[2]], [m01, m12, m20]]);
+                }
+            }
+        }
+
+        mesh.indices = indices;
+        true
+    } // fn tessellation_pass
+
+    /// Tessellate mesh by edge length criteria and displace its vertices.
+    /// Mesh must have texture coordinates.
+    pub fn apply(&self, mesh: &Mesh) -> Mesh {
+        let mut mesh = mesh.clone();
+
+        // Smooth normals keep displaced surface closed across split vertices
+        mesh.compute_smooth_normals();
+
+        while mesh.indices.len() < MAX_TESSELLATED_TRIANGLE_COUNT && self.tessellation_pass(&mut mesh) {}
+
+        for ((position, normal), uv) in mesh.positions.iter_mut().zip(mesh.normals.iter()).zip(mesh.uvs.iter()) {
+            *position += *normal * ((self.height_map.sample(*uv) - self.midlevel) * self.scale);
+        }
+
+        mesh.compute_smooth_normals();
+        mesh
+    }
+}
@@ -0,0 +1,67 @@
+use crate::math::{Ext2u, Vec3f};
+
+/// Equirectangular environment map, lights everything rays escape to
+#[derive(Clone, Debug)]
+pub struct Environment {
+    pub extent: Ext2u,
+    /// Linear RGBA radiance, rows go from top (+Y) to bottom
+    pub data: Vec<f32>,
+    pub intensity: f32,
+    /// Rotation around Y axis, in radians
+    pub rotation: f32,
+}
+
+/// Piecewise-constant distribution for environment map importance sampling
+pub struct EnvironmentDistribution {
+    /// Per-row CDFs, each row ends with 1
+    pub conditional_cdf: Vec<f32>,
+    /// CDF over rows
+    pub marginal_cdf: Vec<f32>,
+    /// Sum of sampling weights of all pixels, zero for black maps
+    pub total_weight: f32,
+}
+
+pub fn luminance(color: Vec3f) -> f32 {
+    color.x * 0.2126 + color.y * 0.7152 + color.z * 0.0722
+}
+
+/// Write normalized prefix sums of `weights` to `cdf`, uniform CDF is used for zero weights. Returns weight sum.
+fn build_cdf(weights: impl Iterator<Item = f32>, cdf: &mut [f32]) -> f32 {
+    let mut sum = 0.0;
+    for (value, weight) in cdf.iter_mut().zip(weights) {
+        sum += weight;
+        *value = sum;
+    }
+
+    let count = cdf.len() as f32;
+    for (index, value) in cdf.iter_mut().enumerate() {
+        *value = if sum > 0.0 { *value / sum } else { (index + 1) as f32 / count };
+    }
+
+    sum
+}
+
+impl Environment {
+    pub fn pixel(&self, x: u32, y: u32) -> Vec3f {
+        let offset = (y * self.extent.w + x) as usize * 4;
+        Vec3f::new(self.data[offset], self.data[offset + 1], self.data[offset + 2])
+    }
+
+    /// Build distribution proportional to pixel luminance multiplied by its solid angle
+    pub fn build_distribution(&self) -> EnvironmentDistribution {
+        let (width, height) = (self.extent.w as usize, self.extent.h as usize);
+        let mut conditional_cdf = vec![0.0; width * height];
+        let mut row_weights = Vec::with_capacity(height);
+
+        for (y, row_cdf) in conditional_cdf.chunks_mut(width).enumerate() {
+            let sin_theta = (std::f32::consts::PI * (y as f32 + 0.5) / height as f32).sin();
+            let weights = (0..width).map(|x| luminance(self.pixel(x as u32, y as u32)) * sin_theta);
+            row_weights.push(build_cdf(weights, row_cdf));
+        }
+
+        let mut marginal_cdf = vec![0.0; height];
+        let total_weight = build_cdf(row_weights.into_iter(), &mut marginal_cdf);
+
+        EnvironmentDistribution { conditional_cdf, marginal_cdf, total_weight }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::{locale::tr_args, math::Vec3f};
+
+use super::{LoadError, Material, Mesh, Scene};
+
+/// Scene loaded by `load_for_lint`, names are indexed as scene meshes
+pub struct LintScene {
+    pub scene: Scene,
+    pub mesh_names: Vec<String>,
+    /// Errors of textures replaced by placeholders
+    pub texture_errors: Vec<LoadError>,
+}
+
+/// Content problem that loads fine but spoils render
+#[derive(Debug)]
+pub enum LintIssue {
+    /// Texture load error message
+    MissingTexture(String),
+    /// Mesh name and number of vertices with NaN or infinite attributes
+    NonFiniteVertices(String, usize),
+    /// Mesh name and number of zero-area triangles
+    DegenerateTriangles(String, usize),
+    /// Name of mesh used by emissive instance and number of edges shared by more than two triangles
+    NonManifoldEmissive(String, usize),
+    MaterialParameter {
+        material: String,
+        parameter: &'static str,
+        value: f32,
+        range: (f32, f32),
+    },
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LintIssue::MissingTexture(error) => f.write_str(&tr_args("lint.missing_texture", &[error])),
+            LintIssue::NonFiniteVertices(mesh, count) => f.write_str(&tr_args("lint.non_finite_vertices", &[mesh, count])),
+            LintIssue::DegenerateTriangles(mesh, count) => f.write_str(&tr_args("lint.degenerate_triangles", &[mesh, count])),
+            LintIssue::NonManifoldEmissive(mesh, count) => f.write_str(&tr_args("lint.non_manifold_emissive", &[mesh, count])),
+            LintIssue::MaterialParameter { material, parameter, value, range } => {
+                f.write_str(&tr_args("lint.material_parameter", &[material, parameter, value, &range.0, &range.1]))
+            }
+        }
+    }
+}
+
+const UNIT_RANGE: (f32, f32) = (0.0, 1.0);
+/// Refraction indices of real transparent materials, lower values make surfaces reflect inside out
+const IOR_RANGE: (f32, f32) = (1.0, 3.0);
+
+/// Material parameters with their valid ranges, colors are checked by components
+fn material_parameters(material: &Material) -> Vec<(&'static str, f32, (f32, f32))> {
+    let color = |name, color: Vec3f, range| [color.x, color.y, color.z].map(|value| (name, value, range));
+
+    match *material {
+        Material::Lambertian { color: albedo } => color("color", albedo, UNIT_RANGE).to_vec(),
+        Material::Metal { color: reflectance, roughness } => {
+            [&color("color", reflectance, UNIT_RANGE)[..], &[("roughness", roughness, UNIT_RANGE)]].concat()
+        }
+        Material::Dielectric { ior, color: transmittance } => {
+            [&color("color", transmittance, UNIT_RANGE)[..], &[("ior", ior, IOR_RANGE)]].concat()
+        }
+        Material::Emissive { color: emission, intensity } => {
+            [&color("color", emission, (0.0, f32::INFINITY))[..], &[("intensity", intensity, (0.0, f32::INFINITY))]].concat()
+        }
+        Material::Principled { base_color, metallic, roughness, specular, clearcoat, clearcoat_roughness, transmission, ior } => [
+            &color("base_color", base_color, UNIT_RANGE)[..],
+            &[
+                ("metallic", metallic, UNIT_RANGE),
+                ("roughness", roughness, UNIT_RANGE),
+                ("specular", specular, UNIT_RANGE),
+                ("clearcoat", clearcoat, UNIT_RANGE),
+                ("clearcoat_roughness", clearcoat_roughness, UNIT_RANGE),
+                ("transmission", transmission, UNIT_RANGE),
+                ("ior", ior, IOR_RANGE),
+            ],
+        ].concat(),
+    }
+} // fn material_parameters
+
+fn is_finite(v: Vec3f) -> bool {
+    v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+}
+
+fn non_finite_vertex_count(mesh: &Mesh) -> usize {
+    (0..mesh.positions.len())
+        .filter(|index| {
+            !is_finite(mesh.positions[*index])
+                || mesh.normals.get(*index).is_some_and(|normal| !is_finite(*normal))
+                || mesh.colors.get(*index).is_some_and(|color| !is_finite(*color))
+                || mesh.uvs.get(*index).is_some_and(|uv| !uv.x.is_finite() || !uv.y.is_finite())
+        })
+        .count()
+}
+
+/// Triangles with repeated vertices or area negligible relative to their edges. Triangles with
+/// non-finite vertices are reported by `non_finite_vertex_count` instead.
+fn degenerate_triangle_count(mesh: &Mesh) -> usize {
+    mesh.indices
+        .iter()
+        .filter(|indices| {
+            let [v0, v1, v2] = indices.map(|index| mesh.positions[index as usize]);
+            if !is_finite(v0) || !is_finite(v1) || !is_finite(v2) {
+                return false;
+            }
+
+            let max_edge_length2 = (v1 - v0).length2().max((v2 - v1).length2()).max((v0 - v2).length2());
+            ((v1 - v0) % (v2 - v0)).length() <= max_edge_length2 * f32::EPSILON
+        })
+        .count()
+}
+
+impl LintScene {
+    /// Find content problems, issues of shared meshes and materials are reported once
+    pub fn issues(&self) -> Vec<LintIssue> {
+        let scene = &self.scene;
+        let mut issues = self.texture_errors.iter().map(|error| LintIssue::MissingTexture(error.to_string())).collect::<Vec<_>>();
+
+        for (mesh, name) in scene.meshes.iter().zip(&self.mesh_names) {
+            match non_finite_vertex_count(mesh) {
+                0 => {}
+                count => issues.push(LintIssue::NonFiniteVertices(name.clone(), count)),
+            }
+            match degenerate_triangle_count(mesh) {
+                0 => {}
+                count => issues.push(LintIssue::DegenerateTriangles(name.clone(), count)),
+            }
+        }
+
+        // Lights are sampled by triangle area, so duplicated and internal faces at non-manifold edges bias emission
+        let mut emissive_mesh_indices = scene.instances
+            .iter()
+            .filter(|instance| matches!(scene.materials[instance.material_index as usize].material, Material::Emissive { .. }))
+            .map(|instance| instance.mesh_index as usize)
+            .collect::<Vec<_>>();
+        emissive_mesh_indices.sort_unstable();
+        emissive_mesh_indices.dedup();
+
+        for mesh_index in emissive_mesh_indices {
+            match scene.meshes[mesh_index].non_manifold_edge_count() {
+                0 => {}
+                count => issues.push(LintIssue::NonManifoldEmissive(self.mesh_names[mesh_index].clone(), count)),
+            }
+        }
+
+        for (material, name) in scene.materials.iter().zip(&scene.material_names) {
+            // NaN fails range check too
+            let invalid = material_parameters(&material.material)
+                .into_iter()
+                .filter(|(_, value, range)| !(range.0..=range.1).contains(value));
+
+            // Color components share name, only the first invalid one is reported
+            let mut reported = Vec::<&str>::new();
+            for (parameter, value, range) in invalid {
+                if !reported.contains(&parameter) {
+                    reported.push(parameter);
+                    issues.push(LintIssue::MaterialParameter { material: name.clone(), parameter, value, range });
+                }
+            }
+        }
+
+        issues
+    } // fn issues
+}
@@ -0,0 +1,680 @@
+use std::{collections::HashMap, path::{Path, PathBuf}};
+
+use serde::Deserialize;
+
+use crate::{locale::{tr, tr_args}, math::{Ext2u, Mat4f, Vec2f, Vec3f, Vec4f}};
+
+use super::{lint::LintScene, Backface, displacement::{Displacement, HeightMap}, obj, Camera, Environment, subdivision::PolygonMesh, Decal, Instance, LensFlare, Material, MaterialMaps, Mesh, Primitive, Projection, Scene, Shape, Sky, Texture, TexturedMaterial, Tolerances, Transform, TEXTURE_EXTENT};
+
+/// RON syntax or structure error with offending source line
+#[derive(Debug)]
+pub struct ParseError {
+    /// One-based, as in text editors
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+    pub source_line: String,
+}
+
+impl ParseError {
+    /// Error at byte `offset` of `source`, for parsers that report spans instead of positions
+    pub fn at_offset(source: &str, offset: usize, message: String) -> Self {
+        let offset = offset.min(source.len());
+        let line_start = source[..offset].rfind('\n').map_or(0, |index| index + 1);
+
+        Self {
+            line: source[..offset].matches('\n').count() + 1,
+            column: source[line_start..offset].chars().count() + 1,
+            message,
+            source_line: source[line_start..].lines().next().unwrap_or_default().to_string(),
+        }
+    }
+
+    pub fn new(source: &str, error: &ron::error::SpannedError) -> Self {
+        Self {
+            line: error.position.line,
+            column: error.position.col,
+            message: error.code.to_string(),
+            source_line: source.lines().nth(error.position.line.saturating_sub(1)).unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Check if line has number with decimal comma, as written in Russian and other locales
+    fn has_decimal_comma(&self) -> bool {
+        self.source_line.as_bytes().windows(3).any(|window| {
+            window[0].is_ascii_digit() && window[1] == b',' && window[2].is_ascii_digit()
+        })
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)?;
+
+        if !self.source_line.is_empty() {
+            let indent = self.source_line.chars().take(self.column.saturating_sub(1)).map(|c| if c == '\t' { '\t' } else { ' ' }).collect::<String>();
+            write!(f, "\n    {}\n    {}^", self.source_line, indent)?;
+        }
+        if self.has_decimal_comma() {
+            write!(f, "\n{}", tr("error.decimal_comma"))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, ParseError),
+    Obj(PathBuf, usize, String),
+    Image(PathBuf, image::ImageError),
+    UnknownMesh(PathBuf, String),
+    UnknownMaterial(PathBuf, String),
+    /// Scene path, mesh name and error message
+    Mesh(PathBuf, String, String),
+    /// Prefab that (possibly indirectly) references itself
+    PrefabCycle(PathBuf),
+    /// Scene path, decal texture and locale key of error message
+    Decal(PathBuf, String, &'static str),
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+            LoadError::Parse(path, error) => write!(f, "{}:{}", path.display(), error),
+            LoadError::Obj(path, line, message) => write!(f, "{}:{}: {}", path.display(), line, message),
+            LoadError::Image(path, error) => write!(f, "{}: {}", path.display(), error),
+            LoadError::UnknownMesh(path, name) => f.write_str(&tr_args("error.unknown_mesh", &[&path.display(), name])),
+            LoadError::UnknownMaterial(path, name) => f.write_str(&tr_args("error.unknown_material", &[&path.display(), name])),
+            LoadError::Mesh(path, name, message) => f.write_str(&tr_args("error.mesh", &[&path.display(), name, message])),
+            LoadError::PrefabCycle(path) => f.write_str(&tr_args("error.prefab_cycle", &[&path.display()])),
+            LoadError::Decal(path, texture, message) => f.write_str(&tr_args("error.decal", &[&path.display(), texture, &tr(message)])),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+fn default_up() -> Vec3f {
+    Vec3f::new(0.0, 1.0, 0.0)
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_custom_data() -> Vec4f {
+    Vec4f::new(1.0, 1.0, 1.0, 1.0)
+}
+
+#[derive(Default, Deserialize)]
+struct MaterialMapsDescriptor {
+    albedo: Option<String>,
+    roughness: Option<String>,
+    metallic: Option<String>,
+    normal: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MaterialDescriptor {
+    name: String,
+    material: Material,
+    #[serde(default)]
+    maps: MaterialMapsDescriptor,
+    #[serde(default)]
+    instance_tint: bool,
+    #[serde(default)]
+    backface: Backface,
+}
+
+#[derive(Deserialize)]
+struct PrimitiveDescriptor {
+    shape: Shape,
+    material: String,
+}
+
+#[derive(Deserialize)]
+enum ShapeDescriptor {
+    Cuboid { min: Vec3f, max: Vec3f },
+    /// XZ plane quad centered at origin
+    Quad { size: Vec2f },
+    Obj(String),
+}
+
+#[derive(Deserialize)]
+struct DisplacementDescriptor {
+    /// Height map, luminance is used as height
+    texture: String,
+    scale: f32,
+    #[serde(default)]
+    midlevel: f32,
+    max_edge_length: f32,
+}
+
+#[derive(Deserialize)]
+struct MeshDescriptor {
+    name: String,
+    shape: ShapeDescriptor,
+    /// Catmull-Clark subdivision level, subdivided meshes are shaded smooth
+    #[serde(default)]
+    subdivision: u32,
+    /// Displacement, applied after subdivision
+    #[serde(default)]
+    displacement: Option<DisplacementDescriptor>,
+    /// Make triangle winding consistent and turn closed parts outward, applied before subdivision normals
+    #[serde(default)]
+    reorient: bool,
+}
+
+#[derive(Deserialize)]
+struct InstanceDescriptor {
+    mesh: String,
+    #[serde(default)]
+    transform: Transform,
+    material: String,
+    #[serde(default = "default_custom_data")]
+    custom_data: Vec4f,
+}
+
+#[derive(Deserialize)]
+struct DecalDescriptor {
+    texture: String,
+    /// Projection box center
+    position: Vec3f,
+    /// Projection direction
+    direction: Vec3f,
+    #[serde(default = "default_up")]
+    up: Vec3f,
+    /// Projected quad width and height
+    size: Vec2f,
+    /// Projection box depth
+    #[serde(default = "default_one")]
+    depth: f32,
+    #[serde(default = "default_one")]
+    opacity: f32,
+}
+
+#[derive(Deserialize)]
+struct EnvironmentDescriptor {
+    /// Equirectangular map, HDR format is expected
+    texture: String,
+    #[serde(default = "default_one")]
+    intensity: f32,
+    /// Rotation around Y axis, in degrees
+    #[serde(default)]
+    rotation: f32,
+}
+
+/// Other scene file placed into scene. Environment, sky, cameras, tolerances and lens flare of prefab scene are ignored.
+#[derive(Deserialize)]
+struct PrefabDescriptor {
+    scene: String,
+    #[serde(default)]
+    transform: Transform,
+    /// Prefab material name to name of material in referencing scene
+    #[serde(default)]
+    materials: HashMap<String, String>,
+}
+
+fn default_focus_distance() -> f32 {
+    4.0
+}
+
+fn default_fov() -> f32 {
+    crate::camera::DEFAULT_FOV
+}
+
+#[derive(Deserialize)]
+struct CameraDescriptor {
+    name: String,
+    location: Vec3f,
+    at: Vec3f,
+    #[serde(default = "default_up")]
+    up: Vec3f,
+    #[serde(default)]
+    projection: Projection,
+    /// Vertical field of view, in degrees
+    #[serde(default = "default_fov")]
+    fov: f32,
+    #[serde(default)]
+    aperture_radius: f32,
+    #[serde(default = "default_focus_distance")]
+    focus_distance: f32,
+    #[serde(default)]
+    aperture_blades: u32,
+    #[serde(default)]
+    aperture_rotation: f32,
+    /// Aperture shape image
+    #[serde(default)]
+    bokeh: Option<String>,
+    #[serde(default)]
+    exposure: f32,
+    #[serde(default)]
+    lens_shift: Vec2f,
+    #[serde(default)]
+    lens_tilt: Vec2f,
+}
+
+#[derive(Deserialize)]
+struct SceneDescriptor {
+    #[serde(default)]
+    materials: Vec<MaterialDescriptor>,
+    #[serde(default)]
+    primitives: Vec<PrimitiveDescriptor>,
+    #[serde(default)]
+    meshes: Vec<MeshDescriptor>,
+    #[serde(default)]
+    instances: Vec<InstanceDescriptor>,
+    #[serde(default)]
+    decals: Vec<DecalDescriptor>,
+    #[serde(default)]
+    environment: Option<EnvironmentDescriptor>,
+    #[serde(default)]
+    sky: Option<Sky>,
+    #[serde(default)]
+    prefabs: Vec<PrefabDescriptor>,
+    #[serde(default)]
+    cameras: Vec<CameraDescriptor>,
+    #[serde(default)]
+    tolerances: TolerancesDescriptor,
+    #[serde(default)]
+    lens_flare: Option<LensFlare>,
+}
+
+/// Overrides of tolerances derived from scene size, in scene units
+#[derive(Default, Deserialize)]
+struct TolerancesDescriptor {
+    #[serde(default)]
+    ray_min_distance: Option<f32>,
+    #[serde(default)]
+    intersection_tolerance: Option<f32>,
+}
+
+/// Files larger than this are memory-mapped, so multi-gigabyte meshes are parsed in place
+const MAPPED_FILE_MIN_SIZE: u64 = 16 << 20;
+
+/// Contents of asset file, either read to heap or memory-mapped
+enum FileData {
+    Heap(Vec<u8>),
+    Mapped(memmap2::Mmap),
+}
+
+impl FileData {
+    fn read(path: &Path) -> std::io::Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let size = file.metadata()?.len();
+        if size < MAPPED_FILE_MIN_SIZE {
+            let mut data = Vec::with_capacity(size as usize);
+            std::io::Read::read_to_end(&mut file, &mut data)?;
+            return Ok(FileData::Heap(data));
+        }
+
+        // Mapping is only read during load, files modified meanwhile are not supported anyway
+        unsafe { memmap2::Mmap::map(&file) }.map(FileData::Mapped)
+    }
+}
+
+impl std::ops::Deref for FileData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            FileData::Heap(data) => data,
+            FileData::Mapped(map) => map,
+        }
+    }
+}
+
+/// Texture storage, color textures are decoded from sRGB on sampling
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+enum TextureKind {
+    Color,
+    Data,
+}
+
+struct Loader {
+    /// Path and directory of scene file being loaded
+    path: PathBuf,
+    directory: PathBuf,
+    scene: Scene,
+    texture_indices: HashMap<(PathBuf, TextureKind), u32>,
+    /// Materials visible from scene file being loaded
+    material_indices: HashMap<String, u32>,
+    /// Meshes by canonical scene file path and mesh name, so repeated prefabs share geometry
+    mesh_indices: HashMap<(PathBuf, String), u32>,
+    /// Canonical paths of scene files being loaded, outermost first
+    file_stack: Vec<PathBuf>,
+    /// Names of loaded meshes and materials, for lint reports
+    mesh_names: Vec<String>,
+    /// Errors of textures replaced by placeholders, textures fail scene load unless this is set
+    texture_errors: Option<Vec<LoadError>>,
+}
+
+impl Loader {
+    fn new(path: &Path, texture_errors: Option<Vec<LoadError>>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            directory: PathBuf::new(),
+            scene: Scene::default(),
+            texture_indices: HashMap::new(),
+            material_indices: HashMap::new(),
+            mesh_indices: HashMap::new(),
+            file_stack: Vec::new(),
+            mesh_names: Vec::new(),
+            texture_errors,
+        }
+    }
+
+    fn material_index(&self, name: &str) -> Result<u32, LoadError> {
+        self.material_indices
+            .get(name)
+            .copied()
+            .ok_or_else(|| LoadError::UnknownMaterial(self.path.clone(), name.to_string()))
+    }
+
+    fn load_texture(&mut self, path: &str, kind: TextureKind) -> Result<u32, LoadError> {
+        let path = self.directory.join(path);
+
+        if let Some(index) = self.texture_indices.get(&(path.clone(), kind)) {
+            return Ok(*index);
+        }
+
+        let image = match (image::open(&path), &mut self.texture_errors) {
+            // All scene textures share single texture array, so they're resized to common extent
+            (Ok(image), _) => image::imageops::resize(&image.to_rgba8(), TEXTURE_EXTENT, TEXTURE_EXTENT, image::imageops::FilterType::Triangle),
+            (Err(error), Some(errors)) => {
+                errors.push(LoadError::Image(path.clone(), error));
+                image::RgbaImage::from_pixel(TEXTURE_EXTENT, TEXTURE_EXTENT, image::Rgba([255; 4]))
+            }
+            (Err(error), None) => return Err(LoadError::Image(path.clone(), error)),
+        };
+
+        let textures = match kind {
+            TextureKind::Color => &mut self.scene.textures,
+            TextureKind::Data => &mut self.scene.data_textures,
+        };
+
+        let index = textures.len() as u32;
+        textures.push(Texture {
+            extent: Ext2u::new(TEXTURE_EXTENT, TEXTURE_EXTENT),
+            data: image.into_raw(),
+        });
+        self.texture_indices.insert((path, kind), index);
+
+        Ok(index)
+    }
+
+    fn load_material(&mut self, descriptor: &MaterialDescriptor) -> Result<TexturedMaterial, LoadError> {
+        let mut load_map = |path: &Option<String>, kind| path.as_ref().map(|path| self.load_texture(path, kind)).transpose();
+
+        Ok(TexturedMaterial {
+            material: descriptor.material,
+            maps: MaterialMaps {
+                albedo: load_map(&descriptor.maps.albedo, TextureKind::Color)?,
+                roughness: load_map(&descriptor.maps.roughness, TextureKind::Data)?,
+                metallic: load_map(&descriptor.maps.metallic, TextureKind::Data)?,
+                normal: load_map(&descriptor.maps.normal, TextureKind::Data)?,
+            },
+            instance_tint: descriptor.instance_tint,
+            backface: descriptor.backface,
+        })
+    }
+
+    fn load_camera(&mut self, descriptor: &CameraDescriptor) -> Result<Camera, LoadError> {
+        Ok(Camera {
+            name: descriptor.name.clone(),
+            location: descriptor.location,
+            at: descriptor.at,
+            up: descriptor.up,
+            projection: descriptor.projection,
+            fov: descriptor.fov,
+            aperture_radius: descriptor.aperture_radius,
+            focus_distance: descriptor.focus_distance,
+            aperture_blades: descriptor.aperture_blades,
+            aperture_rotation: descriptor.aperture_rotation,
+            bokeh_texture: descriptor.bokeh.as_ref().map(|path| self.load_texture(path, TextureKind::Data)).transpose()?,
+            exposure: descriptor.exposure,
+            lens_shift: descriptor.lens_shift,
+            lens_tilt: descriptor.lens_tilt,
+        })
+    }
+
+    fn load_mesh(&mut self, descriptor: &MeshDescriptor) -> Result<Mesh, LoadError> {
+        let polygons = match &descriptor.shape {
+            ShapeDescriptor::Cuboid { min, max } => PolygonMesh::cuboid(*min, *max),
+            ShapeDescriptor::Quad { size } => PolygonMesh::quad(*size),
+            ShapeDescriptor::Obj(path) => {
+                let path = self.directory.join(path);
+                let data = FileData::read(&path).map_err(|error| LoadError::Io(path.clone(), error))?;
+                let source = std::str::from_utf8(&data)
+                    .map_err(|error| LoadError::Io(path.clone(), std::io::Error::new(std::io::ErrorKind::InvalidData, error)))?;
+                obj::parse(source).map_err(|(line, message)| LoadError::Obj(path.clone(), line, message))?
+            }
+        };
+
+        let mut mesh = Mesh::from_polygons(&polygons.subdivided(descriptor.subdivision));
+
+        // Winding defines front side of triangles, so its errors break shading silently
+        let report = mesh.orientation_report();
+        if descriptor.reorient {
+            if !report.is_ok() {
+                crate::crash::info!("{}", tr_args("mesh.reoriented", &[&descriptor.name, &mesh.reorient()]));
+            }
+        } else if !report.is_ok() {
+            crate::crash::error!("{}", tr_args("error.mesh_orientation", &[
+                &self.path.display(),
+                &descriptor.name,
+                &report.inconsistent_edge_count,
+                &report.inverted_part_count,
+            ]));
+        }
+
+        if descriptor.subdivision > 0 {
+            mesh.compute_smooth_normals();
+        }
+
+        if let Some(displacement) = &descriptor.displacement {
+            if mesh.uvs.is_empty() {
+                return Err(LoadError::Mesh(self.path.clone(), descriptor.name.clone(), "displacement requires texture coordinates".to_string()));
+            }
+
+            let path = self.directory.join(&displacement.texture);
+            let image = image::open(&path).map_err(|error| LoadError::Image(path.clone(), error))?;
+
+            let start_time = std::time::Instant::now();
+            mesh = Displacement {
+                height_map: HeightMap::from_image(&image),
+                scale: displacement.scale,
+                midlevel: displacement.midlevel,
+                max_edge_length: displacement.max_edge_length,
+            }.apply(&mesh);
+
+            crate::crash::info!(
+                "Mesh '{}' displaced in {:.3} ms ({} triangles)",
+                descriptor.name,
+                start_time.elapsed().as_secs_f64() * 1000.0,
+                mesh.indices.len()
+            );
+        }
+
+        Ok(mesh)
+    }
+
+    fn load_environment(&mut self, descriptor: &EnvironmentDescriptor) -> Result<Environment, LoadError> {
+        let path = self.directory.join(&descriptor.texture);
+        let image = image::open(&path).map_err(|error| LoadError::Image(path.clone(), error))?.to_rgba32f();
+
+        Ok(Environment {
+            extent: Ext2u::new(image.width(), image.height()),
+            data: image.into_raw(),
+            intensity: descriptor.intensity,
+            rotation: descriptor.rotation.to_radians(),
+        })
+    }
+
+    fn load_decal(&mut self, descriptor: &DecalDescriptor) -> Result<Decal, LoadError> {
+        let error = |message| LoadError::Decal(self.path.clone(), descriptor.texture.clone(), message);
+
+        // Negated comparisons reject NaN too
+        if !(descriptor.size.x > 0.0 && descriptor.size.y > 0.0 && descriptor.depth > 0.0) {
+            return Err(error("error.decal_size"));
+        }
+
+        let forward = descriptor.direction.normalized();
+        let right = forward % descriptor.up.normalized();
+        if right.length().is_nan() || right.length() < 1e-4 {
+            return Err(error("error.decal_up"));
+        }
+        let right = right.normalized();
+        let up = (right % forward).normalized();
+
+        Ok(Decal {
+            position: descriptor.position,
+            right,
+            up,
+            forward,
+            size: Vec3f::new(descriptor.size.x, descriptor.size.y, descriptor.depth),
+            texture_index: self.load_texture(&descriptor.texture, TextureKind::Color)?,
+            opacity: descriptor.opacity,
+        })
+    }
+
+    /// Load scene file contents. `material_indices` contains materials overridden by referencing scene,
+    /// local definitions of them are skipped.
+    fn load_file(&mut self, path: &Path, transform: Mat4f, material_indices: HashMap<String, u32>) -> Result<(), LoadError> {
+        let canonical_path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if self.file_stack.contains(&canonical_path) {
+            return Err(LoadError::PrefabCycle(path.to_path_buf()));
+        }
+
+        let source = std::fs::read_to_string(path).map_err(|error| LoadError::Io(path.to_path_buf(), error))?;
+        let descriptor = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str::<SceneDescriptor>(&source)
+            .map_err(|error| LoadError::Parse(path.to_path_buf(), ParseError::new(&source, &error)))?;
+
+        let outer_path = std::mem::replace(&mut self.path, path.to_path_buf());
+        let outer_directory = std::mem::replace(&mut self.directory, path.parent().map(Path::to_path_buf).unwrap_or_default());
+        let outer_material_indices = std::mem::replace(&mut self.material_indices, material_indices);
+        self.file_stack.push(canonical_path);
+
+        let result = self.load_descriptor(&descriptor, transform);
+
+        self.file_stack.pop();
+        self.path = outer_path;
+        self.directory = outer_directory;
+        self.material_indices = outer_material_indices;
+
+        result
+    } // fn load_file
+
+    fn load_descriptor(&mut self, descriptor: &SceneDescriptor, transform: Mat4f) -> Result<(), LoadError> {
+        let file_path = self.file_stack.last().cloned().unwrap_or_default();
+
+        for material in &descriptor.materials {
+            if self.material_indices.contains_key(&material.name) {
+                continue;
+            }
+
+            let loaded = self.load_material(material)?;
+            self.material_indices.insert(material.name.clone(), self.scene.materials.len() as u32);
+            self.scene.materials.push(loaded);
+            self.scene.material_names.push(material.name.clone());
+        }
+
+        for primitive in &descriptor.primitives {
+            let material_index = self.material_index(&primitive.material)?;
+            self.scene.primitives.push(Primitive { shape: primitive.shape.transformed(&transform), material_index });
+        }
+
+        let mut mesh_indices = HashMap::<&str, u32>::new();
+        for mesh in &descriptor.meshes {
+            let key = (file_path.clone(), mesh.name.clone());
+
+            let index = match self.mesh_indices.get(&key) {
+                Some(index) => *index,
+                None => {
+                    let loaded = self.load_mesh(mesh)?;
+                    let index = self.scene.meshes.len() as u32;
+                    self.scene.meshes.push(loaded);
+                    self.mesh_names.push(mesh.name.clone());
+                    self.mesh_indices.insert(key, index);
+                    index
+                }
+            };
+            mesh_indices.insert(&mesh.name, index);
+        }
+
+        for instance in &descriptor.instances {
+            let mesh_index = *mesh_indices
+                .get(instance.mesh.as_str())
+                .ok_or_else(|| LoadError::UnknownMesh(self.path.clone(), instance.mesh.clone()))?;
+
+            self.scene.instances.push(Instance {
+                mesh_index,
+                transform: transform * instance.transform.matrix(),
+                material_index: self.material_index(&instance.material)?,
+                custom_data: instance.custom_data,
+            });
+        }
+
+        for decal in &descriptor.decals {
+            let decal = self.load_decal(decal)?.transformed(&transform);
+            self.scene.decals.push(decal);
+        }
+
+        for prefab in &descriptor.prefabs {
+            let material_indices = prefab.materials
+                .iter()
+                .map(|(prefab_name, name)| Ok((prefab_name.clone(), self.material_index(name)?)))
+                .collect::<Result<HashMap<_, _>, LoadError>>()?;
+
+            self.load_file(&self.directory.join(&prefab.scene), transform * prefab.transform.matrix(), material_indices)?;
+        }
+
+        // Only outermost scene defines environment, sky, cameras, tolerances and lens flare. Prefabs are loaded by now,
+        // so tolerances are derived from size of the whole scene.
+        if self.file_stack.len() == 1 {
+            let bounds = self.scene.bounds();
+            let derived = if bounds.is_empty() { Tolerances::default() } else { Tolerances::for_scene_size(bounds.extent().length()) };
+            self.scene.tolerances = Tolerances {
+                ray_min_distance: descriptor.tolerances.ray_min_distance.unwrap_or(derived.ray_min_distance),
+                intersection_tolerance: descriptor.tolerances.intersection_tolerance.unwrap_or(derived.intersection_tolerance),
+            };
+
+            if let Some(environment) = &descriptor.environment {
+                self.scene.environment = Some(self.load_environment(environment)?);
+            }
+            self.scene.sky = descriptor.sky;
+            self.scene.lens_flare = descriptor.lens_flare;
+            for camera in &descriptor.cameras {
+                let camera = self.load_camera(camera)?;
+                self.scene.cameras.push(camera);
+            }
+        }
+
+        Ok(())
+    } // fn load_descriptor
+}
+
+/// Load scene from RON file
+pub fn load(path: &Path) -> Result<Scene, LoadError> {
+    let mut loader = Loader::new(path, None);
+    loader.load_file(path, Mat4f::identity(), HashMap::new())?;
+
+    Ok(loader.scene)
+} // fn load
+
+/// Load scene for linting, textures that fail to load are replaced by white placeholders and reported
+pub fn load_for_lint(path: &Path) -> Result<LintScene, LoadError> {
+    let mut loader = Loader::new(path, Some(Vec::new()));
+    loader.load_file(path, Mat4f::identity(), HashMap::new())?;
+
+    Ok(LintScene {
+        scene: loader.scene,
+        mesh_names: loader.mesh_names,
+        texture_errors: loader.texture_errors.unwrap_or_default(),
+    })
+} // fn load_for_lint
@@ -0,0 +1,576 @@
+use std::collections::HashMap;
+
+use crate::{accel::{Aabb, Bounded}, math::{Ext2u, Mat4f, Vec2f, Vec3f, Vec4f}};
+
+pub mod obj;
+pub mod subdivision;
+pub mod displacement;
+pub mod orientation;
+pub mod environment;
+pub mod sky;
+pub mod loader;
+pub mod lint;
+
+pub use environment::Environment;
+pub use sky::Sky;
+pub use loader::{load, load_for_lint, LoadError, ParseError};
+pub use lint::{LintIssue, LintScene};
+
+/// Extent all scene textures are resampled to
+pub const TEXTURE_EXTENT: u32 = 512;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Triangle {
+    pub v0: Vec3f,
+    pub v1: Vec3f,
+    pub v2: Vec3f,
+}
+
+impl Bounded for Triangle {
+    fn bounds(&self) -> Aabb {
+        let mut bounds = Aabb::empty();
+        bounds.extend_point(self.v0);
+        bounds.extend_point(self.v1);
+        bounds.extend_point(self.v2);
+        bounds
+    }
+
+    fn centroid(&self) -> Vec3f {
+        (self.v0 + self.v1 + self.v2) / 3.0
+    }
+}
+
+/// Indexed triangle mesh, shared between all instances referencing it
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vec3f>,
+    /// Per-vertex shading normals, empty for flat-shaded meshes
+    pub normals: Vec<Vec3f>,
+    /// Per-vertex texture coordinates, may be empty
+    pub uvs: Vec<Vec2f>,
+    /// Per-vertex sRGB colors that multiply material albedo, may be empty
+    pub colors: Vec<Vec3f>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+    pub fn from_polygons(polygons: &subdivision::PolygonMesh) -> Self {
+        if polygons.face_uvs.is_empty() {
+            return Self {
+                positions: polygons.positions.clone(),
+                colors: polygons.colors.clone(),
+                indices: polygons.triangulate(),
+                ..Default::default()
+            };
+        }
+
+        // Vertices are split wherever texture coordinates differ
+        let mut mesh = Self::default();
+        let mut vertex_indices = HashMap::<(u32, u32), u32>::new();
+
+        let faces = polygons.faces
+            .iter()
+            .zip(polygons.face_uvs.iter())
+            .map(|(face, face_uvs)| face
+                .iter()
+                .zip(face_uvs.iter())
+                .map(|(position_index, uv_index)| *vertex_indices
+                    .entry((*position_index, *uv_index))
+                    .or_insert_with(|| {
+                        mesh.positions.push(polygons.positions[*position_index as usize]);
+                        mesh.uvs.push(polygons.uvs[*uv_index as usize]);
+                        if !polygons.colors.is_empty() {
+                            mesh.colors.push(polygons.colors[*position_index as usize]);
+                        }
+                        mesh.positions.len() as u32 - 1
+                    })
+                )
+                .collect::<Vec<_>>()
+            )
+            .collect::<Vec<_>>();
+
+        mesh.indices = subdivision::triangulate_faces(&faces);
+        mesh
+    }
+
+    /// Calculate area-weighted vertex normals. Vertices sharing position get the same normal,
+    /// so seams of split vertices stay invisible.
+    pub fn compute_smooth_normals(&mut self) {
+        let position_key = |position: Vec3f| [position.x.to_bits(), position.y.to_bits(), position.z.to_bits()];
+        let mut position_normals = HashMap::<[u32; 3], Vec3f>::new();
+
+        for triangle_indices in &self.indices {
+            let [v0, v1, v2] = triangle_indices.map(|i| self.positions[i as usize]);
+            let normal = (v1 - v0) % (v2 - v0);
+
+            for position in [v0, v1, v2] {
+                *position_normals.entry(position_key(position)).or_default() += normal;
+            }
+        }
+
+        self.normals = self.positions
+            .iter()
+            .map(|position| {
+                let normal = position_normals.get(&position_key(*position)).copied().unwrap_or_default();
+                if normal.length2() > 0.0 { normal.normalized() } else { normal }
+            })
+            .collect();
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = Triangle> + '_ {
+        self.indices.iter().map(|[i0, i1, i2]| Triangle {
+            v0: self.positions[*i0 as usize],
+            v1: self.positions[*i1 as usize],
+            v2: self.positions[*i2 as usize],
+        })
+    }
+}
+
+fn default_scale() -> Vec3f {
+    Vec3f::new(1.0, 1.0, 1.0)
+}
+
+/// Translation, rotation (Euler angles in degrees, applied in X, Y, Z order) and scale
+#[derive(Copy, Clone, Debug, serde::Deserialize)]
+pub struct Transform {
+    #[serde(default)]
+    pub translation: Vec3f,
+    #[serde(default)]
+    pub rotation: Vec3f,
+    #[serde(default = "default_scale")]
+    pub scale: Vec3f,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vec3f::default(),
+            rotation: Vec3f::default(),
+            scale: default_scale(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4f {
+        Mat4f::translate(self.translation)
+            * Mat4f::rotate_z(self.rotation.z.to_radians())
+            * Mat4f::rotate_y(self.rotation.y.to_radians())
+            * Mat4f::rotate_x(self.rotation.x.to_radians())
+            * Mat4f::scale(self.scale)
+    }
+}
+
+/// Placement of mesh in the scene
+#[derive(Copy, Clone, Debug)]
+pub struct Instance {
+    pub mesh_index: u32,
+    pub transform: Mat4f,
+    pub material_index: u32,
+    /// Arbitrary data available to shading, e.g. color variation of instanced props.
+    /// Primitives have all-ones custom data.
+    pub custom_data: Vec4f,
+}
+
+/// Analytic shape
+#[derive(Copy, Clone, Debug, serde::Deserialize)]
+pub enum Shape {
+    Sphere { center: Vec3f, radius: f32 },
+    /// Plane, bounded by square of `extent` half-size if `extent` is positive
+    Plane { point: Vec3f, normal: Vec3f, #[serde(default)] extent: f32 },
+    Box { min: Vec3f, max: Vec3f },
+    Cylinder { a: Vec3f, b: Vec3f, radius: f32 },
+    Cone { a: Vec3f, b: Vec3f, radius_a: f32, radius_b: f32 },
+    Capsule { a: Vec3f, b: Vec3f, radius: f32 },
+    Torus { center: Vec3f, axis: Vec3f, major_radius: f32, minor_radius: f32 },
+}
+
+impl Shape {
+    /// Transform shape. Analytic shapes can't represent arbitrary transforms, so radii are scaled
+    /// by mean axis scale and boxes are replaced with bounds of transformed box.
+    pub fn transformed(&self, transform: &Mat4f) -> Self {
+        let scale = [Vec3f::new(1.0, 0.0, 0.0), Vec3f::new(0.0, 1.0, 0.0), Vec3f::new(0.0, 0.0, 1.0)]
+            .iter()
+            .map(|axis| transform.transform_vector(*axis).length())
+            .sum::<f32>() / 3.0;
+        let point = |p: &Vec3f| transform.transform_point(*p);
+        let direction = |v: &Vec3f| transform.transform_vector(*v).normalized();
+
+        match self {
+            Shape::Sphere { center, radius } => Shape::Sphere { center: point(center), radius: radius * scale },
+            Shape::Plane { point: plane_point, normal, extent } => Shape::Plane { point: point(plane_point), normal: direction(normal), extent: extent * scale },
+            Shape::Box { min, max } => {
+                let bounds = Aabb::new(*min, *max).transformed(transform);
+                Shape::Box { min: bounds.min, max: bounds.max }
+            }
+            Shape::Cylinder { a, b, radius } => Shape::Cylinder { a: point(a), b: point(b), radius: radius * scale },
+            Shape::Cone { a, b, radius_a, radius_b } => Shape::Cone { a: point(a), b: point(b), radius_a: radius_a * scale, radius_b: radius_b * scale },
+            Shape::Capsule { a, b, radius } => Shape::Capsule { a: point(a), b: point(b), radius: radius * scale },
+            Shape::Torus { center, axis, major_radius, minor_radius } => Shape::Torus {
+                center: point(center),
+                axis: direction(axis),
+                major_radius: major_radius * scale,
+                minor_radius: minor_radius * scale,
+            },
+        }
+    }
+
+    /// Bounds of finite shapes, unbounded planes give bounds of their point
+    pub fn bounds(&self) -> Aabb {
+        let around = |points: &[Vec3f], radius: f32| {
+            let mut bounds = Aabb::empty();
+            for point in points {
+                bounds.extend_point(*point - radius);
+                bounds.extend_point(*point + radius);
+            }
+            bounds
+        };
+
+        match *self {
+            Shape::Sphere { center, radius } => around(&[center], radius),
+            Shape::Plane { point, extent, .. } => around(&[point], extent.max(0.0)),
+            Shape::Box { min, max } => Aabb::new(min.min(max), min.max(max)),
+            Shape::Cylinder { a, b, radius } | Shape::Capsule { a, b, radius } => around(&[a, b], radius),
+            Shape::Cone { a, b, radius_a, radius_b } => around(&[a, b], radius_a.max(radius_b)),
+            Shape::Torus { center, major_radius, minor_radius, .. } => around(&[center], major_radius + minor_radius),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Primitive {
+    pub shape: Shape,
+    pub material_index: u32,
+}
+
+fn default_white() -> Vec3f {
+    Vec3f::new(1.0, 1.0, 1.0)
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+fn default_half() -> f32 {
+    0.5
+}
+
+fn default_clearcoat_roughness() -> f32 {
+    0.03
+}
+
+fn default_ior() -> f32 {
+    1.5
+}
+
+/// Surface scattering model
+#[derive(Copy, Clone, Debug, PartialEq, serde::Deserialize)]
+pub enum Material {
+    /// Ideal diffuse surface
+    Lambertian { color: Vec3f },
+    /// Conductor with GGX microfacet distribution, `color` is reflectance at normal incidence
+    Metal { color: Vec3f, #[serde(default)] roughness: f32 },
+    /// Smooth glass-like surface
+    Dielectric { ior: f32, #[serde(default = "default_white")] color: Vec3f },
+    /// Light source, doesn't scatter
+    Emissive { color: Vec3f, #[serde(default = "default_intensity")] intensity: f32 },
+    /// Disney-style uber material, parameters follow glTF metallic-roughness model
+    Principled {
+        base_color: Vec3f,
+        #[serde(default)]
+        metallic: f32,
+        #[serde(default = "default_half")]
+        roughness: f32,
+        /// Dielectric specular amount, 0.5 corresponds to 4% reflectance
+        #[serde(default = "default_half")]
+        specular: f32,
+        #[serde(default)]
+        clearcoat: f32,
+        #[serde(default = "default_clearcoat_roughness")]
+        clearcoat_roughness: f32,
+        #[serde(default)]
+        transmission: f32,
+        #[serde(default = "default_ior")]
+        ior: f32,
+    },
+}
+
+/// Value of single material parameter
+#[derive(Copy, Clone, Debug, serde::Deserialize)]
+pub enum ParameterValue {
+    Float(f32),
+    Color(Vec3f),
+}
+
+impl Material {
+    /// Set parameter by its field name, value kind must match the field
+    pub fn set_parameter(&mut self, name: &str, value: ParameterValue) -> Result<(), String> {
+        let (float, color) = match (self, name) {
+            (Material::Lambertian { color }, "color")
+            | (Material::Metal { color, .. }, "color")
+            | (Material::Dielectric { color, .. }, "color")
+            | (Material::Emissive { color, .. }, "color")
+            | (Material::Principled { base_color: color, .. }, "base_color") => (None, Some(color)),
+            (Material::Metal { roughness, .. }, "roughness")
+            | (Material::Principled { roughness, .. }, "roughness") => (Some(roughness), None),
+            (Material::Dielectric { ior, .. }, "ior") | (Material::Principled { ior, .. }, "ior") => (Some(ior), None),
+            (Material::Emissive { intensity, .. }, "intensity") => (Some(intensity), None),
+            (Material::Principled { metallic, .. }, "metallic") => (Some(metallic), None),
+            (Material::Principled { specular, .. }, "specular") => (Some(specular), None),
+            (Material::Principled { clearcoat, .. }, "clearcoat") => (Some(clearcoat), None),
+            (Material::Principled { clearcoat_roughness, .. }, "clearcoat_roughness") => (Some(clearcoat_roughness), None),
+            (Material::Principled { transmission, .. }, "transmission") => (Some(transmission), None),
+            _ => return Err(format!("material has no parameter '{}'", name)),
+        };
+
+        match (value, float, color) {
+            (ParameterValue::Float(value), Some(float), _) => *float = value,
+            (ParameterValue::Color(value), _, Some(color)) => *color = value,
+            _ => return Err(format!("parameter '{}' has different value kind", name)),
+        }
+        Ok(())
+    } // fn set_parameter
+}
+
+/// Material texture maps. Albedo references `Scene::textures`, others reference `Scene::data_textures`.
+/// Roughness is read from green channel and metallic from blue one, following glTF packing.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MaterialMaps {
+    pub albedo: Option<u32>,
+    pub roughness: Option<u32>,
+    pub metallic: Option<u32>,
+    /// Tangent-space normal map
+    pub normal: Option<u32>,
+}
+
+/// Mapping of image to camera rays
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub enum Projection {
+    /// Rays diverge from camera location within field of view
+    #[default]
+    Perspective,
+    /// Parallel rays start across view of given height in scene units, field of view is ignored.
+    /// For technical drawings and checking geometry scale.
+    Orthographic { height: f32 },
+    /// Equirectangular map of all directions around camera, image center is view direction. Image of 2:1
+    /// aspect ratio taken by level camera is environment map of camera location.
+    Panorama,
+    /// Rays of fisheye lens, field of view is angle of view across image height and may exceed 180 degrees.
+    /// Image parts more than 180 degrees off view direction lie outside of image circle and stay black.
+    Fisheye(FisheyeMapping),
+}
+
+/// Fisheye mapping of angle of ray to view direction to distance from image center
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub enum FisheyeMapping {
+    /// Distance is proportional to angle, so angles are kept across image
+    #[default]
+    Equidistant,
+    /// Distance is proportional to tangent of half angle, so shapes are kept and image periphery is enlarged
+    Stereographic,
+}
+
+/// Handling of surface sides. Front side is the one triangle winding (or primitive normal) points to.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Deserialize)]
+pub enum Backface {
+    /// Back side is the inside of closed surface, rays hitting it leave the object
+    #[default]
+    Closed,
+    /// Both sides are shaded as front ones, for thin single-sided geometry
+    TwoSided,
+    /// Back side is invisible, rays pass through it
+    Cull,
+    /// Sides are swapped, for closed meshes with inverted winding
+    Flip,
+}
+
+/// Material together with its texture maps
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TexturedMaterial {
+    pub material: Material,
+    pub maps: MaterialMaps,
+    /// Multiply color by RGB of instance custom data, so instances vary without unique materials
+    pub instance_tint: bool,
+    pub backface: Backface,
+}
+
+/// RGBA8 texture
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub extent: Ext2u,
+    pub data: Vec<u8>,
+}
+
+/// Textured quad projected along `forward` onto any geometry inside of the decal box
+#[derive(Copy, Clone, Debug)]
+pub struct Decal {
+    /// Box center
+    pub position: Vec3f,
+    pub right: Vec3f,
+    pub up: Vec3f,
+    pub forward: Vec3f,
+    /// Box size along right, up and forward axes
+    pub size: Vec3f,
+    pub texture_index: u32,
+    pub opacity: f32,
+}
+
+impl Decal {
+    pub fn transformed(&self, transform: &Mat4f) -> Self {
+        let [right, up, forward] = [self.right, self.up, self.forward].map(|axis| transform.transform_vector(axis));
+
+        Self {
+            position: transform.transform_point(self.position),
+            right: right.normalized(),
+            up: up.normalized(),
+            forward: forward.normalized(),
+            size: Vec3f::new(self.size.x * right.length(), self.size.y * up.length(), self.size.z * forward.length()),
+            ..*self
+        }
+    }
+}
+
+/// Named viewpoint with its lens and exposure settings
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub name: String,
+    pub location: Vec3f,
+    pub at: Vec3f,
+    pub up: Vec3f,
+    pub projection: Projection,
+    /// Vertical field of view, in degrees
+    pub fov: f32,
+    /// Thin lens radius, zero for pinhole camera
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+    /// Number of aperture diaphragm blades, round aperture if less than 3
+    pub aperture_blades: u32,
+    /// Aperture polygon rotation, in degrees
+    pub aperture_rotation: f32,
+    /// Data texture with aperture shape, overrides blades. Luminance is lens transmittance.
+    pub bokeh_texture: Option<u32>,
+    /// Exposure compensation in stops
+    pub exposure: f32,
+    /// Image plane shift in fractions of frame size, vertical shift corrects converging verticals
+    pub lens_shift: Vec2f,
+    /// Focus plane tilt (X) and swing (Y), in degrees
+    pub lens_tilt: Vec2f,
+}
+
+/// Ray tracing tolerances in scene units, by default proportional to scene size
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tolerances {
+    /// Hits closer to ray origin are ignored
+    pub ray_min_distance: f32,
+    /// Shadow rays stop this far from sampled light points, triangles smaller than it are degenerate
+    pub intersection_tolerance: f32,
+}
+
+impl Tolerances {
+    /// Tolerances of scene with given bounding box diagonal
+    pub fn for_scene_size(size: f32) -> Self {
+        Self {
+            ray_min_distance: size * 1e-6,
+            intersection_tolerance: size * 1e-5,
+        }
+    }
+}
+
+impl Default for Tolerances {
+    fn default() -> Self {
+        Self::for_scene_size(10.0)
+    }
+}
+
+fn default_flare_threshold() -> f32 {
+    4.0
+}
+
+fn default_flare_intensity() -> f32 {
+    0.05
+}
+
+fn default_ghost_count() -> u32 {
+    4
+}
+
+fn default_ghost_spacing() -> f32 {
+    0.4
+}
+
+fn default_halo_radius() -> f32 {
+    0.45
+}
+
+fn default_chromatic_shift() -> f32 {
+    0.01
+}
+
+/// Procedural lens flare post effect: pixels brighter than threshold cast ghosts, mirrored through image center,
+/// and a halo ring around it
+#[derive(Copy, Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct LensFlare {
+    /// Presented luminance, after exposure, above which pixels cast flares
+    #[serde(default = "default_flare_threshold")]
+    pub threshold: f32,
+    /// Flare brightness relative to its source
+    #[serde(default = "default_flare_intensity")]
+    pub intensity: f32,
+    #[serde(default = "default_ghost_count")]
+    pub ghost_count: u32,
+    /// Distance between ghosts, as fraction of distance from source to its mirror image
+    #[serde(default = "default_ghost_spacing")]
+    pub ghost_spacing: f32,
+    /// Halo ring radius, as fraction of image half-height, zero disables halo
+    #[serde(default = "default_halo_radius")]
+    pub halo_radius: f32,
+    /// Radial offset between red and blue flare images, as fraction of image half-height
+    #[serde(default = "default_chromatic_shift")]
+    pub chromatic_shift: f32,
+    /// Flare color multiplier
+    #[serde(default = "default_white")]
+    pub tint: Vec3f,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub materials: Vec<TexturedMaterial>,
+    /// Scene file names of materials, indexed as materials
+    pub material_names: Vec<String>,
+    pub primitives: Vec<Primitive>,
+    pub meshes: Vec<Mesh>,
+    pub instances: Vec<Instance>,
+    /// Color textures, stored in sRGB
+    pub textures: Vec<Texture>,
+    /// Non-color textures, stored linearly
+    pub data_textures: Vec<Texture>,
+    pub decals: Vec<Decal>,
+    pub environment: Option<Environment>,
+    /// Procedural sky, used if there is no environment map
+    pub sky: Option<Sky>,
+    pub cameras: Vec<Camera>,
+    pub tolerances: Tolerances,
+    pub lens_flare: Option<LensFlare>,
+}
+
+impl Scene {
+    /// Bounds of primitives and instances
+    pub fn bounds(&self) -> Aabb {
+        let mesh_bounds = self.meshes
+            .iter()
+            .map(|mesh| mesh.positions.iter().fold(Aabb::empty(), |mut bounds, position| {
+                bounds.extend_point(*position);
+                bounds
+            }))
+            .collect::<Vec<_>>();
+
+        let mut bounds = Aabb::empty();
+        for primitive in &self.primitives {
+            bounds.extend(&primitive.shape.bounds());
+        }
+        for instance in &self.instances {
+            bounds.extend(&mesh_bounds[instance.mesh_index as usize].transformed(&instance.transform));
+        }
+        bounds
+    }
+}
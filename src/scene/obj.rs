@@ -0,0 +1,106 @@
+use crate::math::{Vec2f, Vec3f};
+
+use super::subdivision::PolygonMesh;
+
+/// Parse number, decimal comma written by exporters running in Russian and other locales is accepted too
+fn parse_number(token: &str) -> Option<f32> {
+    token.parse::<f32>().ok().or_else(|| token.replacen(',', ".", 1).parse::<f32>().ok())
+}
+
+/// Resolve one-based or negative (relative) OBJ index
+fn resolve_index(index: i64, count: usize, line_number: usize) -> Result<u32, (usize, String)> {
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err((line_number, format!("face index {} out of range", index)));
+    }
+
+    Ok(resolved as u32)
+}
+
+/// Parse OBJ source. Only positions, vertex colors, texture coordinates and faces are read, polygons are kept as is.
+/// Vertex colors follow position as `v x y z r g b` and are dropped unless every vertex has them.
+/// Texture coordinates are dropped unless every face references them.
+/// Returns error message together with line number on failure.
+pub fn parse(source: &str) -> Result<PolygonMesh, (usize, String)> {
+    let mut positions = Vec::<Vec3f>::new();
+    let mut colors = Vec::<Option<Vec3f>>::new();
+    let mut faces = Vec::<Vec<u32>>::new();
+    let mut uvs = Vec::<Vec2f>::new();
+    let mut face_uvs = Vec::<Option<Vec<u32>>>::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let mut coordinates = [0.0f32; 3];
+                for coordinate in &mut coordinates {
+                    *coordinate = tokens
+                        .next()
+                        .and_then(parse_number)
+                        .ok_or((line_number, "expected three vertex coordinates".to_string()))?;
+                }
+                positions.push(Vec3f::from(coordinates));
+
+                let components = tokens.map(parse_number).collect::<Option<Vec<_>>>()
+                    .ok_or((line_number, "invalid vertex color".to_string()))?;
+                colors.push(match components[..] {
+                    [r, g, b] => Some(Vec3f::new(r, g, b)),
+                    // Homogeneous W coordinate
+                    [] | [_] => None,
+                    _ => return Err((line_number, "expected three vertex color components".to_string())),
+                });
+            }
+            Some("vt") => {
+                let mut coordinates = [0.0f32; 2];
+                for coordinate in &mut coordinates {
+                    *coordinate = tokens
+                        .next()
+                        .and_then(parse_number)
+                        .ok_or((line_number, "expected two texture coordinates".to_string()))?;
+                }
+                uvs.push(Vec2f::from(coordinates));
+            }
+            Some("f") => {
+                let corners = tokens
+                    .map(|token| {
+                        // Normals are skipped
+                        let mut indices = token.split('/');
+                        let index = indices
+                            .next()
+                            .and_then(|t| t.parse::<i64>().ok())
+                            .ok_or((line_number, format!("invalid face index '{}'", token)))?;
+                        let uv_index = match indices.next() {
+                            Some(t) if !t.is_empty() => Some(
+                                t.parse::<i64>().map_err(|_| (line_number, format!("invalid face index '{}'", token)))?
+                            ),
+                            _ => None,
+                        };
+
+                        Ok((
+                            resolve_index(index, positions.len(), line_number)?,
+                            uv_index.map(|index| resolve_index(index, uvs.len(), line_number)).transpose()?,
+                        ))
+                    })
+                    .collect::<Result<Vec<(u32, Option<u32>)>, _>>()?;
+
+                if corners.len() < 3 {
+                    return Err((line_number, "face must have at least three vertices".to_string()));
+                }
+
+                faces.push(corners.iter().map(|(index, _)| *index).collect());
+                face_uvs.push(corners.iter().map(|(_, uv_index)| *uv_index).collect());
+            }
+            _ => {}
+        }
+    }
+
+    let face_uvs = face_uvs.into_iter().collect::<Option<Vec<_>>>().unwrap_or_default();
+    let uvs = if face_uvs.is_empty() { Vec::new() } else { uvs };
+
+    let colors = colors.into_iter().collect::<Option<Vec<_>>>().unwrap_or_default();
+
+    Ok(PolygonMesh { positions, faces, uvs, face_uvs, colors })
+} // fn parse
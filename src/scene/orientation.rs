@@ -0,0 +1,175 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::Mesh;
+
+/// Winding problems found in mesh
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct OrientationReport {
+    /// Edges shared by two triangles that traverse them in the same direction
+    pub inconsistent_edge_count: usize,
+    /// Closed consistently wound parts with normals pointing inside
+    pub inverted_part_count: usize,
+}
+
+impl OrientationReport {
+    pub fn is_ok(&self) -> bool {
+        self.inconsistent_edge_count == 0 && self.inverted_part_count == 0
+    }
+}
+
+/// Triangle adjacency over welded positions, so vertices split by texture seams are connected
+struct Adjacency {
+    /// Triangles using edge and whether they traverse it from lower welded index to higher one
+    edges: HashMap<(u32, u32), Vec<(u32, bool)>>,
+    /// Welded vertex indices of triangles
+    triangles: Vec<[u32; 3]>,
+}
+
+impl Adjacency {
+    fn new(mesh: &Mesh) -> Self {
+        let mut welded_indices = HashMap::<[u32; 3], u32>::new();
+        let welded = mesh.positions
+            .iter()
+            .map(|position| {
+                let count = welded_indices.len() as u32;
+                *welded_indices.entry([position.x.to_bits(), position.y.to_bits(), position.z.to_bits()]).or_insert(count)
+            })
+            .collect::<Vec<_>>();
+
+        let triangles = mesh.indices.iter().map(|indices| indices.map(|index| welded[index as usize])).collect::<Vec<_>>();
+        let mut edges = HashMap::<(u32, u32), Vec<(u32, bool)>>::new();
+
+        for (triangle_index, triangle) in triangles.iter().enumerate() {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                edges.entry((a.min(b), a.max(b))).or_default().push((triangle_index as u32, a < b));
+            }
+        }
+
+        Self { edges, triangles }
+    }
+
+    /// Neighbours across manifold edges, with flag of equal edge direction
+    fn neighbours(&self, triangle_index: u32) -> impl Iterator<Item = (u32, bool)> + '_ {
+        let triangle = self.triangles[triangle_index as usize];
+
+        (0..3).filter_map(move |i| {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            match self.edges[&(a.min(b), a.max(b))][..] {
+                [(t0, d0), (t1, d1)] => Some(if t0 == triangle_index { (t1, d0 == d1) } else { (t0, d0 == d1) }),
+                _ => None,
+            }
+        })
+    }
+
+    /// Check if every edge of connected part is shared by exactly two triangles
+    fn is_closed(&self, part: &[u32]) -> bool {
+        part.iter().all(|triangle_index| {
+            let triangle = self.triangles[*triangle_index as usize];
+            (0..3).all(|i| {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                self.edges[&(a.min(b), a.max(b))].len() == 2
+            })
+        })
+    }
+}
+
+/// Six times signed volume enclosed by triangles, positive for outward-facing closed surfaces
+fn signed_volume(mesh: &Mesh, triangles: impl Iterator<Item = [u32; 3]>) -> f32 {
+    triangles
+        .map(|indices| {
+            let [v0, v1, v2] = indices.map(|index| mesh.positions[index as usize]);
+            v0 ^ (v1 % v2)
+        })
+        .sum()
+}
+
+/// Split triangles into parts connected by manifold edges. Triangles of each part get flip flags
+/// that make winding of the part consistent with its first triangle.
+fn connected_parts(adjacency: &Adjacency) -> Vec<(Vec<u32>, Vec<bool>)> {
+    let triangle_count = adjacency.triangles.len();
+    let mut is_visited = vec![false; triangle_count];
+    let mut is_flipped = vec![false; triangle_count];
+    let mut parts = Vec::new();
+
+    for seed in 0..triangle_count as u32 {
+        if is_visited[seed as usize] {
+            continue;
+        }
+        is_visited[seed as usize] = true;
+
+        let mut part = Vec::new();
+        let mut queue = VecDeque::from([seed]);
+
+        while let Some(triangle_index) = queue.pop_front() {
+            part.push(triangle_index);
+
+            for (neighbour, is_same_direction) in adjacency.neighbours(triangle_index) {
+                if !is_visited[neighbour as usize] {
+                    is_visited[neighbour as usize] = true;
+                    // Consistent neighbours traverse shared edge in opposite directions
+                    is_flipped[neighbour as usize] = is_flipped[triangle_index as usize] ^ is_same_direction;
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        let flips = part.iter().map(|triangle_index| is_flipped[*triangle_index as usize]).collect();
+        parts.push((part, flips));
+    }
+
+    parts
+} // fn connected_parts
+
+fn flipped(indices: [u32; 3], is_flipped: bool) -> [u32; 3] {
+    if is_flipped { [indices[0], indices[2], indices[1]] } else { indices }
+}
+
+impl Mesh {
+    /// Find inconsistently wound and inside-out triangles
+    pub fn orientation_report(&self) -> OrientationReport {
+        let adjacency = Adjacency::new(self);
+
+        let inconsistent_edge_count = adjacency.edges
+            .values()
+            .filter(|triangles| matches!(triangles[..], [(_, d0), (_, d1)] if d0 == d1))
+            .count();
+
+        let inverted_part_count = connected_parts(&adjacency)
+            .into_iter()
+            .filter(|(part, flips)| {
+                !flips.iter().any(|is_flipped| *is_flipped)
+                    && adjacency.is_closed(part)
+                    && signed_volume(self, part.iter().map(|index| self.indices[*index as usize])) < 0.0
+            })
+            .count();
+
+        OrientationReport { inconsistent_edge_count, inverted_part_count }
+    }
+
+    /// Count edges shared by more than two triangles
+    pub fn non_manifold_edge_count(&self) -> usize {
+        Adjacency::new(self).edges.values().filter(|triangles| triangles.len() > 2).count()
+    }
+
+    /// Make winding consistent within connected parts and turn closed parts outward.
+    /// Open parts keep winding of their first triangle. Returns number of flipped triangles.
+    pub fn reorient(&mut self) -> usize {
+        let adjacency = Adjacency::new(self);
+        let mut flipped_count = 0;
+
+        for (part, mut flips) in connected_parts(&adjacency) {
+            let volume = signed_volume(self, part.iter().zip(flips.iter()).map(|(index, is_flipped)| flipped(self.indices[*index as usize], *is_flipped)));
+            if volume < 0.0 && adjacency.is_closed(&part) {
+                flips.iter_mut().for_each(|is_flipped| *is_flipped = !*is_flipped);
+            }
+
+            for (index, is_flipped) in part.into_iter().zip(flips) {
+                self.indices[index as usize] = flipped(self.indices[index as usize], is_flipped);
+                flipped_count += is_flipped as usize;
+            }
+        }
+
+        flipped_count
+    }
+}
@@ -0,0 +1,122 @@
+use crate::math::Vec3f;
+
+/// Renderer radiance unit in kcd/m^2, brings daylight sky to values around one
+pub const SKY_RADIANCE_SCALE: f32 = 0.05;
+
+/// Luminance of sun disk outside of atmosphere, in kcd/m^2
+const SUN_LUMINANCE: f32 = 1.6e6;
+
+/// Angular radius of real sun, in degrees
+const SUN_ANGULAR_RADIUS: f32 = 0.2666;
+
+fn default_turbidity() -> f32 {
+    3.0
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_sun_angular_radius() -> f32 {
+    SUN_ANGULAR_RADIUS
+}
+
+/// Procedural daylight sky (Preetham et al. 'A Practical Analytic Model for Daylight') with sun disk
+#[derive(Copy, Clone, Debug, serde::Deserialize)]
+pub struct Sky {
+    /// Direction towards the sun
+    pub sun_direction: Vec3f,
+    /// Atmosphere haziness, model is fitted for range from 2 (clear) to 10 (hazy)
+    #[serde(default = "default_turbidity")]
+    pub turbidity: f32,
+    #[serde(default = "default_one")]
+    pub intensity: f32,
+    /// Sun disk angular radius in degrees. Sun radiance is scaled to keep its irradiance the same for any size.
+    #[serde(default = "default_sun_angular_radius")]
+    pub sun_angular_radius: f32,
+}
+
+/// Sky model parameters, evaluated by shader
+pub struct SkyModel {
+    /// Normalized sun direction
+    pub sun_direction: Vec3f,
+    /// Perez function coefficients A to E, each contains coefficients of luminance Y and chromaticities x and y
+    pub perez: [Vec3f; 5],
+    /// Zenith Y, x and y divided by Perez function at zenith
+    pub zenith: Vec3f,
+    /// Linear RGB sun disk radiance, in renderer units
+    pub sun_radiance: Vec3f,
+    /// Cosine of sun disk angular radius
+    pub sun_cos_angular_radius: f32,
+}
+
+/// Perez sky luminance distribution function
+fn perez(coefficients: [f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let [a, b, c, d, e] = coefficients;
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+}
+
+/// Zenith chromaticity, `[T^2, T, 1] * M * [theta^3, theta^2, theta, 1]`
+fn zenith_chromaticity(matrix: [[f32; 4]; 3], turbidity: f32, theta: f32) -> f32 {
+    let t = [turbidity * turbidity, turbidity, 1.0];
+    let th = [theta * theta * theta, theta * theta, theta, 1.0];
+
+    matrix.iter().zip(t).map(|(row, t)| t * row.iter().zip(th).map(|(m, th)| m * th).sum::<f32>()).sum()
+}
+
+impl Sky {
+    pub fn model(&self) -> SkyModel {
+        let t = self.turbidity;
+        let sun_direction = self.sun_direction.normalized();
+        // Model isn't defined for sun below horizon
+        let theta = sun_direction.y.clamp(0.0, 1.0).acos();
+
+        let perez_luminance = [0.1787 * t - 1.4630, -0.3554 * t + 0.4275, -0.0227 * t + 5.3251, 0.1206 * t - 2.5771, -0.0670 * t + 0.3703];
+        let perez_chroma_x = [-0.0193 * t - 0.2592, -0.0665 * t + 0.0008, -0.0004 * t + 0.2125, -0.0641 * t - 0.8989, -0.0033 * t + 0.0452];
+        let perez_chroma_y = [-0.0167 * t - 0.2608, -0.0950 * t + 0.0092, -0.0079 * t + 0.2102, -0.0441 * t - 1.6537, -0.0109 * t + 0.0529];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (std::f32::consts::PI - 2.0 * theta);
+        let zenith_luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let zenith_x = zenith_chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ], t, theta);
+        let zenith_y = zenith_chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ], t, theta);
+
+        // Sun transmittance by Rayleigh and aerosol scattering (paper appendix) at RGB wavelengths, in micrometers
+        let sun_radiance = if sun_direction.y > 0.0 {
+            let air_mass = 1.0 / (theta.cos() + 0.15 * (93.885 - theta.to_degrees()).powf(-1.253));
+            let beta = 0.04608 * t - 0.04586;
+            let transmittance = |lambda: f32| {
+                (-0.008735 * lambda.powf(-4.08) * air_mass).exp() * (-beta * lambda.powf(-1.3) * air_mass).exp()
+            };
+
+            let radius = self.sun_angular_radius.max(0.01).to_radians();
+            let size_scale = ((SUN_ANGULAR_RADIUS.to_radians() / 2.0).sin() / (radius / 2.0).sin()).powi(2);
+
+            Vec3f::new(transmittance(0.65), transmittance(0.57), transmittance(0.475))
+                * (SUN_LUMINANCE * SKY_RADIANCE_SCALE * size_scale * self.intensity)
+        } else {
+            Vec3f::default()
+        };
+
+        let coefficient = |index: usize| Vec3f::new(perez_luminance[index], perez_chroma_x[index], perez_chroma_y[index]);
+
+        SkyModel {
+            sun_direction,
+            perez: [0, 1, 2, 3, 4].map(coefficient),
+            zenith: Vec3f::new(
+                zenith_luminance * SKY_RADIANCE_SCALE * self.intensity / perez(perez_luminance, 1.0, theta),
+                zenith_x / perez(perez_chroma_x, 1.0, theta),
+                zenith_y / perez(perez_chroma_y, 1.0, theta),
+            ),
+            sun_radiance,
+            sun_cos_angular_radius: self.sun_angular_radius.max(0.01).to_radians().cos(),
+        }
+    } // fn model
+}
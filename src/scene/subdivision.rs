@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use crate::math::{Vec2f, Vec3f};
+
+/// Mesh of arbitrary polygons, used before triangulation
+#[derive(Clone, Debug, Default)]
+pub struct PolygonMesh {
+    pub positions: Vec<Vec3f>,
+    pub faces: Vec<Vec<u32>>,
+    /// Texture coordinates, indexed separately from positions
+    pub uvs: Vec<Vec2f>,
+    /// Texture coordinate indices of face corners, empty if mesh has no texture coordinates
+    pub face_uvs: Vec<Vec<u32>>,
+    /// sRGB vertex colors, indexed by positions, empty if mesh has no vertex colors
+    pub colors: Vec<Vec3f>,
+}
+
+#[derive(Copy, Clone)]
+struct Edge {
+    /// Adjacent faces, second is None for boundary edges
+    faces: [Option<u32>; 2],
+}
+
+impl PolygonMesh {
+    /// Axis-aligned box built of outward-facing quads
+    pub fn cuboid(p0: Vec3f, p1: Vec3f) -> Self {
+        let min = p0.min(p1);
+        let max = p0.max(p1);
+
+        let positions = (0..8)
+            .map(|i| Vec3f::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            ))
+            .collect();
+
+        let faces = vec![
+            vec![0, 4, 6, 2], // -X
+            vec![1, 3, 7, 5], // +X
+            vec![0, 1, 5, 4], // -Y
+            vec![2, 6, 7, 3], // +Y
+            vec![0, 2, 3, 1], // -Z
+            vec![4, 5, 7, 6], // +Z
+        ];
+
+        // Every face is mapped onto whole texture
+        let uvs = vec![
+            Vec2f::new(0.0, 0.0),
+            Vec2f::new(1.0, 0.0),
+            Vec2f::new(1.0, 1.0),
+            Vec2f::new(0.0, 1.0),
+        ];
+        let face_uvs = vec![vec![0, 1, 2, 3]; 6];
+
+        Self { positions, faces, uvs, face_uvs, colors: Vec::new() }
+    }
+
+    /// Single quad in XZ plane centered at origin, facing +Y
+    pub fn quad(size: Vec2f) -> Self {
+        let (x, z) = (size.x / 2.0, size.y / 2.0);
+
+        Self {
+            positions: vec![
+                Vec3f::new(-x, 0.0, z),
+                Vec3f::new(x, 0.0, z),
+                Vec3f::new(x, 0.0, -z),
+                Vec3f::new(-x, 0.0, -z),
+            ],
+            faces: vec![vec![0, 1, 2, 3]],
+            uvs: vec![
+                Vec2f::new(0.0, 0.0),
+                Vec2f::new(1.0, 0.0),
+                Vec2f::new(1.0, 1.0),
+                Vec2f::new(0.0, 1.0),
+            ],
+            face_uvs: vec![vec![0, 1, 2, 3]],
+            colors: Vec::new(),
+        }
+    }
+
+    /// Triangulate polygons as fans
+    pub fn triangulate(&self) -> Vec<[u32; 3]> {
+        triangulate_faces(&self.faces)
+    }
+
+    /// Subdivide texture coordinates linearly, matching face layout produced by `catmull_clark`
+    fn subdivide_uvs(&self) -> (Vec<Vec2f>, Vec<Vec<u32>>) {
+        if self.face_uvs.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+
+        let mut uvs = self.uvs.clone();
+        let mut edge_uv_indices = HashMap::<(u32, u32), u32>::new();
+        let mut face_uvs = Vec::new();
+
+        for face in &self.face_uvs {
+            let center_index = uvs.len() as u32;
+            uvs.push(face.iter().fold(Vec2f::default(), |sum, index| sum + self.uvs[*index as usize]) / face.len() as f32);
+
+            let edge_uvs = (0..face.len())
+                .map(|i| {
+                    let (a, b) = (face[i], face[(i + 1) % face.len()]);
+                    let key = (a.min(b), a.max(b));
+
+                    match edge_uv_indices.get(&key) {
+                        Some(index) => *index,
+                        None => {
+                            let index = uvs.len() as u32;
+                            uvs.push((self.uvs[a as usize] + self.uvs[b as usize]) / 2.0);
+                            edge_uv_indices.insert(key, index);
+                            index
+                        }
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            for i in 0..face.len() {
+                face_uvs.push(vec![face[i], edge_uvs[i], center_index, edge_uvs[(i + face.len() - 1) % face.len()]]);
+            }
+        }
+
+        (uvs, face_uvs)
+    }
+
+    /// Subdivide vertex colors linearly, laid out as positions produced by `catmull_clark`
+    fn subdivide_colors(&self, edges: &[(u32, u32, Edge)]) -> Vec<Vec3f> {
+        if self.colors.is_empty() {
+            return Vec::new();
+        }
+
+        let mut colors = self.colors.clone();
+        colors.extend(edges.iter().map(|(a, b, _)| (self.colors[*a as usize] + self.colors[*b as usize]) / 2.0));
+        colors.extend(self.faces.iter().map(|face| {
+            face.iter().fold(Vec3f::default(), |sum, index| sum + self.colors[*index as usize]) / face.len() as f32
+        }));
+        colors
+    }
+
+    /// Single Catmull-Clark subdivision step. Result consists of quads only.
+    pub fn catmull_clark(&self) -> PolygonMesh {
+        let vertex_count = self.positions.len();
+
+        let face_points = self.faces
+            .iter()
+            .map(|face| face.iter().fold(Vec3f::default(), |sum, index| sum + self.positions[*index as usize]) / face.len() as f32)
+            .collect::<Vec<_>>();
+
+        // Collect edges with adjacent faces
+        let mut edge_indices = HashMap::<(u32, u32), u32>::new();
+        let mut edges = Vec::<(u32, u32, Edge)>::new();
+
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for i in 0..face.len() {
+                let (a, b) = (face[i], face[(i + 1) % face.len()]);
+                let key = (a.min(b), a.max(b));
+
+                match edge_indices.get(&key) {
+                    Some(edge_index) => edges[*edge_index as usize].2.faces[1] = Some(face_index as u32),
+                    None => {
+                        edge_indices.insert(key, edges.len() as u32);
+                        edges.push((key.0, key.1, Edge { faces: [Some(face_index as u32), None] }));
+                    }
+                }
+            }
+        }
+
+        let edge_points = edges
+            .iter()
+            .map(|(a, b, edge)| {
+                let (a, b) = (self.positions[*a as usize], self.positions[*b as usize]);
+                match edge.faces {
+                    [Some(f0), Some(f1)] => (a + b + face_points[f0 as usize] + face_points[f1 as usize]) / 4.0,
+                    _ => (a + b) / 2.0,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Accumulate vertex neighbourhood
+        let mut face_sums = vec![Vec3f::default(); vertex_count];
+        let mut face_counts = vec![0u32; vertex_count];
+        let mut edge_sums = vec![Vec3f::default(); vertex_count];
+        let mut edge_counts = vec![0u32; vertex_count];
+        let mut boundary_sums = vec![Vec3f::default(); vertex_count];
+        let mut boundary_counts = vec![0u32; vertex_count];
+
+        for (face, face_point) in self.faces.iter().zip(face_points.iter()) {
+            for index in face {
+                face_sums[*index as usize] += *face_point;
+                face_counts[*index as usize] += 1;
+            }
+        }
+
+        for (a, b, edge) in &edges {
+            let middle = (self.positions[*a as usize] + self.positions[*b as usize]) / 2.0;
+
+            for vertex in [*a as usize, *b as usize] {
+                edge_sums[vertex] += middle;
+                edge_counts[vertex] += 1;
+
+                if edge.faces[1].is_none() {
+                    boundary_sums[vertex] += middle;
+                    boundary_counts[vertex] += 1;
+                }
+            }
+        }
+
+        let vertex_points = (0..vertex_count)
+            .map(|vertex| {
+                let position = self.positions[vertex];
+
+                if boundary_counts[vertex] >= 2 {
+                    // Boundary vertices follow cubic B-spline rule of the boundary curve
+                    let r = boundary_sums[vertex] / boundary_counts[vertex] as f32;
+                    (r + position) / 2.0
+                } else if face_counts[vertex] == 0 {
+                    position
+                } else {
+                    let n = face_counts[vertex] as f32;
+                    let f = face_sums[vertex] / n;
+                    let r = edge_sums[vertex] / edge_counts[vertex] as f32;
+                    (f + r * 2.0 + position * (n - 3.0)) / n
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // New vertices are laid out as [vertex points, edge points, face points]
+        let edge_base = vertex_count as u32;
+        let face_base = edge_base + edges.len() as u32;
+        let edge_point_index = |a: u32, b: u32| edge_base + edge_indices[&(a.min(b), a.max(b))];
+
+        let faces = self.faces
+            .iter()
+            .enumerate()
+            .flat_map(|(face_index, face)| {
+                let edge_point_index = &edge_point_index;
+                (0..face.len()).map(move |i| {
+                    let previous = face[(i + face.len() - 1) % face.len()];
+                    let current = face[i];
+                    let next = face[(i + 1) % face.len()];
+
+                    vec![
+                        current,
+                        edge_point_index(current, next),
+                        face_base + face_index as u32,
+                        edge_point_index(previous, current),
+                    ]
+                })
+            })
+            .collect();
+
+        let mut positions = vertex_points;
+        positions.extend(edge_points);
+        positions.extend(face_points);
+
+        let (uvs, face_uvs) = self.subdivide_uvs();
+        let colors = self.subdivide_colors(&edges);
+
+        PolygonMesh { positions, faces, uvs, face_uvs, colors }
+    } // fn catmull_clark
+
+    /// Apply several subdivision steps
+    pub fn subdivided(&self, level: u32) -> PolygonMesh {
+        (0..level).fold(self.clone(), |mesh, _| mesh.catmull_clark())
+    }
+}
+
+/// Triangulate polygons as fans
+pub fn triangulate_faces(faces: &[Vec<u32>]) -> Vec<[u32; 3]> {
+    faces
+        .iter()
+        .flat_map(|face| (1..face.len().saturating_sub(1)).map(move |i| [face[0], face[i], face[i + 1]]))
+        .collect()
+}
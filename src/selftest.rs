@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use crate::{
+    camera::Camera,
+    locale::{tr, tr_args},
+    math::{Ext2u, Vec2f, Vec3d, Vec3f},
+    render::{self, AdapterSelector, Aov, BuildError, GpuContext, RenderSettings},
+    scene::{Backface, Environment, Material, MaterialMaps, Primitive, Projection, Scene, Shape, TexturedMaterial, Tolerances},
+};
+
+// Audit images are small, so audit runs in reasonable time on software adapters too
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 96;
+pub const DEFAULT_SAMPLE_COUNT: u32 = 256;
+const SAMPLES_PER_FRAME: u32 = 8;
+
+/// Relative difference of measured and analytic mean radiance check passes within
+const MAX_RELATIVE_ERROR: f64 = 0.02;
+
+/// Furnace: diffuse sphere lit by uniform environment. Sphere is convex, so its every point reflects
+/// `FURNACE_ALBEDO * FURNACE_RADIANCE` regardless of path depth.
+const FURNACE_ALBEDO: f32 = 0.8;
+const FURNACE_RADIANCE: f32 = 1.0;
+
+/// Lambertian plane y = 0 lit by emissive sphere above it. Sphere is above horizon of every plane point,
+/// so irradiance is `PI * radiance * (radius / distance)^2 * cos(angle)`.
+const PLANE_ALBEDO: f32 = 0.5;
+const LIGHT_RADIANCE: f32 = 4.0;
+const LIGHT_CENTER: Vec3f = Vec3f { x: 0.0, y: 2.0, z: 0.0 };
+const LIGHT_RADIUS: f32 = 0.5;
+/// Distance of viewed plane part from point under light
+const PLANE_VIEW_OFFSET: f64 = 1.5;
+const PLANE_VIEW_HEIGHT: f32 = 1.0;
+
+/// Measured and analytic mean radiance of audit image part
+struct Check {
+    name: &'static str,
+    measured: f64,
+    analytic: f64,
+}
+
+/// Renders check scene with sample count per pixel
+type CheckFn = fn(&Arc<GpuContext>, u32) -> Result<Check, BuildError>;
+
+impl Check {
+    fn relative_error(&self) -> f64 {
+        (self.measured - self.analytic).abs() / self.analytic
+    }
+}
+
+fn material(material: Material) -> TexturedMaterial {
+    TexturedMaterial { material, maps: MaterialMaps::default(), instance_tint: false, backface: Backface::default() }
+}
+
+/// Image of audit scene, rows are top to bottom
+struct Audit {
+    /// Mean radiance of pixels
+    radiance: Vec<f64>,
+    /// Primary hit distance of pixel centers, zero for environment
+    depth: Vec<f32>,
+}
+
+/// Accumulate `sample_count` samples per pixel of scene. Every scene gets its own renderer,
+/// so camera change between scenes isn't traced as motion.
+fn render_scene(context: &Arc<GpuContext>, scene: &Scene, camera: &Camera, sample_count: u32) -> Result<Audit, BuildError> {
+    let mut render = render::RenderBuilder::new(context.clone(), Ext2u::new(WIDTH, HEIGHT)).build()?;
+    render.set_scene(scene);
+    render.set_camera(&camera.descriptor(WIDTH as f32 / HEIGHT as f32));
+    render.set_settings(RenderSettings {
+        target_sample_count: sample_count,
+        samples_per_frame: SAMPLES_PER_FRAME,
+        ..*render.settings()
+    });
+    while !render.progress().is_complete() {
+        render.render();
+    }
+
+    let radiance = render.read_aov(Aov::Beauty).data
+        .chunks(3)
+        .map(|rgb| rgb.iter().map(|&channel| channel as f64).sum::<f64>() / 3.0)
+        .collect();
+    Ok(Audit { radiance, depth: render.read_aov(Aov::Depth).data })
+}
+
+fn furnace(context: &Arc<GpuContext>, sample_count: u32) -> Result<Check, BuildError> {
+    let scene = Scene {
+        materials: vec![material(Material::Lambertian { color: Vec3f::new(FURNACE_ALBEDO, FURNACE_ALBEDO, FURNACE_ALBEDO) })],
+        material_names: vec!["sphere".to_string()],
+        primitives: vec![Primitive { shape: Shape::Sphere { center: Vec3f::new(0.0, 0.0, 0.0), radius: 1.0 }, material_index: 0 }],
+        environment: Some(Environment {
+            extent: Ext2u::new(2, 1),
+            data: vec![FURNACE_RADIANCE; 8],
+            intensity: 1.0,
+            rotation: 0.0,
+        }),
+        tolerances: Tolerances::default(),
+        ..Default::default()
+    };
+    let mut camera = Camera::new();
+    camera.set(Vec3d::new(0.0, 0.0, 4.0), Vec3d::new(0.0, 0.0, 0.0), Vec3f::new(0.0, 1.0, 0.0));
+
+    let Audit { radiance, depth } = render_scene(context, &scene, &camera, sample_count)?;
+
+    // Silhouette pixels mix sphere and environment, so only pixels surrounded by sphere ones are measured
+    let is_hit = |x: i64, y: i64| x >= 0 && y >= 0 && x < WIDTH as i64 && y < HEIGHT as i64 && depth[(y * WIDTH as i64 + x) as usize] > 0.0;
+    let measured = (0..HEIGHT as i64)
+        .flat_map(|y| (0..WIDTH as i64).map(move |x| (x, y)))
+        .filter(|&(x, y)| (-1..=1).all(|dy| (-1..=1).all(|dx| is_hit(x + dx, y + dy))))
+        .map(|(x, y)| radiance[(y * WIDTH as i64 + x) as usize])
+        .collect::<Vec<_>>();
+
+    Ok(Check {
+        name: "furnace",
+        measured: measured.iter().sum::<f64>() / measured.len().max(1) as f64,
+        analytic: (FURNACE_ALBEDO * FURNACE_RADIANCE) as f64,
+    })
+} // fn furnace
+
+fn lambertian_plane(context: &Arc<GpuContext>, sample_count: u32) -> Result<Check, BuildError> {
+    let scene = Scene {
+        materials: vec![
+            material(Material::Lambertian { color: Vec3f::new(PLANE_ALBEDO, PLANE_ALBEDO, PLANE_ALBEDO) }),
+            material(Material::Emissive { color: Vec3f::new(1.0, 1.0, 1.0), intensity: LIGHT_RADIANCE }),
+        ],
+        material_names: vec!["plane".to_string(), "light".to_string()],
+        primitives: vec![
+            Primitive { shape: Shape::Plane { point: Vec3f::new(0.0, 0.0, 0.0), normal: Vec3f::new(0.0, 1.0, 0.0), extent: 0.0 }, material_index: 0 },
+            Primitive { shape: Shape::Sphere { center: LIGHT_CENTER, radius: LIGHT_RADIUS }, material_index: 1 },
+        ],
+        tolerances: Tolerances::default(),
+        ..Default::default()
+    };
+
+    // Orthographic camera looks down at plane part beside light from below light, so light isn't in view
+    let view_center = Vec3d::new(PLANE_VIEW_OFFSET, 0.0, 0.0);
+    let mut camera = Camera::new();
+    camera.projection = Projection::Orthographic { height: PLANE_VIEW_HEIGHT };
+    camera.set(view_center + Vec3d::new(0.0, 1.0, 0.0), view_center, Vec3f::new(0.0, 0.0, -1.0));
+
+    let radiance = render_scene(context, &scene, &camera, sample_count)?.radiance;
+
+    let extent = camera.projection_extent(WIDTH as f32 / HEIGHT as f32);
+    let light_center = LIGHT_CENTER.to_f64();
+    let analytic = (0..HEIGHT).flat_map(|y| (0..WIDTH).map(move |x| (x, y))).map(|(x, y)| {
+        let coord = Vec2f::new((x as f32 + 0.5) / WIDTH as f32 * 2.0 - 1.0, 1.0 - (y as f32 + 0.5) / HEIGHT as f32 * 2.0);
+        let point = view_center + (camera.right * extent.w * coord.x + camera.up * extent.h * coord.y).to_f64();
+        let distance = (light_center - point).length();
+        let cos_angle = light_center.y / distance;
+        (PLANE_ALBEDO * LIGHT_RADIANCE) as f64 * (LIGHT_RADIUS as f64 / distance).powi(2) * cos_angle
+    }).sum::<f64>() / (WIDTH * HEIGHT) as f64;
+
+    Ok(Check {
+        name: "lambertian plane",
+        measured: radiance.iter().sum::<f64>() / radiance.len() as f64,
+        analytic,
+    })
+} // fn lambertian_plane
+
+/// Render analytic scenes with default render settings and compare their mean radiance with analytic one,
+/// `p_tr selftest`. Fails if any check differs by more than 2%, which reveals integrator bias.
+pub fn run(selector: &AdapterSelector, sample_count: u32) -> std::process::ExitCode {
+    let Some(context) = render::GpuContext::new(selector) else {
+        eprintln!("{}", tr("error.no_adapter"));
+        return std::process::ExitCode::FAILURE;
+    };
+    println!("{}", tr_args("selftest.start", &[&context.adapter.get_info().name, &sample_count]));
+
+    let checks: [CheckFn; 2] = [furnace, lambertian_plane];
+    let mut is_passed = true;
+    for check in checks {
+        let check = match check(&context, sample_count) {
+            Ok(check) => check,
+            Err(error) => {
+                eprintln!("{}", tr_args("error.render", &[&error]));
+                return std::process::ExitCode::FAILURE;
+            }
+        };
+        let is_check_passed = check.relative_error() <= MAX_RELATIVE_ERROR;
+        is_passed &= is_check_passed;
+        println!("{}", tr_args(if is_check_passed { "selftest.passed" } else { "selftest.failed" }, &[
+            &check.name,
+            &format!("{:.5}", check.measured),
+            &format!("{:.5}", check.analytic),
+            &format!("{:.2}", check.relative_error() * 100.0),
+        ]));
+    }
+
+    if is_passed { std::process::ExitCode::SUCCESS } else { std::process::ExitCode::FAILURE }
+} // fn run
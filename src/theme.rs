@@ -0,0 +1,98 @@
+use crate::math::Vec3f;
+
+/// Built-in overlay color sets
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ThemePreset {
+    #[default]
+    Dark,
+    Light,
+    HighContrast,
+}
+
+/// Color map of false-color debug views
+#[derive(Copy, Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum FalseColorPalette {
+    /// Rainbow-like map with high perceptual contrast
+    #[default]
+    Turbo,
+    /// Perceptually uniform blue to yellow map, readable with any kind of color blindness
+    Viridis,
+}
+
+impl FalseColorPalette {
+    /// Evenly spaced map stops from 0 to 1
+    pub fn stops(self) -> &'static [Vec3f] {
+        const TURBO: [Vec3f; 6] = [
+            Vec3f { x: 0.190, y: 0.072, z: 0.232 },
+            Vec3f { x: 0.164, y: 0.471, z: 0.955 },
+            Vec3f { x: 0.105, y: 0.896, z: 0.714 },
+            Vec3f { x: 0.634, y: 0.990, z: 0.235 },
+            Vec3f { x: 0.979, y: 0.603, z: 0.163 },
+            Vec3f { x: 0.480, y: 0.016, z: 0.011 },
+        ];
+        const VIRIDIS: [Vec3f; 6] = [
+            Vec3f { x: 0.267, y: 0.005, z: 0.329 },
+            Vec3f { x: 0.254, y: 0.265, z: 0.530 },
+            Vec3f { x: 0.164, y: 0.471, z: 0.558 },
+            Vec3f { x: 0.135, y: 0.659, z: 0.518 },
+            Vec3f { x: 0.478, y: 0.821, z: 0.3176 },
+            Vec3f { x: 0.993, y: 0.906, z: 0.144 },
+        ];
+
+        match self {
+            FalseColorPalette::Turbo => &TURBO,
+            FalseColorPalette::Viridis => &VIRIDIS,
+        }
+    }
+
+    /// Color of `t` in [0, 1] range, linearly interpolated between stops
+    pub fn sample(self, t: f32) -> Vec3f {
+        let stops = self.stops();
+        let position = t.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+        let index = (position as usize).min(stops.len() - 2);
+        let factor = position - index as f32;
+
+        stops[index] * (1.0 - factor) + stops[index + 1] * factor
+    }
+}
+
+/// UI overlay appearance
+#[derive(Copy, Clone, Debug)]
+pub struct Theme {
+    pub outline_color: Vec3f,
+    pub box_color: Vec3f,
+    /// Overlay element scale, outline width and text size are multiplied by it
+    pub font_scale: f32,
+    pub false_color: FalseColorPalette,
+}
+
+impl Theme {
+    pub fn preset(preset: ThemePreset) -> Self {
+        match preset {
+            ThemePreset::Dark => Self {
+                outline_color: Vec3f::new(1.0, 0.6, 0.1),
+                box_color: Vec3f::new(0.9, 0.9, 0.9),
+                font_scale: 1.0,
+                false_color: FalseColorPalette::Turbo,
+            },
+            ThemePreset::Light => Self {
+                outline_color: Vec3f::new(0.1, 0.3, 0.9),
+                box_color: Vec3f::new(0.15, 0.15, 0.15),
+                font_scale: 1.0,
+                false_color: FalseColorPalette::Turbo,
+            },
+            ThemePreset::HighContrast => Self {
+                outline_color: Vec3f::new(1.0, 1.0, 0.0),
+                box_color: Vec3f::new(1.0, 0.0, 1.0),
+                font_scale: 1.5,
+                false_color: FalseColorPalette::Viridis,
+            },
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::preset(ThemePreset::default())
+    }
+}
@@ -58,6 +58,12 @@ impl<'a> State<'a> {
     }
 }
 
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Timer {
     pub fn new() -> Timer {
         let now = std::time::Instant::now();
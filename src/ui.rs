@@ -0,0 +1,391 @@
+use crate::{camera, locale::{tr, tr_args}, math::{Vec2u, Vec3d, Vec3f}, render::{Buffer, BufferView, Channels, Dither, Normalization, OverlayFrame, PixelInfo, RenderSettings}, scene::{Material, Projection, TexturedMaterial}};
+
+/// Values shown by runtime controls
+pub struct Stats {
+    pub fps: f64,
+    pub sample_count: u32,
+    /// Convergence metric of accumulation, see `RenderProgress`
+    pub convergence: Option<f32>,
+    pub camera_location: Vec3d,
+    /// Pixel under cursor with its values, set while pixel inspector is visible
+    pub pixel: Option<(Vec2u, PixelInfo)>,
+}
+
+/// Values edited by runtime controls, compared against previous ones to find changes
+#[derive(Copy, Clone, PartialEq)]
+pub struct Controls {
+    pub settings: RenderSettings,
+    /// Exposure compensation in stops
+    pub exposure: f32,
+    pub aperture_radius: f32,
+    pub focus_distance: f32,
+    /// Vertical field of view, in degrees
+    pub fov: f32,
+    /// Camera projection, selects field of view range
+    pub projection: Projection,
+    /// Fly camera speed in scene units per second
+    pub move_speed: f32,
+    /// Internal texture presented instead of image
+    pub buffer_view: Option<BufferView>,
+}
+
+fn key(code: winit::keyboard::KeyCode) -> Option<egui::Key> {
+    use winit::keyboard::KeyCode;
+
+    Some(match code {
+        KeyCode::ArrowLeft => egui::Key::ArrowLeft,
+        KeyCode::ArrowRight => egui::Key::ArrowRight,
+        KeyCode::ArrowUp => egui::Key::ArrowUp,
+        KeyCode::ArrowDown => egui::Key::ArrowDown,
+        KeyCode::Backspace => egui::Key::Backspace,
+        KeyCode::Delete => egui::Key::Delete,
+        KeyCode::Enter | KeyCode::NumpadEnter => egui::Key::Enter,
+        KeyCode::Escape => egui::Key::Escape,
+        KeyCode::Tab => egui::Key::Tab,
+        KeyCode::Home => egui::Key::Home,
+        KeyCode::End => egui::Key::End,
+        _ => return None,
+    })
+}
+
+fn buffer_key(buffer: Buffer) -> &'static str {
+    match buffer {
+        Buffer::CollectorA => "buffer.collector_a",
+        Buffer::CollectorB => "buffer.collector_b",
+        Buffer::FeaturesA => "buffer.features_a",
+        Buffer::FeaturesB => "buffer.features_b",
+        Buffer::AovAlbedoDepth => "buffer.aov_albedo_depth",
+        Buffer::AovNormalMotion => "buffer.aov_normal_motion",
+        Buffer::DenoiserA => "buffer.denoiser_a",
+        Buffer::DenoiserB => "buffer.denoiser_b",
+    }
+}
+
+fn normalization_key(normalization: Normalization) -> &'static str {
+    match normalization {
+        Normalization::Raw => "normalization.raw",
+        Normalization::PerSample => "normalization.per_sample",
+        Normalization::Signed => "normalization.signed",
+        Normalization::Log => "normalization.log",
+    }
+}
+
+fn dither_key(dither: Dither) -> &'static str {
+    match dither {
+        Dither::None => "dither.none",
+        Dither::Ordered => "dither.ordered",
+        Dither::BlueNoise => "dither.blue_noise",
+    }
+}
+
+/// egui runtime controls drawn over rendered image. Window events are translated to egui input
+/// here, as egui-winit doesn't support used winit version.
+pub struct Ui {
+    context: egui::Context,
+    input: egui::RawInput,
+    modifiers: egui::Modifiers,
+    /// Last cursor position, in points
+    pointer_position: egui::Pos2,
+    start_time: std::time::Instant,
+    /// Material shown by material inspector
+    material_index: usize,
+    pub is_visible: bool,
+    /// Pixel inspector is shown independently of controls, as it's used to debug image
+    pub is_inspector_visible: bool,
+}
+
+impl Ui {
+    pub fn new(is_visible: bool) -> Self {
+        Self {
+            context: egui::Context::default(),
+            input: egui::RawInput::default(),
+            modifiers: egui::Modifiers::default(),
+            pointer_position: egui::Pos2::ZERO,
+            start_time: std::time::Instant::now(),
+            material_index: 0,
+            is_visible,
+            is_inspector_visible: false,
+        }
+    }
+
+    fn has_windows(&self) -> bool {
+        self.is_visible || self.is_inspector_visible
+    }
+
+    /// Pass window event to UI, returns true if UI captures it
+    pub fn on_window_event(&mut self, event: &winit::event::WindowEvent, scale_factor: f32) -> bool {
+        let events = &mut self.input.events;
+
+        match event {
+            winit::event::WindowEvent::ModifiersChanged(modifiers) => {
+                let state = modifiers.state();
+                self.modifiers = egui::Modifiers {
+                    alt: state.alt_key(),
+                    ctrl: state.control_key(),
+                    shift: state.shift_key(),
+                    mac_cmd: false,
+                    command: state.control_key(),
+                };
+                false
+            }
+            winit::event::WindowEvent::CursorMoved { position, .. } => {
+                self.pointer_position = egui::pos2(position.x as f32 / scale_factor, position.y as f32 / scale_factor);
+                events.push(egui::Event::PointerMoved(self.pointer_position));
+                false
+            }
+            winit::event::WindowEvent::CursorLeft { .. } => {
+                events.push(egui::Event::PointerGone);
+                false
+            }
+            winit::event::WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    winit::event::MouseButton::Left => egui::PointerButton::Primary,
+                    winit::event::MouseButton::Right => egui::PointerButton::Secondary,
+                    winit::event::MouseButton::Middle => egui::PointerButton::Middle,
+                    _ => return false,
+                };
+                let pressed = *state == winit::event::ElementState::Pressed;
+                events.push(egui::Event::PointerButton { pos: self.pointer_position, button, pressed, modifiers: self.modifiers });
+
+                // Releases aren't captured, so drags started outside of UI end normally
+                pressed && self.has_windows() && self.context.is_pointer_over_area()
+            }
+            winit::event::WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    winit::event::MouseScrollDelta::LineDelta(x, y) => (egui::MouseWheelUnit::Line, egui::vec2(*x, *y)),
+                    winit::event::MouseScrollDelta::PixelDelta(delta) => {
+                        (egui::MouseWheelUnit::Point, egui::vec2(delta.x as f32, delta.y as f32) / scale_factor)
+                    }
+                };
+                events.push(egui::Event::MouseWheel { unit, delta, modifiers: self.modifiers });
+                self.has_windows() && self.context.is_pointer_over_area()
+            }
+            winit::event::WindowEvent::KeyboardInput { event, .. } => {
+                let pressed = event.state == winit::event::ElementState::Pressed;
+                let physical_key = match event.physical_key {
+                    winit::keyboard::PhysicalKey::Code(code) => key(code),
+                    winit::keyboard::PhysicalKey::Unidentified(_) => None,
+                };
+                if let Some(key) = physical_key {
+                    events.push(egui::Event::Key { key, physical_key, pressed, repeat: event.repeat, modifiers: self.modifiers });
+                }
+                if let Some(text) = event.text.as_ref().filter(|text| pressed && text.chars().all(|char| !char.is_control())) {
+                    events.push(egui::Event::Text(text.to_string()));
+                }
+
+                // Releases aren't captured, so no key gets stuck in input state
+                pressed && self.has_windows() && self.context.wants_keyboard_input()
+            }
+            winit::event::WindowEvent::Focused(focused) => {
+                events.push(egui::Event::WindowFocused(*focused));
+                false
+            }
+            _ => false,
+        }
+    } // fn on_window_event
+
+    /// Run UI frame, `controls` and `materials` are changed by user edits. Material names are indexed as materials.
+    pub fn run(
+        &mut self,
+        size: winit::dpi::PhysicalSize<u32>,
+        scale_factor: f32,
+        stats: &Stats,
+        controls: &mut Controls,
+        materials: &mut [TexturedMaterial],
+        material_names: &[String],
+    ) -> OverlayFrame {
+        let mut input = std::mem::take(&mut self.input);
+        input.screen_rect = Some(egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(size.width as f32, size.height as f32) / scale_factor));
+        input.time = Some(self.start_time.elapsed().as_secs_f64());
+        input.modifiers = self.modifiers;
+        input.viewports.entry(egui::ViewportId::ROOT).or_default().native_pixels_per_point = Some(scale_factor);
+
+        let is_visible = self.is_visible;
+        let material_index = &mut self.material_index;
+        let output = self.context.run(input, |context| {
+            if is_visible {
+                Self::show_controls(context, stats, controls);
+                Self::show_materials(context, materials, material_names, material_index);
+            }
+            if let Some((pixel, info)) = &stats.pixel {
+                Self::show_pixel(context, *pixel, info);
+            }
+        });
+
+        OverlayFrame {
+            primitives: self.context.tessellate(output.shapes, output.pixels_per_point),
+            textures_delta: output.textures_delta,
+            pixels_per_point: output.pixels_per_point,
+        }
+    }
+
+    fn show_controls(context: &egui::Context, stats: &Stats, controls: &mut Controls) {
+        egui::Window::new(tr("ui.title"))
+            .default_pos([8.0, 8.0])
+            .resizable(false)
+            .show(context, |ui| {
+                ui.label(tr_args("hud.fps", &[&format!("{:.1}", stats.fps)]));
+                ui.label(tr_args("ui.samples", &[&stats.sample_count]));
+                if let Some(convergence) = stats.convergence {
+                    ui.label(tr_args("ui.convergence", &[&format!("{:.5}", convergence)]));
+                }
+                let location = stats.camera_location;
+                ui.label(tr_args("ui.camera_location", &[&format!("{:.2} {:.2} {:.2}", location.x, location.y, location.z)]));
+                ui.separator();
+
+                let settings = &mut controls.settings;
+                ui.add(egui::Slider::new(&mut controls.exposure, -8.0..=8.0).text(tr("ui.exposure")));
+                ui.checkbox(&mut settings.auto_exposure, tr("ui.auto_exposure"));
+                ui.add_enabled(settings.auto_exposure, egui::Slider::new(&mut settings.auto_exposure_min_ev, -16.0..=0.0).text(tr("ui.auto_exposure_min_ev")));
+                ui.add_enabled(settings.auto_exposure, egui::Slider::new(&mut settings.auto_exposure_max_ev, 0.0..=16.0).text(tr("ui.auto_exposure_max_ev")));
+                ui.add_enabled(settings.auto_exposure, egui::Slider::new(&mut settings.auto_exposure_speed, 0.1..=10.0).logarithmic(true).text(tr("ui.auto_exposure_speed")));
+                ui.add(egui::Slider::new(&mut controls.aperture_radius, 0.0..=1.0).text(tr("ui.aperture_radius")));
+                ui.add(egui::Slider::new(&mut controls.focus_distance, camera::FOCUS_DISTANCE_RANGE).logarithmic(true).text(tr("ui.focus_distance")));
+                ui.add(egui::Slider::new(&mut controls.fov, camera::fov_range(controls.projection)).suffix("°").text(tr("ui.fov")));
+                ui.add(egui::Slider::new(&mut controls.move_speed, camera::MOVE_SPEED_RANGE).logarithmic(true).text(tr("ui.move_speed")));
+                ui.add(egui::Slider::new(&mut settings.max_depth, 1..=64).text(tr("ui.max_depth")));
+                ui.add(egui::Slider::new(&mut settings.rr_start_depth, 0..=64).text(tr("ui.rr_start_depth")));
+                ui.add(egui::Slider::new(&mut settings.samples_per_frame, 1..=64).text(tr("ui.samples_per_frame")));
+                ui.add(egui::Slider::new(&mut settings.max_indirect_radiance, 0.0..=1000.0).logarithmic(true).text(tr("ui.max_indirect_radiance")));
+                ui.add(egui::Slider::new(&mut settings.adaptive_threshold, 0.0..=0.1).logarithmic(true).text(tr("ui.adaptive_threshold")));
+                ui.add(egui::Slider::new(&mut settings.target_error, 0.0..=0.1).logarithmic(true).text(tr("ui.target_error")));
+                ui.checkbox(&mut settings.next_event_estimation, tr("ui.next_event_estimation"));
+                ui.add(egui::Slider::new(&mut settings.glare, 0.0..=10.0).logarithmic(true).text(tr("ui.glare")));
+                ui.add(egui::Slider::new(&mut settings.film_grain, 0.0..=0.1).text(tr("ui.film_grain")));
+                egui::ComboBox::from_label(tr("ui.dither"))
+                    .selected_text(tr(dither_key(settings.dither)))
+                    .show_ui(ui, |ui| for dither in Dither::ALL {
+                        ui.selectable_value(&mut settings.dither, dither, tr(dither_key(dither)));
+                    });
+                ui.checkbox(&mut settings.denoise, tr("ui.denoise"));
+                ui.add_enabled(settings.denoise, egui::Checkbox::new(&mut settings.denoise_components, tr("ui.denoise_components")));
+                ui.add(egui::Slider::new(&mut settings.sharpen, 0.0..=1.0).text(tr("ui.sharpen")));
+                ui.checkbox(&mut settings.temporal_reprojection, tr("ui.temporal_reprojection"));
+
+                ui.collapsing(tr("ui.buffer_view"), |ui| Self::show_buffer_view(ui, &mut controls.buffer_view));
+            });
+    } // fn show_controls
+
+    fn show_buffer_view(ui: &mut egui::Ui, buffer_view: &mut Option<BufferView>) {
+        let mut is_enabled = buffer_view.is_some();
+        ui.checkbox(&mut is_enabled, tr("ui.show_buffer"));
+        *buffer_view = is_enabled.then(|| buffer_view.unwrap_or_default());
+        let Some(view) = buffer_view else {
+            return;
+        };
+
+        egui::ComboBox::from_label(tr("ui.buffer"))
+            .selected_text(tr(buffer_key(view.buffer)))
+            .show_ui(ui, |ui| for buffer in Buffer::ALL {
+                ui.selectable_value(&mut view.buffer, buffer, tr(buffer_key(buffer)));
+            });
+        egui::ComboBox::from_label(tr("ui.channels"))
+            .selected_text(format!("{:?}", view.channels).to_uppercase())
+            .show_ui(ui, |ui| for channels in Channels::ALL {
+                ui.selectable_value(&mut view.channels, channels, format!("{:?}", channels).to_uppercase());
+            });
+        egui::ComboBox::from_label(tr("ui.normalization"))
+            .selected_text(tr(normalization_key(view.normalization)))
+            .show_ui(ui, |ui| for normalization in Normalization::ALL {
+                ui.selectable_value(&mut view.normalization, normalization, tr(normalization_key(normalization)));
+            });
+        ui.add(egui::Slider::new(&mut view.scale, 0.001..=1000.0).logarithmic(true).text(tr("ui.buffer_scale")));
+    } // fn show_buffer_view
+
+    /// Raw values of pixel under cursor, non-finite values are highlighted
+    fn show_pixel(context: &egui::Context, pixel: Vec2u, info: &PixelInfo) {
+        egui::Window::new(tr("ui.pixel_inspector"))
+            .default_pos([320.0, 8.0])
+            .resizable(false)
+            .show(context, |ui| {
+                let row = |ui: &mut egui::Ui, key: &'static str, values: &[f32]| {
+                    let text = values.iter().map(|value| format!("{:.6}", value)).collect::<Vec<_>>().join("  ");
+                    ui.horizontal(|ui| {
+                        ui.label(tr(key));
+                        if values.iter().all(|value| value.is_finite()) {
+                            ui.monospace(text);
+                        } else {
+                            ui.colored_label(egui::Color32::RED, egui::RichText::new(text).monospace());
+                        }
+                    });
+                };
+
+                ui.label(tr_args("ui.pixel", &[&pixel.x, &pixel.y]));
+                ui.separator();
+
+                let radiance = info.radiance();
+                let aovs = &info.aovs;
+                row(ui, "ui.radiance", &[radiance.x, radiance.y, radiance.z]);
+                row(ui, "ui.radiance_sum", &[info.radiance_sum.x, info.radiance_sum.y, info.radiance_sum.z]);
+                row(ui, "ui.pass_count", &[info.pass_count]);
+                row(ui, "ui.luminance_moment", &[info.luminance_moment]);
+                row(ui, "ui.albedo", &[aovs.albedo.x, aovs.albedo.y, aovs.albedo.z]);
+                row(ui, "ui.normal", &[aovs.normal.x, aovs.normal.y, aovs.normal.z]);
+                row(ui, "ui.depth", &[aovs.depth]);
+                row(ui, "ui.motion_vector", &[aovs.motion_vector.x, aovs.motion_vector.y]);
+            });
+    } // fn show_pixel
+
+    /// Material list and parameter editor of selected material
+    fn show_materials(context: &egui::Context, materials: &mut [TexturedMaterial], names: &[String], material_index: &mut usize) {
+        egui::Window::new(tr("ui.materials"))
+            .default_pos([8.0, 360.0])
+            .resizable(false)
+            .show(context, |ui| {
+                if materials.is_empty() {
+                    ui.label(tr("ui.no_materials"));
+                    return;
+                }
+
+                // Index of previous scene may exceed material count
+                *material_index = (*material_index).min(materials.len() - 1);
+                egui::ComboBox::from_id_source("material")
+                    .selected_text(names[*material_index].as_str())
+                    .show_ui(ui, |ui| {
+                        for (index, name) in names.iter().enumerate() {
+                            ui.selectable_value(material_index, index, name.as_str());
+                        }
+                    });
+                ui.separator();
+
+                let color = |ui: &mut egui::Ui, color: &mut Vec3f, key: &'static str| {
+                    ui.horizontal(|ui| {
+                        let mut rgb = [color.x, color.y, color.z];
+                        if ui.color_edit_button_rgb(&mut rgb).changed() {
+                            *color = Vec3f::new(rgb[0], rgb[1], rgb[2]);
+                        }
+                        ui.label(tr(key));
+                    });
+                };
+                let slider = |ui: &mut egui::Ui, value: &mut f32, range: std::ops::RangeInclusive<f32>, key: &'static str| {
+                    ui.add(egui::Slider::new(value, range).text(tr(key)));
+                };
+
+                match &mut materials[*material_index].material {
+                    Material::Lambertian { color: albedo } => color(ui, albedo, "ui.color"),
+                    Material::Metal { color: reflectance, roughness } => {
+                        color(ui, reflectance, "ui.color");
+                        slider(ui, roughness, 0.0..=1.0, "ui.roughness");
+                    }
+                    Material::Dielectric { ior, color: transmittance } => {
+                        color(ui, transmittance, "ui.color");
+                        slider(ui, ior, 1.0..=3.0, "ui.ior");
+                    }
+                    Material::Emissive { color: emission, intensity } => {
+                        color(ui, emission, "ui.color");
+                        ui.add(egui::Slider::new(intensity, 0.0..=1000.0).logarithmic(true).text(tr("ui.intensity")));
+                    }
+                    Material::Principled { base_color, metallic, roughness, specular, clearcoat, clearcoat_roughness, transmission, ior } => {
+                        color(ui, base_color, "ui.color");
+                        slider(ui, metallic, 0.0..=1.0, "ui.metallic");
+                        slider(ui, roughness, 0.0..=1.0, "ui.roughness");
+                        slider(ui, specular, 0.0..=1.0, "ui.specular");
+                        slider(ui, clearcoat, 0.0..=1.0, "ui.clearcoat");
+                        slider(ui, clearcoat_roughness, 0.0..=1.0, "ui.clearcoat_roughness");
+                        slider(ui, transmission, 0.0..=1.0, "ui.transmission");
+                        slider(ui, ior, 1.0..=3.0, "ui.ior");
+                    }
+                }
+            });
+    } // fn show_materials
+}